@@ -1,17 +1,9 @@
 #[macro_use]
 extern crate criterion;
 use criterion::Criterion;
-use ecm::Error;
+use ecm::ecm;
 use rug::Integer;
-use std::{collections::HashMap, str::FromStr};
-
-fn ecm(n: &Integer) -> Result<HashMap<Integer, usize>, Error> {
-    ecm::ecm(
-        n,
-        #[cfg(feature = "progress-bar")]
-        None,
-    )
-}
+use std::str::FromStr;
 
 fn bench_factorization(c: &mut Criterion) {
     let mut group = c.benchmark_group("ecm");
@@ -70,5 +62,40 @@ fn bench_factorization(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_factorization);
+fn bench_poly(c: &mut Criterion) {
+    let mut group = c.benchmark_group("poly");
+    let n = Integer::from_str("7060005655815754299976961394452809").unwrap();
+
+    for len in [64, 1024, 16384] {
+        let a: Vec<Integer> = (0..len)
+            .map(|i| Integer::from(i * 2654435761u64) * &n / (len * 2654435761u64))
+            .collect();
+        group.bench_function(format!("mul_mod_{len}"), |b| {
+            b.iter(|| ecm::poly::mul_mod(&a, &a, &n))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_corpus(c: &mut Criterion) {
+    let mut group = c.benchmark_group("corpus");
+    group.sample_size(10);
+
+    // Same numbers as `ecm-rs-gen semiprime <BITS> <BITS> 4`
+    for bits in [24, 32, 40] {
+        let corpus = ecm::corpus::semiprimes(1, bits, bits, 4);
+        group.bench_function(format!("semiprime_{bits}x{bits}"), |b| {
+            b.iter(|| corpus.iter().map(|entry| ecm(&entry.n)).collect::<Vec<_>>())
+        });
+    }
+    let corpus = ecm::corpus::almost_primes(1, 4, 24, 4);
+    group.bench_function("almost_prime_4x24", |b| {
+        b.iter(|| corpus.iter().map(|entry| ecm(&entry.n)).collect::<Vec<_>>())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_factorization, bench_poly, bench_corpus);
 criterion_main!(benches);
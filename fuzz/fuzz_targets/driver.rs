@@ -0,0 +1,12 @@
+#![no_main]
+
+//! Runs the full driver on arbitrary inputs of up to 256 bits.
+//!
+//! Crashing inputs are added to `tests/corpus/driver`, so they are replayed by `cargo test`.
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../tests/driver/mod.rs"]
+mod driver;
+
+fuzz_target!(|data: &[u8]| driver::drive(data));
@@ -0,0 +1,347 @@
+use crate::{
+    ecm::{check_bounds, Stages},
+    Parametrization, Point,
+};
+use hmac::{Hmac, Mac};
+use rug::{integer::Order, Integer};
+use sha2::Sha256;
+use std::sync::Mutex;
+
+/// Curve recorded in an [`AuditLog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// Position of the entry in the log, starting at 0.
+    pub index: u64,
+    /// Composite the curve was run on.
+    pub n: Integer,
+    /// Parameter of the curve, see [`SuyamaCurve`](crate::SuyamaCurve).
+    pub sigma: Integer,
+    /// Family of the curve of the sigma.
+    pub parametrization: Parametrization,
+    /// Stage 1 bound of the curve.
+    pub b1: u64,
+    /// HMAC-SHA256 keyed by the key of the log over the previous MAC, the index, n, sigma, parametrization and B1.
+    pub mac: [u8; 32],
+}
+
+/// Log of every curve counted by a factorization, chained with HMACs.
+///
+/// Each entry is authenticated by an HMAC-SHA256 keyed by a secret given to
+/// [`AuditLog::new`], over the MAC of the previous entry, its index, the composite,
+/// sigma, the family of the curve and B1. Anyone holding the key can check with
+/// [`AuditLog::verify`] that no curve was removed, reordered or altered since it
+/// was recorded, and replay the curves from the seed of the factorization, see
+/// [`Factorizer::seed`](crate::Factorizer::seed).
+///
+/// The HMAC is symmetric: holders of the key can verify the log but also forge
+/// it, so it only protects the log against parties without the key, e.g. while
+/// it is stored or sent. The key should be a random secret of at least 16 bytes,
+/// kept by the party checking the log and never shared with the one running the curves
+/// if that party is not trusted.
+///
+/// ```
+/// use ecm::{AuditLog, EcmOptions};
+/// use rug::Integer;
+/// use std::sync::Arc;
+///
+/// let key = b"a secret of the verifier";
+/// let log = Arc::new(AuditLog::new(key));
+/// let n = Integer::from(398883434337287u64);
+/// let options = EcmOptions::new().seed(42).small_cofactor_bits(0);
+/// options.audit_log(log.clone()).run(&n).unwrap();
+///
+/// let mut entries = log.entries();
+/// assert!(!entries.is_empty());
+/// assert!(AuditLog::verify(&entries, key));
+/// assert!(!AuditLog::verify(&entries, b"another key"));
+/// entries[0].sigma += 1;
+/// assert!(!AuditLog::verify(&entries, key));
+/// ```
+pub struct AuditLog {
+    key: Vec<u8>,
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The key is not printed
+        f.debug_struct("AuditLog")
+            .field("entries", &self.entries)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuditLog {
+    /// Creates an empty log.
+    ///
+    /// # Parameters
+    ///
+    /// - `key`: Secret key of the HMAC, of any length.
+    pub fn new(key: impl Into<Vec<u8>>) -> AuditLog {
+        AuditLog {
+            key: key.into(),
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Appends the curve of parameter `sigma` run on n.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Composite the curve is run on.
+    /// - `sigma`: Parameter of the curve.
+    /// - `parametrization`: Family of the curve.
+    /// - `b1`: Stage 1 bound of the curve.
+    pub(crate) fn record(
+        &self,
+        n: &Integer,
+        sigma: &Integer,
+        parametrization: Parametrization,
+        b1: u64,
+    ) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = entries.last().map_or([0; 32], |entry| entry.mac);
+        let index = entries.len() as u64;
+        let mac = entry_mac(&self.key, &previous, index, n, sigma, parametrization, b1);
+        entries.push(AuditEntry {
+            index,
+            n: n.clone(),
+            sigma: sigma.clone(),
+            parametrization,
+            b1,
+            mac,
+        });
+    }
+
+    /// Entries recorded so far, in order.
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Returns true if the entries form a complete chain authenticated by `key`.
+    ///
+    /// # Parameters
+    ///
+    /// - `entries`: Entries of the log, from the first one.
+    /// - `key`: Secret key given to [`AuditLog::new`].
+    pub fn verify(entries: &[AuditEntry], key: &[u8]) -> bool {
+        let mut previous = [0; 32];
+        for (index, entry) in entries.iter().enumerate() {
+            let mac = entry_mac(
+                key,
+                &previous,
+                index as u64,
+                &entry.n,
+                &entry.sigma,
+                entry.parametrization,
+                entry.b1,
+            );
+            if entry.index != index as u64 || entry.mac != mac {
+                return false;
+            }
+            previous = mac;
+        }
+        true
+    }
+}
+
+/// Products accumulated by stage 2 of a curve, to check the factor it found independently.
+///
+/// Stage 2 walks the primes of `(B1, B2]` by blocks of `2d` integers, multiplying
+/// one term per prime into a product modulo n, and the factor is the gcd of the
+/// final product with n. The product at the end of each block is recorded, so a
+/// third party can run stage 2 again from the result of stage 1 and compare the
+/// blocks one by one. The curve is identified by its sigma, e.g. from an [`AuditEntry`].
+///
+/// ```
+/// use ecm::{stage2_transcript, Parametrization};
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// // Curve splitting n = 4009823 * 99476569 in stage 2
+/// let transcript = stage2_transcript(&n, &Integer::from(12), Parametrization::Suyama, 2_000, 160_000).unwrap();
+/// assert_eq!(transcript.gcd, 99476569);
+/// assert_eq!(transcript.blocks.last().unwrap().clone().gcd(&n), 99476569);
+/// assert!(transcript.verify());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Stage2Transcript {
+    /// Composite the curve was run on.
+    pub n: Integer,
+    /// Parameter of the curve, see [`SuyamaCurve`](crate::SuyamaCurve).
+    pub sigma: Integer,
+    /// Family of the curve of the sigma.
+    pub parametrization: Parametrization,
+    /// Stage 1 bound of the curve.
+    pub b1: u64,
+    /// Stage 2 bound of the curve.
+    pub b2: u64,
+    /// Number of baby steps, each block covers `2d` integers.
+    pub d: usize,
+    /// Result of stage 1, the starting point of stage 2.
+    pub stage1: Point,
+    /// Product accumulated modulo n at the end of each block.
+    pub blocks: Vec<Integer>,
+    /// Gcd of the last product with n, a proper factor if the curve split n in stage 2.
+    pub gcd: Integer,
+}
+
+impl Stage2Transcript {
+    /// Returns true if running the curve again gives the same stage 1 result and blocks.
+    pub fn verify(&self) -> bool {
+        let run = stage2_transcript(&self.n, &self.sigma, self.parametrization, self.b1, self.b2);
+        run.is_some_and(|run| {
+            run.d == self.d
+                && run.stage1.x_cord == self.stage1.x_cord
+                && run.stage1.z_cord == self.stage1.z_cord
+                && run.blocks == self.blocks
+                && run.gcd == self.gcd
+        })
+    }
+}
+
+/// Runs the curve of parameter `sigma` of a family on n, recording the products of stage 2.
+///
+/// Returns `None` if stage 2 is not reached: the bounds are rejected by the
+/// factorizer, the curve cannot be built, or stage 1 already gives a gcd other than 1.
+///
+/// # Parameters
+///
+/// - `n`: Number the curve is run on.
+/// - `sigma`: Parameter of the curve, see [`SuyamaCurve`](crate::SuyamaCurve).
+/// - `parametrization`: Family of the curve, e.g. from an [`AuditEntry`].
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound.
+pub fn stage2_transcript(
+    n: &Integer,
+    sigma: &Integer,
+    parametrization: Parametrization,
+    b1: u64,
+    b2: u64,
+) -> Option<Stage2Transcript> {
+    check_bounds(b1, b2).ok()?;
+    let stages = Stages::new(b1, b2);
+    let q = stages.stage1(&parametrization.derive(sigma, n).ok()?.into_point());
+    if q.z_cord.clone().gcd(n) != 1 {
+        return None;
+    }
+
+    let mut blocks = Vec::new();
+    let (gcd, _) = stages.stage2_counted(n, &q, None, Some(&mut blocks));
+    Some(Stage2Transcript {
+        n: n.clone(),
+        sigma: sigma.clone(),
+        parametrization,
+        b1,
+        b2,
+        d: stages.d,
+        stage1: q,
+        blocks,
+        gcd,
+    })
+}
+
+/// MAC of an entry, integers are length-prefixed so the message is unambiguous.
+fn entry_mac(
+    key: &[u8],
+    previous: &[u8; 32],
+    index: u64,
+    n: &Integer,
+    sigma: &Integer,
+    parametrization: Parametrization,
+    b1: u64,
+) -> [u8; 32] {
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(key) else {
+        unreachable!("HMAC accepts keys of any length");
+    };
+    mac.update(previous);
+    mac.update(&index.to_be_bytes());
+    for value in [n, sigma] {
+        let digits = value.to_digits::<u8>(Order::Msf);
+        mac.update(&(digits.len() as u64).to_be_bytes());
+        mac.update(&digits);
+    }
+    mac.update(&[match parametrization {
+        Parametrization::Suyama => 0,
+        Parametrization::Z12 => 1,
+        Parametrization::Z2xZ8 => 2,
+    }]);
+    mac.update(&b1.to_be_bytes());
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain() {
+        let log = AuditLog::new(*b"key");
+        let n = Integer::from(398883434337287u64);
+        for sigma in 6..10 {
+            log.record(&n, &Integer::from(sigma), Parametrization::Suyama, 2_000);
+        }
+        let entries = log.entries();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[3].index, 3);
+        assert!(AuditLog::verify(&entries, b"key"));
+        assert!(AuditLog::verify(&entries[..2], b"key"));
+        assert!(!AuditLog::verify(&entries, b"other key"));
+
+        // Missing, reordered and altered entries are detected
+        assert!(!AuditLog::verify(&entries[1..], b"key"));
+        let mut swapped = entries.clone();
+        swapped.swap(1, 2);
+        assert!(!AuditLog::verify(&swapped, b"key"));
+        let mut altered = entries.clone();
+        altered[2].b1 = 11_000;
+        assert!(!AuditLog::verify(&altered, b"key"));
+        let mut altered = entries;
+        altered[2].parametrization = Parametrization::Z12;
+        assert!(!AuditLog::verify(&altered, b"key"));
+    }
+
+    #[test]
+    fn transcript() {
+        let n = Integer::from(398883434337287u64);
+        let transcript = stage2_transcript(
+            &n,
+            &Integer::from(12),
+            Parametrization::Suyama,
+            2_000,
+            160_000,
+        )
+        .unwrap();
+        assert_eq!(transcript.gcd, 99476569);
+        assert!(transcript.verify());
+
+        // The factor appears in one block and stays in the following ones
+        let gcds: Vec<Integer> = transcript
+            .blocks
+            .iter()
+            .map(|g| g.clone().gcd(&n))
+            .collect();
+        let first = gcds.iter().position(|g| *g != 1).unwrap();
+        assert!(gcds[first..].iter().all(|g| *g == 99476569));
+
+        let mut altered = transcript.clone();
+        altered.blocks[first] += 1;
+        assert!(!altered.verify());
+        let mut altered = transcript;
+        altered.sigma += 1;
+        assert!(!altered.verify());
+
+        // No stage 2 if the curve cannot be built
+        assert!(stage2_transcript(
+            &n,
+            &Integer::from(1),
+            Parametrization::Suyama,
+            2_000,
+            160_000
+        )
+        .is_none());
+    }
+}
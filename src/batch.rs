@@ -0,0 +1,357 @@
+use crate::{ecm::available_threads, EcmOptions, Error, Factorization};
+use rug::Integer;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// Order in which a [`Batch`] yields its results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Delivery {
+    /// In the order of the inputs: a slow input holds back the results after it.
+    #[default]
+    Ordered,
+    /// As soon as each factorization is done.
+    Unordered,
+}
+
+/// Result of one input of a [`Batch`].
+#[derive(Debug)]
+pub struct BatchResult {
+    /// Position of the input, starting at 0.
+    pub index: usize,
+    /// The input.
+    pub n: Integer,
+    /// Its factorization.
+    pub result: Result<Factorization, Error>,
+}
+
+/// Settings of the factorization of many numbers on a pool of threads.
+///
+/// Inputs are pulled lazily: at most `max_in_flight` inputs are queued, being
+/// factored or waiting for delivery at any time, so millions of candidates
+/// can be fed from an iterator without holding them all in memory.
+///
+/// The stage 1 multiplier and the primes of stage 2 are computed once
+/// for all the inputs needing the same bounds.
+///
+/// ```
+/// use ecm::{BatchOptions, Delivery, EcmOptions};
+/// use rug::Integer;
+///
+/// let inputs = (1_000_000u64..1_000_100).map(Integer::from);
+/// let batch = BatchOptions::new(EcmOptions::new())
+///     .threads(4)
+///     .max_in_flight(8)
+///     .delivery(Delivery::Ordered)
+///     .run(inputs);
+/// for (i, result) in batch.enumerate() {
+///     assert_eq!(result.index, i);
+///     assert_eq!(result.result.unwrap().product(), result.n);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    options: EcmOptions,
+    threads: usize,
+    max_in_flight: Option<usize>,
+    delivery: Delivery,
+}
+
+impl BatchOptions {
+    /// Factors each input with `options`, on the number of threads of the options.
+    ///
+    /// Each input is factored on a single thread, see [`EcmOptions::threads`].
+    ///
+    /// # Parameters
+    ///
+    /// - `options`: Settings of each factorization.
+    pub fn new(options: EcmOptions) -> BatchOptions {
+        BatchOptions {
+            threads: options.thread_count(),
+            options,
+            max_in_flight: None,
+            delivery: Delivery::default(),
+        }
+    }
+
+    /// Sets the number of worker threads, 0 for all available cores.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = match threads {
+            0 => available_threads(),
+            threads => threads,
+        };
+        self
+    }
+
+    /// Sets the maximum number of inputs pulled but not yet yielded, at least 1.
+    ///
+    /// Defaults to twice the number of threads. With [`Delivery::Ordered`],
+    /// results waiting for a slower input count too.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    /// Sets the order of the results, defaults to [`Delivery::Ordered`].
+    pub fn delivery(mut self, delivery: Delivery) -> Self {
+        self.delivery = delivery;
+        self
+    }
+
+    /// Starts the factorization of `inputs`, returning an iterator over the results.
+    ///
+    /// Dropping the batch stops pulling inputs, factorizations already started
+    /// finish in the background.
+    ///
+    /// # Parameters
+    ///
+    /// - `inputs`: Numbers to be factored.
+    pub fn run<I>(&self, inputs: I) -> Batch<I::IntoIter>
+    where
+        I: IntoIterator<Item = Integer>,
+    {
+        let (job_sender, jobs) = mpsc::channel::<(usize, Integer)>();
+        let (result_sender, results) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(jobs));
+        // Inputs of the same size use the same bounds, the workers already use all the threads
+        let options = self.options.clone().share_stages().threads(1);
+
+        for _ in 0..self.threads {
+            let jobs = Arc::clone(&jobs);
+            let results = result_sender.clone();
+            let options = options.clone();
+            thread::spawn(move || loop {
+                // The lock is released before factoring
+                let job = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                let Ok((index, n)) = job else {
+                    break;
+                };
+                // Panics are raised again by the iterator, instead of losing the input
+                let result =
+                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| options.run(&n)));
+                if results.send((index, n, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Batch {
+            inputs: inputs.into_iter(),
+            jobs: Some(job_sender),
+            results: Mutex::new(results),
+            pending: BTreeMap::new(),
+            next_index: 0,
+            next_delivery: 0,
+            in_flight: 0,
+            max_in_flight: self.max_in_flight.unwrap_or(2 * self.threads),
+            delivery: self.delivery,
+        }
+    }
+}
+
+/// Factors each input with `options`, on the number of threads of the options.
+///
+/// Same as collecting the results of [`BatchOptions::run`], the setup of the
+/// stages and the threads are shared by all the inputs. Results are in the
+/// order of the inputs.
+///
+/// ```
+/// use ecm::{ecm_many, EcmOptions};
+/// use rug::Integer;
+///
+/// let inputs: Vec<Integer> = (1_000_000u64..1_000_100).map(Integer::from).collect();
+/// let results = ecm_many(&inputs, &EcmOptions::new());
+/// for (n, result) in inputs.iter().zip(results) {
+///     assert_eq!(result.unwrap().product(), *n);
+/// }
+/// ```
+///
+/// # Parameters
+///
+/// - `inputs`: Numbers to be factored.
+/// - `options`: Settings of each factorization.
+pub fn ecm_many(inputs: &[Integer], options: &EcmOptions) -> Vec<Result<Factorization, Error>> {
+    BatchOptions::new(options.clone())
+        .run(inputs.iter().cloned())
+        .map(|result| result.result)
+        .collect()
+}
+
+type WorkerResult = (usize, Integer, thread::Result<Result<Factorization, Error>>);
+
+/// Iterator over the results of a batch, see [`BatchOptions::run`].
+pub struct Batch<I> {
+    inputs: I,
+    /// Sender of the inputs to the workers, `None` once the inputs are exhausted.
+    jobs: Option<Sender<(usize, Integer)>>,
+    /// Behind a mutex so that the batch is `Sync`, only locked through `&mut self`.
+    results: Mutex<Receiver<WorkerResult>>,
+    /// Results received before the ones of previous inputs, with [`Delivery::Ordered`].
+    pending: BTreeMap<usize, (Integer, Result<Factorization, Error>)>,
+    /// Index of the next input pulled.
+    next_index: usize,
+    /// Index of the next result yielded, with [`Delivery::Ordered`].
+    next_delivery: usize,
+    /// Inputs pulled but not yet yielded.
+    in_flight: usize,
+    max_in_flight: usize,
+    delivery: Delivery,
+}
+
+impl<I: Iterator<Item = Integer>> Batch<I> {
+    /// Pulls inputs until `max_in_flight` inputs are in flight.
+    fn fill(&mut self) {
+        while self.in_flight < self.max_in_flight {
+            let Some(jobs) = &self.jobs else {
+                return;
+            };
+            match self.inputs.next() {
+                Some(n) => {
+                    // Workers only stop once the sender is dropped
+                    let _ = jobs.send((self.next_index, n));
+                    self.next_index += 1;
+                    self.in_flight += 1;
+                }
+                None => self.jobs = None,
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Option<(usize, Integer, Result<Factorization, Error>)> {
+        let results = self.results.get_mut().unwrap_or_else(|e| e.into_inner());
+        let (index, n, result) = results.recv().ok()?;
+        match result {
+            Ok(result) => Some((index, n, result)),
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Integer>> Iterator for Batch<I> {
+    type Item = BatchResult;
+
+    fn next(&mut self) -> Option<BatchResult> {
+        self.fill();
+        if self.in_flight == 0 {
+            return None;
+        }
+
+        let (index, n, result) = match self.delivery {
+            Delivery::Unordered => self.receive()?,
+            Delivery::Ordered => loop {
+                if let Some((n, result)) = self.pending.remove(&self.next_delivery) {
+                    self.next_delivery += 1;
+                    break (self.next_delivery - 1, n, result);
+                }
+                let (index, n, result) = self.receive()?;
+                self.pending.insert(index, (n, result));
+            },
+        };
+        self.in_flight -= 1;
+        Some(BatchResult { index, n, result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> impl Iterator<Item = Integer> {
+        // Products of two numbers, all small enough to be fast
+        (0..40u64).map(|i| Integer::from(1_000_003 + 2 * i) * Integer::from(999_983 - 2 * i))
+    }
+
+    #[test]
+    fn ordered() {
+        let options = BatchOptions::new(EcmOptions::new().seed(1)).threads(3);
+        let results: Vec<_> = options.run(inputs()).collect();
+        assert_eq!(results.len(), 40);
+        for ((i, result), n) in results.into_iter().enumerate().zip(inputs()) {
+            assert_eq!(result.index, i);
+            assert_eq!(result.n, n);
+            assert_eq!(result.result.unwrap().product(), n);
+        }
+    }
+
+    #[test]
+    fn unordered() {
+        let options = BatchOptions::new(EcmOptions::new().seed(1))
+            .threads(4)
+            .max_in_flight(2)
+            .delivery(Delivery::Unordered);
+        let mut indices: Vec<_> = options
+            .run(inputs())
+            .map(|result| {
+                assert_eq!(result.result.unwrap().product(), result.n);
+                result.index
+            })
+            .collect();
+        indices.sort();
+        assert_eq!(indices, (0..40).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn backpressure() {
+        // Counts the inputs pulled from the iterator
+        let pulled = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&pulled);
+        let inputs = inputs().inspect(move |_| *counter.lock().unwrap() += 1);
+
+        let mut batch = BatchOptions::new(EcmOptions::new())
+            .threads(2)
+            .max_in_flight(5)
+            .run(inputs);
+        assert!(batch.next().is_some());
+        assert_eq!(*pulled.lock().unwrap(), 5);
+        assert!(batch.next().is_some());
+        assert_eq!(*pulled.lock().unwrap(), 6);
+        assert_eq!(batch.count(), 38);
+    }
+
+    #[test]
+    fn many() {
+        let inputs: Vec<Integer> = inputs().collect();
+        let results = ecm_many(&inputs, &EcmOptions::new().seed(1));
+        assert_eq!(results.len(), inputs.len());
+        for (n, result) in inputs.iter().zip(results) {
+            let factors = result.unwrap();
+            assert!(factors.is_complete());
+            assert_eq!(factors.product(), *n);
+        }
+        assert!(ecm_many(&[], &EcmOptions::new()).is_empty());
+    }
+
+    #[test]
+    fn threads() {
+        let options = EcmOptions::new().threads(3);
+        assert_eq!(BatchOptions::new(options.clone()).threads, 3);
+        assert_eq!(
+            BatchOptions::new(options).threads(0).threads,
+            available_threads()
+        );
+        assert_eq!(
+            BatchOptions::new(EcmOptions::new().threads(0)).threads,
+            available_threads()
+        );
+
+        let inputs: Vec<Integer> = inputs().take(4).collect();
+        for result in ecm_many(&inputs, &EcmOptions::new().threads(1)) {
+            assert!(result.unwrap().is_complete());
+        }
+    }
+
+    #[test]
+    fn errors_and_empty() {
+        let options = BatchOptions::new(EcmOptions::new());
+        assert_eq!(options.run(Vec::new()).count(), 0);
+
+        let results: Vec<_> = options.run([Integer::new(), Integer::from(15)]).collect();
+        assert!(matches!(results[0].result, Err(Error::ZeroInput)));
+        assert_eq!(results[1].result.as_ref().unwrap().len(), 2);
+    }
+}
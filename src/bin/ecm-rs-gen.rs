@@ -0,0 +1,75 @@
+//! Writes a deterministic benchmark corpus to the standard output, one entry per line.
+//!
+//! ```text
+//! ecm-rs-gen [--seed SEED] semiprime <P_BITS> <Q_BITS> <COUNT>
+//! ecm-rs-gen [--seed SEED] almost-prime <K> <BITS> <COUNT>
+//! ecm-rs-gen special <BASE> <MIN_EXP> <MAX_EXP>
+//! ```
+
+use ecm::corpus::{almost_primes, semiprimes, special_forms, Entry};
+use std::{io::Write, process::ExitCode, str::FromStr};
+
+const USAGE: &str = "\
+Usage: ecm-rs-gen [--seed SEED] semiprime <P_BITS> <Q_BITS> <COUNT>
+       ecm-rs-gen [--seed SEED] almost-prime <K> <BITS> <COUNT>
+       ecm-rs-gen special <BASE> <MIN_EXP> <MAX_EXP>
+
+Each line is `name<TAB>n<TAB>factors`, factors being `?` when unknown.
+The default seed is 1.";
+
+fn arg<T: FromStr>(args: &[String], i: usize, name: &str) -> Result<T, String> {
+    let value = args.get(i).ok_or_else(|| format!("missing {name}"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid {name}: {value}"))
+}
+
+fn corpus(mut args: &[String]) -> Result<Vec<Entry>, String> {
+    let mut seed = 1;
+    if args.first().map(String::as_str) == Some("--seed") {
+        seed = arg(args, 1, "seed")?;
+        args = &args[2..];
+    }
+
+    let kind = args.first().ok_or("missing corpus kind")?;
+    let (a, b, c) = (
+        arg::<u32>(args, 1, "first parameter")?,
+        arg::<u32>(args, 2, "second parameter")?,
+        arg::<u32>(args, 3, "third parameter")?,
+    );
+    if args.len() > 4 {
+        return Err(format!("unexpected argument: {}", args[4]));
+    }
+    match kind.as_str() {
+        "semiprime" if a >= 2 && b >= 2 => Ok(semiprimes(seed, a, b, c as usize)),
+        "almost-prime" if b >= 2 => Ok(almost_primes(seed, a, b, c as usize)),
+        "special" if a >= 2 => Ok(special_forms(a, b..=c)),
+        "semiprime" | "almost-prime" => Err("primes have at least 2 bits".to_string()),
+        "special" => Err("the base is at least 2".to_string()),
+        _ => Err(format!("unknown corpus kind: {kind}")),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    match corpus(&args) {
+        Ok(corpus) => {
+            let mut stdout = std::io::stdout().lock();
+            for entry in corpus {
+                if writeln!(stdout, "{entry}").is_err() {
+                    return ExitCode::FAILURE;
+                }
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Handle to abort a factorization from another thread.
+///
+/// Clones share the same flag: cancelling any of them cancels all the
+/// factorizations using the token. The flag is checked between curves and
+/// between the blocks of stage 2, the factorization then fails with
+/// [`Error::Cancelled`](crate::Error::Cancelled), carrying the factors found so far.
+///
+/// ```
+/// use ecm::{CancelToken, EcmOptions, Error};
+/// use rug::Integer;
+///
+/// let token = CancelToken::new();
+/// let options = EcmOptions::new().cancel_token(token.clone());
+/// token.cancel();
+///
+/// let n = Integer::from(2 * 398883434337287u64);
+/// let Err(Error::Cancelled(partial)) = options.run(&n) else {
+///     panic!("not cancelled");
+/// };
+/// assert_eq!(partial.product(), n);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Creates a token which is not cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Requests the cancellation of the factorizations using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true once [`CancelToken::cancel`] was called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancelToken {
+    /// Uses an existing flag, the factorization is cancelled once it is set to true.
+    fn from(flag: Arc<AtomicBool>) -> CancelToken {
+        CancelToken(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_flag() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let token = CancelToken::from(Arc::clone(&flag));
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(flag.load(Ordering::Relaxed));
+        assert!(!CancelToken::new().is_cancelled());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{ecm::Pretest, FactorMethod, Parametrization, ResumeState};
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// Version of the checkpoint format, increased on incompatible changes.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Error occured while restoring a [`Checkpoint`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum CheckpointError {
+    /// The checkpoint was written for an incompatible version.
+    #[error("Unsupported checkpoint version {0}")]
+    UnsupportedVersion(u32),
+    /// The checkpoint is of the factorization of another number.
+    #[error("The checkpoint is of the factorization of {0}")]
+    OtherNumber(Integer),
+    /// The factorizer already started, checkpoints are restored before the first step.
+    #[error("The factorization already started")]
+    AlreadyStarted,
+    /// The checkpoint is of curves of another family than the factorizer's one.
+    #[error("The checkpoint is of curves of the {0:?} parametrization")]
+    OtherParametrization(Parametrization),
+}
+
+/// Internal state of a [`Factorizer`](crate::Factorizer) between two steps, with the `serde` feature.
+///
+/// Unlike [`ResumeState`], a checkpoint also holds the factors found so far,
+/// the composites left and the state of the pseudorandom generator: a restored
+/// factorizer continues exactly where the checkpoint was taken, running the
+/// same curves as the original run would have. It can be written in any serde
+/// format, e.g. JSON or bincode, to continue a long factorization after a crash.
+///
+/// The settings of the run, e.g. the mode or the progress sink, are not part
+/// of the checkpoint, and the statistics start over. The family of the curves
+/// is, see [`Factorizer::set_parametrization`](crate::Factorizer::set_parametrization):
+/// the same sigmas give other curves in another family.
+///
+/// ```
+/// use ecm::{Checkpoint, EcmOptions, StepOutcome};
+/// use rug::Integer;
+/// use std::time::Duration;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let options = EcmOptions::new().b1(2_000).b2(160_000).max_curves(100).seed(1);
+/// let mut factorizer = options.factorizer(&n).unwrap();
+/// factorizer.step(Duration::ZERO).unwrap();
+/// let json = serde_json::to_string(&factorizer.checkpoint()).unwrap();
+///
+/// // After a crash
+/// let checkpoint: Checkpoint = serde_json::from_str(&json).unwrap();
+/// let mut resumed = options.factorizer(checkpoint.n()).unwrap();
+/// resumed.restore_checkpoint(checkpoint).unwrap();
+/// while resumed.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+/// assert_eq!(resumed.into_factors().product(), n);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub(crate) version: u32,
+    /// Number being factored.
+    pub(crate) n: Integer,
+    /// Part of n not yet through trial division.
+    pub(crate) pending: Option<Integer>,
+    /// Primes tried by trial division on `pending`.
+    pub(crate) trial_primes: usize,
+    /// n is `pending^power`.
+    pub(crate) power: usize,
+    /// Prime factors found, with their multiplicity and the method which found them.
+    pub(crate) factors: Vec<(Integer, usize, Option<FactorMethod>)>,
+    /// Composites on which the factorization failed.
+    pub(crate) cofactors: Vec<(Integer, usize)>,
+    /// Composites left to factor.
+    pub(crate) composites: Vec<(Integer, usize)>,
+    /// Next method run before the curves on the composites left, none in
+    /// checkpoints written before they were recorded.
+    #[serde(default)]
+    pub(crate) pretests: Vec<(Integer, Pretest)>,
+    /// Index of the next seed of p+1 tried on the last composite, see
+    /// [`DriverMode::PPlusOne`](crate::DriverMode::PPlusOne).
+    #[serde(default)]
+    pub(crate) pp1_seed: usize,
+    pub(crate) progress: ResumeState,
+    /// Seed of the pseudorandom generator, drawn from it when taking the checkpoint.
+    pub(crate) seed: u64,
+    /// Family of the curves run, Suyama's one in checkpoints written before it was recorded.
+    #[serde(default)]
+    pub(crate) parametrization: Parametrization,
+}
+
+impl Checkpoint {
+    /// Number being factored, to create the factorizer restoring the checkpoint.
+    pub fn n(&self) -> &Integer {
+        &self.n
+    }
+
+    /// Progress of the factorization, see [`Factorizer::resume_state`](crate::Factorizer::resume_state).
+    pub fn progress(&self) -> &ResumeState {
+        &self.progress
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EcmOptions, Factorizer, StepOutcome};
+    use std::time::Duration;
+
+    fn finish(factorizer: &mut Factorizer) {
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+    }
+
+    #[test]
+    fn restore() {
+        // Small factors, a square and two factors found by curves
+        let n = Integer::from_str_radix("1048843748843747081710817107", 10).unwrap()
+            * Integer::from(398883434337287u64)
+            * 1_000_003u64
+            * 1_000_003u64
+            * 360u64;
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .max_curves(1_000)
+            .seed(1)
+            .small_cofactor_bits(0);
+
+        for steps in 0..6 {
+            let mut factorizer = options.factorizer(&n).unwrap();
+            for _ in 0..steps {
+                factorizer.step(Duration::ZERO).unwrap();
+            }
+            let json = serde_json::to_string(&factorizer.checkpoint()).unwrap();
+            let checkpoint: Checkpoint = serde_json::from_str(&json).unwrap();
+            assert_eq!(checkpoint.n(), &n);
+
+            let mut resumed = options.factorizer(&n).unwrap();
+            resumed.restore_checkpoint(checkpoint).unwrap();
+            finish(&mut factorizer);
+            finish(&mut resumed);
+            // Same curves, same factors found by the same methods
+            assert_eq!(resumed.resume_state(), factorizer.resume_state());
+            assert_eq!(resumed.stats().factors, factorizer.stats().factors);
+            let factors = factorizer.into_factors();
+            assert!(factors.is_complete());
+            assert_eq!(resumed.into_factors(), factors);
+        }
+    }
+
+    #[test]
+    fn pretests() {
+        // Split by none of the methods run before the curves
+        let n = Integer::from_str_radix("100000000000000000000000000319", 10).unwrap()
+            * Integer::from_str_radix("300000000000000000000000000007", 10).unwrap();
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .trial_division_bound(0)
+            .seed(1);
+
+        let expected = [Pretest::Rho, Pretest::PMinusOne, Pretest::PPlusOne];
+        for (steps, pretest) in (2..).zip(expected) {
+            let mut factorizer = options.factorizer(&n).unwrap();
+            for _ in 0..steps {
+                factorizer.step(Duration::ZERO).unwrap();
+            }
+            let checkpoint = factorizer.checkpoint();
+            assert_eq!(checkpoint.pretests, [(n.clone(), pretest)]);
+
+            let json = serde_json::to_string(&checkpoint).unwrap();
+            let mut resumed = options.factorizer(&n).unwrap();
+            resumed
+                .restore_checkpoint(serde_json::from_str(&json).unwrap())
+                .unwrap();
+            // The resumed factorizer continues with the same method
+            factorizer.step(Duration::ZERO).unwrap();
+            resumed.step(Duration::ZERO).unwrap();
+            let pretests = factorizer.checkpoint().pretests;
+            assert_eq!(resumed.checkpoint().pretests, pretests);
+            assert_ne!(pretests, checkpoint.pretests);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let n = Integer::from(398883434337287u64);
+        let options = EcmOptions::new().seed(1);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        let checkpoint = factorizer.checkpoint();
+
+        let mut other = options.factorizer(&Integer::from(15)).unwrap();
+        assert_eq!(
+            other.restore_checkpoint(checkpoint.clone()),
+            Err(CheckpointError::OtherNumber(n.clone()))
+        );
+        let version = Checkpoint {
+            version: CHECKPOINT_VERSION + 1,
+            ..checkpoint.clone()
+        };
+        let mut resumed = options.factorizer(&n).unwrap();
+        assert_eq!(
+            resumed.restore_checkpoint(version),
+            Err(CheckpointError::UnsupportedVersion(CHECKPOINT_VERSION + 1))
+        );
+
+        let other = options.clone().parametrization(Parametrization::Z12);
+        let mut other = other.factorizer(&n).unwrap();
+        assert_eq!(
+            other.restore_checkpoint(checkpoint.clone()),
+            Err(CheckpointError::OtherParametrization(
+                Parametrization::Suyama
+            ))
+        );
+        // Written before the parametrization and the state of the methods run
+        // before the curves were recorded
+        let mut json = serde_json::to_value(&checkpoint).unwrap();
+        json.as_object_mut().unwrap().remove("parametrization");
+        json.as_object_mut().unwrap().remove("pretests");
+        json.as_object_mut().unwrap().remove("pp1_seed");
+        let old: Checkpoint = serde_json::from_value(json).unwrap();
+        assert_eq!(old, checkpoint);
+
+        factorizer.step(Duration::MAX).unwrap();
+        assert_eq!(
+            factorizer.restore_checkpoint(checkpoint),
+            Err(CheckpointError::AlreadyStarted)
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::Error;
+use rug::Integer;
+
+/// Known most significant bits of a prime factor p of n.
+///
+/// The factor is `p = high_bits * 2^unknown_bits + x` with `0 <= x < 2^unknown_bits`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighBitsHint {
+    /// Known most significant bits of the factor.
+    pub high_bits: Integer,
+    /// Number of unknown least significant bits.
+    pub unknown_bits: u32,
+}
+
+impl HighBitsHint {
+    /// Initial parameters for the HighBitsHint struct.
+    ///
+    /// # Parameters
+    ///
+    /// - `high_bits`: Known most significant bits of the factor.
+    /// - `unknown_bits`: Number of unknown least significant bits.
+    pub fn new(high_bits: Integer, unknown_bits: u32) -> HighBitsHint {
+        HighBitsHint {
+            high_bits,
+            unknown_bits,
+        }
+    }
+}
+
+/// Recovers a factor of n from the known high bits of this factor.
+///
+/// This is the integration point for partial key exposure attacks:
+/// Coppersmith's method finds p as soon as about half of its bits are known,
+/// using lattice reduction which is not implemented in this crate.
+/// Implement this trait on top of a lattice reduction library to plug it in,
+/// see [`Factorizer::set_partial_key_solver`](crate::Factorizer::set_partial_key_solver).
+pub trait PartialKeySolver: Send + Sync {
+    /// Returns a factor of `n` matching the hint, if any.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `hint`: Known high bits of the factor.
+    fn solve(&self, n: &Integer, hint: &HighBitsHint) -> Option<Integer>;
+}
+
+/// Exhaustive search of the unknown bits.
+///
+/// Only usable when few bits are unknown, bigger hints are rejected, as well
+/// as hints of 64 unknown bits or more whatever `max_unknown_bits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BruteForceSolver {
+    /// Maximum number of unknown bits searched.
+    pub max_unknown_bits: u32,
+}
+
+impl Default for BruteForceSolver {
+    fn default() -> Self {
+        BruteForceSolver {
+            max_unknown_bits: 24,
+        }
+    }
+}
+
+impl PartialKeySolver for BruteForceSolver {
+    fn solve(&self, n: &Integer, hint: &HighBitsHint) -> Option<Integer> {
+        if hint.unknown_bits > self.max_unknown_bits {
+            return None;
+        }
+
+        let candidates = 1u64.checked_shl(hint.unknown_bits)?;
+        let base = Integer::from(&hint.high_bits << hint.unknown_bits);
+        (0..candidates)
+            .map(|x| Integer::from(&base + x))
+            .find(|p| *p > 1 && n.is_divisible(p))
+    }
+}
+
+/// Factors n using the known high bits of one of its factors.
+///
+/// The factor returned by the solver is checked, then `(p, n/p)` is returned.
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `hint`: Known high bits of the factor.
+/// - `solver`: Solver used to recover the unknown bits.
+pub fn factor_with_hint(
+    n: &Integer,
+    hint: &HighBitsHint,
+    solver: &(impl PartialKeySolver + ?Sized),
+) -> Result<(Integer, Integer), Error> {
+    match solver.solve(n, hint) {
+        Some(p) if p > 1 && p < *n && n.is_divisible(&p) => {
+            let q = Integer::from(n / &p);
+            Ok((p, q))
+        }
+        _ => Err(Error::PartialKeyFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn brute_force() {
+        let p = Integer::from_str("100327907731").unwrap();
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q);
+        let hint = HighBitsHint::new(Integer::from(&p >> 16), 16);
+
+        assert_eq!(
+            factor_with_hint(&n, &hint, &BruteForceSolver::default()).unwrap(),
+            (p, q)
+        );
+    }
+
+    #[test]
+    fn wrong_hint() {
+        let n = Integer::from(3233);
+        let hint = HighBitsHint::new(Integer::from(9), 2);
+        assert!(matches!(
+            factor_with_hint(&n, &hint, &BruteForceSolver::default()),
+            Err(Error::PartialKeyFailed)
+        ));
+    }
+
+    #[test]
+    fn too_many_unknown_bits() {
+        let n = Integer::from(3233);
+        let hint = HighBitsHint::new(Integer::from(0), 40);
+        assert!(matches!(
+            factor_with_hint(&n, &hint, &BruteForceSolver::default()),
+            Err(Error::PartialKeyFailed)
+        ));
+
+        // Not searched even if allowed
+        let solver = BruteForceSolver {
+            max_unknown_bits: u32::MAX,
+        };
+        for unknown_bits in [64, 100] {
+            let hint = HighBitsHint::new(Integer::from(0), unknown_bits);
+            assert_eq!(solver.solve(&n, &hint), None);
+        }
+    }
+
+    #[test]
+    fn bogus_solver() {
+        struct Bogus;
+        impl PartialKeySolver for Bogus {
+            fn solve(&self, n: &Integer, _: &HighBitsHint) -> Option<Integer> {
+                Some(n.clone())
+            }
+        }
+
+        let hint = HighBitsHint::new(Integer::from(0), 8);
+        assert!(matches!(
+            factor_with_hint(&Integer::from(3233), &hint, &Bogus),
+            Err(Error::PartialKeyFailed)
+        ));
+    }
+}
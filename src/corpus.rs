@@ -0,0 +1,199 @@
+//! Deterministic benchmark corpora.
+//!
+//! Every number is derived from a seed with the GMP Mersenne twister, drawing
+//! 32 bits at a time, so a corpus is the same on every machine and benchmarks
+//! run on different hardware compare the same work.
+//! The `ecm-rs-gen` binary (feature `gen`) writes corpora to files.
+//!
+//! ```
+//! use ecm::corpus::semiprimes;
+//!
+//! let corpus = semiprimes(1, 20, 24, 3);
+//! assert_eq!(corpus, semiprimes(1, 20, 24, 3));
+//! for entry in &corpus {
+//!     assert_eq!(entry.factors.as_ref().unwrap().product(), entry.n);
+//! }
+//! ```
+
+use crate::{ecm::random_below, Factorization};
+use rug::{rand::RandState, Integer};
+use std::{fmt, ops::RangeInclusive};
+
+/// Number of a corpus, with its factorization when known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Name of the entry, unique in its corpus.
+    pub name: String,
+    /// The number.
+    pub n: Integer,
+    /// Complete factorization, `None` for special forms.
+    pub factors: Option<Factorization>,
+}
+
+/// Formats the entry as a tab separated line `name n factors`, `?` standing for unknown factors.
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t", self.name, self.n)?;
+        match &self.factors {
+            Some(factors) => write!(f, "{factors}"),
+            None => f.write_str("?"),
+        }
+    }
+}
+
+fn rand_state(seed: u64) -> RandState<'static> {
+    let mut rgen = RandState::new();
+    rgen.seed(&seed.into());
+    rgen
+}
+
+/// Returns a random prime of exactly `bits` bits.
+///
+/// # Parameters
+///
+/// - `bits`: Size of the prime, at least 2.
+/// - `rgen`: Pseudorandom generator.
+///
+/// # Panics
+///
+/// Panics if `bits` is below 2.
+pub fn random_prime(bits: u32, rgen: &mut RandState<'_>) -> Integer {
+    assert!(bits >= 2, "primes have at least 2 bits");
+
+    let low = Integer::from(1) << (bits - 1);
+    loop {
+        // Smallest prime above a random integer of `bits` bits
+        let prime = (random_below(&low, rgen) + &low - 1u32).next_prime();
+        if prime.significant_bits() == bits {
+            return prime;
+        }
+    }
+}
+
+/// Products of two primes of chosen sizes.
+///
+/// # Parameters
+///
+/// - `seed`: Seed of the pseudorandom generator.
+/// - `p_bits`: Size of the first prime, at least 2.
+/// - `q_bits`: Size of the second prime, at least 2.
+/// - `count`: Number of semiprimes.
+pub fn semiprimes(seed: u64, p_bits: u32, q_bits: u32, count: usize) -> Vec<Entry> {
+    let mut rgen = rand_state(seed);
+    (0..count)
+        .map(|i| {
+            let p = random_prime(p_bits, &mut rgen);
+            let q = random_prime(q_bits, &mut rgen);
+            Entry {
+                name: format!("semiprime-{p_bits}x{q_bits}-{i}"),
+                n: Integer::from(&p * &q),
+                factors: Some([(p, 1), (q, 1)].into_iter().collect()),
+            }
+        })
+        .collect()
+}
+
+/// Products of `k` primes of the same size, not necessarily distinct.
+///
+/// # Parameters
+///
+/// - `seed`: Seed of the pseudorandom generator.
+/// - `k`: Number of prime factors.
+/// - `bits`: Size of each prime, at least 2.
+/// - `count`: Number of almost primes.
+pub fn almost_primes(seed: u64, k: u32, bits: u32, count: usize) -> Vec<Entry> {
+    let mut rgen = rand_state(seed);
+    (0..count)
+        .map(|i| {
+            let primes: Vec<Integer> = (0..k).map(|_| random_prime(bits, &mut rgen)).collect();
+            Entry {
+                name: format!("almost-prime-{k}x{bits}-{i}"),
+                n: primes.iter().product(),
+                factors: Some(primes.into_iter().map(|p| (p, 1)).collect()),
+            }
+        })
+        .collect()
+}
+
+/// Numbers `base^e - 1` and `base^e + 1` for every `e` in `exponents`.
+///
+/// Their factors are left unknown.
+///
+/// # Parameters
+///
+/// - `base`: Base of the powers, at least 2.
+/// - `exponents`: Exponents of the powers.
+pub fn special_forms(base: u32, exponents: RangeInclusive<u32>) -> Vec<Entry> {
+    exponents
+        .flat_map(|e| {
+            let power = Integer::from(Integer::u_pow_u(base, e));
+            [
+                Entry {
+                    name: format!("{base}^{e}-1"),
+                    n: Integer::from(&power - 1),
+                    factors: None,
+                },
+                Entry {
+                    name: format!("{base}^{e}+1"),
+                    n: power + 1,
+                    factors: None,
+                },
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let corpus = semiprimes(42, 32, 40, 4);
+        assert_eq!(corpus, semiprimes(42, 32, 40, 4));
+        assert_ne!(corpus, semiprimes(43, 32, 40, 4));
+
+        // Pinned, a change of the generator would break comparisons with older corpora
+        assert_eq!(
+            corpus[0].to_string(),
+            "semiprime-32x40-0\t3342785896540618652743\t3193817797 * 1046642641819"
+        );
+        for (i, entry) in corpus.iter().enumerate() {
+            assert_eq!(entry.name, format!("semiprime-32x40-{i}"));
+            let factors: Vec<_> = entry.factors.as_ref().unwrap().iter().collect();
+            assert_eq!(factors.len(), 2);
+            let bits: Vec<_> = factors.iter().map(|(p, _)| p.significant_bits()).collect();
+            assert_eq!(bits, [32, 40]);
+            assert!(entry.factors.as_ref().unwrap().is_complete());
+            assert_eq!(entry.factors.as_ref().unwrap().product(), entry.n);
+        }
+    }
+
+    #[test]
+    fn almost() {
+        for entry in almost_primes(1, 4, 16, 5) {
+            let factors = entry.factors.as_ref().unwrap();
+            assert!(factors.is_complete());
+            assert_eq!(factors.iter().map(|(_, m)| m).sum::<usize>(), 4);
+            assert_eq!(factors.product(), entry.n);
+        }
+        assert!(almost_primes(1, 3, 2, 20).iter().all(|entry| {
+            entry
+                .factors
+                .as_ref()
+                .unwrap()
+                .iter()
+                .all(|(p, _)| *p == 2 || *p == 3)
+        }));
+    }
+
+    #[test]
+    fn special() {
+        let corpus = special_forms(2, 3..=4);
+        let lines: Vec<_> = corpus.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            ["2^3-1\t7\t?", "2^3+1\t9\t?", "2^4-1\t15\t?", "2^4+1\t17\t?"]
+        );
+    }
+}
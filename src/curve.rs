@@ -0,0 +1,483 @@
+use crate::{ecm::pow_mod, point::Point, stats::record_inversion};
+use rug::Integer;
+
+/// Elliptic curve in Montgomery form modulo n, with a starting point.
+///
+/// The curve is `b*y^2 = x^3 + a*x^2 + x (mod n)` and is only built by
+/// parametrizations such as [`SuyamaCurve::derive`], after checking that all the
+/// inversions needed to compute it succeeded modulo n.
+/// Holding a `Curve` is therefore the proof that its parameters are well defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Curve {
+    a_24: Integer,
+    x_cord: Integer,
+    z_cord: Integer,
+    modulus: Integer,
+}
+
+impl Curve {
+    /// Curve `b*y^2 = x^3 + a*x^2 + x (mod n)` with the starting point `(x0 : 1)`.
+    ///
+    /// Returns [`FactorFound`] if the curve is singular modulo a factor of n,
+    /// i.e. if `a^2 - 4` is not invertible, or if n is even.
+    ///
+    /// # Parameters
+    ///
+    /// - `a`: Parameter of the curve in Montgomery form.
+    /// - `x0`: X coordinate of the starting point.
+    /// - `n`: Modulus.
+    pub fn montgomery(a: &Integer, x0: &Integer, n: &Integer) -> Result<Curve, FactorFound> {
+        let discriminant = Integer::from(a.square_ref()) - 4u32;
+        let g = discriminant.gcd(n);
+        if g != 1 {
+            return Err(FactorFound(g));
+        }
+
+        let a_24 = Integer::from(a + 2u32) * try_invert_or_factor(&Integer::from(4), n)? % n;
+
+        Ok(Curve {
+            a_24,
+            x_cord: Integer::from(x0 % n),
+            z_cord: Integer::from(1),
+            modulus: n.clone(),
+        })
+    }
+
+    /// Curve of parameter `a_24` with the starting point `(x0 : z0)`.
+    ///
+    /// Returns [`FactorFound`] with `gcd(a^2 - 4, n)` if the curve is singular modulo a factor of n.
+    fn checked(
+        a_24: Integer,
+        x_cord: Integer,
+        z_cord: Integer,
+        n: &Integer,
+    ) -> Result<Curve, FactorFound> {
+        // a^2 - 4 = 16*a_24*(a_24 - 1)
+        let g = (Integer::from(&a_24 - 1u32) * &a_24).gcd(n);
+        if g != 1 {
+            return Err(FactorFound(g));
+        }
+
+        Ok(Curve {
+            a_24,
+            x_cord,
+            z_cord,
+            modulus: n.clone(),
+        })
+    }
+
+    /// Parameter of the curve, equal to `(a + 2)/4 (mod n)`.
+    pub fn a_24(&self) -> &Integer {
+        &self.a_24
+    }
+
+    /// X coordinate of the starting point.
+    pub fn x_cord(&self) -> &Integer {
+        &self.x_cord
+    }
+
+    /// Z coordinate of the starting point.
+    pub fn z_cord(&self) -> &Integer {
+        &self.z_cord
+    }
+
+    /// Modulus n.
+    pub fn modulus(&self) -> &Integer {
+        &self.modulus
+    }
+
+    /// Starting point of the curve.
+    pub fn point(&self) -> Point {
+        Point::new(
+            self.x_cord.clone(),
+            self.z_cord.clone(),
+            self.a_24.clone(),
+            self.modulus.clone(),
+        )
+    }
+
+    /// Starting point of the curve, without copying the parameters.
+    pub fn into_point(self) -> Point {
+        Point::new(self.x_cord, self.z_cord, self.a_24, self.modulus)
+    }
+}
+
+/// Arithmetic of stage 1, see [`Factorizer::set_curve_model`](crate::Factorizer::set_curve_model).
+///
+/// Both models run the same curves and find the same factors,
+/// stage 2 runs in Montgomery form in any case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CurveModel {
+    /// Montgomery ladder of [`Point`], 11 modular multiplications per bit of the multiplier.
+    #[default]
+    Montgomery,
+    /// Twisted Edwards curve in extended coordinates mapped from the Montgomery curve,
+    /// about 9.5 modular multiplications per bit, see [`EdwardsPoint`](crate::EdwardsPoint).
+    Edwards,
+}
+
+/// Divisor of n found while deriving a curve, when an inversion modulo n failed.
+///
+/// The divisor is greater than 1 but may be n itself
+/// when the parameter is degenerate (e.g. `sigma = 0`),
+/// in which case another parameter must be tried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactorFound(pub Integer);
+
+/// Inverse of x modulo n, or the divisor of n which prevents the inversion.
+///
+/// A failed inversion modulo a composite n is a free factor: `gcd(x, n)`
+/// is greater than 1, and a proper factor of n unless x is a multiple of n.
+/// Every inversion of the crate goes through this function, which records
+/// the attempts, failures and factors found, see [`inversion_stats`](crate::inversion_stats).
+///
+/// ```
+/// use ecm::{try_invert_or_factor, FactorFound};
+/// use rug::Integer;
+///
+/// let n = Integer::from(53 * 61);
+/// assert_eq!(try_invert_or_factor(&Integer::from(17), &n), Ok(Integer::from(2092)));
+/// assert_eq!(
+///     try_invert_or_factor(&Integer::from(4 * 53), &n),
+///     Err(FactorFound(Integer::from(53)))
+/// );
+/// ```
+///
+/// # Parameters
+///
+/// - `x`: Number to be inverted.
+/// - `n`: Modulus, positive.
+pub fn try_invert_or_factor(x: &Integer, n: &Integer) -> Result<Integer, FactorFound> {
+    let result = match x.invert_ref(n) {
+        Some(inverse) => Ok(Integer::from(inverse)),
+        None => Err(FactorFound(Integer::from(x.gcd_ref(n)))),
+    };
+    record_inversion(result.as_ref().err().map(|FactorFound(g)| g), n);
+    result
+}
+
+/// Suyama's parametrization of curves with a torsion subgroup of order 12.
+///
+/// With `u = sigma^2 - 5` and `v = 4*sigma`, the curve is
+/// `a = (v - u)^3 * (3*u + v) / (4*u^3*v) - 2` and the starting point `(u^3 : v^3)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuyamaCurve;
+
+impl SuyamaCurve {
+    /// Derives the curve and starting point of `sigma` modulo n.
+    ///
+    /// Returns [`FactorFound`] with `gcd(4*u^3*v, n)` if `4*u^3*v` is not invertible modulo n.
+    ///
+    /// # Parameters
+    ///
+    /// - `sigma`: Parameter of the curve.
+    /// - `n`: Modulus.
+    pub fn derive(sigma: &Integer, n: &Integer) -> Result<Curve, FactorFound> {
+        let three = Integer::from(3);
+
+        let u = (Integer::from(sigma * sigma) - 5u32) % n;
+        let v = Integer::from(sigma * 4u32) % n;
+        let diff = Integer::from(&v - &u);
+        let u_3 = pow_mod(&u, &three, n);
+        let v_3 = pow_mod(&v, &three, n);
+
+        let denominator = Integer::from(4u32 * &u_3) * &v;
+        let inverse = try_invert_or_factor(&denominator, n)?;
+        let a = (pow_mod(&diff, &three, n) * (3u32 * u + v) * inverse - 2u32) % n;
+
+        // n is odd, so 4 is invertible
+        let a_24 = (a + 2u32) * try_invert_or_factor(&Integer::from(4), n)? % n;
+
+        Ok(Curve {
+            a_24,
+            x_cord: u_3,
+            z_cord: v_3,
+            modulus: n.clone(),
+        })
+    }
+}
+
+/// Montgomery's parametrization of curves with a rational torsion subgroup Z/12Z.
+///
+/// With `(u, v) = k*(-2, 4)` on `v^2 = u^3 - 12*u` and `t = (u - 6)*(u + 2)/(u^2 + 12*u - 12)`,
+/// the curve is `a = (-3*t^4 - 6*t^2 + 1)/(4*t^3)` and the starting point `((3*t^2 + 1)/(4*t) : 1)`,
+/// whose group order is a multiple of 12 modulo every prime, as with [`SuyamaCurve`],
+/// with the point of order 4 defined over the rationals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Z12Curve;
+
+impl Z12Curve {
+    /// Derives the curve and starting point of the multiple `k` modulo n.
+    ///
+    /// Returns [`FactorFound`] if an inversion fails modulo n, e.g. n itself for `k` 0 or 1.
+    ///
+    /// # Parameters
+    ///
+    /// - `k`: Multiple of the point `(-2, 4)`, parameter of the curve.
+    /// - `n`: Modulus.
+    pub fn derive(k: &Integer, n: &Integer) -> Result<Curve, FactorFound> {
+        let (u, _) = weierstrass_mul(k, (-2, 4), -12, n)?;
+
+        // t = t_n/t_d, a_24 = (a + 2)/4 = -(t - 1)^3*(3*t + 1)/(16*t^3)
+        let t_n = Integer::from(&u - 6u32) * Integer::from(&u + 2u32) % n;
+        let t_d = (Integer::from(&u + 12u32) * &u - 12u32) % n;
+        let t_n_3 = pow_mod(&t_n, &Integer::from(3), n);
+        let inverse = try_invert_or_factor(&(Integer::from(&t_n_3 * &t_d) * 16u32), n)?;
+        let t_1 = Integer::from(&t_n - &t_d);
+        let a_24 = -(pow_mod(&t_1, &Integer::from(3), n) * (Integer::from(3u32 * &t_n) + &t_d)) % n
+            * inverse
+            % n;
+
+        let x_cord = (Integer::from(t_n.square_ref()) * 3u32 + t_d.square_ref()) % n;
+        let z_cord = t_n * t_d * 4u32 % n;
+        Curve::checked(a_24, x_cord, z_cord, n)
+    }
+}
+
+/// Parametrization of curves with a rational torsion subgroup Z/2Z x Z/8Z.
+///
+/// With `(x, y) = k*(4, 8)` on `y^2 = x^3 + 4*x - 16` and `u = (8 - y)/(y - 2*x)`,
+/// the curve is `(a + 2)/4 = (u^2 + 2*u + 2)^4/(16*u^2*(u + 1)^2*(u + 2)^2)` and the
+/// starting point `(-u^2/2 : 1)`, whose group order is a multiple of 16 modulo every prime.
+/// It is the Montgomery form of the Edwards curve of `d = (2*x8^2 - 1)/x8^4`, with
+/// `x8 = (u^2 + 2*u + 2)/(u^2 - 2)`, see Bernstein, Birkner, Lange, Peters,
+/// "ECM using Edwards curves".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Z2xZ8Curve;
+
+impl Z2xZ8Curve {
+    /// Derives the curve and starting point of the multiple `k` modulo n.
+    ///
+    /// Returns [`FactorFound`] if an inversion fails modulo n, e.g. n itself for `k` 0 or 1.
+    ///
+    /// # Parameters
+    ///
+    /// - `k`: Multiple of the point `(4, 8)`, parameter of the curve.
+    /// - `n`: Modulus.
+    pub fn derive(k: &Integer, n: &Integer) -> Result<Curve, FactorFound> {
+        let (x, y) = weierstrass_mul(k, (4, 8), 4, n)?;
+
+        // u = u_n/u_d
+        let u_n = Integer::from(8 - &y) % n;
+        let u_d = (y - 2u32 * x) % n;
+        let u_n_d = Integer::from(&u_n * &u_d) % n;
+        let u_n_2 = Integer::from(u_n.square_ref()) % n;
+        let u_d_2 = Integer::from(u_d.square_ref()) % n;
+
+        let numerator = (Integer::from(&u_n_2 + 2u32 * &u_n_d) + 2u32 * &u_d_2) % n;
+        let denominator =
+            Integer::from(&u_n + &u_d) * &u_n_d * Integer::from(&u_n + 2u32 * &u_d) % n;
+        let inverse = try_invert_or_factor(&(denominator.square() * 16u32), n)?;
+        let a_24 = pow_mod(&numerator, &Integer::from(4), n) * inverse % n;
+
+        Curve::checked(a_24, -u_n_2 % n, 2u32 * u_d_2, n)
+    }
+}
+
+/// Family of the curves run for each sigma, see
+/// [`Factorizer::set_parametrization`](crate::Factorizer::set_parametrization).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Parametrization {
+    /// [`SuyamaCurve`], group orders multiple of 12.
+    #[default]
+    Suyama,
+    /// [`Z12Curve`], group orders multiple of 12 with a rational point of order 4.
+    Z12,
+    /// [`Z2xZ8Curve`], group orders multiple of 16.
+    Z2xZ8,
+}
+
+impl Parametrization {
+    /// Derives the curve and starting point of `sigma` modulo n.
+    ///
+    /// # Parameters
+    ///
+    /// - `sigma`: Parameter of the curve, a multiple of a point for the torsion families.
+    /// - `n`: Modulus.
+    pub fn derive(self, sigma: &Integer, n: &Integer) -> Result<Curve, FactorFound> {
+        match self {
+            Parametrization::Suyama => SuyamaCurve::derive(sigma, n),
+            Parametrization::Z12 => Z12Curve::derive(sigma, n),
+            Parametrization::Z2xZ8 => Z2xZ8Curve::derive(sigma, n),
+        }
+    }
+}
+
+/// Affine coordinates of `k*P` on `y^2 = x^3 + a*x + b (mod n)`.
+///
+/// Jacobian coordinates defer the inversion to the end, the point at infinity
+/// fails with [`FactorFound`] and n.
+fn weierstrass_mul(
+    k: &Integer,
+    p: (i32, i32),
+    a: i32,
+    n: &Integer,
+) -> Result<(Integer, Integer), FactorFound> {
+    // -P = (x, -y)
+    let (px, py) = (Integer::from(p.0), Integer::from(p.1) * k.cmp0() as i32);
+    let k = Integer::from(k.abs_ref());
+    let (mut x, mut y, mut z) = (px.clone(), py.clone(), Integer::from(k != 0));
+    for i in (0..k.significant_bits().saturating_sub(1)).rev() {
+        // Doubling
+        let yy = Integer::from(y.square_ref()) % n;
+        let zz = Integer::from(z.square_ref()) % n;
+        let s = Integer::from(&x * &yy) % n * 4u32;
+        let m = (Integer::from(x.square_ref()) * 3u32 + zz.square() * a) % n;
+        z = Integer::from(&y * &z) * 2u32 % n;
+        x = (Integer::from(m.square_ref()) - 2u32 * &s) % n;
+        y = (m * (s - &x) - yy.square() * 8u32) % n;
+
+        // Mixed addition of P
+        if k.get_bit(i) {
+            let zz = Integer::from(z.square_ref()) % n;
+            let h = (Integer::from(&px * &zz) - &x) % n;
+            let r = (Integer::from(&py * &zz) * &z - &y) % n;
+            let hh = Integer::from(h.square_ref()) % n;
+            let hhh = Integer::from(&h * &hh) % n;
+            let v = x * hh % n;
+            z = z * h % n;
+            x = (Integer::from(r.square_ref()) - &hhh - 2u32 * &v) % n;
+            y = (r * (v - &x) - y * hhh) % n;
+        }
+    }
+
+    let inverse = try_invert_or_factor(&z, n)?;
+    let inverse_2 = Integer::from(inverse.square_ref()) % n;
+    let y = y * &inverse_2 % n * inverse % n;
+    Ok((x * inverse_2 % n, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derive() {
+        let n = Integer::from(1_000_003);
+        let curve = SuyamaCurve::derive(&Integer::from(6), &n).unwrap();
+
+        // u = 31, v = 24
+        assert_eq!(*curve.x_cord(), 29791);
+        assert_eq!(*curve.z_cord(), 13824);
+        // (a + 2) * 4*u^3*v = (v - u)^3 * (3*u + v)
+        let a_plus_2 = Integer::from(4 * curve.a_24());
+        let lhs = a_plus_2 * 4 * 29791 * 24;
+        let rhs = Integer::from(-343) * (3 * 31 + 24);
+        let diff: Integer = lhs - rhs;
+        assert!(diff.is_divisible(&n));
+    }
+
+    #[test]
+    fn montgomery() {
+        let n = Integer::from(1_000_003);
+        let curve = Curve::montgomery(&Integer::from(10), &Integer::from(2), &n).unwrap();
+        assert_eq!(Integer::from(curve.a_24() * 4u32) % &n, 12);
+        assert_eq!(*curve.x_cord(), 2);
+        assert_eq!(*curve.z_cord(), 1);
+        assert_eq!(curve.point(), curve.clone().into_point());
+
+        // a^2 - 4 = 0 (mod 3)
+        let n = Integer::from(3 * 1_000_003);
+        assert_eq!(
+            Curve::montgomery(&Integer::from(1), &Integer::from(2), &n),
+            Err(FactorFound(Integer::from(3)))
+        );
+    }
+
+    /// Number of points modulo the prime n of the curve of `curve`, the
+    /// one on which its starting point lies, `None` if it is of order 2.
+    fn group_order(curve: &Curve) -> Option<u64> {
+        let n = curve.modulus();
+        let a = Integer::from(curve.a_24() * 4u32) - 2u32;
+        let f = |x: &Integer| (Integer::from(x.square_ref()) + &a * x + 1u32) * x % n;
+        let x0 = curve.x_cord() * try_invert_or_factor(curve.z_cord(), n).ok()? % n;
+        let b = f(&x0);
+        if b == 0 {
+            return None;
+        }
+        // One point at infinity, then 1 + (b*f(x) / n) points of abscissa x
+        let mut order = n.to_i64()? + 1;
+        for x in 0..n.to_u32()? {
+            order += i64::from((f(&x.into()) * &b).legendre(n));
+        }
+        u64::try_from(order).ok()
+    }
+
+    #[test]
+    fn torsion() {
+        let n = Integer::from(1_000_003);
+        // t = -3/13, the point (t : 1) has order 3 and (1 : 1) order 4
+        let curve = Z12Curve::derive(&Integer::from(2), &n).unwrap();
+        let t = Integer::from(-3) * Integer::from(13).invert(&n).unwrap();
+        let order = |x: Integer, k: u32| {
+            let point = Point::new(x, 1.into(), curve.a_24().clone(), n.clone());
+            (1..=k).find(|&k| point.mont_ladder(&k.into()).z_cord.is_divisible(&n))
+        };
+        assert_eq!(order(t, 12), Some(3));
+        assert_eq!(order(1.into(), 12), Some(4));
+
+        // u = -725/541, the point (-u*(u + 1)/(u + 2) : 1) has order 8
+        let curve = Z2xZ8Curve::derive(&Integer::from(2), &n).unwrap();
+        let u = Integer::from(-725) * Integer::from(541).invert(&n).unwrap() % &n;
+        let x8 = -Integer::from(&u + 1u32) * &u * Integer::from(&u + 2u32).invert(&n).unwrap();
+        let point = Point::new(x8, 1.into(), curve.a_24().clone(), n.clone());
+        assert!(point.mont_ladder(&8.into()).z_cord.is_divisible(&n));
+        assert!(!point.mont_ladder(&4.into()).z_cord.is_divisible(&n));
+
+        // Modulo every prime
+        for p in primal::Primes::all().skip(30).take(100) {
+            let n = Integer::from(p);
+            for k in 2..6 {
+                let families = [
+                    (Parametrization::Suyama, k + 4, 12),
+                    (Parametrization::Z12, k, 12),
+                    (Parametrization::Z2xZ8, k, 16),
+                ];
+                for (family, sigma, torsion) in families {
+                    let curve = family.derive(&Integer::from(sigma), &n);
+                    if let Some(order) = curve.ok().as_ref().and_then(group_order) {
+                        assert_eq!(order % torsion, 0, "{family:?} {sigma} mod {p}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn weierstrass() {
+        let n = Integer::from(1_000_003);
+        // 2*(-2, 4) = (4, -4) on y^2 = x^3 - 12*x
+        let double = weierstrass_mul(&Integer::from(2), (-2, 4), -12, &n).unwrap();
+        assert_eq!(double, (Integer::from(4), Integer::from(-4)));
+        let triple = weierstrass_mul(&Integer::from(-3), (-2, 4), -12, &n).unwrap();
+        let (x, y) = weierstrass_mul(&Integer::from(3), (-2, 4), -12, &n).unwrap();
+        assert_eq!(triple.0, x);
+        assert!(Integer::from(&triple.1 + &y).is_divisible(&n));
+        assert_eq!(
+            weierstrass_mul(&Integer::from(0), (-2, 4), -12, &n),
+            Err(FactorFound(n))
+        );
+    }
+
+    #[test]
+    fn factor_found() {
+        let before = crate::inversion_stats();
+
+        // v = 4*53 is not invertible modulo 53*61
+        assert_eq!(
+            SuyamaCurve::derive(&Integer::from(53), &Integer::from(3233)),
+            Err(FactorFound(Integer::from(53)))
+        );
+        assert_eq!(
+            SuyamaCurve::derive(&Integer::from(0), &Integer::from(3233)),
+            Err(FactorFound(Integer::from(3233)))
+        );
+
+        // Other tests may run inversions concurrently
+        let after = crate::inversion_stats();
+        assert!(after.inversions >= before.inversions + 2);
+        assert!(after.failures >= before.failures + 2);
+        assert!(after.factors > before.factors);
+    }
+}
@@ -0,0 +1,187 @@
+//! Step by step factorization with tiny parameters, to teach how ECM works.
+//!
+//! ```
+//! use ecm::demo::factor_verbose;
+//! use rug::Integer;
+//!
+//! let factors = factor_verbose(&Integer::from(398883434337287u64), |line| println!("{line}"));
+//! assert_eq!(factors.len(), 2);
+//! ```
+
+use crate::{ecm::Stages, FactorFound, Factorization, SuyamaCurve};
+use primal::Primes;
+use rug::{integer::IsPrime, Integer};
+
+/// Stage 1 bound of the demo.
+pub const B1: u64 = 1_000;
+
+/// Stage 2 bound of the demo.
+pub const B2: u64 = 50_000;
+
+/// Maximum number of curves run on each composite.
+pub const MAX_CURVES: u64 = 50;
+
+/// Bound of the trial division run before ECM.
+const TRIAL_BOUND: usize = 100;
+
+/// Factors n, describing each computation in human-readable lines.
+///
+/// The run is deterministic: curves use `sigma = 6, 7, 8, ...`
+/// and the bounds are [`B1`] and [`B2`].
+/// For each curve, `log` receives sigma, the derived curve,
+/// the result of stage 1 and the gcds computed by both stages.
+/// Composites without factor found after [`MAX_CURVES`] curves are kept as cofactors.
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `log`: Callback receiving each line of the explanation.
+pub fn factor_verbose(n: &Integer, mut log: impl FnMut(&str)) -> Factorization {
+    let mut factorization = Factorization::new();
+    if *n < 2 {
+        log(&format!("{n} has no prime factor"));
+        return factorization;
+    }
+
+    log(&format!("Factoring n = {n} with B1 = {B1} and B2 = {B2}"));
+    let stages = Stages::new(B1, B2);
+    log(&format!(
+        "Stage 1 multiplier k = product of the prime powers up to B1 = {} ({} bits)",
+        stages.k,
+        stages.k.significant_bits()
+    ));
+
+    let mut n = n.clone();
+    for p in Primes::all().take_while(|&p| p < TRIAL_BOUND) {
+        let mut multiplicity = 0;
+        while n.is_divisible_u(p as u32) {
+            n /= p as u32;
+            multiplicity += 1;
+        }
+        if multiplicity > 0 {
+            log(&format!("Trial division: {p}^{multiplicity} divides n"));
+            factorization.add(p.into(), multiplicity);
+        }
+    }
+
+    let mut composites = vec![n];
+    while let Some(m) = composites.pop() {
+        if m == 1 {
+            continue;
+        }
+        if m.is_probably_prime(30) != IsPrime::No {
+            log(&format!("{m} is prime"));
+            factorization.add(m, 1);
+            continue;
+        }
+
+        log(&format!("Running ECM on the composite {m}"));
+        match find_factor(&stages, &m, &mut log) {
+            Some(factor) => {
+                let cofactor = Integer::from(&m / &factor);
+                log(&format!("Found {m} = {factor} * {cofactor}"));
+                composites.push(factor);
+                composites.push(cofactor);
+            }
+            None => {
+                log(&format!(
+                    "No factor of {m} found after {MAX_CURVES} curves, keeping it as a cofactor"
+                ));
+                factorization.add_cofactor(m, 1);
+            }
+        }
+    }
+
+    factorization
+}
+
+/// Runs up to [`MAX_CURVES`] curves on n, returning the first proper factor found.
+fn find_factor(stages: &Stages, n: &Integer, log: &mut impl FnMut(&str)) -> Option<Integer> {
+    let is_proper = |g: &Integer| *g != 1 && g != n;
+
+    for sigma in (6..).take(MAX_CURVES as usize) {
+        log(&format!("Curve sigma = {sigma}"));
+        let curve = match SuyamaCurve::derive(&Integer::from(sigma), n) {
+            Ok(curve) => curve,
+            Err(FactorFound(g)) => {
+                log(&format!(
+                    "  4*u^3*v is not invertible, gcd(4*u^3*v, n) = {g}"
+                ));
+                if is_proper(&g) {
+                    return Some(g);
+                }
+                continue;
+            }
+        };
+        log(&format!(
+            "  a24 = (a + 2)/4 = {}, starting point P = ({} : {})",
+            curve.a_24(),
+            curve.x_cord(),
+            curve.z_cord()
+        ));
+
+        let q = stages.stage1(&curve.into_point());
+        let g = Integer::from(q.z_cord.gcd_ref(n));
+        log(&format!(
+            "  Stage 1: k*P = ({} : {}), gcd(Z, n) = {g}",
+            q.x_cord, q.z_cord
+        ));
+        if is_proper(&g) {
+            return Some(g);
+        }
+        if g == *n {
+            log("  k*P is the point at infinity modulo every factor, trying another curve");
+            continue;
+        }
+
+        let g = stages.stage2(n, &q);
+        log(&format!(
+            "  Stage 2: gcd(product over primes in (B1, B2], n) = {g}"
+        ));
+        if is_proper(&g) {
+            return Some(g);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbose_factorization() {
+        let n = Integer::from(12) * 398883434337287u64;
+        let mut lines = Vec::new();
+        let factors = factor_verbose(&n, |line| lines.push(line.to_string()));
+
+        assert_eq!(
+            factors,
+            Factorization::from([
+                (2.into(), 2),
+                (3.into(), 1),
+                (4009823.into(), 1),
+                (99476569.into(), 1),
+            ])
+        );
+        assert!(lines[0].starts_with("Factoring n = 4786601212047444"));
+        assert!(lines.iter().any(|line| line.contains("Stage 1: k*P")));
+        assert!(lines
+            .iter()
+            .any(|line| line == "Found 398883434337287 = 4009823 * 99476569"
+                || line == "Found 398883434337287 = 99476569 * 4009823"));
+
+        // Deterministic
+        let mut again = Vec::new();
+        factor_verbose(&n, |line| again.push(line.to_string()));
+        assert_eq!(lines, again);
+    }
+
+    #[test]
+    fn trivial_inputs() {
+        let mut lines = Vec::new();
+        assert!(factor_verbose(&Integer::from(1), |line| lines.push(line.to_string())).is_empty());
+        assert_eq!(lines, vec!["1 has no prime factor"]);
+    }
+}
@@ -1,17 +1,59 @@
-use crate::point::Point;
+#[cfg(any(feature = "parallel", feature = "rayon"))]
+use crate::pool::CurvePool;
+#[cfg(feature = "audit")]
+use crate::AuditLog;
+#[cfg(feature = "events")]
+use crate::EcmEvent;
+use crate::{
+    edwards::EdwardsCurve,
+    fermat::{fermat, DEFAULT_FERMAT_STEPS},
+    olf::{one_line_factor, ONE_LINE_BITS, ONE_LINE_STEPS},
+    pm1::{pm1, DEFAULT_PM1_B1, DEFAULT_PM1_B2},
+    point::Point,
+    pp1::{pp1_stages, pp1_with_seeds, DEFAULT_PP1_SEEDS, PP1_SEEDS},
+    priority::throttle_delay,
+    progress::decimal_digits,
+    rho::{pollard_brent, pollard_rho, DEFAULT_RHO_STEPS},
+    siqs::{Siqs, DEFAULT_SIQS_DIGITS, SIQS_MIN_DIGITS, SIQS_PRETEST_RATIO},
+    small::{small_factor, SMALL_RHO_STEPS},
+    squfof::squfof,
+    tables::success_probability,
+    tree, BoundPreset, CancelToken, Curve, CurveBuckets, CurveModel, EcmDiagnostics, Estimate,
+    FactorFound, FactorMethod, Factorization, Parametrization, ParseError, PrimalityStandard,
+    PrimeTable, Priority, Progress, ProgressObserver, ProgressSink, RecycleStats, ResidueLog,
+    ResumeFormat, ResumeState, Stats, WorkUnit, FIRST_SIGMA, SMALL_COFACTOR_BITS,
+};
+#[cfg(feature = "coppersmith")]
+use crate::{factor_with_hint, BruteForceSolver, HighBitsHint, PartialKeySolver};
+#[cfg(feature = "serde")]
+use crate::{Checkpoint, CheckpointError, CHECKPOINT_VERSION};
 #[cfg(feature = "progress-bar")]
 use indicatif::ProgressBar;
 use primal::Primes;
 use rug::{integer::IsPrime, rand::RandState, Integer};
-use std::collections::HashMap;
+#[cfg(feature = "events")]
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    task::Poll,
+    time::{Duration, Instant},
+};
 
 /// Error occured during ecm factorization.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Bounds should be an even integer.
     #[error("Bounds should be an even integer")]
     BoundsNotEven,
-    /// Too small bounds.
+    /// Too small bounds: B1 below [`MIN_B1`], or B2 below B1.
     #[error("Too small bounds")]
     BoundsTooSmall,
     /// The factorization failed.
@@ -20,6 +62,116 @@ pub enum Error {
     /// The number is prime.
     #[error("The number is prime")]
     NumberIsPrime,
+    /// The RSA exponents do not match the modulus.
+    #[error("The RSA exponents do not match the modulus")]
+    InvalidRsaKey,
+    /// No factor matches the given partial key.
+    #[error("No factor matches the given partial key")]
+    PartialKeyFailed,
+    /// The cofactor is a probable prime, but its primality could not be proven.
+    #[error("Could not prove the primality of {0}")]
+    PrimalityNotProven(Integer),
+    /// Unexpected panic caught during the factorization (see the `catch-unwind` feature).
+    #[error("Internal error: {0}")]
+    Internal(String),
+    /// The number to be factored could not be parsed.
+    #[error("Invalid number: {0}")]
+    InvalidNumber(#[from] ParseError),
+    /// The number to be factored has more bits than the limit, see [`MAX_INPUT_BITS`].
+    #[error("Input of {0} bits is too large for ECM, use a method suited to its special form")]
+    InputTooLarge(u32),
+    /// The number to be factored is 0, which has no factorization.
+    #[error("0 has no factorization")]
+    ZeroInput,
+    /// The factorization was cancelled by a [`CancelToken`], with the factors found so far.
+    ///
+    /// Parts not factored yet are cofactors of the partial factorization.
+    #[error("The factorization was cancelled")]
+    Cancelled(Box<Factorization>),
+    /// The resume file of an interrupted factorization could not be written, see [`EcmOptions::resume_file`].
+    #[error("Could not write the resume file: {0}")]
+    ResumeFile(#[source] io::Error),
+}
+
+/// Kind of an [`Error`], without the attached data.
+///
+/// Each kind has a stable numeric code, which is never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// See [`Error::BoundsNotEven`].
+    BoundsNotEven,
+    /// See [`Error::BoundsTooSmall`].
+    BoundsTooSmall,
+    /// See [`Error::ECMFailed`].
+    ECMFailed,
+    /// See [`Error::NumberIsPrime`].
+    NumberIsPrime,
+    /// See [`Error::InvalidRsaKey`].
+    InvalidRsaKey,
+    /// See [`Error::PartialKeyFailed`].
+    PartialKeyFailed,
+    /// See [`Error::Internal`].
+    Internal,
+    /// See [`Error::PrimalityNotProven`].
+    PrimalityNotProven,
+    /// See [`Error::InvalidNumber`].
+    InvalidNumber,
+    /// See [`Error::InputTooLarge`].
+    InputTooLarge,
+    /// See [`Error::ZeroInput`].
+    ZeroInput,
+    /// See [`Error::Cancelled`].
+    Cancelled,
+    /// See [`Error::ResumeFile`].
+    ResumeFile,
+}
+
+impl ErrorKind {
+    /// Stable numeric code of the error kind.
+    pub fn code(self) -> u32 {
+        match self {
+            ErrorKind::BoundsNotEven => 1,
+            ErrorKind::BoundsTooSmall => 2,
+            ErrorKind::ECMFailed => 3,
+            ErrorKind::NumberIsPrime => 4,
+            ErrorKind::InvalidRsaKey => 5,
+            ErrorKind::PartialKeyFailed => 6,
+            ErrorKind::Internal => 7,
+            ErrorKind::PrimalityNotProven => 8,
+            ErrorKind::InvalidNumber => 9,
+            ErrorKind::InputTooLarge => 10,
+            ErrorKind::ZeroInput => 11,
+            ErrorKind::Cancelled => 12,
+            ErrorKind::ResumeFile => 13,
+        }
+    }
+}
+
+impl Error {
+    /// Kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::BoundsNotEven => ErrorKind::BoundsNotEven,
+            Error::BoundsTooSmall => ErrorKind::BoundsTooSmall,
+            Error::ECMFailed => ErrorKind::ECMFailed,
+            Error::NumberIsPrime => ErrorKind::NumberIsPrime,
+            Error::InvalidRsaKey => ErrorKind::InvalidRsaKey,
+            Error::PartialKeyFailed => ErrorKind::PartialKeyFailed,
+            Error::Internal(_) => ErrorKind::Internal,
+            Error::PrimalityNotProven(_) => ErrorKind::PrimalityNotProven,
+            Error::InvalidNumber(_) => ErrorKind::InvalidNumber,
+            Error::InputTooLarge(_) => ErrorKind::InputTooLarge,
+            Error::ZeroInput => ErrorKind::ZeroInput,
+            Error::Cancelled(_) => ErrorKind::Cancelled,
+            Error::ResumeFile(_) => ErrorKind::ResumeFile,
+        }
+    }
+
+    /// Stable numeric code of the error, see [`ErrorKind::code`].
+    pub fn code(&self) -> u32 {
+        self.kind().code()
+    }
 }
 
 /// Returns one factor of n using Lenstra's 2 Stage Elliptic curve Factorization
@@ -44,6 +196,9 @@ pub enum Error {
 /// of |E(FF(q))|. In this case, we only need to compute the scalar multiplication by p
 /// to get p*k*P = O. Here a second bound B2 restricts the size of possible values of p.
 ///
+/// The sign of n is ignored and the factor is positive. 0 fails with [`Error::ZeroInput`],
+/// 1 and -1, which have no proper factor, with [`Error::ECMFailed`].
+///
 /// Parameters:
 ///
 /// - `n`: Number to be factored.
@@ -51,81 +206,600 @@ pub enum Error {
 /// - `B2`: Stage 2 Bound.
 /// - `max_curve`: Maximum number of curves generated.
 /// - `rgen`: Random number generator.
+/// - `observer`: Receiver of the curves run and of the factor found, if any.
 pub fn ecm_one_factor(
     n: &Integer,
-    b1: usize,
-    b2: usize,
-    max_curve: usize,
+    b1: u64,
+    b2: u64,
+    max_curve: u64,
     rgen: &mut RandState<'_>,
-    #[cfg(feature = "progress-bar")] pb: Option<&ProgressBar>,
+    observer: Option<&dyn ProgressObserver>,
 ) -> Result<Integer, Error> {
-    if b1 % 2 != 0 || b2 % 2 != 0 {
-        return Err(Error::BoundsNotEven);
-    }
+    ecm_one_factor_detailed(n, b1, b2, max_curve, rgen, observer).map(|found| found.factor)
+}
+
+/// Same as [`ecm_one_factor`], without testing the primality of n first.
+///
+/// The test with the default [`PrimalityStandard`] dominates the runtime
+/// for large inputs: callers who already know that n is composite,
+/// e.g. from their own primality test, can skip it.
+/// On a prime n, all curves fail and [`Error::ECMFailed`] is returned.
+///
+/// # Parameters
+///
+/// - `n`: Composite number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound.
+/// - `max_curve`: Maximum number of curves generated.
+/// - `rgen`: Random number generator.
+/// - `observer`: Receiver of the curves run and of the factor found, if any.
+pub fn ecm_one_factor_unchecked(
+    n: &Integer,
+    b1: u64,
+    b2: u64,
+    max_curve: u64,
+    rgen: &mut RandState<'_>,
+    observer: Option<&dyn ProgressObserver>,
+) -> Result<Integer, Error> {
+    one_factor_with_sigmas(
+        n,
+        b1,
+        b2,
+        random_sigmas(n, max_curve, rgen),
+        Parametrization::Suyama,
+        None,
+        &mut EcmDiagnostics::default(),
+        observer,
+    )
+    .map(|found| found.factor)
+}
+
+/// Stage of ECM which found a factor.
+///
+/// Stages are ordered as they are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EcmStage {
+    /// The curve could not be built, a denominator shares a factor with n.
+    Setup,
+    /// Stage 1: `gcd(k*P.z, n)`.
+    Stage1,
+    /// Stage 2: gcd of the product accumulated over the primes in `(B1, B2]`.
+    Stage2,
+}
+
+/// Factor found by ECM, with the curve which found it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CurveFactor {
+    /// Proper factor of n.
+    pub factor: Integer,
+    /// Parameter of the curve, see [`SuyamaCurve`](crate::SuyamaCurve).
+    pub sigma: Integer,
+    /// Family of the curve of the sigma, Suyama's one except for [`WorkUnit`]s of other families.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parametrization: Parametrization,
+    /// Number of the curve, starting at 1.
+    pub curve: u64,
+    /// Stage which found the factor.
+    pub stage: EcmStage,
+}
+
+/// Same as [`ecm_one_factor`], but also returns the curve which found the factor.
+///
+/// The sigma can be given to [`ecm_one_factor_with_sigmas`] to run the curve again.
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound.
+/// - `max_curve`: Maximum number of curves generated.
+/// - `rgen`: Random number generator.
+/// - `observer`: Receiver of the curves run and of the factor found, if any.
+pub fn ecm_one_factor_detailed(
+    n: &Integer,
+    b1: u64,
+    b2: u64,
+    max_curve: u64,
+    rgen: &mut RandState<'_>,
+    observer: Option<&dyn ProgressObserver>,
+) -> Result<CurveFactor, Error> {
+    one_factor_with_sigmas(
+        n,
+        b1,
+        b2,
+        random_sigmas(n, max_curve, rgen),
+        Parametrization::Suyama,
+        Some(PrimalityStandard::default()),
+        &mut EcmDiagnostics::default(),
+        observer,
+    )
+}
+
+/// Same as [`ecm_one_factor_detailed`], also returning statistics of the run.
+///
+/// The statistics are returned whether a factor was found or not,
+/// so bounds can be tuned empirically, including from failed runs.
+///
+/// ```
+/// use ecm::{ecm_one_factor_ex, EcmStage};
+/// use rug::{rand::RandState, Integer};
+///
+/// let n = Integer::from(398883434337287u64);
+/// let (found, diagnostics) = ecm_one_factor_ex(
+///     &n,
+///     2_000,
+///     160_000,
+///     100,
+///     &mut RandState::new(),
+///     None,
+/// );
+/// let found = found.unwrap();
+/// assert_eq!(diagnostics.curves, found.curve);
+/// assert_eq!(diagnostics.stage, Some(found.stage));
+/// assert!(diagnostics.mulmods > 0);
+/// println!("{} curves in {:?}", diagnostics.curves, diagnostics.elapsed());
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound.
+/// - `max_curve`: Maximum number of curves generated.
+/// - `rgen`: Random number generator.
+/// - `observer`: Receiver of the curves run and of the factor found, if any.
+pub fn ecm_one_factor_ex(
+    n: &Integer,
+    b1: u64,
+    b2: u64,
+    max_curve: u64,
+    rgen: &mut RandState<'_>,
+    observer: Option<&dyn ProgressObserver>,
+) -> (Result<CurveFactor, Error>, EcmDiagnostics) {
+    let mut diagnostics = EcmDiagnostics::default();
+    let found = one_factor_with_sigmas(
+        n,
+        b1,
+        b2,
+        random_sigmas(n, max_curve, rgen),
+        Parametrization::Suyama,
+        Some(PrimalityStandard::default()),
+        &mut diagnostics,
+        observer,
+    );
+    (found, diagnostics)
+}
+
+/// Random sigmas of the curves run by [`ecm_one_factor`].
+fn random_sigmas<'a, 'r>(
+    n: &Integer,
+    max_curve: u64,
+    rgen: &'a mut RandState<'r>,
+) -> impl Iterator<Item = Integer> + use<'a, 'r> {
+    let n_minus_one = Integer::from(n.abs_ref()) - 1;
+    std::iter::repeat_with(move || random_below(&n_minus_one, rgen)).take(max_curve as usize + 1)
+}
+
+/// Returns one factor of n using the curves given by Suyama's parametrization of `sigmas`.
+///
+/// Same as [`ecm_one_factor`], but curves are not random: a curve which found
+/// a factor can be run again, e.g. on another machine.
+/// Sigmas are reduced modulo n, the ones giving degenerate curves are skipped.
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound.
+/// - `sigmas`: Parameters of the curves, tried in order.
+/// - `observer`: Receiver of the curves run and of the factor found, if any.
+pub fn ecm_one_factor_with_sigmas(
+    n: &Integer,
+    b1: u64,
+    b2: u64,
+    sigmas: impl IntoIterator<Item = Integer>,
+    observer: Option<&dyn ProgressObserver>,
+) -> Result<Integer, Error> {
+    one_factor_with_sigmas(
+        n,
+        b1,
+        b2,
+        sigmas,
+        Parametrization::Suyama,
+        Some(PrimalityStandard::default()),
+        &mut EcmDiagnostics::default(),
+        observer,
+    )
+    .map(|found| found.factor)
+}
+
+/// Runs the curves of `sigmas` in the family `parametrization` until one finds a factor of n.
+///
+/// n is first tested with `primality`, if any. Statistics of the run are added to `diagnostics`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn one_factor_with_sigmas(
+    n: &Integer,
+    b1: u64,
+    b2: u64,
+    sigmas: impl IntoIterator<Item = Integer>,
+    parametrization: Parametrization,
+    primality: Option<PrimalityStandard>,
+    diagnostics: &mut EcmDiagnostics,
+    observer: Option<&dyn ProgressObserver>,
+) -> Result<CurveFactor, Error> {
+    let n = &Integer::from(n.abs_ref());
+    // Sigma of the last curve run, the one which found the factor on success
+    let mut sigma = Integer::new();
+    let curves = sigmas.into_iter().map(|s| {
+        sigma = s % n;
+        parametrization.derive(&sigma, n)
+    });
+    let (factor, curve, stage) =
+        one_factor_with_curves(n, b1, b2, curves, primality, diagnostics, observer)?;
+    Ok(CurveFactor {
+        factor,
+        sigma,
+        parametrization,
+        curve,
+        stage,
+    })
+}
+
+/// Returns one factor of n using the given Montgomery curves instead of Suyama's parametrization.
+///
+/// Each curve is given by `(a, x0)`: the curve `b*y^2 = x^3 + a*x^2 + x (mod n)`
+/// and the starting point `(x0 : 1)`, see [`Curve::montgomery`].
+/// This allows to study specific curve families.
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound.
+/// - `curves`: Parameter `a` and starting point `x0` of the curves, tried in order.
+/// - `observer`: Receiver of the curves run and of the factor found, if any.
+pub fn ecm_one_factor_with_montgomery_curves(
+    n: &Integer,
+    b1: u64,
+    b2: u64,
+    curves: impl IntoIterator<Item = (Integer, Integer)>,
+    observer: Option<&dyn ProgressObserver>,
+) -> Result<Integer, Error> {
+    let n = &Integer::from(n.abs_ref());
+    let curves = curves
+        .into_iter()
+        .map(|(a, x0)| Curve::montgomery(&a, &x0, n));
+    one_factor_with_curves(
+        n,
+        b1,
+        b2,
+        curves,
+        Some(PrimalityStandard::default()),
+        &mut EcmDiagnostics::default(),
+        observer,
+    )
+    .map(|(factor, _, _)| factor)
+}
+
+/// Runs the curves until one finds a factor of n.
+///
+/// n must be positive, it is first tested with `primality`, if any.
+/// Returns the factor, the number of the curve which found it, starting at 1, and the stage.
+/// Statistics of the run are added to `diagnostics`.
+fn one_factor_with_curves(
+    n: &Integer,
+    b1: u64,
+    b2: u64,
+    mut curves: impl Iterator<Item = Result<Curve, FactorFound>>,
+    primality: Option<PrimalityStandard>,
+    diagnostics: &mut EcmDiagnostics,
+    observer: Option<&dyn ProgressObserver>,
+) -> Result<(Integer, u64, EcmStage), Error> {
+    check_bounds(b1, b2)?;
 
-    if n.is_probably_prime(1000) != IsPrime::No {
+    match n.to_u8() {
+        Some(0) => return Err(Error::ZeroInput),
+        // 1 has no proper factor
+        Some(1) => return Err(Error::ECMFailed),
+        _ => {}
+    }
+    if primality.is_some() {
+        check_size(n, MAX_INPUT_BITS)?;
+    }
+    if primality.is_some_and(|primality| primality.test(n) != IsPrime::No) {
         return Err(Error::NumberIsPrime);
     }
 
-    #[cfg(feature = "progress-bar")]
-    if let Some(pb) = pb {
-        pb.set_length(max_curve as u64);
-        pb.set_position(0);
+    let max_curves = curves.size_hint().1.map(|len| len as u64);
+    let stages = Stages::new(b1, b2);
+    for number in 1.. {
+        // Curves are derived lazily, the derivation is the setup of the curve
+        let setup_start = Instant::now();
+        let Some(curve) = curves.next() else {
+            break;
+        };
+        diagnostics.setup_time += setup_start.elapsed();
+        diagnostics.curves += 1;
+
+        if let Some(observer) = observer {
+            observer.curve_started(number, max_curves);
+        }
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("curve", curve = number, b1).entered();
+        let found = stages.run_curve(n, curve, CurveModel::Montgomery, diagnostics, None, |_| {});
+        if let Some(observer) = observer {
+            observer.curve_finished(number);
+        }
+
+        if let Some((factor, stage, _)) = found {
+            #[cfg(feature = "tracing")]
+            tracing::info!(%factor, composite = %n, ?stage, "factor found");
+            if let Some(observer) = observer {
+                observer.factor_found(&factor, n);
+            }
+            return Ok((factor, number, stage));
+        }
     }
 
-    let mut curve = 0;
-    let d = (b2 as f64).sqrt() as usize;
-    let two_d = 2 * d;
-    let mut beta: Vec<Integer> = vec![Integer::default(); d + 1];
-    let mut s: Vec<Point> = vec![Point::default(); d + 1];
-    let mut k = Integer::from(1);
-    let three = Integer::from(3);
+    // ECM failed, Increase the bounds
+    Err(Error::ECMFailed)
+}
+
+/// Factor of a number, with the result of its primality test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Factor {
+    /// Value of the factor.
+    pub value: Integer,
+    /// Result of the primality test of the factor.
+    pub primality: IsPrime,
+}
 
-    for p in Primes::all().take_while(|&p| p <= b1) {
-        k *= p.pow(b1.ilog(p));
+impl Factor {
+    /// Tests the primality of `value` with the default [`PrimalityStandard`].
+    fn new(value: Integer) -> Factor {
+        let primality = PrimalityStandard::default().test(&value);
+        Factor { value, primality }
     }
 
-    while curve <= max_curve {
-        curve += 1;
+    /// Returns true if the factor is prime or probably prime.
+    pub fn is_prime(&self) -> bool {
+        self.primality != IsPrime::No
+    }
+}
 
-        #[cfg(feature = "progress-bar")]
-        if let Some(pb) = pb {
-            pb.inc(1);
-        }
+/// Splits n in two using [`ecm_one_factor`].
+///
+/// Returns `(g, n/g)` where g is the factor found by ECM,
+/// each part being classified as prime or composite,
+/// so the caller can decide which part to keep factoring.
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound.
+/// - `max_curve`: Maximum number of curves generated.
+/// - `rgen`: Random number generator.
+/// - `observer`: Receiver of the curves run and of the factor found, if any.
+pub fn split(
+    n: &Integer,
+    b1: u64,
+    b2: u64,
+    max_curve: u64,
+    rgen: &mut RandState<'_>,
+    observer: Option<&dyn ProgressObserver>,
+) -> Result<(Factor, Factor), Error> {
+    let factor = ecm_one_factor(n, b1, b2, max_curve, rgen, observer)?;
+    let cofactor = Integer::from(n / &factor);
+
+    Ok((Factor::new(factor), Factor::new(cofactor)))
+}
+
+/// Checks that n has at most `max_bits` bits.
+fn check_size(n: &Integer, max_bits: u32) -> Result<(), Error> {
+    match n.significant_bits() {
+        bits if bits > max_bits => Err(Error::InputTooLarge(bits)),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the bounds are usable by [`Stages`].
+pub(crate) fn check_bounds(b1: u64, b2: u64) -> Result<(), Error> {
+    if !b1.is_multiple_of(2) || !b2.is_multiple_of(2) {
+        return Err(Error::BoundsNotEven);
+    }
+    if b1 < MIN_B1 || b2 < b1 {
+        return Err(Error::BoundsTooSmall);
+    }
+    Ok(())
+}
+
+/// Precomputed data shared by all the curves using the same bounds.
+#[derive(Debug, Clone)]
+pub(crate) struct Stages {
+    /// Stage 1 Bound.
+    pub(crate) b1: u64,
+    /// Stage 2 Bound.
+    pub(crate) b2: u64,
+    /// Number of baby steps of stage 2.
+    pub(crate) d: usize,
+    /// Stage 1 multiplier, product of all prime powers below B1.
+    pub(crate) k: Integer,
+    /// Primes of stage 2, generated on the fly if missing or too small.
+    prime_table: Option<Arc<PrimeTable>>,
+}
+
+impl Stages {
+    /// Computes the stage 1 multiplier and the stage 2 table size.
+    ///
+    /// # Parameters
+    ///
+    /// - `B1`: Stage 1 Bound.
+    /// - `B2`: Stage 2 Bound.
+    pub(crate) fn new(b1: u64, b2: u64) -> Stages {
+        let mut builder = StagesBuilder::new(b1, b2, Stage2Width::default(), None);
+        while !builder.advance() {}
+        builder.finish()
+    }
 
-        // Suyama's Parametrization
-        let sigma = (n - Integer::from(1)).random_below(rgen);
-        let u = (&sigma * &sigma - Integer::from(5)) % n;
-        let v: Integer = (4 * sigma) % n;
-        let diff = Integer::from(&v - &u);
-        let u_3 = u.clone().pow_mod(&three, n).unwrap();
-        let v_3 = v.clone().pow_mod(&three, n).unwrap();
+    /// Stages running stage 2 only, on results of stage 1 computed elsewhere.
+    ///
+    /// # Parameters
+    ///
+    /// - `B1`: Stage 1 Bound, stage 2 starts above it.
+    /// - `B2`: Stage 2 Bound.
+    pub(crate) fn stage2_only(b1: u64, b2: u64) -> Stages {
+        Stages {
+            b1,
+            b2,
+            d: Stage2Width::default().baby_steps(b1, b2),
+            k: Integer::from(1),
+            prime_table: None,
+        }
+    }
 
-        let c = match (Integer::from(4) * &u_3 * &v).invert(n) {
-            Ok(c) => {
-                (diff.pow_mod(&three, n).unwrap() * (Integer::from(4) * &u + &v) * c
-                    - Integer::from(2))
-                    % n
+    /// Runs stage 1 and stage 2 on a curve.
+    ///
+    /// Returns a proper factor of n if one was found, including while deriving the curve,
+    /// with the stage which found it and the result of stage 1 if stage 1 found it.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `curve`: Curve, or factor found while deriving it.
+    /// - `model`: Arithmetic of stage 1, the curve is mapped to Edwards form in the setup.
+    /// - `diagnostics`: Statistics the stages run are added to, except the count of curves.
+    /// - `cancel`: Token stopping stage 2 early once cancelled.
+    /// - `stage2_started`: Called with the result of stage 1 once stage 2 starts.
+    fn run_curve(
+        &self,
+        n: &Integer,
+        curve: Result<Curve, FactorFound>,
+        model: CurveModel,
+        diagnostics: &mut EcmDiagnostics,
+        cancel: Option<&CancelToken>,
+        stage2_started: impl FnOnce(&Point),
+    ) -> Option<(Integer, EcmStage, Option<Point>)> {
+        diagnostics.reach(EcmStage::Setup);
+        let start = curve.and_then(|curve| match model {
+            CurveModel::Montgomery => Ok((curve.into_point(), None)),
+            CurveModel::Edwards => EdwardsCurve::from_montgomery(&curve)
+                .map(|edwards| (curve.into_point(), Some(edwards))),
+        });
+        let (q, edwards) = match start {
+            Ok(start) => start,
+            Err(FactorFound(g)) => {
+                return proper_factor(g, n).map(|g| (g, EcmStage::Setup, None));
             }
-            _ => return Ok((Integer::from(4) * u_3 * v).gcd(n)),
         };
 
-        let a24 = (c + 2) * Integer::from(4).invert(n).unwrap() % n;
-        let q = Point::new(u_3, v_3, a24, n.clone());
-        let q = q.mont_ladder(&k);
-        let g = q.z_cord.clone().gcd(n);
+        diagnostics.reach(EcmStage::Stage1);
+        let stage1_start = Instant::now();
+        let (q, mulmods) = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("stage1", b1 = self.b1).entered();
+            match edwards {
+                None => (self.stage1(&q), ladder_mulmods(&self.k)),
+                Some((curve, point)) => {
+                    let (point, mulmods) = point.mul_counted(&self.k, &curve);
+                    (point.into_montgomery(&curve, q.a_24), mulmods)
+                }
+            }
+        };
+        let g = Integer::from(q.z_cord.gcd_ref(n));
+        diagnostics.stage1_time += stage1_start.elapsed();
+        diagnostics.mulmods += mulmods;
+        diagnostics.gcds += 1;
 
         // Stage 1 factor
         if &g != n && g != 1 {
-            return Ok(g);
+            return Some((g, EcmStage::Stage1, Some(q)));
         }
 
         // Stage 1 failure. Q.z = 0, Try another curve
         if &g == n {
-            continue;
+            return None;
+        }
+
+        // Stage 2 Factor found
+        diagnostics.reach(EcmStage::Stage2);
+        stage2_started(&q);
+        let stage2_start = Instant::now();
+        let (g, mulmods) = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("stage2", b2 = self.b2).entered();
+            self.stage2_counted(n, &q, cancel, None)
+        };
+        diagnostics.stage2_time += stage2_start.elapsed();
+        diagnostics.mulmods += mulmods;
+        diagnostics.gcds += 1;
+        proper_factor(g, n).map(|g| (g, EcmStage::Stage2, None))
+    }
+
+    /// Residue recycling: continues a curve which split n on a composite part `m` of n.
+    ///
+    /// When stage 1 splits n, stage 2 was not run. The result of stage 1 reduced
+    /// modulo m is the result of stage 1 on the same curve modulo m, so its gcd
+    /// with m, then stage 2, can split m without running a fresh curve.
+    ///
+    /// # Parameters
+    ///
+    /// - `q`: Result of stage 1 modulo n.
+    /// - `m`: Composite divisor of n.
+    fn recycle(&self, q: &Point, m: &Integer) -> Option<Integer> {
+        let q = Point::new(
+            Integer::from(&q.x_cord % m),
+            Integer::from(&q.z_cord % m),
+            Integer::from(&q.a_24 % m),
+            m.clone(),
+        );
+        let g = Integer::from(q.z_cord.gcd_ref(m));
+        if g != 1 {
+            return proper_factor(g, m);
         }
+        proper_factor(self.stage2(m, &q), m)
+    }
+
+    /// Stage 1: returns `k*P`.
+    ///
+    /// # Parameters
+    ///
+    /// - `p`: Starting point of the curve.
+    pub(crate) fn stage1(&self, p: &Point) -> Point {
+        p.ladder_with_exponent(&self.k)
+    }
+
+    /// Stage 2 (Improved Standard Continuation) on `q = k*P`.
+    ///
+    /// Returns the gcd of n and of the product accumulated over the primes in `(B1, B2]`.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `q`: Result of stage 1.
+    pub(crate) fn stage2(&self, n: &Integer, q: &Point) -> Integer {
+        self.stage2_counted(n, q, None, None).0
+    }
+
+    /// Same as [`Stages::stage2`], also returning the number of modular multiplications.
+    ///
+    /// Once `cancel` is cancelled, the remaining blocks are skipped
+    /// and the gcd of the product accumulated so far is returned.
+    /// The product accumulated at the end of each block is appended to `transcript`, if any.
+    pub(crate) fn stage2_counted(
+        &self,
+        n: &Integer,
+        q: &Point,
+        cancel: Option<&CancelToken>,
+        mut transcript: Option<&mut Vec<Integer>>,
+    ) -> (Integer, u64) {
+        let d = self.d;
+        let two_d = 2 * d as u64;
 
-        // Stage 2 - Improved Standard Continuation
+        let mut beta: Vec<Integer> = vec![Integer::default(); d + 1];
+        let mut s: Vec<Point> = vec![Point::default(); d + 1];
         s[1] = q.double();
         s[2] = s[1].double();
         beta[1] = Integer::from(&s[1].x_cord * &s[1].z_cord) % n;
@@ -137,170 +811,3349 @@ pub fn ecm_one_factor(
         }
 
         let mut g = Integer::from(1);
-        let b = b1 - 1;
-        let mut t = q.mont_ladder(&Integer::from(b - two_d));
-        let mut r = q.mont_ladder(&Integer::from(b));
+        let b = self.b1 - 1;
+        let t_multiplier = Integer::from(b - two_d);
+        let r_multiplier = Integer::from(b);
+        let mut t = q.mont_ladder(&t_multiplier);
+        let mut r = q.mont_ladder(&r_multiplier);
+        // 2 doublings and d - 2 additions, each followed by one product for beta
+        let mut mulmods = 2 * (DOUBLE_MULMODS + 1)
+            + (d as u64 - 2) * (ADD_MULMODS + 1)
+            + ladder_mulmods(&t_multiplier)
+            + ladder_mulmods(&r_multiplier);
 
-        let mut primes = Primes::all().skip_while(|&q| q < b);
-        for rr in (b..b2).step_by(two_d) {
+        // Last prime of the last step
+        let end = b + self.b2.saturating_sub(b).div_ceil(two_d) * two_d;
+        // Primes up to B1 - 1 are covered by stage 1
+        let mut primes = self.primes_above(b, end).peekable();
+        for rr in (b..self.b2).step_by(two_d as usize) {
+            if cancel.is_some_and(CancelToken::is_cancelled) {
+                break;
+            }
             let alpha = Integer::from(&r.x_cord * &r.z_cord) % n;
-            for q in primes.by_ref().take_while(|&q| q <= rr + two_d) {
-                let delta = (q - rr) / 2;
-                let f = Integer::from(&r.x_cord - &s[d].x_cord)
-                    * Integer::from(&r.z_cord + &s[d].z_cord)
+            while let Some(q) = primes.next_if(|&q| q <= rr + two_d) {
+                let delta = ((q - rr) / 2) as usize;
+                let f = Integer::from(&r.x_cord - &s[delta].x_cord)
+                    * Integer::from(&r.z_cord + &s[delta].z_cord)
                     - &alpha
                     + &beta[delta];
                 g = (g * f) % n;
+                mulmods += 2;
+            }
+            if let Some(transcript) = transcript.as_deref_mut() {
+                transcript.push(g.clone());
             }
-            // Swap
-            std::mem::swap(&mut t, &mut r);
-            r = r.add(&s[d], &t);
+            // Next giant step (rr + 2d) * Q, the difference with the baby step is the previous one
+            let next = r.add(&s[d], &t);
+            t = std::mem::replace(&mut r, next);
+            // alpha and the addition
+            mulmods += 1 + ADD_MULMODS;
         }
-        g = g.gcd(n);
 
-        // Stage 2 Factor found
-        if &g != n && g != 1 {
-            return Ok(g);
-        }
+        (g.gcd(n), mulmods)
     }
 
-    // ECM failed, Increase the bounds
-    Err(Error::ECMFailed)
-}
-
-/// Optimal params retrieved from <https://gitlab.inria.fr/zimmerma/ecm>
-fn optimal_params(digits: usize) -> (usize, usize, usize) {
-    match digits {
-        1..=10 => (2_000, 160_000, 35),
-        11..=15 => (5_000, 500_000, 500),
-        16..=20 => (11_000, 1_900_000, 74),
-        21..=25 => (50_000, 13_000_000, 214),
-        26..=30 => (250_000, 130_000_000, 430),
-        31..=35 => (1_000_000, 1_000_000_000, 904),
-        36..=40 => (3_000_000, 5_700_000_000, 2350),
-        41..=45 => (11_000_000, 35_000_000_000, 4480),
-        46..=50 => (44_000_000, 240_000_000_000, 7553),
-        51..=55 => (110_000_000, 780_000_000_000, 17769),
-        56..=60 => (260_000_000, 3_200_000_000_000, 42017),
-        _ => (850_000_000, 16_000_000_000_000, 69408),
+    /// Primes above `b`, in order, from the table of primes if it covers them up to `end`.
+    ///
+    /// # Parameters
+    ///
+    /// - `b`: Bound of the primes, excluded.
+    /// - `end`: Last prime needed.
+    pub(crate) fn primes_above(&self, b: u64, end: u64) -> Box<dyn Iterator<Item = u64> + '_> {
+        match &self.prime_table {
+            Some(table) if table.covers(b, end) => {
+                let primes = table.primes();
+                Box::new(
+                    primes[primes.partition_point(|&q| q <= b)..]
+                        .iter()
+                        .copied(),
+                )
+            }
+            _ => Box::new(Primes::all().map(|q| q as u64).skip_while(move |&q| q <= b)),
+        }
     }
 }
 
-/// Performs factorization using Lenstra's Elliptic curve method.
-///
-/// This function repeatedly calls `ecm_one_factor` to compute the factors
-/// of n. First all the small factors are taken out using trial division.
-/// Then `ecm_one_factor` is used to compute one factor at a time.
-///
-/// # Parameters
+/// Baby steps of stage 2 are capped to this number by default, see [`Stage2Width::Adaptive`].
 ///
-/// - `n`: Number to be factored.
-pub fn ecm(
-    n: &Integer,
-    #[cfg(feature = "progress-bar")] pb: Option<&ProgressBar>,
-) -> Result<HashMap<Integer, usize>, Error> {
-    let optimal_params = optimal_params(n.to_string().len());
+/// Each baby step keeps a point and a product, 3 integers of the size of n:
+/// about 30 MB for numbers of 300 digits.
+pub const DEFAULT_MAX_BABY_STEPS: usize = 1 << 16;
 
-    ecm_with_params(
-        n,
-        optimal_params.0,
-        optimal_params.1,
-        optimal_params.2,
-        1234,
-        #[cfg(feature = "progress-bar")]
-        pb,
-    )
+/// Choice of the number of baby steps d of stage 2, the width of its table.
+///
+/// Stage 2 precomputes d baby steps, then walks `(B1, B2]` by giant steps of `2d`:
+/// each baby step and each giant step costs a point addition and a product,
+/// and the table takes memory proportional to d.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage2Width {
+    /// Minimizes the modular multiplications of the baby and giant steps,
+    /// `d = sqrt((B2 - B1) / 2)`, with at most `max_baby_steps` baby steps.
+    Adaptive {
+        /// Memory cap, in baby steps.
+        max_baby_steps: usize,
+    },
+    /// `d = sqrt(B2)`, the width used before the cost model, for comparisons.
+    Sqrt,
 }
 
-/// Performs factorization using Lenstra's Elliptic curve method.
-///
-/// This function repeatedly calls `ecm_one_factor` to compute the factors
-/// of n. First all the small factors are taken out using trial division.
-/// Then `ecm_one_factor` is used to compute one factor at a time.
-///
-/// # Parameters
-///
-/// - `n`: Number to be factored.
-/// - `B1`: Stage 1 Bound.
-/// - `B2`: Stage 2 Bound.
-/// - `max_curve`: Maximum number of curves generated.
-/// - `seed`: Initialize pseudorandom generator.
-pub fn ecm_with_params(
-    n: &Integer,
-    b1: usize,
-    b2: usize,
-    max_curve: usize,
-    seed: usize,
-    #[cfg(feature = "progress-bar")] pb: Option<&ProgressBar>,
-) -> Result<HashMap<Integer, usize>, Error> {
-    let mut factors = HashMap::new();
+impl Default for Stage2Width {
+    fn default() -> Self {
+        Stage2Width::Adaptive {
+            max_baby_steps: DEFAULT_MAX_BABY_STEPS,
+        }
+    }
+}
 
-    let mut n: Integer = n.clone();
-    for prime in Primes::all().take(100_000) {
-        if n.is_divisible_u(prime as u32) {
-            let prime = Integer::from(prime);
-            while n.is_divisible(&prime) {
-                n /= &prime;
-                *factors.entry(prime.clone()).or_insert(0) += 1;
+impl Stage2Width {
+    /// Number of baby steps for the bounds B1 and B2.
+    ///
+    /// Giant steps start at `B1 - 1 - 2d`, so d is also kept below `B1 / 2`, and at least 2.
+    ///
+    /// # Parameters
+    ///
+    /// - `B1`: Stage 1 Bound.
+    /// - `B2`: Stage 2 Bound.
+    pub fn baby_steps(self, b1: u64, b2: u64) -> usize {
+        let d = match self {
+            Stage2Width::Adaptive { max_baby_steps } => {
+                let d = (b2.saturating_sub(b1) / 2).isqrt();
+                usize::try_from(d).unwrap_or(usize::MAX).min(max_baby_steps)
             }
+            Stage2Width::Sqrt => usize::try_from(b2.isqrt()).unwrap_or(usize::MAX),
+        };
+        let below_b1 = usize::try_from(b1.saturating_sub(2) / 2).unwrap_or(usize::MAX);
+        d.min(below_b1).max(2)
+    }
+
+    /// Adaptive width whose table of baby steps takes at most `bytes` for numbers of `bits` bits.
+    ///
+    /// Beyond the cap, stage 2 takes more giant steps of a narrower table,
+    /// which costs more multiplications but no more memory.
+    ///
+    /// # Parameters
+    ///
+    /// - `bytes`: Memory cap of the table.
+    /// - `bits`: Size of the numbers factored.
+    pub fn for_memory(bytes: usize, bits: u32) -> Stage2Width {
+        Stage2Width::Adaptive {
+            max_baby_steps: baby_steps_in(bytes, bits),
+        }
+    }
+
+    /// Same width, with at most `max_baby_steps` baby steps.
+    fn capped(self, max_baby_steps: usize) -> Stage2Width {
+        match self {
+            Stage2Width::Adaptive { max_baby_steps: d } => Stage2Width::Adaptive {
+                max_baby_steps: d.min(max_baby_steps),
+            },
+            Stage2Width::Sqrt => Stage2Width::Adaptive { max_baby_steps },
         }
     }
+}
 
-    let mut rand_state = RandState::new();
-    rand_state.seed(&seed.into());
+/// Integers kept by each baby step of stage 2: the coordinates of its point and their product.
+const BABY_STEP_INTEGERS: usize = 3;
 
-    while n != 1 {
-        let factor = ecm_one_factor(
-            &n,
+/// Number of baby steps fitting in `bytes` for numbers of `bits` bits.
+fn baby_steps_in(bytes: usize, bits: u32) -> usize {
+    let integer = size_of::<Integer>() + bits.div_ceil(64) as usize * 8;
+    bytes / (BABY_STEP_INTEGERS * integer)
+}
+
+/// Modular multiplications of [`Point::add`], squarings included.
+const ADD_MULMODS: u64 = 6;
+
+/// Modular multiplications of [`Point::double`], squarings included.
+const DOUBLE_MULMODS: u64 = 5;
+
+/// Modular multiplications of [`Point::mont_ladder`] by k: one doubling, then
+/// one addition and one doubling per bit of k after the leading one.
+fn ladder_mulmods(k: &Integer) -> u64 {
+    DOUBLE_MULMODS
+        + u64::from(k.significant_bits().saturating_sub(1)) * (ADD_MULMODS + DOUBLE_MULMODS)
+}
+
+/// Incremental computation of [`Stages`], which takes minutes for large bounds.
+struct StagesBuilder {
+    b1: u64,
+    b2: u64,
+    /// Number of baby steps of stage 2.
+    d: usize,
+    /// Primes not processed yet.
+    primes: Primes,
+    /// Products of the prime powers processed so far, by chunk.
+    chunks: Vec<Integer>,
+    done: bool,
+    prime_table: Option<Arc<PrimeTable>>,
+}
+
+impl StagesBuilder {
+    fn new(
+        b1: u64,
+        b2: u64,
+        width: Stage2Width,
+        prime_table: Option<Arc<PrimeTable>>,
+    ) -> StagesBuilder {
+        StagesBuilder {
             b1,
             b2,
-            max_curve,
-            &mut rand_state,
-            #[cfg(feature = "progress-bar")]
-            pb,
-        )
-        .unwrap_or(n.clone());
+            d: width.baby_steps(b1, b2),
+            primes: Primes::all(),
+            chunks: Vec::new(),
+            done: false,
+            prime_table,
+        }
+    }
 
-        while n.is_divisible(&factor) {
-            n /= &factor;
-            *factors.entry(factor.clone()).or_insert(0) += 1;
+    /// Processes the next [`PRIMES_PER_CHECK`] primes.
+    ///
+    /// Returns true once all primes below B1 are processed.
+    fn advance(&mut self) -> bool {
+        if self.done {
+            return true;
         }
+
+        let b1 = self.b1;
+        let mut count = 0;
+        let chunk = tree::product(
+            self.primes
+                .by_ref()
+                .map(|p| p as u64)
+                .take_while(|&p| p <= b1)
+                .take(PRIMES_PER_CHECK)
+                .inspect(|_| count += 1)
+                .map(|p| p.pow(b1.ilog(p))),
+        );
+        self.chunks.push(chunk);
+        self.done = count < PRIMES_PER_CHECK;
+        self.done
     }
 
-    Ok(factors)
+    /// Returns the stages, all primes must have been processed.
+    fn finish(&mut self) -> Stages {
+        Stages {
+            b1: self.b1,
+            b2: self.b2,
+            d: self.d,
+            k: tree::product_of(std::mem::take(&mut self.chunks)),
+            prime_table: self.prime_table.take(),
+        }
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::str::FromStr;
+/// Stages with a stage 2 bound up to this one get a shared table of primes in a [`StagesCache`].
+///
+/// The table of the primes below 10^8 takes about 46 MB.
+const SHARED_PRIME_TABLE_BOUND: u64 = 100_000_000;
 
-    use super::*;
+/// Stages shared by the factorizers of a batch, by bounds.
+///
+/// The stage 1 multiplier is computed once per bounds instead of once per
+/// input, and so is the table of the primes of stage 2 for moderate bounds.
+/// Factorizers needing the same bounds at the same time may both compute
+/// them, the first ones stored are kept.
+#[derive(Default)]
+pub(crate) struct StagesCache(Mutex<HashMap<(u64, u64, usize), Arc<Stages>>>);
 
-    fn ecm(n: &Integer) -> Result<HashMap<Integer, usize>, Error> {
-        super::ecm(
-            n,
-            #[cfg(feature = "progress-bar")]
-            None,
-        )
+impl std::fmt::Debug for StagesCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StagesCache")
     }
+}
 
-    #[test]
-    fn sympy_1() {
-        assert_eq!(
-            ecm(&Integer::from_str("398883434337287").unwrap()).unwrap(),
-            HashMap::from([
-                (Integer::from_str("99476569").unwrap(), 1),
-                (Integer::from_str("4009823").unwrap(), 1),
-            ])
-        );
+impl StagesCache {
+    fn get(&self, b1: u64, b2: u64, d: usize) -> Option<Arc<Stages>> {
+        let stages = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        stages.get(&(b1, b2, d)).cloned()
     }
 
-    #[test]
-    fn sympy_2() {
-        assert_eq!(
-            ecm(&Integer::from_str("46167045131415113").unwrap()).unwrap(),
-            HashMap::from([
-                (Integer::from_str("43").unwrap(), 1),
+    /// Stores `stages`, returning the stages stored for the same bounds.
+    fn insert(&self, mut stages: Stages) -> Arc<Stages> {
+        if stages.prime_table.is_none() && stages.b2 <= SHARED_PRIME_TABLE_BOUND {
+            stages.prime_table = Some(Arc::new(PrimeTable::for_bounds(stages.b1, stages.b2)));
+        }
+        let mut cache = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(
+            cache
+                .entry((stages.b1, stages.b2, stages.d))
+                .or_insert_with(|| Arc::new(stages)),
+        )
+    }
+}
+
+/// Stages of a [`Factorizer`], computed on first use.
+enum LazyStages {
+    Building(Box<StagesBuilder>),
+    Ready(Arc<Stages>),
+}
+
+/// Returns `g` if it is a proper factor of n.
+pub(crate) fn proper_factor(g: Integer, n: &Integer) -> Option<Integer> {
+    if &g != n && g != 1 {
+        Some(g)
+    } else {
+        None
+    }
+}
+
+/// Returns `base^exp mod n` for a non-negative `exp`.
+///
+/// GMP only fails on negative exponents without inverse, which callers never use.
+pub(crate) fn pow_mod(base: &Integer, exp: &Integer, n: &Integer) -> Integer {
+    debug_assert!(*exp >= 0, "negative exponent");
+    base.pow_mod_ref(exp, n)
+        .map(Integer::from)
+        .unwrap_or_default()
+}
+
+/// Returns a random integer in `[0, bound)`.
+///
+/// Unlike [`Integer::random_below`], the result does not depend on the size
+/// of GMP limbs: the integer is built from 32-bit outputs of the generator,
+/// so a given seed produces the same values on 32-bit and 64-bit targets.
+pub(crate) fn random_below(bound: &Integer, rgen: &mut RandState<'_>) -> Integer {
+    let bits = bound.significant_bits();
+    let words = bits.div_ceil(32);
+
+    // Rejection sampling over [0, 2^bits)
+    loop {
+        let mut value = Integer::new();
+        for _ in 0..words {
+            value <<= 32;
+            value += rgen.bits(32);
+        }
+        value.keep_bits_mut(bits);
+        if value < *bound {
+            return value;
+        }
+    }
+}
+
+/// Number of threads which can run in parallel, at least 1.
+pub(crate) fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Returns a seed drawn from OS entropy, different on each call.
+///
+/// The standard library seeds the keys of `HashMap` from OS entropy,
+/// hashing with fresh keys gives a new random value without extra dependencies.
+pub fn entropy_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    if let Ok(elapsed) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    hasher.finish()
+}
+
+/// Number of primes processed between two checks of the time budget.
+const PRIMES_PER_CHECK: usize = 1 << 14;
+
+/// Probability of missing every factor under which raising the bounds is given up, see [`bounds_exhausted`].
+const EXHAUSTED_MISS_PROBABILITY: f64 = 1e-6;
+
+/// Number of times the bounds are raised on a composite before giving up, by default.
+const DEFAULT_MAX_ESCALATIONS: u32 = 2;
+
+/// Number of primes whose trial division chunks are cached.
+const TRIAL_DIVISION_PRIMES: usize = 100_000;
+
+/// Inputs with more bits, from about 10^10000, are rejected by default with [`Error::InputTooLarge`].
+///
+/// On such numbers a single curve takes hours and ECM only finds
+/// factors small relative to n: numbers of special forms, like
+/// `b^k +- 1`, are factored faster by dedicated methods.
+pub const MAX_INPUT_BITS: u32 = 33_219;
+
+/// Smallest stage 1 bound, fails with [`Error::BoundsTooSmall`] below.
+///
+/// Stage 2 has at least 2 baby steps and its giant steps start at `B1 - 1 - 2d`,
+/// see [`Stage2Width::baby_steps`].
+pub const MIN_B1: u64 = 6;
+
+/// Trial division tries the primes below this bound by default: the first 100,000 primes.
+pub const DEFAULT_TRIAL_DIVISION_BOUND: u32 = 1_299_710;
+
+/// Optimal params for factors of `digits` digits, see [`BoundPreset`].
+fn optimal_params(digits: usize) -> (u64, u64, u64) {
+    BoundPreset::for_digits(digits).params()
+}
+
+/// Next [`BoundPreset`] with a stage 1 bound greater than `b1`.
+fn next_params(b1: u64) -> Option<(u64, u64, u64)> {
+    BoundPreset::ALL
+        .into_iter()
+        .map(BoundPreset::params)
+        .find(|&(next_b1, _, _)| next_b1 > b1)
+}
+
+/// Expected number of curves run on n with bounds `b1` and `b2` until one finds a factor,
+/// at most `curves`, see [`success_probability`].
+///
+/// Factors are assumed to have half the bits of n, the most the smallest one can have.
+fn expected_curves(n: &Integer, b1: u64, b2: u64, curves: u64) -> u64 {
+    let p = success_probability(b1, b2, n.significant_bits().div_ceil(2));
+    if p <= 0.0 {
+        return curves;
+    }
+    // Mean of a geometric law truncated to `curves` trials
+    ((1.0 - (1.0 - p).powf(curves as f64)) / p).ceil() as u64
+}
+
+/// Returns true if `curves` curves with bounds `b1` and `b2` would have found a factor of n
+/// with near certainty, see [`success_probability`].
+///
+/// Larger bounds would then not do better: the curves failed because they find all
+/// the factors at once, or n is not the composite it was taken for.
+fn bounds_exhausted(n: &Integer, b1: u64, b2: u64, curves: u64) -> bool {
+    let p = success_probability(b1, b2, n.significant_bits().div_ceil(2));
+    (1.0 - p).powf(curves as f64) < EXHAUSTED_MISS_PROBABILITY
+}
+
+/// Stage 2 bound suited to the stage 1 bound `b1`.
+///
+/// Fitted on the default bounds of GMP-ECM, `B2` grows like `3.26 * B1^1.42`.
+/// The result is even and at least `b1`.
+///
+/// # Parameters
+///
+/// - `b1`: Stage 1 Bound.
+pub fn optimal_b2(b1: u64) -> u64 {
+    let b2 = (3.26 * (b1 as f64).powf(1.42)) as u64;
+    b2.max(b1) & !1
+}
+
+/// Exponent of stage 1: the product of the largest powers of the primes up to `b1`
+/// which are at most `b1`, times the `multipliers`.
+///
+/// Extra multipliers allow custom stage 1 strategies, e.g. forcing a known
+/// guess of the group order, see [`Point::ladder_with_exponent`].
+///
+/// ```
+/// use ecm::stage1_exponent;
+/// use rug::Integer;
+///
+/// // 2^3 * 3^2 * 5 * 7
+/// assert_eq!(stage1_exponent(10, []), 2520);
+/// assert_eq!(stage1_exponent(10, [Integer::from(11), Integer::from(11)]), 2520 * 121);
+/// ```
+///
+/// # Parameters
+///
+/// - `b1`: Bound of the primes.
+/// - `multipliers`: Extra factors of the exponent.
+pub fn stage1_exponent(b1: u64, multipliers: impl IntoIterator<Item = Integer>) -> Integer {
+    let mut builder = StagesBuilder::new(b1, b1, Stage2Width::default(), None);
+    while !builder.advance() {}
+    multipliers
+        .into_iter()
+        .fold(builder.finish().k, |k, multiplier| k * multiplier)
+}
+
+/// Performs factorization using Lenstra's Elliptic curve method.
+///
+/// This function repeatedly calls `ecm_one_factor` to compute the factors
+/// of n. First all the small factors are taken out using trial division.
+/// Then `ecm_one_factor` is used to compute one factor at a time.
+/// Composites of up to [`DEFAULT_SIQS_DIGITS`] digits on which the curves
+/// would take longer are split by [`siqs`](crate::siqs) instead.
+///
+/// The sign of n is ignored: -n has the same factorization as n,
+/// 1 and -1 have an empty one, and 0 fails with [`Error::ZeroInput`].
+///
+/// See [`EcmOptions::progress_observer`] to follow the curves run and the factors found.
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+pub fn ecm(n: &Integer) -> Result<Factorization, Error> {
+    EcmOptions::new().run(n)
+}
+
+/// Performs factorization using Lenstra's Elliptic curve method.
+///
+/// This function runs a [`Factorizer`] to completion.
+/// If n is a perfect power m^k, only m is factored and the exponents are multiplied by k.
+/// Then all the small factors are taken out using trial division,
+/// and cofactors which are perfect powers are reduced the same way.
+/// Then elliptic curves are used to compute one factor at a time.
+/// When all curves fail on a composite, the bounds are raised
+/// up to 2 times (see [`EcmOptions::max_escalations`]).
+/// Composites still not split are returned as cofactors, so the result
+/// is partial unless [`Factorization::is_complete`].
+///
+/// See [`EcmOptions`] for more settings, e.g. [`EcmOptions::progress_observer`].
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound, [`optimal_b2`] of B1 if `None`.
+/// - `max_curve`: Maximum number of curves generated.
+/// - `seed`: Initialize pseudorandom generator, from OS entropy if `None`.
+pub fn ecm_with_params(
+    n: &Integer,
+    b1: u64,
+    b2: Option<u64>,
+    max_curve: u64,
+    seed: Option<u64>,
+) -> Result<Factorization, Error> {
+    let options = EcmOptions::new().b1(b1).max_curves(max_curve);
+    let options = match seed {
+        Some(seed) => options.seed(seed),
+        None => options,
+    };
+    let options = match b2 {
+        Some(b2) => options.b2(b2),
+        None => options,
+    };
+
+    options.run(n)
+}
+
+/// Performs factorization using Lenstra's Elliptic curve method, with a caller-owned generator.
+///
+/// Same as [`ecm_with_params`], except that curves are chosen by `rgen`, which
+/// is advanced by the curves run, see [`EcmOptions::run_with_rand_state`].
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound, [`optimal_b2`] of B1 if `None`.
+/// - `max_curve`: Maximum number of curves generated.
+/// - `rgen`: Pseudorandom generator choosing the curves.
+pub fn ecm_with_rand_state(
+    n: &Integer,
+    b1: u64,
+    b2: Option<u64>,
+    max_curve: u64,
+    rgen: &mut RandState<'static>,
+) -> Result<Factorization, Error> {
+    let options = EcmOptions::new().b1(b1).max_curves(max_curve);
+    let options = match b2 {
+        Some(b2) => options.b2(b2),
+        None => options,
+    };
+
+    options.run_with_rand_state(n, rgen)
+}
+
+/// Settings of a factorization using Lenstra's Elliptic curve method.
+///
+/// Bounds and number of curves which are not set are chosen
+/// from the number of digits of n. If only B1 is set, B2 is [`optimal_b2`] of B1.
+///
+/// ```
+/// use ecm::EcmOptions;
+/// use rug::Integer;
+///
+/// let factors = EcmOptions::new()
+///     .b1(2_000)
+///     .b2(160_000)
+///     .max_curves(100)
+///     .seed(42)
+///     .run(&Integer::from(398883434337287u64))
+///     .unwrap();
+/// assert_eq!(factors.len(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct EcmOptions {
+    b1: Option<u64>,
+    b2: Option<u64>,
+    max_curves: Option<u64>,
+    target_digits: Option<usize>,
+    mode: DriverMode,
+    seed: Option<u64>,
+    primality: PrimalityStandard,
+    pub(crate) priority: Priority,
+    cpu_limit: Option<u8>,
+    max_escalations: u32,
+    stop_after_factors: Option<usize>,
+    trial_bound: u32,
+    small_bits: u32,
+    one_line_bits: RangeInclusive<u32>,
+    fermat_steps: u64,
+    rho_steps: u64,
+    pm1_bounds: (u64, u64),
+    pp1_bounds: (u64, u64),
+    pp1_seeds: usize,
+    /// Size of the composites given to the quadratic sieve, `None` to leave it to the bounds.
+    siqs_digits: Option<usize>,
+    max_input_bits: u32,
+    known_factors: Vec<Integer>,
+    #[cfg(feature = "coppersmith")]
+    hint: Option<HighBitsHint>,
+    #[cfg(feature = "coppersmith")]
+    solver: Option<SharedSolver>,
+    sink: Option<SharedSink>,
+    prime_table: Option<Arc<PrimeTable>>,
+    stage2_width: Stage2Width,
+    /// Memory cap of the table of stage 2, in bytes.
+    max_memory: Option<usize>,
+    curve_model: CurveModel,
+    parametrization: Parametrization,
+    stages_cache: Option<Arc<StagesCache>>,
+    resume: Option<ResumeState>,
+    /// Number of threads, `None` for all available cores.
+    threads: Option<usize>,
+    deterministic: bool,
+    cancel: Option<CancelToken>,
+    pub(crate) max_duration: Option<Duration>,
+    #[cfg(feature = "audit")]
+    audit: Option<Arc<AuditLog>>,
+    residues: Option<Arc<ResidueLog>>,
+    resume_file: Option<(PathBuf, ResumeFormat)>,
+    #[cfg(feature = "events")]
+    events: Option<Sender<EcmEvent>>,
+    observer: Option<SharedObserver>,
+}
+
+/// Progress sink shared by the options and the factorizers.
+#[derive(Clone)]
+struct SharedSink(Arc<dyn ProgressSink>);
+
+impl std::fmt::Debug for SharedSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressSink")
+    }
+}
+
+/// Progress observer shared by the options and the factorizers.
+#[derive(Clone)]
+struct SharedObserver(Arc<dyn ProgressObserver>);
+
+impl std::fmt::Debug for SharedObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressObserver")
+    }
+}
+
+/// Solver of partial keys shared by the options and the factorizers.
+#[cfg(feature = "coppersmith")]
+#[derive(Clone)]
+struct SharedSolver(Arc<dyn PartialKeySolver>);
+
+#[cfg(feature = "coppersmith")]
+impl std::fmt::Debug for SharedSolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PartialKeySolver")
+    }
+}
+
+impl Default for EcmOptions {
+    fn default() -> Self {
+        EcmOptions {
+            b1: None,
+            b2: None,
+            max_curves: None,
+            target_digits: None,
+            mode: DriverMode::Ecm,
+            seed: None,
+            primality: PrimalityStandard::default(),
+            priority: Priority::default(),
+            cpu_limit: None,
+            max_escalations: DEFAULT_MAX_ESCALATIONS,
+            stop_after_factors: None,
+            trial_bound: DEFAULT_TRIAL_DIVISION_BOUND,
+            small_bits: SMALL_COFACTOR_BITS,
+            one_line_bits: ONE_LINE_BITS,
+            fermat_steps: DEFAULT_FERMAT_STEPS,
+            rho_steps: DEFAULT_RHO_STEPS,
+            pm1_bounds: (DEFAULT_PM1_B1, DEFAULT_PM1_B2),
+            pp1_bounds: (0, 0),
+            pp1_seeds: DEFAULT_PP1_SEEDS,
+            siqs_digits: None,
+            max_input_bits: MAX_INPUT_BITS,
+            known_factors: Vec::new(),
+            #[cfg(feature = "coppersmith")]
+            hint: None,
+            #[cfg(feature = "coppersmith")]
+            solver: None,
+            sink: None,
+            prime_table: None,
+            stage2_width: Stage2Width::default(),
+            max_memory: None,
+            curve_model: CurveModel::default(),
+            parametrization: Parametrization::default(),
+            stages_cache: None,
+            resume: None,
+            threads: None,
+            deterministic: false,
+            cancel: None,
+            max_duration: None,
+            #[cfg(feature = "audit")]
+            audit: None,
+            residues: None,
+            resume_file: None,
+            #[cfg(feature = "events")]
+            events: None,
+            observer: None,
+        }
+    }
+}
+
+impl EcmOptions {
+    /// Default settings.
+    pub fn new() -> EcmOptions {
+        EcmOptions::default()
+    }
+
+    /// Sets the stage 1 bound, must be even.
+    ///
+    /// A bound of 0 disables elliptic curves, as [`DriverMode::TrialDivision`].
+    pub fn b1(mut self, b1: u64) -> Self {
+        self.b1 = Some(b1);
+        self
+    }
+
+    /// Sets the stage 2 bound, must be even.
+    pub fn b2(mut self, b2: u64) -> Self {
+        self.b2 = Some(b2);
+        self
+    }
+
+    /// Sets the maximum number of curves generated per composite.
+    pub fn max_curves(mut self, max_curves: u64) -> Self {
+        self.max_curves = Some(max_curves);
+        self
+    }
+
+    /// Sets the bounds and the maximum number of curves of a standard level.
+    ///
+    /// Same as [`EcmOptions::b1`], [`EcmOptions::b2`] and [`EcmOptions::max_curves`]
+    /// with the values of the preset. Bounds are still raised to the next levels
+    /// on composites not split, unless [`EcmOptions::max_escalations`] is 0.
+    ///
+    /// # Parameters
+    ///
+    /// - `preset`: Level to run, such as [`BoundPreset::Digits35`] for t35.
+    pub fn preset(self, preset: BoundPreset) -> Self {
+        let (b1, b2, max_curves) = preset.params();
+        self.b1(b1).b2(b2).max_curves(max_curves)
+    }
+
+    /// Only looks for factors of up to `digits` decimal digits (t-level factoring).
+    ///
+    /// The bounds and number of curves are the standard ones for factors of
+    /// that size, instead of being chosen from the size of n, and the bounds
+    /// are never raised. Composites still not split once the curves are run
+    /// are returned as cofactors: they very likely have no factor of `digits`
+    /// digits or less. Bounds and curves set explicitly take precedence.
+    ///
+    /// ```
+    /// use ecm::EcmOptions;
+    /// use rug::Integer;
+    ///
+    /// // 1000000007 * (2^89 - 1) * (2^107 - 1)
+    /// let p = Integer::from(1_000_000_007);
+    /// let big = (Integer::from(1) << 89u32) - 1u32;
+    /// let bigger = (Integer::from(1) << 107u32) - 1u32;
+    /// let n = Integer::from(&p * &big) * &bigger;
+    ///
+    /// let factors = EcmOptions::new().target_digits(10).seed(1).run(&n).unwrap();
+    /// assert_eq!(factors.multiplicity(&p), 1);
+    /// assert_eq!(factors.cofactors().count(), 1);
+    /// assert_eq!(factors.product(), n);
+    /// ```
+    pub fn target_digits(mut self, digits: usize) -> Self {
+        self.target_digits = Some(digits);
+        self
+    }
+
+    /// Sets the methods run after trial division, see [`Factorizer::set_mode`].
+    pub fn mode(mut self, mode: DriverMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the seed of the pseudorandom generator, for reproducible runs.
+    ///
+    /// Without a seed, each run draws one from OS entropy, see [`entropy_seed`].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the standard cofactors must meet to be considered prime.
+    pub fn primality(mut self, primality: PrimalityStandard) -> Self {
+        self.primality = primality;
+        self
+    }
+
+    /// Sets the operating system priority of the factorization.
+    ///
+    /// With a priority other than [`Priority::Normal`], [`EcmOptions::run`]
+    /// factors n on a dedicated thread, so the priority of the caller is unchanged.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Limits the CPU usage to `cpu_percent` percent of one core, by sleeping between curves.
+    pub fn cpu_limit(mut self, cpu_percent: u8) -> Self {
+        self.cpu_limit = Some(cpu_percent);
+        self
+    }
+
+    /// Sets how many times the bounds are raised when all curves failed, defaults to 2.
+    ///
+    /// See [`Factorizer::set_max_escalations`]. Ignored with [`EcmOptions::target_digits`].
+    pub fn max_escalations(mut self, max_escalations: u32) -> Self {
+        self.max_escalations = max_escalations;
+        self
+    }
+
+    /// Stops once `count` distinct prime factors are found, see [`Factorizer::set_stop_after_factors`].
+    pub fn stop_after_factors(mut self, count: usize) -> Self {
+        self.stop_after_factors = Some(count);
+        self
+    }
+
+    /// Sets how the number of baby steps of stage 2 is chosen, see [`Factorizer::set_stage2_width`].
+    pub fn stage2_width(mut self, width: Stage2Width) -> Self {
+        self.stage2_width = width;
+        self
+    }
+
+    /// Caps the memory of the table of stage 2 to `bytes`, see [`Factorizer::set_max_memory`].
+    pub fn max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    /// Sets the arithmetic of stage 1, see [`Factorizer::set_curve_model`].
+    pub fn curve_model(mut self, model: CurveModel) -> Self {
+        self.curve_model = model;
+        self
+    }
+
+    /// Sets the family of the curves, see [`Factorizer::set_parametrization`].
+    pub fn parametrization(mut self, parametrization: Parametrization) -> Self {
+        self.parametrization = parametrization;
+        self
+    }
+
+    /// Sets the bound of trial division, see [`Factorizer::set_trial_division_bound`].
+    pub fn trial_division_bound(mut self, bound: u32) -> Self {
+        self.trial_bound = bound;
+        self
+    }
+
+    /// Sets the size of the cofactors split without curves, see [`Factorizer::set_small_cofactor_bits`].
+    pub fn small_cofactor_bits(mut self, bits: u32) -> Self {
+        self.small_bits = bits;
+        self
+    }
+
+    /// Sets the sizes of the cofactors given to Hart's one line factorization, see [`Factorizer::set_one_line_bits`].
+    pub fn one_line_bits(mut self, bits: RangeInclusive<u32>) -> Self {
+        self.one_line_bits = bits;
+        self
+    }
+
+    /// Sets the steps of Fermat's method on large cofactors, see [`Factorizer::set_fermat_steps`].
+    pub fn fermat_steps(mut self, steps: u64) -> Self {
+        self.fermat_steps = steps;
+        self
+    }
+
+    /// Sets the steps of Pollard's rho method on large cofactors, see [`Factorizer::set_rho_steps`].
+    pub fn rho_steps(mut self, steps: u64) -> Self {
+        self.rho_steps = steps;
+        self
+    }
+
+    /// Sets the bounds of Pollard's p-1 method on large cofactors, see [`Factorizer::set_pm1_bounds`].
+    pub fn pm1_bounds(mut self, b1: u64, b2: u64) -> Self {
+        self.pm1_bounds = (b1, b2);
+        self
+    }
+
+    /// Sets the bounds of Williams' p+1 method on large cofactors, see [`Factorizer::set_pp1_bounds`].
+    pub fn pp1_bounds(mut self, b1: u64, b2: u64) -> Self {
+        self.pp1_bounds = (b1, b2);
+        self
+    }
+
+    /// Sets the number of seeds tried by Williams' p+1 method, see [`Factorizer::set_pp1_seeds`].
+    pub fn pp1_seeds(mut self, seeds: usize) -> Self {
+        self.pp1_seeds = seeds;
+        self
+    }
+
+    /// Sets the size of the composites given to the quadratic sieve, see [`Factorizer::set_siqs_digits`].
+    ///
+    /// Defaults to [`DEFAULT_SIQS_DIGITS`] if neither the bounds, the number of
+    /// curves nor the target digits are set, and to 0 otherwise: the curves
+    /// asked for are then the whole effort.
+    pub fn siqs_digits(mut self, digits: usize) -> Self {
+        self.siqs_digits = Some(digits);
+        self
+    }
+
+    /// Sets the size limit of n, see [`Factorizer::set_max_input_bits`].
+    pub fn max_input_bits(mut self, bits: u32) -> Self {
+        self.max_input_bits = bits;
+        self
+    }
+
+    /// Adds factors of n already known, see [`Factorizer::add_known_factor`].
+    pub fn known_factors(mut self, factors: impl IntoIterator<Item = Integer>) -> Self {
+        self.known_factors.extend(factors);
+        self
+    }
+
+    /// Gives the known high bits of a factor of n, see [`Factorizer::set_high_bits_hint`].
+    #[cfg(feature = "coppersmith")]
+    pub fn hint(mut self, hint: HighBitsHint) -> Self {
+        self.hint = Some(hint);
+        self
+    }
+
+    /// Recovers the factor of the hint with `solver`, see [`Factorizer::set_partial_key_solver`].
+    #[cfg(feature = "coppersmith")]
+    pub fn partial_key_solver(mut self, solver: Arc<dyn PartialKeySolver>) -> Self {
+        self.solver = Some(SharedSolver(solver));
+        self
+    }
+
+    /// Uses a precomputed table of primes in stage 2, see [`Factorizer::set_prime_table`].
+    pub fn prime_table(mut self, table: Arc<PrimeTable>) -> Self {
+        self.prime_table = Some(table);
+        self
+    }
+
+    /// Reports the progress of the factorization to `sink`.
+    pub fn progress_sink(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.sink = Some(SharedSink(sink));
+        self
+    }
+
+    /// Sets the number of threads, 0 for all available cores, the default.
+    ///
+    /// Bounds the workers of [`ecm_many`](crate::ecm_many) and [`BatchOptions`](crate::BatchOptions),
+    /// and with the `parallel` or `rayon` feature, the curves run concurrently by each
+    /// factorization, see [`Factorizer::set_threads`].
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = (threads != 0).then_some(threads);
+        self
+    }
+
+    /// Makes the curves run concurrently give the same results as one thread, see [`Factorizer::set_deterministic`].
+    pub fn deterministic(mut self) -> Self {
+        self.deterministic = true;
+        self
+    }
+
+    /// Number of threads to use, see [`EcmOptions::threads`].
+    pub(crate) fn thread_count(&self) -> usize {
+        self.threads.unwrap_or_else(available_threads)
+    }
+
+    /// Aborts the factorization once `token` is cancelled, see [`CancelToken`].
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Gives up after `duration` of wall-clock time, unlimited by default.
+    ///
+    /// [`EcmOptions::run`] then returns the factors found so far, the parts of n
+    /// not factored yet being cofactors. Curves are not interrupted, so the
+    /// duration may be exceeded by the duration of one curve.
+    ///
+    /// ```
+    /// use ecm::EcmOptions;
+    /// use rug::Integer;
+    /// use std::time::Duration;
+    ///
+    /// let p: Integer = "100000000000000000000000000319".parse().unwrap();
+    /// let q: Integer = "300000000000000000000000000007".parse().unwrap();
+    /// let n = Integer::from(&p * &q) * 12;
+    /// let options = EcmOptions::new().b1(2_000).max_duration(Duration::from_millis(100));
+    /// let partial = options.run(&n).unwrap();
+    /// assert_eq!(partial.iter().collect::<Vec<_>>(), [(&2.into(), 2), (&3.into(), 1)]);
+    /// assert_eq!(partial.cofactors().collect::<Vec<_>>(), [(&(p * q), 1)]);
+    /// ```
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Records every curve counted in `log`, see [`AuditLog`].
+    #[cfg(feature = "audit")]
+    pub fn audit_log(mut self, log: Arc<AuditLog>) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// Records the stage 1 residue of every curve reaching stage 2 in `log`, see [`ResidueLog`].
+    pub fn residue_log(mut self, log: Arc<ResidueLog>) -> Self {
+        self.residues = Some(log);
+        self
+    }
+
+    /// Writes a resume file to `path` when the factorization is interrupted, see [`Factorizer::set_resume_file`].
+    pub fn resume_file(mut self, path: impl Into<PathBuf>, format: ResumeFormat) -> Self {
+        self.resume_file = Some((path.into(), format));
+        self
+    }
+
+    /// Sends the events of the factorization to `sender`, see [`Factorizer::events`].
+    #[cfg(feature = "events")]
+    pub fn event_sender(mut self, sender: Sender<EcmEvent>) -> Self {
+        self.events = Some(sender);
+        self
+    }
+
+    /// Reports the curves run and the factors found to `observer`.
+    pub fn progress_observer(mut self, observer: Arc<dyn ProgressObserver>) -> Self {
+        self.observer = Some(SharedObserver(observer));
+        self
+    }
+
+    /// Reports the curves run on each composite to `pb`, see [`EcmOptions::progress_observer`].
+    #[cfg(feature = "progress-bar")]
+    pub fn progress_bar(self, pb: ProgressBar) -> Self {
+        self.progress_observer(Arc::new(pb))
+    }
+
+    /// Continues the progress saved by [`Factorizer::resume_state`], see [`Factorizer::restore_progress`].
+    pub fn resume_from(mut self, state: ResumeState) -> Self {
+        self.resume = Some(state);
+        self
+    }
+
+    /// Shares the stages of the factorizers created with these settings, see [`StagesCache`].
+    pub(crate) fn share_stages(mut self) -> Self {
+        self.stages_cache = Some(Arc::default());
+        self
+    }
+
+    /// Creates a [`Factorizer`] for n with these settings.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    pub fn factorizer(&self, n: &Integer) -> Result<Factorizer, Error> {
+        let (b1, b2, max_curves) = match self.target_digits {
+            Some(digits) => optimal_params(digits.max(1)),
+            None => optimal_params(decimal_digits(n)),
+        };
+        let b2 = match (self.b1, self.b2) {
+            (_, Some(b2)) => b2,
+            (Some(b1), None) => optimal_b2(b1),
+            (None, None) => b2,
+        };
+        let mut factorizer = Factorizer::new(
+            n,
+            self.b1.unwrap_or(b1),
+            b2,
+            self.max_curves.unwrap_or(max_curves),
+            self.seed,
+        )?;
+        factorizer.set_primality_standard(self.primality);
+        if self.mode != DriverMode::Ecm {
+            factorizer.set_mode(self.mode);
+        }
+        factorizer.set_max_escalations(match self.target_digits {
+            // Larger bounds would look for larger factors than asked for
+            Some(_) => 0,
+            None => self.max_escalations,
+        });
+        if let Some(count) = self.stop_after_factors {
+            factorizer.set_stop_after_factors(count);
+        }
+        factorizer.set_trial_division_bound(self.trial_bound);
+        factorizer.set_small_cofactor_bits(self.small_bits);
+        factorizer.set_one_line_bits(self.one_line_bits.clone());
+        factorizer.set_fermat_steps(self.fermat_steps);
+        factorizer.set_rho_steps(self.rho_steps);
+        factorizer.set_pm1_bounds(self.pm1_bounds.0, self.pm1_bounds.1);
+        factorizer.set_pp1_bounds(self.pp1_bounds.0, self.pp1_bounds.1);
+        factorizer.set_pp1_seeds(self.pp1_seeds);
+        let siqs_digits = match (self.b1, self.b2, self.max_curves, self.target_digits) {
+            (None, None, None, None) => DEFAULT_SIQS_DIGITS,
+            _ => 0,
+        };
+        factorizer.set_siqs_digits(self.siqs_digits.unwrap_or(siqs_digits));
+        factorizer.set_max_input_bits(self.max_input_bits);
+        for factor in &self.known_factors {
+            factorizer.add_known_factor(factor.clone());
+        }
+        #[cfg(feature = "coppersmith")]
+        if let Some(hint) = &self.hint {
+            factorizer.set_high_bits_hint(hint.clone());
+        }
+        #[cfg(feature = "coppersmith")]
+        if let Some(SharedSolver(solver)) = &self.solver {
+            factorizer.set_partial_key_solver(Arc::clone(solver));
+        }
+        if let Some(SharedSink(sink)) = &self.sink {
+            factorizer.set_progress_sink(Arc::clone(sink));
+        }
+        if let Some(cpu_percent) = self.cpu_limit {
+            factorizer.set_cpu_limit(cpu_percent);
+        }
+        factorizer.set_stage2_width(self.stage2_width);
+        if let Some(bytes) = self.max_memory {
+            factorizer.set_max_memory(bytes);
+        }
+        factorizer.set_curve_model(self.curve_model);
+        factorizer.set_parametrization(self.parametrization);
+        if let Some(table) = &self.prime_table {
+            factorizer.set_prime_table(Arc::clone(table));
+        }
+        if let Some(cache) = &self.stages_cache {
+            factorizer.set_stages_cache(Arc::clone(cache));
+        }
+        if let Some(state) = &self.resume {
+            factorizer.restore_progress(state.clone());
+        }
+        #[cfg(any(feature = "parallel", feature = "rayon"))]
+        {
+            factorizer.set_threads(self.thread_count());
+            factorizer.set_deterministic(self.deterministic);
+        }
+        if let Some(token) = &self.cancel {
+            factorizer.set_cancel_token(token.clone());
+        }
+        #[cfg(feature = "audit")]
+        if let Some(log) = &self.audit {
+            factorizer.set_audit_log(Arc::clone(log));
+        }
+        if let Some(log) = &self.residues {
+            factorizer.set_residue_log(Arc::clone(log));
+        }
+        if let Some((path, format)) = &self.resume_file {
+            factorizer.set_resume_file(path.clone(), *format);
+        }
+        #[cfg(feature = "events")]
+        if let Some(sender) = &self.events {
+            factorizer.set_event_sender(sender.clone());
+        }
+        if let Some(SharedObserver(observer)) = &self.observer {
+            factorizer.set_progress_observer(Arc::clone(observer));
+        }
+
+        Ok(factorizer)
+    }
+
+    /// Factors n with these settings.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    pub fn run(&self, n: &Integer) -> Result<Factorization, Error> {
+        self.run_with(n, None).map(|(factors, _)| factors)
+    }
+
+    /// Same as [`EcmOptions::run`], also returning the statistics of the run, see [`Factorizer::stats`].
+    ///
+    /// ```
+    /// use ecm::EcmOptions;
+    /// use rug::Integer;
+    ///
+    /// let n = Integer::from(398883434337287u64);
+    /// let options = EcmOptions::new().seed(1).small_cofactor_bits(0);
+    /// let (factors, stats) = options.run_with_stats(&n).unwrap();
+    /// assert_eq!(stats.factors.len(), factors.len());
+    /// assert_eq!(stats.curves(), stats.stages.curves);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    pub fn run_with_stats(&self, n: &Integer) -> Result<(Factorization, Stats), Error> {
+        self.run_with(n, None)
+    }
+
+    /// Splits the curves run on n with these settings into `parts` [`WorkUnit`]s.
+    ///
+    /// The units share the bounds, number of curves and parametrization of the
+    /// first composite, with sigmas from [`FIRST_SIGMA`]. Trial division and the escalation of
+    /// the bounds are left to the caller, see [`WorkUnit::split`] for other sigmas.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `parts`: Number of units.
+    pub fn work_units(&self, n: &Integer, parts: usize) -> Result<Vec<WorkUnit>, Error> {
+        let state = self.factorizer(n)?.resume_state();
+        let sigmas = FIRST_SIGMA..FIRST_SIGMA + state.max_curves;
+        let mut units = WorkUnit::split(n, state.b1, state.b2, sigmas, parts);
+        for unit in &mut units {
+            unit.parametrization = self.parametrization;
+        }
+        Ok(units)
+    }
+
+    /// Returns a proper factor of n, stopping as soon as one is found.
+    ///
+    /// Same as [`EcmOptions::run`], except that the factorization stops at the
+    /// first proper factor of n found, e.g. by trial division, which is not necessarily prime.
+    /// Returns `None` if n has no proper factor (0, 1, -1 and primes), on errors,
+    /// or if no factor was found with these settings.
+    ///
+    /// ```
+    /// use ecm::EcmOptions;
+    /// use rug::Integer;
+    ///
+    /// let n = Integer::from(398883434337287u64);
+    /// let factor = EcmOptions::new().seed(1).first_factor(&n).unwrap();
+    /// assert!(factor != 1 && factor != n && n.is_divisible(&factor));
+    /// assert_eq!(EcmOptions::new().first_factor(&Integer::from(1_000_003)), None);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    pub fn first_factor(&self, n: &Integer) -> Option<Integer> {
+        let n = Integer::from(n.abs_ref());
+        let mut factorizer = self.factorizer(&n).ok()?;
+        loop {
+            // Steps as short as possible: one chunk of primes or one curve
+            let outcome = factorizer.step(Duration::ZERO).ok()?;
+            let found = factorizer
+                .factors()
+                .keys()
+                .chain(factorizer.cofactors().keys())
+                .find(|&factor| *factor != 1 && *factor != n);
+            if let Some(factor) = found {
+                return Some(factor.clone());
+            }
+            if outcome == StepOutcome::Done {
+                return None;
+            }
+        }
+    }
+
+    /// Factors n with these settings, choosing the curves with `rgen` instead of the seed.
+    ///
+    /// The generator is advanced by the curves run, so integrators can draw
+    /// every curve of their application from a single generator.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `rgen`: Pseudorandom generator choosing the curves.
+    pub fn run_with_rand_state(
+        &self,
+        n: &Integer,
+        rgen: &mut RandState<'static>,
+    ) -> Result<Factorization, Error> {
+        self.run_with(n, Some(rgen)).map(|(factors, _)| factors)
+    }
+
+    fn run_with(
+        &self,
+        n: &Integer,
+        mut rgen: Option<&mut RandState<'static>>,
+    ) -> Result<(Factorization, Stats), Error> {
+        let run = || {
+            let mut factorizer = self.factorizer(n)?;
+            if let Some(rgen) = &mut rgen {
+                std::mem::swap(*rgen, factorizer.rand_state_mut());
+            }
+            let deadline = self
+                .max_duration
+                .and_then(|d| Instant::now().checked_add(d));
+            let result = loop {
+                let budget = match deadline {
+                    Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                    None => Duration::MAX,
+                };
+                match factorizer.step(budget) {
+                    Ok(StepOutcome::Pending) if deadline.is_some_and(|d| Instant::now() >= d) => {
+                        if let Err(err) = factorizer.write_resume_file() {
+                            break Err(err);
+                        }
+                        break Ok((factorizer.partial_factors(), factorizer.stats()));
+                    }
+                    Ok(StepOutcome::Pending) => {}
+                    Ok(StepOutcome::Done) => {
+                        break Ok((factorizer.partial_factors(), factorizer.stats()))
+                    }
+                    Err(err) => break Err(err),
+                }
+            };
+            if let Some(rgen) = rgen {
+                std::mem::swap(rgen, factorizer.rand_state_mut());
+            }
+            result
+        };
+
+        if self.priority == Priority::Normal {
+            return run();
+        }
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    self.priority.apply();
+                    run()
+                })
+                .join()
+                .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+        })
+    }
+}
+
+/// Outcome of [`Factorizer::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The time budget is exhausted, `step` must be called again.
+    Pending,
+    /// The factorization is complete.
+    Done,
+}
+
+/// Methods run by a [`Factorizer`] on the composites left by trial division.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DriverMode {
+    /// Elliptic curves.
+    #[default]
+    Ecm,
+    /// None: composites left by trial division are returned as cofactors.
+    TrialDivision,
+    /// Pollard's rho method only, which finds factors up to about 10^12.
+    Rho,
+    /// Williams' p+1 method only, with the bounds of the factorizer and the seeds
+    /// of [`Factorizer::set_pp1_seeds`], which finds the factors p such that p+1 is smooth.
+    PPlusOne,
+}
+
+/// Methods tried on a composite before the curves, one per iteration of [`Factorizer::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Pretest {
+    /// Squfof, the one line factorization or Pollard's rho on native integers.
+    Small,
+    /// Fermat's method.
+    Fermat,
+    /// Pollard's rho method.
+    Rho,
+    /// Pollard's p-1 method.
+    PMinusOne,
+    /// Williams' p+1 method.
+    PPlusOne,
+}
+
+impl Pretest {
+    /// First method tried on a composite of `bits` bits, none if it fits in 128 bits
+    /// but is too large for the small factorizer.
+    fn first(bits: u32, small_bits: u32) -> Option<Pretest> {
+        match bits {
+            ..=128 => (bits <= small_bits).then_some(Pretest::Small),
+            _ => Some(Pretest::Fermat),
+        }
+    }
+
+    /// Method tried once this one failed.
+    fn next(self) -> Option<Pretest> {
+        match self {
+            Pretest::Fermat => Some(Pretest::Rho),
+            Pretest::Rho => Some(Pretest::PMinusOne),
+            Pretest::PMinusOne => Some(Pretest::PPlusOne),
+            Pretest::Small | Pretest::PPlusOne => None,
+        }
+    }
+}
+
+/// Steps of the sequence of Pollard's rho method run by [`DriverMode::Rho`] on each composite.
+const RHO_STEPS: u64 = 1 << 22;
+
+/// Resumable factorization using Lenstra's Elliptic curve method.
+///
+/// Each call to [`Factorizer::step`] performs a bounded amount of work,
+/// so the factorization can be interleaved with other tasks
+/// (e.g. GUI main loops) without threads or async.
+///
+/// Trial division and the computation of the stage 1 multiplier are split
+/// in chunks of primes, the methods run before the curves one at a time,
+/// the quadratic sieve stops between two polynomials, curves are never
+/// interrupted, so a step may exceed its budget by the duration of one chunk,
+/// one method or one curve.
+pub struct Factorizer {
+    /// Number to be factored, until the trial division is done.
+    n: Option<Integer>,
+    /// Number of primes already tried by trial division.
+    trial_primes: usize,
+    /// n is the power of this exponent of the number left to trial division.
+    power: usize,
+    /// Trial division tries the primes below this bound.
+    trial_bound: u32,
+    /// Composites of at most this many bits are first given to the small factorizer.
+    small_bits: u32,
+    /// Small composites with a number of bits in this range are given to Hart's one line factorization.
+    one_line_bits: RangeInclusive<u32>,
+    /// Steps of Fermat's method on the composites too large for the small factorizer.
+    fermat_steps: u64,
+    /// Steps of Pollard's rho method on the same composites, after Fermat's method.
+    rho_steps: u64,
+    /// Bounds of Pollard's p-1 method on the same composites, after rho.
+    pm1_bounds: (u64, u64),
+    /// Bounds of Williams' p+1 method on the same composites, after p-1.
+    pp1_bounds: (u64, u64),
+    /// Seeds of [`PP1_SEEDS`](crate::PP1_SEEDS) tried by p+1 on each composite.
+    pp1_seeds: usize,
+    /// Index of the next seed tried on the last composite by [`DriverMode::PPlusOne`].
+    pp1_seed: usize,
+    /// Composites of at most this many digits are given to the quadratic sieve once ECM failed on them.
+    siqs_digits: usize,
+    /// Quadratic sieve of one of the composites, going on with the next step.
+    siqs: Option<Box<Siqs>>,
+    max_input_bits: u32,
+    /// Factors of n given by the user, divided out before trial division.
+    known_factors: Vec<Integer>,
+    /// Known high bits of a factor of n, tried once after the known factors.
+    #[cfg(feature = "coppersmith")]
+    hint: Option<HighBitsHint>,
+    #[cfg(feature = "coppersmith")]
+    solver: SharedSolver,
+    /// Methods run after trial division.
+    mode: DriverMode,
+    /// Prime factors found so far.
+    factors: HashMap<Integer, usize>,
+    /// Composites on which ECM failed.
+    cofactors: HashMap<Integer, usize>,
+    /// Composite cofactors left to factor, with their multiplicity.
+    composites: Vec<(Integer, usize)>,
+    /// Next method tried on the composites not yet through the methods cheaper than the curves.
+    pretests: HashMap<Integer, Pretest>,
+    /// Number of curves run on the last composite.
+    curve: u64,
+    max_curve: u64,
+    /// Curves run on all composites, by stage 1 bound.
+    curves_by_b1: BTreeMap<u64, u64>,
+    /// Wall-clock time of the curves run by this factorizer, and their number.
+    curve_time: (Duration, u64),
+    stages: LazyStages,
+    /// Number of baby steps of the stages built.
+    stage2_width: Stage2Width,
+    /// Arithmetic of stage 1.
+    curve_model: CurveModel,
+    /// Family of the curves of the sigmas.
+    parametrization: Parametrization,
+    /// Seed of `rand_state`.
+    seed: u64,
+    rand_state: RandState<'static>,
+    /// Curves needed by each factor found.
+    buckets: CurveBuckets,
+    recycling: RecycleStats,
+    /// Standard cofactors must meet to be considered prime.
+    primality: PrimalityStandard,
+    /// Maximum CPU usage, in percent of one core.
+    cpu_limit: Option<u8>,
+    /// Number of times the bounds were raised.
+    escalations: u32,
+    max_escalations: u32,
+    /// Number of distinct prime factors after which the run stops.
+    stop_after_factors: Option<usize>,
+    /// Method which found each prime factor.
+    methods: HashMap<Integer, FactorMethod>,
+    /// Statistics of the curves run.
+    diagnostics: EcmDiagnostics,
+    trial_division_time: Duration,
+    /// Gcds computed by trial division.
+    trial_gcds: u64,
+    /// Digits of n.
+    digits: usize,
+    sink: Option<SharedSink>,
+    prime_table: Option<Arc<PrimeTable>>,
+    stages_cache: Option<Arc<StagesCache>>,
+    /// Threads running the curves, `None` to run them one at a time.
+    #[cfg(any(feature = "parallel", feature = "rayon"))]
+    pool: Option<CurvePool>,
+    /// Curves run concurrently give the same results as one at a time.
+    #[cfg(any(feature = "parallel", feature = "rayon"))]
+    deterministic: bool,
+    cancel: Option<CancelToken>,
+    #[cfg(feature = "audit")]
+    audit: Option<Arc<AuditLog>>,
+    residues: Option<Arc<ResidueLog>>,
+    resume_file: Option<(PathBuf, ResumeFormat)>,
+    #[cfg(feature = "events")]
+    events: Option<Sender<EcmEvent>>,
+    /// [`EcmEvent::Finished`] was sent.
+    #[cfg(feature = "events")]
+    finished: bool,
+    observer: Option<SharedObserver>,
+}
+
+impl Factorizer {
+    /// Initial parameters for the Factorizer struct.
+    ///
+    /// The sign of n is ignored, 0 fails with [`Error::ZeroInput`].
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `B1`: Stage 1 Bound, 0 to disable elliptic curves, see [`DriverMode::TrialDivision`].
+    /// - `B2`: Stage 2 Bound.
+    /// - `max_curve`: Maximum number of curves generated per composite.
+    /// - `seed`: Initialize pseudorandom generator, from OS entropy if `None`.
+    pub fn new(
+        n: &Integer,
+        b1: u64,
+        b2: u64,
+        max_curve: u64,
+        seed: Option<u64>,
+    ) -> Result<Factorizer, Error> {
+        match b1 {
+            // No curves, B2 is not used
+            0 if !b2.is_multiple_of(2) => return Err(Error::BoundsNotEven),
+            0 => {}
+            _ => check_bounds(b1, b2)?,
+        }
+        if *n == 0 {
+            return Err(Error::ZeroInput);
+        }
+
+        let seed = seed.unwrap_or_else(entropy_seed);
+        let mut rand_state = RandState::new();
+        rand_state.seed(&seed.into());
+
+        Ok(Factorizer {
+            n: Some(Integer::from(n.abs_ref())),
+            trial_primes: 0,
+            power: 1,
+            trial_bound: DEFAULT_TRIAL_DIVISION_BOUND,
+            small_bits: SMALL_COFACTOR_BITS,
+            one_line_bits: ONE_LINE_BITS,
+            fermat_steps: DEFAULT_FERMAT_STEPS,
+            rho_steps: DEFAULT_RHO_STEPS,
+            pm1_bounds: (DEFAULT_PM1_B1, DEFAULT_PM1_B2),
+            pp1_bounds: (0, 0),
+            pp1_seeds: DEFAULT_PP1_SEEDS,
+            pp1_seed: 0,
+            siqs_digits: 0,
+            siqs: None,
+            max_input_bits: MAX_INPUT_BITS,
+            known_factors: Vec::new(),
+            #[cfg(feature = "coppersmith")]
+            hint: None,
+            #[cfg(feature = "coppersmith")]
+            solver: SharedSolver(Arc::new(BruteForceSolver::default())),
+            mode: match b1 {
+                0 => DriverMode::TrialDivision,
+                _ => DriverMode::Ecm,
+            },
+            factors: HashMap::new(),
+            cofactors: HashMap::new(),
+            composites: Vec::new(),
+            pretests: HashMap::new(),
+            curve: 0,
+            max_curve,
+            curves_by_b1: BTreeMap::new(),
+            curve_time: (Duration::ZERO, 0),
+            stages: LazyStages::Building(Box::new(StagesBuilder::new(
+                b1,
+                b2,
+                Stage2Width::default(),
+                None,
+            ))),
+            stage2_width: Stage2Width::default(),
+            curve_model: CurveModel::default(),
+            parametrization: Parametrization::default(),
+            seed,
+            rand_state,
+            buckets: CurveBuckets::new(),
+            recycling: RecycleStats::default(),
+            primality: PrimalityStandard::default(),
+            cpu_limit: None,
+            escalations: 0,
+            max_escalations: DEFAULT_MAX_ESCALATIONS,
+            stop_after_factors: None,
+            methods: HashMap::new(),
+            diagnostics: EcmDiagnostics::default(),
+            trial_division_time: Duration::ZERO,
+            trial_gcds: 0,
+            digits: decimal_digits(n),
+            sink: None,
+            prime_table: None,
+            stages_cache: None,
+            #[cfg(any(feature = "parallel", feature = "rayon"))]
+            pool: None,
+            #[cfg(any(feature = "parallel", feature = "rayon"))]
+            deterministic: false,
+            cancel: None,
+            #[cfg(feature = "audit")]
+            audit: None,
+            residues: None,
+            resume_file: None,
+            #[cfg(feature = "events")]
+            events: None,
+            #[cfg(feature = "events")]
+            finished: false,
+            observer: None,
+        })
+    }
+
+    /// Records every curve counted in `log`, authenticated by the key of the log.
+    ///
+    /// The seed drawn from OS entropy when none was given to [`Factorizer::new`]
+    /// is needed to replay the curves, see [`Factorizer::seed`].
+    ///
+    /// # Parameters
+    ///
+    /// - `log`: Log of the curves, see [`AuditLog`].
+    #[cfg(feature = "audit")]
+    pub fn set_audit_log(&mut self, log: Arc<AuditLog>) {
+        self.audit = Some(log);
+    }
+
+    /// Records the stage 1 residue of every curve reaching stage 2 in `log`.
+    ///
+    /// Curves whose stage 1 finds a factor are not recorded. The log can be
+    /// written as a GMP-ECM save file, to continue the curves with larger stage 2 bounds.
+    ///
+    /// # Parameters
+    ///
+    /// - `log`: Log of the residues, see [`ResidueLog`].
+    pub fn set_residue_log(&mut self, log: Arc<ResidueLog>) {
+        self.residues = Some(log);
+    }
+
+    /// Writes a resume file to `path` when the factorization is interrupted, so the work is not lost.
+    ///
+    /// The file is written when the factorization is cancelled, see
+    /// [`Factorizer::set_cancel_token`], and when [`EcmOptions::run`] runs out of
+    /// time, see [`EcmOptions::max_duration`]. Other callers stepping the factorizer
+    /// write it with [`Factorizer::write_resume_file`]. The stage 1 residues of a
+    /// GMP-ECM save file are recorded from now on, in the residue log if any.
+    ///
+    /// ```
+    /// use ecm::{read_save_file, CancelToken, EcmOptions, Error, ResumeFormat};
+    /// use rug::Integer;
+    /// use std::{fs::File, io::BufReader, time::Duration};
+    ///
+    /// let path = std::env::temp_dir().join(format!("ecm-doc-{}.save", std::process::id()));
+    /// let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1);
+    /// let mut factorizer = options.factorizer(&Integer::from(398883434337287u64)).unwrap();
+    /// factorizer.set_resume_file(&path, ResumeFormat::GmpEcm);
+    /// factorizer.step(Duration::ZERO).unwrap();
+    /// factorizer.step(Duration::ZERO).unwrap();
+    ///
+    /// let token = CancelToken::new();
+    /// factorizer.set_cancel_token(token.clone());
+    /// token.cancel();
+    /// assert!(matches!(factorizer.step(Duration::MAX), Err(Error::Cancelled(_))));
+    /// let residues = read_save_file(BufReader::new(File::open(&path).unwrap())).unwrap();
+    /// assert!(residues.iter().all(|residue| residue.b1 == 2_000));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: Path of the resume file, replaced on each interruption.
+    /// - `format`: Content of the resume file.
+    pub fn set_resume_file(&mut self, path: impl Into<PathBuf>, format: ResumeFormat) {
+        if format == ResumeFormat::GmpEcm && self.residues.is_none() {
+            self.residues = Some(Arc::new(ResidueLog::new()));
+        }
+        self.resume_file = Some((path.into(), format));
+    }
+
+    /// Writes the resume file set by [`Factorizer::set_resume_file`], if any.
+    ///
+    /// A GMP-ECM save file holds the residues of the curves which completed stage 1,
+    /// a checkpoint the whole state of the factorizer, see [`Factorizer::checkpoint`].
+    pub fn write_resume_file(&mut self) -> Result<(), Error> {
+        let Some((path, format)) = self.resume_file.clone() else {
+            return Ok(());
+        };
+        let mut file = BufWriter::new(File::create(path).map_err(Error::ResumeFile)?);
+        let written = match format {
+            ResumeFormat::GmpEcm => match &self.residues {
+                Some(log) => log.write_to(&mut file),
+                None => Ok(()),
+            },
+            #[cfg(feature = "serde")]
+            ResumeFormat::Checkpoint => {
+                serde_json::to_writer_pretty(&mut file, &self.checkpoint()).map_err(io::Error::from)
+            }
+        };
+        written
+            .and_then(|()| file.flush())
+            .map_err(Error::ResumeFile)
+    }
+
+    /// Seed of the pseudorandom generator choosing the curves.
+    ///
+    /// Without [`EcmOptions::seed`], it is drawn from OS entropy: setting it
+    /// to this one runs the same curves again.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Runs up to `threads` curves concurrently, with the `parallel` or `rayon` feature.
+    ///
+    /// The curves run on scoped threads of the standard library, or on
+    /// a dedicated rayon pool with the `rayon` feature, never on the global one.
+    ///
+    /// Each step then runs a batch of `threads` curves, curves not yet started
+    /// are skipped as soon as one of them finds a factor. Which curve finds
+    /// the factor depends on the scheduling, so a seeded run is only reproducible
+    /// with one thread, or in deterministic mode, see [`Factorizer::set_deterministic`].
+    /// 0 uses all available cores, 1 runs the curves one at a time, on the calling thread.
+    ///
+    /// # Parameters
+    ///
+    /// - `threads`: Number of curves run concurrently.
+    #[cfg(any(feature = "parallel", feature = "rayon"))]
+    pub fn set_threads(&mut self, threads: usize) {
+        self.pool = match threads {
+            1 => None,
+            // Without a pool, e.g. if threads cannot be spawned, curves run one at a time
+            _ => CurvePool::new(threads),
+        };
+    }
+
+    /// Makes the curves run concurrently give the same results as one at a time.
+    ///
+    /// The sigma of each curve is the one a single thread would draw for the
+    /// same curve number, whatever the number of threads. When several curves
+    /// of a batch find a factor, the one of the lowest curve number is kept, once
+    /// all the curves before it are completed, and the curves after it are not
+    /// counted. A seeded run then gives the same factors, curves and progress
+    /// with any number of threads, at the cost of waiting for the slowest curve
+    /// before the one finding a factor.
+    ///
+    /// ```
+    /// use ecm::EcmOptions;
+    /// use rug::Integer;
+    ///
+    /// let n = Integer::from(398883434337287u64) * 1_000_000_007;
+    /// let options = EcmOptions::new().seed(1).small_cofactor_bits(0).deterministic();
+    /// let (factors, stats) = options.clone().threads(4).run_with_stats(&n).unwrap();
+    /// let (expected, expected_stats) = options.threads(1).run_with_stats(&n).unwrap();
+    /// assert_eq!(factors, expected);
+    /// assert_eq!(stats.curves_by_b1, expected_stats.curves_by_b1);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `deterministic`: True for results independent of the threads.
+    #[cfg(any(feature = "parallel", feature = "rayon"))]
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// True if curves run concurrently in deterministic mode.
+    fn is_deterministic(&self) -> bool {
+        #[cfg(any(feature = "parallel", feature = "rayon"))]
+        return self.deterministic && self.pool.is_some();
+        #[cfg(not(any(feature = "parallel", feature = "rayon")))]
+        false
+    }
+
+    /// Reports the curves run and the factors found to `observer`.
+    pub fn set_progress_observer(&mut self, observer: Arc<dyn ProgressObserver>) {
+        self.observer = Some(SharedObserver(observer));
+    }
+
+    /// Reports the curves run on each composite to `pb`, see [`Factorizer::set_progress_observer`].
+    #[cfg(feature = "progress-bar")]
+    pub fn set_progress_bar(&mut self, pb: ProgressBar) {
+        self.set_progress_observer(Arc::new(pb));
+    }
+
+    /// Pseudorandom generator choosing the curves, seeded by [`Factorizer::new`].
+    ///
+    /// It can be replaced, e.g. by a generator shared by the whole application.
+    pub fn rand_state_mut(&mut self) -> &mut RandState<'static> {
+        &mut self.rand_state
+    }
+
+    /// Returns a channel receiving the events of the factorization, see [`EcmEvent`].
+    ///
+    /// The channel is closed once the factorizer is dropped, and replaces
+    /// the sender of previous calls.
+    #[cfg(feature = "events")]
+    pub fn events(&mut self) -> Receiver<EcmEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.events = Some(sender);
+        receiver
+    }
+
+    /// Sends the events of the factorization to `sender`, e.g. shared by several factorizers.
+    #[cfg(feature = "events")]
+    pub fn set_event_sender(&mut self, sender: Sender<EcmEvent>) {
+        self.events = Some(sender);
+    }
+
+    /// Reports the progress of the factorization to `sink` after each curve.
+    pub fn set_progress_sink(&mut self, sink: Arc<dyn ProgressSink>) {
+        self.sink = Some(SharedSink(sink));
+    }
+
+    /// Progress of the factorization.
+    ///
+    /// The current composite is the last one queued, the next one a curve is run on.
+    pub fn progress(&self) -> Progress {
+        let remaining = |n: &Integer, power: usize| decimal_digits(n) * power;
+        let remaining_digits = self.n.as_ref().map_or(0, |n| remaining(n, 1))
+            + self
+                .composites
+                .iter()
+                .map(|(n, power)| remaining(n, *power))
+                .sum::<usize>();
+
+        Progress {
+            curve: self.curve,
+            max_curves: self.max_curve,
+            total_curves: self.curves_by_b1.values().sum(),
+            composite_digits: self.composites.last().map_or(0, |(n, _)| decimal_digits(n)),
+            remaining_digits,
+            total_digits: self.digits,
+        }
+    }
+
+    /// Estimated curves and time left, see [`Estimate`].
+    ///
+    /// ```
+    /// use ecm::{EcmOptions, StepOutcome};
+    /// use rug::Integer;
+    /// use std::time::Duration;
+    ///
+    /// let n = Integer::from(398883434337287u64);
+    /// let mut factorizer = EcmOptions::new().small_cofactor_bits(0).factorizer(&n).unwrap();
+    /// while factorizer.step(Duration::from_millis(100)).unwrap() == StepOutcome::Pending {
+    ///     let estimate = factorizer.estimate();
+    ///     println!("{:.1}% done, {:?} left", estimate.percent(), estimate.eta);
+    /// }
+    /// assert_eq!(factorizer.estimate().fraction(), 1.0);
+    /// ```
+    pub fn estimate(&self) -> Estimate {
+        // Curves expected to find a factor of half the digits of n, at most the schedule
+        let expected = |n: &Integer| {
+            let (b1, b2, curves) = optimal_params(decimal_digits(n).div_ceil(2));
+            expected_curves(n, b1, b2, curves.min(self.max_curve))
+        };
+        let curves_left = match self.mode {
+            DriverMode::Ecm => {
+                let (b1, b2) = self.bounds();
+                let mut composites = self.composites.iter().rev().map(|(n, _)| n);
+                let current = composites.next().map_or(0, |n| {
+                    expected_curves(n, b1, b2, self.max_curve.saturating_sub(self.curve))
+                });
+                current + composites.chain(&self.n).map(expected).sum::<u64>()
+            }
+            DriverMode::TrialDivision | DriverMode::Rho | DriverMode::PPlusOne => 0,
+        };
+
+        let (time, curves) = self.curve_time;
+        let eta = (curves > 0)
+            .then(|| {
+                Duration::try_from_secs_f64(time.as_secs_f64() / curves as f64 * curves_left as f64)
+            })
+            .and_then(Result::ok);
+        Estimate {
+            curves_run: self.curves_by_b1.values().sum(),
+            curves_left,
+            eta,
+        }
+    }
+
+    /// Saves the progress of the factorization, to be continued by [`Factorizer::restore_progress`].
+    ///
+    /// Factors found so far are not included, they must be given to the
+    /// resumed run as known factors, see [`Factorizer::add_known_factor`].
+    pub fn resume_state(&self) -> ResumeState {
+        let (b1, b2) = self.bounds();
+        ResumeState {
+            b1,
+            b2,
+            curve: self.curve,
+            max_curves: self.max_curve,
+            escalations: self.escalations,
+            curves_by_b1: self.curves_by_b1.clone(),
+        }
+    }
+
+    /// Continues the progress saved by [`Factorizer::resume_state`], before the first step.
+    ///
+    /// The bounds and curve counts continue where they left off, so progress
+    /// reports and the progress bar do not start over, and curves already run
+    /// on the current composite are not run again: curves being random,
+    /// running new ones is as likely to find a factor.
+    ///
+    /// # Parameters
+    ///
+    /// - `state`: Progress saved by a previous run on the same n.
+    pub fn restore_progress(&mut self, state: ResumeState) {
+        if self.bounds() != (state.b1, state.b2) {
+            self.stages = LazyStages::Building(Box::new(StagesBuilder::new(
+                state.b1,
+                state.b2,
+                self.stage2_width,
+                self.prime_table.clone(),
+            )));
+        }
+        self.curve = state.curve;
+        self.max_curve = state.max_curves;
+        self.escalations = state.escalations;
+        self.curves_by_b1 = state.curves_by_b1;
+    }
+
+    /// Saves the internal state of the factorization, to be continued by [`Factorizer::restore_checkpoint`].
+    ///
+    /// The pseudorandom generator is reseeded from itself, the seed being saved
+    /// in the checkpoint: this run and the restored one then draw the same curves.
+    #[cfg(feature = "serde")]
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let seed = u64::from(self.rand_state.bits(32)) << 32 | u64::from(self.rand_state.bits(32));
+        self.rand_state.seed(&seed.into());
+
+        let mut factors: Vec<_> = (self.factors.iter())
+            .map(|(p, power)| (p.clone(), *power, self.methods.get(p).copied()))
+            .collect();
+        factors.sort_by(|(p, ..), (q, ..)| p.cmp(q));
+        let mut cofactors: Vec<_> = (self.cofactors.iter())
+            .map(|(n, power)| (n.clone(), *power))
+            .collect();
+        cofactors.sort();
+        let mut pretests: Vec<_> = (self.pretests.iter())
+            .map(|(n, pretest)| (n.clone(), *pretest))
+            .collect();
+        pretests.sort_by(|(n, _), (m, _)| n.cmp(m));
+        Checkpoint {
+            version: CHECKPOINT_VERSION,
+            n: self.partial_factors().product(),
+            pending: self.n.clone(),
+            trial_primes: self.trial_primes,
+            power: self.power,
+            factors,
+            cofactors,
+            composites: self.composites.clone(),
+            pretests,
+            pp1_seed: self.pp1_seed,
+            progress: self.resume_state(),
+            seed,
+            parametrization: self.parametrization,
+        }
+    }
+
+    /// Continues the factorization saved by [`Factorizer::checkpoint`], before the first step.
+    ///
+    /// Fails if the checkpoint is of another number, of curves of another
+    /// parametrization, or of an incompatible version.
+    ///
+    /// # Parameters
+    ///
+    /// - `checkpoint`: State saved by a previous run on the same n.
+    #[cfg(feature = "serde")]
+    pub fn restore_checkpoint(&mut self, checkpoint: Checkpoint) -> Result<(), CheckpointError> {
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::UnsupportedVersion(checkpoint.version));
+        }
+        if self.n.is_none() || self.trial_primes > 0 {
+            return Err(CheckpointError::AlreadyStarted);
+        }
+        if self.n.as_ref() != Some(&checkpoint.n) {
+            return Err(CheckpointError::OtherNumber(checkpoint.n));
+        }
+        if checkpoint.parametrization != self.parametrization {
+            return Err(CheckpointError::OtherParametrization(
+                checkpoint.parametrization,
+            ));
+        }
+
+        self.n = checkpoint.pending;
+        self.trial_primes = checkpoint.trial_primes;
+        self.power = checkpoint.power;
+        for (p, power, method) in checkpoint.factors {
+            if let Some(method) = method {
+                self.methods.insert(p.clone(), method);
+            }
+            self.factors.insert(p, power);
+        }
+        self.cofactors.extend(checkpoint.cofactors);
+        self.composites = checkpoint.composites;
+        self.pretests.extend(checkpoint.pretests);
+        self.pp1_seed = checkpoint.pp1_seed;
+        self.restore_progress(checkpoint.progress);
+        self.rand_state.seed(&checkpoint.seed.into());
+        Ok(())
+    }
+
+    /// Current stage 1 and stage 2 bounds.
+    fn bounds(&self) -> (u64, u64) {
+        match &self.stages {
+            LazyStages::Building(builder) => (builder.b1, builder.b2),
+            LazyStages::Ready(stages) => (stages.b1, stages.b2),
+        }
+    }
+
+    /// Sets the standard cofactors must meet to be considered prime.
+    ///
+    /// Defaults to [`PrimalityStandard::MillerRabin`] with 1000 rounds.
+    pub fn set_primality_standard(&mut self, primality: PrimalityStandard) {
+        self.primality = primality;
+    }
+
+    /// Limits the CPU usage to `cpu_percent` percent of one core.
+    ///
+    /// After each curve, the factorizer sleeps so that the time spent
+    /// running curves is at most `cpu_percent` of the elapsed time.
+    pub fn set_cpu_limit(&mut self, cpu_percent: u8) {
+        self.cpu_limit = Some(cpu_percent);
+    }
+
+    /// Adds a factor of n already known, e.g. from a previous run.
+    ///
+    /// Known factors are divided out of n, with their multiplicity, before trial division.
+    /// They do not need to be prime: composite known factors are factored too.
+    /// Only the part of the factor which divides n is used, and
+    /// factors added once the first step started are ignored.
+    ///
+    /// # Parameters
+    ///
+    /// - `factor`: Known factor of n.
+    pub fn add_known_factor(&mut self, factor: Integer) {
+        self.known_factors.push(factor);
+    }
+
+    /// Gives the known high bits of a prime factor p of n, e.g. from a partial key exposure.
+    ///
+    /// After the known factors, the solver recovers p from the hint, see
+    /// [`Factorizer::set_partial_key_solver`], and p is divided out of n like a
+    /// known factor. If it fails, n is factored as without the hint.
+    ///
+    /// ```
+    /// use ecm::{DriverMode, EcmOptions, HighBitsHint};
+    /// use rug::Integer;
+    ///
+    /// let p = Integer::from(100327907731u64);
+    /// let n = Integer::from(&p * 10454157497791297u64);
+    /// let options = EcmOptions::new().mode(DriverMode::TrialDivision);
+    /// assert!(!options.clone().run(&n).unwrap().is_complete());
+    ///
+    /// let hint = HighBitsHint::new(Integer::from(&p >> 16), 16);
+    /// let factors = options.hint(hint).run(&n).unwrap();
+    /// assert!(factors.is_complete());
+    /// assert_eq!(factors.multiplicity(&p), 1);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `hint`: Known high bits of the factor.
+    #[cfg(feature = "coppersmith")]
+    pub fn set_high_bits_hint(&mut self, hint: HighBitsHint) {
+        self.hint = Some(hint);
+    }
+
+    /// Sets the solver recovering the factor of [`Factorizer::set_high_bits_hint`],
+    /// defaults to [`BruteForceSolver`].
+    ///
+    /// # Parameters
+    ///
+    /// - `solver`: Solver of the unknown bits, e.g. Coppersmith's method.
+    #[cfg(feature = "coppersmith")]
+    pub fn set_partial_key_solver(&mut self, solver: Arc<dyn PartialKeySolver>) {
+        self.solver = SharedSolver(solver);
+    }
+
+    /// Sets how many times the bounds are raised when all curves failed on a composite.
+    ///
+    /// Each time, B1, B2 and the number of curves are set to the next row of the
+    /// table of optimal parameters, and the bounds stay raised for the following composites.
+    /// Once the cap is hit, composites on which all curves failed are kept as cofactors.
+    /// The bounds are not raised either when the curves run would have found a factor
+    /// of any size with near certainty, see [`success_probability`]. Defaults to 2.
+    pub fn set_max_escalations(&mut self, max_escalations: u32) {
+        self.max_escalations = max_escalations;
+    }
+
+    /// Stops the factorization once `count` distinct prime factors are found.
+    ///
+    /// Composites not split yet are then returned as cofactors, whatever their
+    /// size, e.g. when a few factors are enough to prove n composite.
+    /// Factors from trial division and known factors count as well.
+    ///
+    /// ```
+    /// use ecm::EcmOptions;
+    /// use rug::Integer;
+    ///
+    /// // 4009823 * 99476569 * 1000000007
+    /// let n = Integer::from(398883434337287u64) * 1_000_000_007;
+    /// let options = EcmOptions::new().trial_division_bound(0).seed(1);
+    /// let factors = options.small_cofactor_bits(0).stop_after_factors(1).run(&n).unwrap();
+    /// assert_eq!(factors.len(), 1);
+    /// assert_eq!(factors.cofactors().count(), 1);
+    /// assert_eq!(factors.product(), n);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `count`: Number of distinct prime factors to find.
+    pub fn set_stop_after_factors(&mut self, count: usize) {
+        self.stop_after_factors = Some(count);
+    }
+
+    /// Sets the bound of trial division, see [`DEFAULT_TRIAL_DIVISION_BOUND`].
+    ///
+    /// Only primes below the bound are tried, 0 skips trial division, e.g. when
+    /// small factors were already taken out. Must be called before the first step.
+    ///
+    /// # Parameters
+    ///
+    /// - `bound`: Trial division tries the primes below it.
+    pub fn set_trial_division_bound(&mut self, bound: u32) {
+        self.trial_bound = bound;
+    }
+
+    /// Sets the size of the composites split without curves, see [`SMALL_COFACTOR_BITS`].
+    ///
+    /// As soon as a composite of at most `bits` bits, and at most 128, is left,
+    /// it is split on native integers first, whatever the schedule of the
+    /// curves: by [`squfof`] up to [`SQUFOF_BITS`](crate::SQUFOF_BITS) bits,
+    /// by [`one_line_factor`] in the range of [`Factorizer::set_one_line_bits`],
+    /// then by Pollard's rho method. Composites they cannot split quickly,
+    /// e.g. with two factors of 20 digits, go on with the curves. 0 disables it.
+    ///
+    /// # Parameters
+    ///
+    /// - `bits`: Maximum number of bits of the composites.
+    pub fn set_small_cofactor_bits(&mut self, bits: u32) {
+        self.small_bits = bits;
+    }
+
+    /// Sets the sizes of the small composites given to Hart's one line factorization,
+    /// defaults to [`ONE_LINE_BITS`].
+    ///
+    /// Small composites, see [`Factorizer::set_small_cofactor_bits`], with a
+    /// number of bits in the range and not split by [`squfof`] go through
+    /// [`one_line_factor`] before Pollard's rho method. `0..=0` disables it.
+    ///
+    /// # Parameters
+    ///
+    /// - `bits`: Numbers of bits of the composites.
+    pub fn set_one_line_bits(&mut self, bits: RangeInclusive<u32>) {
+        self.one_line_bits = bits;
+    }
+
+    /// Sets the steps of Fermat's method on large composites, defaults to [`DEFAULT_FERMAT_STEPS`].
+    ///
+    /// Composites of more than 128 bits left by trial division, or split from n,
+    /// first go through [`fermat`], which splits the products of two close
+    /// factors, e.g. RSA moduli from a broken key generator, at once. Smaller
+    /// composites are left to the small factorizer, see
+    /// [`Factorizer::set_small_cofactor_bits`]. 0 disables it.
+    ///
+    /// # Parameters
+    ///
+    /// - `steps`: Maximum number of steps on each composite.
+    pub fn set_fermat_steps(&mut self, steps: u64) {
+        self.fermat_steps = steps;
+    }
+
+    /// Sets the steps of Pollard's rho method on large composites, defaults to [`DEFAULT_RHO_STEPS`].
+    ///
+    /// Composites left by Fermat's method, see [`Factorizer::set_fermat_steps`],
+    /// then go through [`pollard_rho`], which finds factors a bit larger than
+    /// the bound of trial division much faster than curves. 0 disables it.
+    ///
+    /// # Parameters
+    ///
+    /// - `steps`: Maximum number of steps of the sequences on each composite.
+    pub fn set_rho_steps(&mut self, steps: u64) {
+        self.rho_steps = steps;
+    }
+
+    /// Sets the bounds of Pollard's p-1 method on large composites,
+    /// defaults to [`DEFAULT_PM1_B1`] and [`DEFAULT_PM1_B2`].
+    ///
+    /// Composites left by Pollard's rho method, see [`Factorizer::set_rho_steps`],
+    /// then go through [`pm1`], which costs about as much as one curve and
+    /// splits the ones with a factor p such that p-1 is smooth. A stage 1 bound
+    /// of 0 disables it.
+    ///
+    /// # Parameters
+    ///
+    /// - `B1`: Stage 1 Bound.
+    /// - `B2`: Stage 2 Bound.
+    pub fn set_pm1_bounds(&mut self, b1: u64, b2: u64) {
+        self.pm1_bounds = (b1, b2);
+    }
+
+    /// Sets the bounds of Williams' p+1 method on large composites, disabled by default.
+    ///
+    /// Composites left by Pollard's p-1 method, see [`Factorizer::set_pm1_bounds`],
+    /// then go through [`pp1_with_seeds`](crate::pp1_with_seeds), which costs a few curves
+    /// with its seeds, see [`Factorizer::set_pp1_seeds`], and splits the ones with a
+    /// factor p such that p+1 is smooth. A stage 1 bound
+    /// of 0 disables it, [`factorize`](crate::factorize) enables it.
+    ///
+    /// # Parameters
+    ///
+    /// - `B1`: Stage 1 Bound.
+    /// - `B2`: Stage 2 Bound.
+    pub fn set_pp1_bounds(&mut self, b1: u64, b2: u64) {
+        self.pp1_bounds = (b1, b2);
+    }
+
+    /// Sets the number of seeds of [`PP1_SEEDS`](crate::PP1_SEEDS) tried by Williams' p+1 method
+    /// on each composite, defaults to [`DEFAULT_PP1_SEEDS`].
+    ///
+    /// p+1 finds a factor p only with the seeds x0 such that `x0^2 - 4` is not a
+    /// square modulo p, each seed misses about half of the primes the previous ones
+    /// missed. The seed which found a factor is recorded in [`FactorMethod::PPlusOne`].
+    ///
+    /// ```
+    /// use ecm::{DriverMode, EcmOptions, FactorMethod, StepOutcome};
+    /// use rug::Integer;
+    /// use std::time::Duration;
+    ///
+    /// // x0^2 - 4 is a square modulo p for the seeds 3, 4 and 6, not for 16
+    /// let p = Integer::from(1000000000000001879u64);
+    /// let n = Integer::from_str_radix("100000000000000000000000000319", 10).unwrap() * &p;
+    /// let options = EcmOptions::new().b1(10_000).b2(1_000_000).mode(DriverMode::PPlusOne);
+    /// assert!(!options.clone().run(&n).unwrap().is_complete());
+    ///
+    /// let mut factorizer = options.pp1_seeds(4).factorizer(&n).unwrap();
+    /// while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+    /// assert_eq!(factorizer.stats().factors[&p], FactorMethod::PPlusOne { seed: 16 });
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `seeds`: Number of seeds tried, at most the length of [`PP1_SEEDS`](crate::PP1_SEEDS).
+    pub fn set_pp1_seeds(&mut self, seeds: usize) {
+        self.pp1_seeds = seeds;
+    }
+
+    /// Sets the size of the composites given to the quadratic sieve, defaults to 0,
+    /// see [`EcmOptions::siqs_digits`].
+    ///
+    /// The curves run on a composite of [`SIQS_MIN_DIGITS`] to `digits` digits
+    /// as long as they cost less than the t-level of about 4/13 of its digits,
+    /// e.g. t25 on 80 digits, counting the curves already run on n with all
+    /// bounds. Past that, or if the next curve alone would cost more, the
+    /// composite goes through [`siqs`](crate::siqs), whose running time does
+    /// not depend on the size of the factors. So do the composites on which
+    /// all the curves failed. 0 disables it.
+    ///
+    /// The sieve stops with the budget of the step and goes on with the next
+    /// one, but its relations are not saved by [`Factorizer::checkpoint`].
+    ///
+    /// # Parameters
+    ///
+    /// - `digits`: Maximum number of decimal digits of the composites.
+    pub fn set_siqs_digits(&mut self, digits: usize) {
+        self.siqs_digits = digits;
+    }
+
+    /// Sets the size limit of n, defaults to [`MAX_INPUT_BITS`].
+    ///
+    /// The first step fails with [`Error::InputTooLarge`] if n has more bits.
+    ///
+    /// # Parameters
+    ///
+    /// - `bits`: Maximum number of bits of n.
+    pub fn set_max_input_bits(&mut self, bits: u32) {
+        self.max_input_bits = bits;
+    }
+
+    /// Uses a precomputed table of primes in stage 2, see [`PrimeTable`].
+    ///
+    /// The table is used by stages built after this call, which include the
+    /// first ones if no step ran yet. Bounds not covered by the table
+    /// generate their primes on the fly.
+    ///
+    /// # Parameters
+    ///
+    /// - `table`: Primes of stage 2.
+    pub fn set_prime_table(&mut self, table: Arc<PrimeTable>) {
+        if let LazyStages::Building(builder) = &mut self.stages {
+            builder.prime_table = Some(Arc::clone(&table));
+        }
+        self.prime_table = Some(table);
+    }
+
+    /// Sets how the number of baby steps of stage 2 is chosen, see [`Stage2Width`].
+    ///
+    /// Applies to the stages built after this call, which include the first ones if no step ran yet.
+    ///
+    /// # Parameters
+    ///
+    /// - `width`: Choice of the number of baby steps.
+    pub fn set_stage2_width(&mut self, width: Stage2Width) {
+        if let LazyStages::Building(builder) = &mut self.stages {
+            builder.d = width.baby_steps(builder.b1, builder.b2);
+        }
+        self.stage2_width = width;
+    }
+
+    /// Caps the memory of the table of baby steps of stage 2 to `bytes`.
+    ///
+    /// The table of [`Stage2Width::Sqrt`] is about `3 * sqrt(B2)` integers of the
+    /// size of n, gigabytes for large bounds and numbers. Beyond the cap, stage 2
+    /// walks `(B1, B2]` with a narrower table and more giant steps, see
+    /// [`Stage2Width::for_memory`]. Replaces [`Stage2Width::Sqrt`] by an adaptive width.
+    ///
+    /// ```
+    /// use ecm::EcmOptions;
+    /// use rug::Integer;
+    ///
+    /// let n = Integer::from(398883434337287u64);
+    /// let options = EcmOptions::new().b1(2_000).b2(1_000_000).seed(1);
+    /// let factors = options.small_cofactor_bits(0).max_memory(4_096).run(&n).unwrap();
+    /// assert_eq!(factors.product(), n);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `bytes`: Memory cap of the table.
+    pub fn set_max_memory(&mut self, bytes: usize) {
+        let bits = (self.digits as f64 * std::f64::consts::LOG2_10).ceil() as u32;
+        self.set_stage2_width(self.stage2_width.capped(baby_steps_in(bytes, bits)));
+    }
+
+    /// Sets the arithmetic of stage 1, see [`CurveModel`].
+    ///
+    /// [`CurveModel::Edwards`] maps each curve to a twisted Edwards curve, which needs
+    /// fewer modular multiplications than the Montgomery ladder. The curves, and so the
+    /// factors found by each sigma, are the same, and stage 2 runs in Montgomery form.
+    ///
+    /// ```
+    /// use ecm::{CurveModel, EcmOptions};
+    /// use rug::Integer;
+    ///
+    /// let n = Integer::from(398883434337287u64);
+    /// let options = EcmOptions::new().b1(2_000).b2(160_000).small_cofactor_bits(0).seed(1);
+    /// let edwards = options.clone().curve_model(CurveModel::Edwards);
+    /// assert_eq!(edwards.run(&n).unwrap(), options.run(&n).unwrap());
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `model`: Arithmetic of stage 1.
+    pub fn set_curve_model(&mut self, model: CurveModel) {
+        self.curve_model = model;
+    }
+
+    /// Sets the family of the curves derived from the sigmas, see [`Parametrization`].
+    ///
+    /// The torsion subgroups of [`Parametrization::Z12`] and [`Parametrization::Z2xZ8`]
+    /// are defined over the rationals, with a starting point of infinite order: modulo
+    /// every prime, the group orders are multiples of 12 or 16, which makes them more
+    /// likely to be smooth. Their sigma is a multiple of a point of an auxiliary curve,
+    /// which takes an inversion per curve. Residues are only logged for
+    /// [`Parametrization::Suyama`], the only family of GMP-ECM save files.
+    ///
+    /// ```
+    /// use ecm::{EcmOptions, Parametrization};
+    /// use rug::Integer;
+    ///
+    /// let n = Integer::from(398883434337287u64);
+    /// let options = EcmOptions::new().b1(2_000).b2(160_000).small_cofactor_bits(0).seed(1);
+    /// let factors = options.parametrization(Parametrization::Z2xZ8).run(&n).unwrap();
+    /// assert_eq!(factors.len(), 2);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `parametrization`: Family of the curves.
+    pub fn set_parametrization(&mut self, parametrization: Parametrization) {
+        self.parametrization = parametrization;
+    }
+
+    /// Shares the stages with the other factorizers using `cache`.
+    pub(crate) fn set_stages_cache(&mut self, cache: Arc<StagesCache>) {
+        self.stages_cache = Some(cache);
+    }
+
+    /// Sets the methods run on the composites left by trial division, defaults to [`DriverMode::Ecm`].
+    ///
+    /// The other modes make the factorizer a general front-end where
+    /// ECM-class effort is never warranted, composites which could not be
+    /// split being returned as cofactors.
+    ///
+    /// # Parameters
+    ///
+    /// - `mode`: Methods run after trial division.
+    pub fn set_mode(&mut self, mode: DriverMode) {
+        self.mode = mode;
+    }
+
+    /// Aborts the factorization once `token` is cancelled.
+    ///
+    /// The token is checked between chunks of primes, between the methods run
+    /// before the curves, between curves and between the blocks of stage 2,
+    /// [`Factorizer::step`] then fails with
+    /// [`Error::Cancelled`]. The factorizer is left as is: the factors found
+    /// so far are also available from [`Factorizer::factors`].
+    ///
+    /// # Parameters
+    ///
+    /// - `token`: Token cancelling the factorization.
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = Some(token);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// Performs work until `budget` is exhausted or the factorization is complete.
+    ///
+    /// Fails with [`Error::Cancelled`] if the factorization is cancelled
+    /// before it is complete, see [`Factorizer::set_cancel_token`].
+    ///
+    /// # Parameters
+    ///
+    /// - `budget`: Time after which the step returns [`StepOutcome::Pending`].
+    pub fn step(&mut self, budget: Duration) -> Result<StepOutcome, Error> {
+        let outcome = match self.is_cancelled() {
+            true => StepOutcome::Pending,
+            false => self.advance(budget)?,
+        };
+        if outcome == StepOutcome::Pending && self.is_cancelled() {
+            self.write_resume_file()?;
+            return Err(Error::Cancelled(Box::new(self.partial_factors())));
+        }
+        #[cfg(feature = "events")]
+        if outcome == StepOutcome::Done && !std::mem::replace(&mut self.finished, true) {
+            self.emit(|| EcmEvent::Finished {
+                curves: self.curves_by_b1.values().sum(),
+            });
+        }
+        Ok(outcome)
+    }
+
+    /// Same as [`Factorizer::step`], returning early once cancelled.
+    fn advance(&mut self, budget: Duration) -> Result<StepOutcome, Error> {
+        let start = Instant::now();
+        let cancel = self.cancel.clone();
+        let out_of_time =
+            || start.elapsed() >= budget || cancel.as_ref().is_some_and(CancelToken::is_cancelled);
+
+        if let Some(n) = &self.n {
+            if self.trial_primes == 0 {
+                check_size(n, self.max_input_bits)?;
+            }
+        }
+        if let Some(mut n) = self.n.take() {
+            if self.trial_primes == 0 {
+                for factor in std::mem::take(&mut self.known_factors) {
+                    n = self.divide_known_factor(n, factor)?;
+                }
+                #[cfg(feature = "coppersmith")]
+                if let Some(hint) = self.hint.take() {
+                    let solver = Arc::clone(&self.solver.0);
+                    if let Ok((p, _)) = catch_panic(&n, || factor_with_hint(&n, &hint, &*solver))? {
+                        n = self.divide_known_factor(n, p)?;
+                    }
+                }
+
+                // n = m^k, only m goes through trial division and ECM
+                let (m, k) = perfect_power(&n);
+                n = m;
+                self.power = k;
+            }
+            self.trial_division(n, out_of_time)?;
+            if out_of_time() {
+                return Ok(self.outcome());
+            }
+        }
+        if self.enough_factors() {
+            return Ok(self.outcome());
+        }
+
+        match self.mode {
+            DriverMode::Ecm => {}
+            DriverMode::TrialDivision => {
+                for (n, power) in std::mem::take(&mut self.composites) {
+                    *self.cofactors.entry(n).or_insert(0) += power;
+                }
+                return Ok(self.outcome());
+            }
+            DriverMode::Rho => {
+                while let Some((n, power)) = self.composites.pop() {
+                    match catch_panic(&n, || pollard_brent(&n, 1, RHO_STEPS))? {
+                        Some(factor) => {
+                            let cofactor = Integer::from(&n / &factor);
+                            self.push(factor, power, FactorMethod::Rho)?;
+                            self.push(cofactor, power, FactorMethod::Rho)?;
+                        }
+                        None => *self.cofactors.entry(n).or_insert(0) += power,
+                    }
+                    if self.enough_factors() || out_of_time() {
+                        break;
+                    }
+                }
+                return Ok(self.outcome());
+            }
+            DriverMode::PPlusOne => {
+                if self.composites.is_empty() {
+                    return Ok(self.outcome());
+                }
+                let Some(stages) = self.build_stages(&out_of_time) else {
+                    return Ok(StepOutcome::Pending);
+                };
+                let seeds = &PP1_SEEDS[..self.pp1_seeds.min(PP1_SEEDS.len())];
+                // One seed on the last composite per iteration
+                while let Some((n, power)) = self.composites.pop() {
+                    let Some(&seed) = seeds.get(self.pp1_seed) else {
+                        self.pp1_seed = 0;
+                        *self.cofactors.entry(n).or_insert(0) += power;
+                        continue;
+                    };
+                    match catch_panic(&n, || pp1_stages(&n, &stages, seed))? {
+                        Some(factor) => {
+                            self.pp1_seed = 0;
+                            let cofactor = Integer::from(&n / &factor);
+                            self.push(factor, power, FactorMethod::PPlusOne { seed })?;
+                            self.push(cofactor, power, FactorMethod::PPlusOne { seed })?;
+                        }
+                        None => {
+                            self.pp1_seed += 1;
+                            self.composites.push((n, power));
+                        }
+                    }
+                    if self.enough_factors() || out_of_time() {
+                        break;
+                    }
+                }
+                return Ok(self.outcome());
+            }
+        }
+
+        if self.run_pretests(&out_of_time)? {
+            return Ok(self.outcome());
+        }
+
+        // Sieved before building stages which would not be used on them, starting with the sieve in progress
+        let (b1, _) = self.bounds();
+        loop {
+            let sieving =
+                |(n, _): &(Integer, usize)| self.siqs.as_ref().is_some_and(|s| s.n() == n);
+            let due = |(n, _): &(Integer, usize)| self.siqs_due(n, b1);
+            let composites = &self.composites;
+            let Some(i) =
+                (composites.iter().position(sieving)).or_else(|| composites.iter().position(due))
+            else {
+                break;
+            };
+            let (n, power) = self.composites.remove(i);
+            self.quadratic_sieve(n, power, &out_of_time)?;
+            if self.enough_factors() || out_of_time() || self.run_pretests(&out_of_time)? {
+                return Ok(self.outcome());
+            }
+        }
+
+        if matches!(self.stages, LazyStages::Building(_)) && self.composites.is_empty() {
+            return Ok(self.outcome());
+        }
+        let Some(stages) = self.build_stages(&out_of_time) else {
+            return Ok(StepOutcome::Pending);
+        };
+
+        while let Some((n, power)) = self.composites.pop() {
+            if self.pretests.contains_key(&n) {
+                // Parts split by the curves or the sieve
+                self.composites.push((n, power));
+                if self.run_pretests(&out_of_time)? {
+                    break;
+                }
+                continue;
+            }
+            if self.siqs_due(&n, stages.b1) {
+                self.quadratic_sieve(n, power, &out_of_time)?;
+                if self.enough_factors() || out_of_time() {
+                    break;
+                }
+                continue;
+            }
+            if self.curve == self.max_curve {
+                self.curve = 0;
+                let exhausted = bounds_exhausted(&n, stages.b1, stages.b2, self.max_curve);
+                let next = match self.escalations < self.max_escalations && !exhausted {
+                    true => next_params(stages.b1),
+                    false => None,
+                };
+                match next {
+                    Some((b1, b2, max_curve)) => {
+                        // Retry with larger bounds, built by the next step
+                        self.escalations += 1;
+                        self.max_curve = max_curve;
+                        self.stages = LazyStages::Building(Box::new(StagesBuilder::new(
+                            b1,
+                            b2,
+                            self.stage2_width,
+                            self.prime_table.clone(),
+                        )));
+                        self.composites.push((n, power));
+                        return Ok(StepOutcome::Pending);
+                    }
+                    None => {
+                        // ECM failed, the sieve keeps the composite as a cofactor if it cannot split it
+                        self.quadratic_sieve(n, power, &out_of_time)?;
+                        if out_of_time() {
+                            break;
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let batch_size = self.batch_size(&n, stages.b1);
+            if let Some(SharedObserver(observer)) = &self.observer {
+                // Also continues the curves of a restored run
+                for curve in self.curve + 1..=self.curve + batch_size {
+                    observer.curve_started(curve, Some(self.max_curve));
+                }
+            }
+
+            let curve_start = Instant::now();
+            // In deterministic mode, the draws of the curves not counted are replayed by the next batch
+            let rand_state = self.is_deterministic().then(|| self.rand_state.clone());
+            let sigmas: Vec<Integer> = (0..batch_size)
+                .map(|_| random_below(&Integer::from(&n - 1), &mut self.rand_state))
+                .collect();
+            #[cfg(feature = "events")]
+            for (i, sigma) in sigmas.iter().enumerate() {
+                self.emit(|| EcmEvent::CurveStarted {
+                    curve: self.curve + 1 + i as u64,
+                    sigma: sigma.clone(),
+                    parametrization: self.parametrization,
+                    b1: stages.b1,
+                });
+            }
+            let mut diagnostics = EcmDiagnostics::default();
+            let (curves, found) = catch_panic(&n, || {
+                self.run_curves(&stages, &n, &sigmas, &mut diagnostics)
+            })?;
+            if let Some(rand_state) = rand_state {
+                self.rand_state = rand_state;
+                for _ in 0..curves {
+                    random_below(&Integer::from(&n - 1), &mut self.rand_state);
+                }
+            }
+            self.diagnostics.merge(&diagnostics);
+            if found.is_none() && self.is_cancelled() {
+                // The curves may have been cut short, the composite is kept for later
+                self.composites.push((n, power));
+                break;
+            }
+            if let Some(SharedObserver(observer)) = &self.observer {
+                for curve in self.curve + 1..=self.curve + curves {
+                    observer.curve_finished(curve);
+                }
+            }
+            self.curve += curves;
+            *self.curves_by_b1.entry(stages.b1).or_insert(0) += curves;
+            if let Some(cpu_percent) = self.cpu_limit {
+                std::thread::sleep(throttle_delay(curve_start.elapsed(), cpu_percent));
+            }
+            self.curve_time.0 += curve_start.elapsed();
+            self.curve_time.1 += curves;
+
+            match found {
+                Some((factor, stage, residue)) => {
+                    let cofactor = Integer::from(&n / &factor);
+                    self.factor_found(&factor, &n, self.curve, Some(stage));
+                    // A curve may find several prime factors at once, their product says nothing about one size
+                    if factor.is_probably_prime(25) != IsPrime::No {
+                        self.buckets.record(&factor, stages.b1, self.curve);
+                    }
+                    self.curve = 0;
+                    for part in [factor, cofactor] {
+                        let recycled = match &residue {
+                            Some(q) if part.is_probably_prime(25) == IsPrime::No => {
+                                self.recycling.attempts += 1;
+                                catch_panic(&part, || stages.recycle(q, &part))?
+                            }
+                            _ => None,
+                        };
+                        match recycled {
+                            Some(g) => {
+                                self.recycling.hits += 1;
+                                self.factor_found(&g, &part, 0, None);
+                                let h = Integer::from(&part / &g);
+                                self.push(g, power, FactorMethod::Recycling)?;
+                                self.push(h, power, FactorMethod::Recycling)?;
+                            }
+                            None => self.push(part, power, FactorMethod::Ecm(stage))?,
+                        }
+                    }
+                }
+                None => self.composites.push((n, power)),
+            }
+            self.enough_factors();
+
+            if let Some(SharedSink(sink)) = &self.sink {
+                sink.progress(&self.progress());
+            }
+            if let Some(SharedObserver(observer)) = &self.observer {
+                observer.estimate_updated(&self.estimate());
+            }
+            #[cfg(feature = "events")]
+            self.emit(|| EcmEvent::Stats(self.progress()));
+
+            if out_of_time() {
+                break;
+            }
+        }
+
+        Ok(self.outcome())
+    }
+
+    /// Returns true if curves of stage 1 bound `b1` on n would cost more than the pretest before the sieve.
+    fn siqs_due(&self, n: &Integer, b1: u64) -> bool {
+        self.curves_before_siqs(n, b1) == Some(0)
+    }
+
+    /// Number of curves of stage 1 bound `b1` run on n before it is handed to the sieve,
+    /// or None if the sieve does not take n.
+    fn curves_before_siqs(&self, n: &Integer, b1: u64) -> Option<u64> {
+        let digits = decimal_digits(n);
+        if !(SIQS_MIN_DIGITS..=self.siqs_digits).contains(&digits) {
+            return None;
+        }
+        let (num, den) = SIQS_PRETEST_RATIO;
+        let pretest = BoundPreset::for_digits(digits * num / den);
+        // Curves cost about their stage 1 bound, and the ones run on n also look for factors of n's parts
+        let work: u128 = (self.curves_by_b1.iter())
+            .map(|(&b1, &curves)| u128::from(b1) * u128::from(curves))
+            .sum();
+        let budget = u128::from(pretest.b1()) * u128::from(pretest.curves());
+        let curves = budget.saturating_sub(work) / u128::from(b1.max(1));
+        Some(u64::try_from(curves).unwrap_or(u64::MAX))
+    }
+
+    /// Splits n with the quadratic sieve if it has at most [`Factorizer::set_siqs_digits`] digits,
+    /// keeps it as a cofactor otherwise.
+    ///
+    /// Once out of time, n is put back with the composites and its sieve goes on with the next call.
+    fn quadratic_sieve(
+        &mut self,
+        n: Integer,
+        power: usize,
+        out_of_time: &dyn Fn() -> bool,
+    ) -> Result<(), Error> {
+        if !(SIQS_MIN_DIGITS..=self.siqs_digits).contains(&decimal_digits(&n)) {
+            *self.cofactors.entry(n).or_insert(0) += power;
+            return Ok(());
+        }
+        let mut siqs = match self.siqs.take().filter(|siqs| *siqs.n() == n) {
+            Some(siqs) => siqs,
+            None => match catch_panic(&n, || Siqs::new(&n))? {
+                Ok(siqs) => Box::new(siqs),
+                Err(factor) => return self.sieved(n, power, factor),
+            },
+        };
+        match catch_panic(&n, || siqs.run(out_of_time))? {
+            Poll::Ready(factor) => self.sieved(n, power, factor),
+            Poll::Pending => {
+                self.siqs = Some(siqs);
+                self.composites.push((n, power));
+                Ok(())
+            }
+        }
+    }
+
+    /// Pushes the parts of n split by the quadratic sieve, or keeps n as a cofactor.
+    fn sieved(&mut self, n: Integer, power: usize, factor: Option<Integer>) -> Result<(), Error> {
+        let Some(factor) = factor else {
+            *self.cofactors.entry(n).or_insert(0) += power;
+            return Ok(());
+        };
+        self.factor_found(&factor, &n, 0, None);
+        let cofactor = Integer::from(&n / &factor);
+        self.push(factor, power, FactorMethod::Siqs)?;
+        self.push(cofactor, power, FactorMethod::Siqs)
+    }
+
+    /// Number of curves of stage 1 bound `b1` run by the next step on the composite n.
+    ///
+    /// Batches stop at the hand-off to the sieve, which then does not depend on the threads.
+    fn batch_size(&self, n: &Integer, b1: u64) -> u64 {
+        #[cfg(any(feature = "parallel", feature = "rayon"))]
+        let threads = self.pool.as_ref().map_or(1, |pool| pool.threads() as u64);
+        #[cfg(not(any(feature = "parallel", feature = "rayon")))]
+        let threads = 1;
+        let left = self.max_curve.saturating_sub(self.curve);
+        let left = left.min(self.curves_before_siqs(n, b1).unwrap_or(u64::MAX));
+        left.clamp(1, threads)
+    }
+
+    /// Runs the curves of `sigmas` on n until one of them finds a factor.
+    ///
+    /// Returns the number of curves run and the factor found, see [`Stages::run`].
+    fn run_curves(
+        &self,
+        stages: &Stages,
+        n: &Integer,
+        sigmas: &[Integer],
+        diagnostics: &mut EcmDiagnostics,
+    ) -> (u64, Option<(Integer, EcmStage, Option<Point>)>) {
+        // Curve numbers, the curves of the batch follow the ones already run
+        let curves: Vec<(u64, &Integer)> = (self.curve + 1..).zip(sigmas).collect();
+        // Curves run on other threads are still children of the current span
+        #[cfg(feature = "tracing")]
+        let parent = tracing::Span::current();
+        let run = |&(curve, sigma): &(u64, &Integer), diagnostics: &mut EcmDiagnostics| {
+            #[cfg(feature = "tracing")]
+            let _span =
+                tracing::debug_span!(parent: &parent, "curve", curve, %sigma, b1 = stages.b1)
+                    .entered();
+            let setup_start = Instant::now();
+            let derived = self.parametrization.derive(sigma, n);
+            diagnostics.setup_time += setup_start.elapsed();
+            diagnostics.curves += 1;
+            let model = self.curve_model;
+            stages.run_curve(n, derived, model, diagnostics, self.cancel.as_ref(), |q| {
+                // Save files only describe the curves of Suyama's parametrization
+                if let (Some(log), Parametrization::Suyama) = (&self.residues, self.parametrization)
+                {
+                    log.record(n, sigma, stages.b1, q);
+                }
+                self.stage2_started(curve)
+            })
+        };
+
+        #[cfg(any(feature = "parallel", feature = "rayon"))]
+        if let (Some(pool), true, true) = (&self.pool, sigmas.len() > 1, self.deterministic) {
+            // Only the curves counted are in the diagnostics, as with one thread
+            let locals = std::sync::Mutex::new(vec![EcmDiagnostics::default(); curves.len()]);
+            let found = pool.find_first(&curves, |curve| {
+                let mut local = EcmDiagnostics::default();
+                let found = run(curve, &mut local);
+                let i = (curve.0 - self.curve - 1) as usize;
+                locals.lock().unwrap_or_else(|e| e.into_inner())[i] = local;
+                found
+            });
+            let counted = found.as_ref().map_or(curves.len(), |(i, _)| i + 1);
+            // The curves after the one finding a factor are drawn again by the next batch
+            #[cfg(feature = "audit")]
+            self.audit(n, stages, &sigmas[..counted]);
+            let locals = locals.into_inner().unwrap_or_else(|e| e.into_inner());
+            for local in &locals[..counted] {
+                diagnostics.merge(local);
+            }
+            return (counted as u64, found.map(|(_, found)| found));
+        }
+
+        #[cfg(any(feature = "parallel", feature = "rayon"))]
+        if let (Some(pool), true) = (&self.pool, sigmas.len() > 1) {
+            let shared = std::sync::Mutex::new(std::mem::take(diagnostics));
+            let (curves, found) = pool.find_any(&curves, |curve| {
+                #[cfg(feature = "audit")]
+                self.audit(n, stages, std::slice::from_ref(curve.1));
+                let mut local = EcmDiagnostics::default();
+                let found = run(curve, &mut local);
+                shared
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .merge(&local);
+                found
+            });
+            *diagnostics = shared.into_inner().unwrap_or_else(|e| e.into_inner());
+            // Several curves may find a factor, the smallest is kept
+            let found = found.into_iter().min_by(|(a, ..), (b, ..)| a.cmp(b));
+            return (curves, found);
+        }
+
+        for (i, curve) in curves.iter().enumerate() {
+            #[cfg(feature = "audit")]
+            self.audit(n, stages, std::slice::from_ref(curve.1));
+            if let Some(found) = run(curve, diagnostics) {
+                return (i as u64 + 1, Some(found));
+            }
+        }
+        (curves.len() as u64, None)
+    }
+
+    /// Records curves counted by [`Factorizer::run_curves`] in the audit log.
+    #[cfg(feature = "audit")]
+    fn audit(&self, n: &Integer, stages: &Stages, sigmas: &[Integer]) {
+        if let Some(log) = &self.audit {
+            for sigma in sigmas {
+                log.record(n, sigma, self.parametrization, stages.b1);
+            }
+        }
+    }
+
+    /// Prime factors found so far.
+    ///
+    /// Composites on which the factorization failed are not included, see [`Factorizer::cofactors`].
+    pub fn factors(&self) -> &HashMap<Integer, usize> {
+        &self.factors
+    }
+
+    /// Curves needed by each factor found by ECM, grouped by factor size.
+    pub fn buckets(&self) -> &CurveBuckets {
+        &self.buckets
+    }
+
+    /// Statistics of the run so far: curves, time of each phase, gcds and the method of each factor.
+    ///
+    /// ```
+    /// use ecm::{EcmOptions, FactorMethod, StepOutcome};
+    /// use rug::Integer;
+    /// use std::time::Duration;
+    ///
+    /// let n = Integer::from(398883434337287u64) * 6;
+    /// let options = EcmOptions::new().seed(1).small_cofactor_bits(0);
+    /// let mut factorizer = options.factorizer(&n).unwrap();
+    /// while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+    ///
+    /// let stats = factorizer.stats();
+    /// assert_eq!(stats.factors[&Integer::from(2)], FactorMethod::TrialDivision);
+    /// assert!(matches!(stats.factors[&Integer::from(4009823)], FactorMethod::Ecm(_)));
+    /// assert!(stats.curves() > 0 && stats.gcds > stats.curves());
+    /// println!("{:?} in stage 1", stats.stages.stage1_time);
+    /// ```
+    pub fn stats(&self) -> Stats {
+        Stats {
+            curves_by_b1: self.curves_by_b1.clone(),
+            trial_division_time: self.trial_division_time,
+            stages: self.diagnostics,
+            gcds: self.trial_gcds + self.diagnostics.gcds,
+            factors: self
+                .methods
+                .iter()
+                .map(|(factor, method)| (factor.clone(), *method))
+                .collect(),
+        }
+    }
+
+    /// Attempts and hits of residue recycling, see [`RecycleStats`].
+    pub fn recycle_stats(&self) -> RecycleStats {
+        self.recycling
+    }
+
+    /// Composites on which ECM failed, with their multiplicity.
+    pub fn cofactors(&self) -> &HashMap<Integer, usize> {
+        &self.cofactors
+    }
+
+    /// Factors found so far, the parts of n not factored yet are cofactors.
+    pub(crate) fn partial_factors(&self) -> Factorization {
+        let mut factorization = Factorization::from(self.factors.clone());
+        let cofactors = self.cofactors.iter().map(|(n, power)| (n, *power));
+        let composites = self.composites.iter().map(|(n, power)| (n, *power));
+        let pending = self.n.iter().map(|n| (n, self.power));
+        for (cofactor, multiplicity) in cofactors.chain(composites).chain(pending) {
+            factorization.add_cofactor(cofactor.clone(), multiplicity);
+        }
+        factorization
+    }
+
+    /// Consumes the factorizer, returning the factors found so far.
+    ///
+    /// Composites on which ECM failed are returned as cofactors.
+    pub fn into_factors(self) -> Factorization {
+        let mut factorization = Factorization::from(self.factors);
+        for (cofactor, multiplicity) in self.cofactors {
+            factorization.add_cofactor(cofactor, multiplicity);
+        }
+        factorization
+    }
+
+    /// Reports that `factor` was found in `composite` by the curve `curve`, 0 if none.
+    #[cfg_attr(
+        not(any(feature = "events", feature = "tracing")),
+        allow(unused_variables)
+    )]
+    fn factor_found(
+        &self,
+        factor: &Integer,
+        composite: &Integer,
+        curve: u64,
+        stage: Option<EcmStage>,
+    ) {
+        if let Some(SharedObserver(observer)) = &self.observer {
+            observer.factor_found(factor, composite);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::info!(%factor, %composite, curve, ?stage, "factor found");
+        #[cfg(feature = "events")]
+        self.emit(|| EcmEvent::FactorFound {
+            factor: factor.clone(),
+            composite: composite.clone(),
+            curve,
+            stage,
+        });
+    }
+
+    /// Reports that stage 2 of the curve `curve` starts.
+    #[cfg_attr(not(feature = "events"), allow(unused_variables))]
+    fn stage2_started(&self, curve: u64) {
+        #[cfg(feature = "events")]
+        self.emit(|| EcmEvent::Stage2Started { curve });
+    }
+
+    #[cfg(feature = "events")]
+    fn emit(&self, event: impl FnOnce() -> EcmEvent) {
+        if let Some(sender) = &self.events {
+            // Events are dropped once the receiver is gone
+            let _ = sender.send(event());
+        }
+    }
+
+    fn outcome(&self) -> StepOutcome {
+        if self.n.is_none() && self.composites.is_empty() {
+            StepOutcome::Done
+        } else {
+            StepOutcome::Pending
+        }
+    }
+
+    /// Returns true if the factors asked for are found, the composites left are then cofactors.
+    fn enough_factors(&mut self) -> bool {
+        if self.n.is_some()
+            || self
+                .stop_after_factors
+                .is_none_or(|count| self.factors.len() < count)
+        {
+            return false;
+        }
+        self.pretests.clear();
+        for (n, power) in std::mem::take(&mut self.composites) {
+            *self.cofactors.entry(n).or_insert(0) += power;
+        }
+        true
+    }
+
+    /// Divides n by the part of `factor` dividing n as many times as possible,
+    /// then records or queues this part.
+    fn divide_known_factor(&mut self, mut n: Integer, factor: Integer) -> Result<Integer, Error> {
+        let mut g = factor.gcd(&n);
+        if g == n {
+            return Ok(n);
+        }
+
+        while g != 1 {
+            let mut power = 0;
+            while n.is_divisible(&g) {
+                n /= &g;
+                power += 1;
+            }
+
+            // Part of g still dividing n, e.g. g = p*q with n = p*r
+            let h = Integer::from(g.gcd_ref(&n));
+            self.push(Integer::from(&g / &h), power, FactorMethod::KnownFactor)?;
+            if h != 1 {
+                self.push(h.clone(), power, FactorMethod::KnownFactor)?;
+            }
+            g = h;
+        }
+        Ok(n)
+    }
+
+    /// Takes out small factors, then queues the cofactor.
+    ///
+    /// Primes are processed by chunks, n is kept for the next step if out of time.
+    fn trial_division(
+        &mut self,
+        mut n: Integer,
+        out_of_time: impl Fn() -> bool,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let first = self.trial_primes / PRIMES_PER_CHECK;
+        let mut i = first;
+        loop {
+            if i > first && out_of_time() {
+                self.trial_primes = i * PRIMES_PER_CHECK;
+                self.trial_division_time += start.elapsed();
+                self.n = Some(n);
+                return Ok(());
+            }
+
+            let chunk = trial_division_chunk(i, self.trial_bound);
+            let (primes, primorial) = &*chunk;
+            // Only primes dividing the gcd with the primorial divide n
+            let g = Integer::from(n.gcd_ref(primorial));
+            self.trial_gcds += 1;
+            if g != 1 {
+                for &prime in primes {
+                    if g.is_divisible_u(prime) {
+                        let prime = Integer::from(prime);
+                        while n.is_divisible(&prime) {
+                            n /= &prime;
+                            self.methods
+                                .insert(prime.clone(), FactorMethod::TrialDivision);
+                            *self.factors.entry(prime.clone()).or_insert(0) += self.power;
+                        }
+                    }
+                }
+            }
+
+            i += 1;
+            if primes.len() < PRIMES_PER_CHECK {
+                break;
+            }
+        }
+
+        self.trial_primes = i * PRIMES_PER_CHECK;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(cofactor = %n, "trial division done");
+        #[cfg(feature = "events")]
+        self.emit(|| EcmEvent::TrialDivisionDone {
+            cofactor: n.clone(),
+        });
+        self.trial_division_time += start.elapsed();
+        self.push(n, self.power, FactorMethod::TrialDivision)
+    }
+
+    /// Proper factor of the composite n found by `pretest`, with the method which found it.
+    ///
+    /// The small factorizer is used on composites fitting in [`Factorizer::set_small_cofactor_bits`],
+    /// Fermat's, Pollard's rho, p-1 and p+1 methods on the ones too large for it.
+    fn pretest(&self, n: &Integer, pretest: Pretest) -> Option<(Integer, FactorMethod)> {
+        match pretest {
+            Pretest::Small => {
+                let small = n.to_u128()?;
+                if let Some(g) = u64::try_from(small).ok().and_then(squfof) {
+                    return Some((g.into(), FactorMethod::Squfof));
+                }
+                let one_line = (self.one_line_bits.contains(&n.significant_bits()))
+                    .then(|| one_line_factor(small, ONE_LINE_STEPS));
+                if let Some(g) = one_line.flatten() {
+                    return Some((g.into(), FactorMethod::OneLine));
+                }
+                small_factor(small, SMALL_RHO_STEPS).map(|g| (g.into(), FactorMethod::Rho))
+            }
+            Pretest::Fermat => {
+                let fermat = (self.fermat_steps > 0).then(|| fermat(n, self.fermat_steps));
+                fermat.flatten().map(|g| (g, FactorMethod::Fermat))
+            }
+            Pretest::Rho => {
+                let rho = (self.rho_steps > 0).then(|| pollard_rho(n, self.rho_steps));
+                rho.flatten().map(|g| (g, FactorMethod::Rho))
+            }
+            Pretest::PMinusOne => {
+                let (b1, b2) = self.pm1_bounds;
+                let pm1 = (b1 > 0).then(|| pm1(n, b1, b2));
+                pm1.flatten().map(|g| (g, FactorMethod::PMinusOne))
+            }
+            Pretest::PPlusOne => {
+                let (b1, b2) = self.pp1_bounds;
+                let pp1 = (b1 > 0).then(|| pp1_with_seeds(n, b1, b2, self.pp1_seeds));
+                pp1.flatten()
+                    .map(|(g, seed)| (g, FactorMethod::PPlusOne { seed }))
+            }
+        }
+    }
+
+    /// Stages of the current bounds, built in chunks of primes unless the cache of the batch has them.
+    ///
+    /// Returns None if out of time before they are built, the next call goes on with the build.
+    fn build_stages(&mut self, out_of_time: &dyn Fn() -> bool) -> Option<Arc<Stages>> {
+        if let LazyStages::Building(builder) = &mut self.stages {
+            let cache = self.stages_cache.as_deref();
+            match cache.and_then(|cache| cache.get(builder.b1, builder.b2, builder.d)) {
+                Some(stages) => self.stages = LazyStages::Ready(stages),
+                None => {
+                    while !builder.advance() {
+                        if out_of_time() {
+                            return None;
+                        }
+                    }
+                    let stages = builder.finish();
+                    self.stages = LazyStages::Ready(match cache {
+                        Some(cache) => cache.insert(stages),
+                        None => Arc::new(stages),
+                    });
+                }
+            }
+        }
+        let LazyStages::Ready(stages) = &self.stages else {
+            unreachable!("stages are built above");
+        };
+        Some(Arc::clone(stages))
+    }
+
+    /// Runs the methods cheaper than the curves on the composites, one method at a time.
+    ///
+    /// The parts of a composite split are queued in its place, so the composites
+    /// are in the same order as if the methods ran as soon as they were found.
+    /// Returns true if out of time or if enough factors are found.
+    fn run_pretests(&mut self, out_of_time: &dyn Fn() -> bool) -> Result<bool, Error> {
+        while let Some(i) =
+            (self.composites.iter()).position(|(n, _)| self.pretests.contains_key(n))
+        {
+            let n = self.composites[i].0.clone();
+            let pretest = self.pretests[&n];
+            match catch_panic(&n, || self.pretest(&n, pretest))? {
+                Some((g, method)) => {
+                    let (n, power) = self.composites.remove(i);
+                    self.pretests.remove(&n);
+                    self.factor_found(&g, &n, 0, None);
+                    let h = Integer::from(&n / &g);
+                    let len = self.composites.len();
+                    self.push(g, power, method)?;
+                    self.push(h, power, method)?;
+                    let parts: Vec<_> = self.composites.drain(len..).collect();
+                    self.composites.splice(i..i, parts);
+                }
+                None => match pretest.next() {
+                    Some(next) => {
+                        self.pretests.insert(n, next);
+                    }
+                    None => {
+                        self.pretests.remove(&n);
+                    }
+                },
+            }
+            if self.enough_factors() || out_of_time() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Records n as a factor found by `method` if it is prime, otherwise queues it,
+    /// first for the methods cheaper than the curves, see [`Factorizer::run_pretests`].
+    ///
+    /// Numbers already known to be prime or queued as composite
+    /// are not tested again, only their multiplicity is updated.
+    fn push(&mut self, n: Integer, power: usize, method: FactorMethod) -> Result<(), Error> {
+        // n = m^k, factor m only
+        let (n, k) = perfect_power(&n);
+        let power = power * k;
+        if n == 1 {
+            return Ok(());
+        }
+
+        if let Some(count) = self.factors.get_mut(&n) {
+            *count += power;
+        } else if let Some(count) = self.cofactors.get_mut(&n) {
+            *count += power;
+        } else if let Some((_, count)) = self.composites.iter_mut().find(|(m, _)| *m == n) {
+            *count += power;
+        } else {
+            match self.primality.test(&n) {
+                IsPrime::No => {
+                    let pretest = Pretest::first(n.significant_bits(), self.small_bits);
+                    if let Some(pretest) = pretest.filter(|_| self.mode == DriverMode::Ecm) {
+                        self.pretests.insert(n.clone(), pretest);
+                    }
+                    self.composites.push((n, power));
+                }
+                IsPrime::Probably if self.primality == PrimalityStandard::Proven => {
+                    return Err(Error::PrimalityNotProven(n));
+                }
+                _ => {
+                    self.methods.insert(n.clone(), method);
+                    self.factors.insert(n, power);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Chunk `i` of the primes below `bound` tried by trial division, with their product.
+///
+/// Chunks of the first [`TRIAL_DIVISION_PRIMES`] primes are computed once and shared.
+fn trial_division_chunk(i: usize, bound: u32) -> Cow<'static, (Vec<u32>, Integer)> {
+    static CHUNKS: OnceLock<Vec<(Vec<u32>, Integer)>> = OnceLock::new();
+    let chunks = CHUNKS.get_or_init(|| {
+        let primes: Vec<u32> = Primes::all()
+            .take(TRIAL_DIVISION_PRIMES)
+            .map(|p| p as u32)
+            .collect();
+        primes
+            .chunks(PRIMES_PER_CHECK)
+            .map(|chunk| {
+                let primorial = tree::product(chunk.iter().map(|&p| p as u64));
+                (chunk.to_vec(), primorial)
+            })
+            .collect()
+    });
+
+    if let Some(chunk) = chunks.get(i) {
+        // The last cached chunk is partial, primes may follow it
+        let complete = chunk.0.len() == PRIMES_PER_CHECK || bound <= DEFAULT_TRIAL_DIVISION_BOUND;
+        if complete && chunk.0.last().is_some_and(|&p| p < bound) {
+            return Cow::Borrowed(chunk);
+        }
+    }
+
+    let primes: Vec<u32> = Primes::all()
+        .skip(i * PRIMES_PER_CHECK)
+        .take(PRIMES_PER_CHECK)
+        .take_while(|&p| p < bound as usize)
+        .map(|p| p as u32)
+        .collect();
+    let primorial = tree::product(primes.iter().map(|&p| p as u64));
+    Cow::Owned((primes, primorial))
+}
+
+/// Returns `(m, k)` such that `n = m^k` with k as large as possible.
+fn perfect_power(n: &Integer) -> (Integer, usize) {
+    let mut root = n.clone();
+    let mut power = 1;
+
+    while root > 1 && root.is_perfect_power() {
+        for k in Primes::all() {
+            let (r, rem) = root.clone().root_rem(Integer::new(), k as u32);
+            if rem == 0 {
+                root = r;
+                power *= k;
+                break;
+            }
+        }
+    }
+
+    (root, power)
+}
+
+/// Runs `f`, turning an unexpected panic into [`Error::Internal`].
+///
+/// Only enabled with the `catch-unwind` feature, otherwise panics are propagated.
+#[cfg(feature = "catch-unwind")]
+fn catch_panic<T>(n: &Integer, f: impl FnOnce() -> T) -> Result<T, Error> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+        let msg = payload
+            .downcast_ref::<&str>()
+            .map(|msg| msg.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Error::Internal(format!("panic while factoring {n}: {msg}"))
+    })
+}
+
+/// Runs `f`, turning an unexpected panic into [`Error::Internal`].
+///
+/// Only enabled with the `catch-unwind` feature, otherwise panics are propagated.
+#[cfg(not(feature = "catch-unwind"))]
+fn catch_panic<T>(_n: &Integer, f: impl FnOnce() -> T) -> Result<T, Error> {
+    Ok(f())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Mutex};
+
+    use super::*;
+    use crate::SuyamaCurve;
+
+    #[test]
+    fn sympy_1() {
+        assert_eq!(
+            ecm(&Integer::from_str("398883434337287").unwrap()).unwrap(),
+            Factorization::from([
+                (Integer::from_str("99476569").unwrap(), 1),
+                (Integer::from_str("4009823").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn sympy_2() {
+        assert_eq!(
+            ecm(&Integer::from_str("46167045131415113").unwrap()).unwrap(),
+            Factorization::from([
+                (Integer::from_str("43").unwrap(), 1),
                 (Integer::from_str("2634823").unwrap(), 1),
                 (Integer::from_str("407485517").unwrap(), 1),
             ])
@@ -308,115 +4161,1823 @@ mod tests {
     }
 
     #[test]
-    fn sympy_3() {
+    fn sympy_3() {
+        assert_eq!(
+            ecm(&Integer::from_str("64211816600515193").unwrap()).unwrap(),
+            Factorization::from([
+                (Integer::from_str("281719").unwrap(), 1),
+                (Integer::from_str("359641").unwrap(), 1),
+                (Integer::from_str("633767").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn sympy_4() {
+        assert_eq!(
+            ecm(&Integer::from_str("168541512131094651323").unwrap()).unwrap(),
+            Factorization::from([
+                (Integer::from_str("79").unwrap(), 1),
+                (Integer::from_str("113").unwrap(), 1),
+                (Integer::from_str("11011069").unwrap(), 1),
+                (Integer::from_str("1714635721").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn sympy_5() {
+        assert_eq!(
+            ecm(&Integer::from_str("631211032315670776841").unwrap()).unwrap(),
+            Factorization::from([
+                (Integer::from_str("9312934919").unwrap(), 1),
+                (Integer::from_str("67777885039").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn sympy_6() {
+        assert_eq!(
+            ecm(&Integer::from_str("4132846513818654136451").unwrap()).unwrap(),
+            Factorization::from([
+                (Integer::from_str("47").unwrap(), 1),
+                (Integer::from_str("160343").unwrap(), 1),
+                (Integer::from_str("2802377").unwrap(), 1),
+                (Integer::from_str("195692803").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn sympy_7() {
+        assert_eq!(
+            ecm(&Integer::from_str("4516511326451341281684513").unwrap()).unwrap(),
+            Factorization::from([
+                (Integer::from_str("3").unwrap(), 2),
+                (Integer::from_str("39869").unwrap(), 1),
+                (Integer::from_str("131743543").unwrap(), 1),
+                (Integer::from_str("95542348571").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn sympy_8() {
+        assert_eq!(
+            ecm(&Integer::from_str("3146531246531241245132451321").unwrap(),).unwrap(),
+            Factorization::from([
+                (Integer::from_str("3").unwrap(), 1),
+                (Integer::from_str("100327907731").unwrap(), 1),
+                (Integer::from_str("10454157497791297").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn sympy_9() {
+        assert_eq!(
+            ecm(&Integer::from_str("4269021180054189416198169786894227").unwrap()).unwrap(),
+            Factorization::from([
+                (Integer::from_str("184039").unwrap(), 1),
+                (Integer::from_str("241603").unwrap(), 1),
+                (Integer::from_str("333331").unwrap(), 1),
+                (Integer::from_str("477973").unwrap(), 1),
+                (Integer::from_str("618619").unwrap(), 1),
+                (Integer::from_str("974123").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn same_factors() {
+        assert_eq!(
+            ecm(&Integer::from_str("7853316850129").unwrap()).unwrap(),
+            Factorization::from([(Integer::from_str("2802377").unwrap(), 2)])
+        );
+    }
+
+    #[test]
+    fn perfect_square() {
+        let p = Integer::from_str("10454157497791297").unwrap();
+        assert_eq!(
+            ecm(&p.clone().square()).unwrap(),
+            Factorization::from([(p, 2)])
+        );
+    }
+
+    #[test]
+    fn product_of_squares() {
+        let p = Integer::from(100327907731u64);
+        let q = Integer::from_str("2802377").unwrap();
+        assert_eq!(
+            ecm(&(Integer::from(&p * &q).square())).unwrap(),
+            Factorization::from([(p, 2), (q, 2)])
+        );
+    }
+
+    #[test]
+    fn repeated_factor() {
+        let p = Integer::from_str("2802377").unwrap();
+        let q = Integer::from_str("100327907731").unwrap();
+        assert_eq!(
+            ecm(&(p.clone().square() * &q)).unwrap(),
+            Factorization::from([(p, 2), (q, 1)])
+        );
+    }
+
+    #[test]
+    fn perfect_powers() {
+        assert_eq!(perfect_power(&Integer::from(17)), (17.into(), 1));
+        assert_eq!(perfect_power(&Integer::from(1 << 12)), (2.into(), 12));
+        assert_eq!(perfect_power(&Integer::from(36 * 36 * 36)), (6.into(), 6));
+        assert_eq!(perfect_power(&Integer::from(72)), (72.into(), 1));
+        assert_eq!(perfect_power(&Integer::from(1)), (1.into(), 1));
+    }
+
+    #[test]
+    fn perfect_power_input() {
+        let p = Integer::from(100327907731u64);
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let m = Integer::from(&p * &q) * 2u32 * 1_000_003u32;
+        let n = Integer::from(rug::ops::Pow::pow(&m, 5u32));
+
+        // The pre-pass reduces n to m before trial division
+        let mut factorizer = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .seed(1)
+            .factorizer(&n)
+            .unwrap();
+        factorizer.step(Duration::ZERO).unwrap();
+        assert_eq!(factorizer.n, Some(Integer::from(&m / 2)));
+        assert_eq!(factorizer.factors()[&Integer::from(2)], 5);
+
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        assert_eq!(
+            factorizer.into_factors(),
+            Factorization::from([(2.into(), 5), (1_000_003.into(), 5), (p, 5), (q, 5)])
+        );
+    }
+
+    #[test]
+    fn parametrizations() {
+        let n = Integer::from_str("398883434337287").unwrap();
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .max_curves(100)
+            .small_cofactor_bits(0)
+            .seed(1);
+        for family in [
+            Parametrization::Suyama,
+            Parametrization::Z12,
+            Parametrization::Z2xZ8,
+        ] {
+            for model in [CurveModel::Montgomery, CurveModel::Edwards] {
+                let log = Arc::new(ResidueLog::new());
+                let factors = options
+                    .clone()
+                    .parametrization(family)
+                    .curve_model(model)
+                    .residue_log(Arc::clone(&log))
+                    .run(&n)
+                    .unwrap();
+                assert_eq!(factors.len(), 2, "{family:?} {model:?}");
+                // Only Suyama's curves can be written to save files
+                let suyama = family == Parametrization::Suyama;
+                assert_eq!(log.residues().is_empty(), !suyama);
+            }
+        }
+    }
+
+    #[test]
+    fn explicit_sigmas() {
+        let n = Integer::from_str("398883434337287").unwrap();
+        let one_factor =
+            |sigmas: Vec<Integer>| ecm_one_factor_with_sigmas(&n, 2_000, 160_000, sigmas, None);
+
+        let (sigma, factor) = (6..1_000)
+            .find_map(|sigma| Some((sigma, one_factor(vec![Integer::from(sigma)]).ok()?)))
+            .unwrap();
+        assert!(n.is_divisible(&factor));
+        // The same curve finds the same factor
+        assert_eq!(one_factor(vec![Integer::from(sigma)]).unwrap(), factor);
+        assert_eq!(one_factor(vec![Integer::from(&n + sigma)]).unwrap(), factor);
+
+        assert!(matches!(one_factor(vec![]), Err(Error::ECMFailed)));
+        assert!(matches!(
+            one_factor(vec![Integer::from(0), Integer::from(1)]),
+            Err(Error::ECMFailed)
+        ));
+    }
+
+    #[test]
+    fn detailed_factor() {
+        let n = Integer::from_str("398883434337287").unwrap();
+        let found =
+            ecm_one_factor_detailed(&n, 2_000, 160_000, 100, &mut RandState::new(), None).unwrap();
+        assert!(n.is_divisible(&found.factor));
+        assert!((1..=101).contains(&found.curve));
+
+        // The winning curve alone finds the same factor in the same stage
+        let again =
+            ecm_one_factor_with_sigmas(&n, 2_000, 160_000, [found.sigma.clone()], None).unwrap();
+        assert_eq!(again, found.factor);
+
+        // Also with a stage 1 on the Edwards form of the curve
+        let stages = Stages::new(2_000, 160_000);
+        for model in [CurveModel::Montgomery, CurveModel::Edwards] {
+            let curve = SuyamaCurve::derive(&found.sigma, &n);
+            assert_eq!(
+                stages
+                    .run_curve(
+                        &n,
+                        curve,
+                        model,
+                        &mut EcmDiagnostics::default(),
+                        None,
+                        |_| {}
+                    )
+                    .map(|(factor, stage, _)| (factor, stage)),
+                Some((found.factor.clone(), found.stage))
+            );
+        }
+
+        // Curves are numbered in the order of the sigmas, degenerate ones included
+        let found = one_factor_with_sigmas(
+            &n,
+            2_000,
+            160_000,
+            (1..10).map(Integer::from),
+            Parametrization::Suyama,
+            None,
+            &mut EcmDiagnostics::default(),
+            None,
+        )
+        .unwrap();
+        assert!(found.curve >= 1);
+        assert_eq!(found.sigma, found.curve);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let one_factor =
+            |n: &Integer, b1, b2| ecm_one_factor_ex(n, b1, b2, 4, &mut RandState::new(), None);
+
+        let n = Integer::from_str("398883434337287").unwrap();
+        let (found, diagnostics) = one_factor(&n, 2_000, 160_000);
+        let found = found.unwrap();
+        assert_eq!(diagnostics.curves, found.curve);
+        assert_eq!(diagnostics.stage, Some(found.stage));
+        assert!(diagnostics.mulmods > 0);
+        assert!(diagnostics.stage1_time > Duration::ZERO);
+
+        // Failed runs report all their curves, every curve costs the same
+        let n = Integer::from_str("1048843748843747081710817107").unwrap();
+        let (found, diagnostics) = one_factor(&n, 100, 2_000);
+        assert!(matches!(found, Err(Error::ECMFailed)));
+        assert_eq!(diagnostics.curves, 5);
+        assert_eq!(diagnostics.stage, Some(EcmStage::Stage2));
+        assert!(diagnostics.stage2_time > Duration::ZERO);
+        let stages = Stages::new(100, 2_000);
+        let q = SuyamaCurve::derive(&Integer::from(6), &n).unwrap().point();
+        let per_curve = ladder_mulmods(&stages.k) + stages.stage2_counted(&n, &q, None, None).1;
+        assert_eq!(diagnostics.mulmods, 5 * per_curve);
+
+        // No curve is run on primes
+        let (found, diagnostics) = one_factor(&Integer::from(1_000_003), 2_000, 160_000);
+        assert!(matches!(found, Err(Error::NumberIsPrime)));
+        assert_eq!(diagnostics, EcmDiagnostics::default());
+    }
+
+    #[test]
+    fn stage2_width() {
+        let adaptive = Stage2Width::default();
+        assert_eq!(adaptive.baby_steps(2_000, 160_000), 281);
+        assert_eq!(Stage2Width::Sqrt.baby_steps(2_000, 160_000), 400);
+        // Capped by the memory, and below B1 / 2
+        let capped = Stage2Width::Adaptive {
+            max_baby_steps: 100,
+        };
+        assert_eq!(capped.baby_steps(1_000_000, 1_000_000_000), 100);
+        assert_eq!(adaptive.baby_steps(200, 100_000), 99);
+        assert_eq!(adaptive.baby_steps(2_000, 2_000), 2);
+        assert_eq!(Stage2Width::Sqrt.baby_steps(100, 1_000_000), 49);
+
+        // Fewer multiplications than the square root of B2, same factors
+        let n = Integer::from(398883434337287u64);
+        let q = SuyamaCurve::derive(&Integer::from(12), &n).unwrap().point();
+        let stage2 = |width: Stage2Width| {
+            let mut builder = StagesBuilder::new(2_000, 160_000, width, None);
+            while !builder.advance() {}
+            let stages = builder.finish();
+            stages.stage2_counted(&n, &stages.stage1(&q), None, None)
+        };
+        let (g, mulmods) = stage2(adaptive);
+        let (sqrt_g, sqrt_mulmods) = stage2(Stage2Width::Sqrt);
+        assert_eq!(g, sqrt_g);
+        assert!(g > 1 && g < n && n.is_divisible(&g));
+        assert!(mulmods < sqrt_mulmods);
+
+        let options = EcmOptions::new().seed(1).small_cofactor_bits(0);
+        let sqrt = options.stage2_width(Stage2Width::Sqrt);
+        let factors = sqrt.clone().run(&n).unwrap();
+        assert_eq!(factors.product(), n);
+        // B2 much larger than B1
+        let factors = sqrt.b1(100).b2(1_000_000).run(&n).unwrap();
+        assert_eq!(factors.product(), n);
+
+        // B2 below B1, no stage 2
+        let stages = Stages::new(100, 50);
+        let (g, _) = stages.stage2_counted(&n, &stages.stage1(&q), None, None);
+        assert_eq!(g, 1);
+    }
+
+    #[test]
+    fn max_memory() {
+        // 3 integers of 32 bytes per baby step for numbers of 128 bits
+        let width = Stage2Width::for_memory(96_000, 128);
+        assert_eq!(
+            width,
+            Stage2Width::Adaptive {
+                max_baby_steps: 1_000
+            }
+        );
+        assert_eq!(width.baby_steps(1_000_000, 1_000_000_000), 1_000);
+        assert_eq!(
+            Stage2Width::for_memory(0, 128).baby_steps(2_000, 160_000),
+            2
+        );
+
+        let n = Integer::from(398883434337287u64);
+        let factorizer = |options: EcmOptions| {
+            let factorizer = options.factorizer(&n).unwrap();
+            match factorizer.stages {
+                LazyStages::Building(builder) => builder.d,
+                LazyStages::Ready(_) => panic!("stages already built"),
+            }
+        };
+        let options = EcmOptions::new().b1(2_000).b2(160_000);
+        assert_eq!(factorizer(options.clone()), 281);
+        assert_eq!(factorizer(options.clone().max_memory(1 << 20)), 281);
+        assert_eq!(factorizer(options.clone().max_memory(4_096)), 56);
+        // The lowest cap wins, the square root width is capped too
+        let capped = Stage2Width::Adaptive { max_baby_steps: 10 };
+        assert_eq!(
+            factorizer(options.clone().stage2_width(capped).max_memory(4_096)),
+            10
+        );
+        let sqrt = options.clone().stage2_width(Stage2Width::Sqrt);
+        assert_eq!(factorizer(sqrt.max_memory(4_096)), 56);
+
+        // Narrow tables find the same factors
+        let options = options.seed(1).small_cofactor_bits(0).max_memory(0);
+        assert_eq!(options.run(&n).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn unchecked() {
+        let one_factor = |n: &Integer, unchecked: bool| {
+            let f = match unchecked {
+                true => ecm_one_factor_unchecked,
+                false => ecm_one_factor,
+            };
+            f(n, 2_000, 160_000, 10, &mut RandState::new(), None)
+        };
+
+        let p = Integer::from(1_000_003);
+        assert!(matches!(one_factor(&p, false), Err(Error::NumberIsPrime)));
+        assert!(matches!(one_factor(&p, true), Err(Error::ECMFailed)));
+
+        let n = Integer::from_str("398883434337287").unwrap();
+        assert_eq!(
+            one_factor(&n, true).unwrap(),
+            one_factor(&n, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn montgomery_curves() {
+        let n = Integer::from_str("398883434337287").unwrap();
+        let one_factor = |curves: Vec<(Integer, Integer)>| {
+            ecm_one_factor_with_montgomery_curves(&n, 2_000, 160_000, curves, None)
+        };
+
+        let curves = (3..200).map(|a| (Integer::from(a), Integer::from(2)));
+        let factor = one_factor(curves.collect()).unwrap();
+        assert!(n.is_divisible(&factor));
+
+        // Singular curve, a^2 - 4 = 0
+        assert!(matches!(
+            one_factor(vec![(Integer::from(2), Integer::from(3))]),
+            Err(Error::ECMFailed)
+        ));
+    }
+
+    #[test]
+    fn split_classifies_parts() {
+        let mut rgen = RandState::new();
+        let n = Integer::from_str("46167045131415113").unwrap() / 43;
+        let (factor, cofactor) = split(&n, 2_000, 160_000, 100, &mut rgen, None).unwrap();
+
+        assert_eq!(Integer::from(&factor.value * &cofactor.value), n);
+        assert!(factor.is_prime());
+        assert!(cofactor.is_prime());
+
+        let n = Integer::from_str("4269021180054189416198169786894227").unwrap();
+        let (factor, cofactor) = split(&n, 2_000, 160_000, 100, &mut rgen, None).unwrap();
+        assert_eq!(Integer::from(&factor.value * &cofactor.value), n);
+        assert!(!factor.is_prime() || !cofactor.is_prime());
+        assert_eq!(
+            factor.is_prime(),
+            factor.value.is_probably_prime(30) != IsPrime::No
+        );
+    }
+
+    #[test]
+    fn buckets_prime_factors() {
+        // The first curve finds both 7-digit factors at once
+        let r = Integer::from(Integer::u_pow_u(10, 20)).next_prime();
+        let n = Integer::from(1_000_003u64 * 1_000_033) * &r;
+        let options = EcmOptions::new()
+            .b1(100)
+            .b2(2_000_000)
+            .max_curves(2)
+            .max_escalations(0)
+            .trial_division_bound(0)
+            .rho_steps(0)
+            .fermat_steps(0)
+            .pm1_bounds(0, 0)
+            .pp1_seeds(0)
+            .small_cofactor_bits(0)
+            .seed(1);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        let digits: Vec<usize> = factorizer.buckets().iter().map(|((d, _), _)| d).collect();
+        // The product of both is not recorded, only the prime one found next
+        assert_eq!(digits, [7]);
+    }
+
+    #[test]
+    fn pretest_steps() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from_str("300000000000000000000000000007").unwrap();
+        let n = p * q;
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .trial_division_bound(0);
+        let mut factorizer = options.seed(1).factorizer(&n).unwrap();
+
+        // Each step without budget runs one of the methods cheaper than the curves
+        let mut pretests = Vec::new();
+        loop {
+            factorizer.step(Duration::ZERO).unwrap();
+            match factorizer.pretests.get(&n) {
+                Some(&pretest) => pretests.push(pretest),
+                None => break,
+            }
+        }
+        assert_eq!(
+            pretests,
+            [
+                Pretest::Fermat,
+                Pretest::Rho,
+                Pretest::PMinusOne,
+                Pretest::PPlusOne
+            ]
+        );
+        assert_eq!(factorizer.progress().curve, 0);
+        assert_eq!(factorizer.composites, [(n, 1)]);
+    }
+
+    #[test]
+    fn factorizer_steps() {
+        let n = Integer::from_str("631211032315670776841").unwrap();
+        let mut factorizer = Factorizer::new(&n, 2_000, 160_000, 100, Some(1234)).unwrap();
+        factorizer.set_small_cofactor_bits(0);
+
+        let mut steps = 0;
+        while factorizer.step(Duration::ZERO).unwrap() == StepOutcome::Pending {
+            steps += 1;
+        }
+        assert!(steps > 1);
+        assert_eq!(factorizer.step(Duration::ZERO).unwrap(), StepOutcome::Done);
+        assert_eq!(
+            factorizer
+                .buckets()
+                .iter()
+                .map(|(_, b)| b.factors)
+                .sum::<u64>(),
+            1
+        );
+
+        assert_eq!(
+            factorizer.into_factors(),
+            Factorization::from([
+                (Integer::from_str("9312934919").unwrap(), 1),
+                (Integer::from_str("67777885039").unwrap(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn factorizer_interruptible_setup() {
+        // Computing the stage 1 multiplier for such a B1 would never end
+        let b1 = 1_000_000_000_000;
+
+        let n = Integer::from(2 * 3 * 5 * 1299709u64);
+        let mut factorizer = Factorizer::new(&n, b1, b1, 100, Some(1234)).unwrap();
+        assert_eq!(factorizer.step(Duration::MAX).unwrap(), StepOutcome::Done);
+        assert_eq!(factorizer.into_factors().len(), 4);
+
+        let n = Integer::from_str("631211032315670776841").unwrap();
+        let mut factorizer = Factorizer::new(&n, b1, b1, 100, Some(1234)).unwrap();
+        let start = Instant::now();
+        for _ in 0..5 {
+            assert_eq!(
+                factorizer.step(Duration::ZERO).unwrap(),
+                StepOutcome::Pending
+            );
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn escalation() {
+        let p = Integer::from(100327907731u64);
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q);
+        let options = EcmOptions::new().b1(100).b2(2_000).max_curves(1).seed(1);
+        let options = options.small_cofactor_bits(0);
+        let mut partial = Factorization::new();
+        partial.add_cofactor(n.clone(), 1);
+
+        assert_eq!(options.clone().max_escalations(0).run(&n).unwrap(), partial);
+        assert_eq!(
+            options.max_escalations(4).run(&n).unwrap(),
+            Factorization::from([(p, 1), (q, 1)])
+        );
+    }
+
+    #[test]
+    fn target_digits() {
+        let p = Integer::from(100327907731u64);
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q);
+        let options = EcmOptions::new().seed(1234);
+
+        // A factor of 12 digits is found with the bounds of 15 digits factors
+        assert_eq!(
+            options.clone().target_digits(15).run(&n).unwrap(),
+            Factorization::from([(p, 1), (q.clone(), 1)])
+        );
+
+        // Explicit bounds take precedence, and are never raised
+        let mut partial = Factorization::new();
+        partial.add_cofactor(n.clone(), 1);
+        let options = options.b1(100).b2(2_000).max_curves(1).max_escalations(4);
+        let options = options.seed(1);
+        let options = options.small_cofactor_bits(0);
+        assert_eq!(options.target_digits(15).run(&n).unwrap(), partial);
+
+        // The bounds of the smallest factors are used below 1 digit
+        let n = Integer::from(&q * 1_000_000_007);
+        let factors = EcmOptions::new().target_digits(0).seed(1).run(&n).unwrap();
+        assert_eq!(factors.multiplicity(&q), 1);
+    }
+
+    #[test]
+    fn shared_stages() {
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .seed(1)
+            .small_cofactor_bits(0)
+            .share_stages();
+        let run = |n: &str| {
+            let mut factorizer = options.factorizer(&Integer::from_str(n).unwrap()).unwrap();
+            while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+            match factorizer.stages {
+                LazyStages::Ready(stages) => stages,
+                LazyStages::Building(_) => panic!("stages not built"),
+            }
+        };
+
+        let stages = run("398883434337287");
+        assert!(Arc::ptr_eq(&stages, &run("631211032315670776841")));
+        assert!(stages.prime_table.is_some());
+
+        // Other settings do not share the stages
+        let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1);
+        let mut factorizer = options
+            .small_cofactor_bits(0)
+            .factorizer(&Integer::from(398883434337287u64))
+            .unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        let LazyStages::Ready(other) = factorizer.stages else {
+            panic!("stages not built");
+        };
+        assert!(!Arc::ptr_eq(&stages, &other));
+        assert!(other.prime_table.is_none());
+    }
+
+    #[test]
+    fn resume_progress() {
+        #[derive(Default)]
+        struct Reports(Mutex<Vec<Progress>>);
+
+        impl ProgressSink for Reports {
+            fn progress(&self, progress: &Progress) {
+                self.0.lock().unwrap().push(*progress);
+            }
+        }
+
+        let n = Integer::from_str("1048843748843747081710817107").unwrap();
+        let options = EcmOptions::new()
+            .b1(100)
+            .b2(2_000)
+            .max_curves(10)
+            .max_escalations(1)
+            .small_cofactor_bits(0)
+            .seed(1);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        while factorizer.progress().curve < 3 {
+            factorizer.step(Duration::ZERO).unwrap();
+        }
+        let state = factorizer.resume_state();
+        assert_eq!((state.b1, state.b2, state.curve), (100, 2_000, 3));
+        assert_eq!(state.curves_by_b1, BTreeMap::from([(100, 3)]));
+
+        let reports = Arc::new(Reports::default());
+        let mut resumed = options
+            .resume_from(state)
+            .progress_sink(reports.clone())
+            .factorizer(&n)
+            .unwrap();
+        while resumed.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+
+        // The progress continues from the fourth curve, then the bounds are raised once
+        let reports = reports.0.lock().unwrap();
+        assert_eq!((reports[0].curve, reports[0].total_curves), (4, 4));
+        let after = resumed.resume_state();
+        assert_eq!(after.escalations, 1);
+        assert_eq!(after.curves_by_b1[&100], 10);
+        assert_eq!(
+            reports.last().unwrap().total_curves,
+            after.curves_by_b1.values().sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn driver_modes() {
+        let p = Integer::from(100327907731u64);
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q) * 2802377 * 3;
+
+        // Trial division only
+        let mut partial = Factorization::from([(3.into(), 1)]);
+        partial.add_cofactor(Integer::from(&p * &q) * 2802377, 1);
+        let options = EcmOptions::new().seed(1234);
+        assert_eq!(
+            options
+                .clone()
+                .mode(DriverMode::TrialDivision)
+                .run(&n)
+                .unwrap(),
+            partial
+        );
+        assert_eq!(options.clone().b1(0).run(&n).unwrap(), partial);
+
+        // Rho finds factors up to about 10^12
+        assert_eq!(
+            options.clone().mode(DriverMode::Rho).run(&n).unwrap(),
+            Factorization::from([(3.into(), 1), (2802377.into(), 1), (p, 1), (q, 1)])
+        );
+
+        // p+1 finds 1000313, p + 1 = 2 * 3^2 * 7 * 17 * 467, with stage 2
+        let n = Integer::from(1_000_313) * 100_000_000_000_000_003u64 * 7;
+        let options = options.b1(100).b2(3_000).trial_division_bound(1_000);
+        let options = options.mode(DriverMode::PPlusOne);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        let methods = factorizer.stats().factors;
+        assert_eq!(
+            methods[&1_000_313.into()],
+            FactorMethod::PPlusOne { seed: 3 }
+        );
+        assert!(factorizer.into_factors().is_complete());
+        let partial = options.clone().b2(100).run(&n).unwrap();
+        assert_eq!(partial.cofactors().count(), 1);
+
+        // One seed per iteration with the stages built once,
+        // x0^2 - 4 is a square modulo p for the seeds 3, 4 and 6, not for 16
+        let p = Integer::from(1000000000000001879u64);
+        let n = Integer::from_str("100000000000000000000000000319").unwrap() * &p;
+        let options = options.b1(10_000).b2(1_000_000).pp1_seeds(4);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        let (mut seeds, mut built) = (Vec::new(), None);
+        while factorizer.step(Duration::ZERO).unwrap() == StepOutcome::Pending {
+            if let LazyStages::Ready(stages) = &factorizer.stages {
+                let built = built.get_or_insert_with(|| Arc::clone(stages));
+                assert!(Arc::ptr_eq(built, stages));
+            }
+            seeds.push(factorizer.pp1_seed);
+        }
+        seeds.dedup();
+        assert_eq!(seeds, [0, 1, 2, 3]);
+        let methods = factorizer.stats().factors;
+        assert_eq!(methods[&p], FactorMethod::PPlusOne { seed: 16 });
+    }
+
+    #[cfg(any(feature = "parallel", feature = "rayon"))]
+    #[test]
+    fn parallel_curves() {
+        let p = Integer::from(100327907731u64);
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q);
+        let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1234);
+        let options = options.small_cofactor_bits(0);
+        assert_eq!(
+            options.clone().threads(4).run(&n).unwrap(),
+            Factorization::from([(p, 1), (q, 1)])
+        );
+
+        // One step runs one curve per thread
+        let mut factorizer = options.clone().threads(3).factorizer(&n).unwrap();
+        while factorizer.resume_state().curves_by_b1.is_empty() {
+            factorizer.step(Duration::ZERO).unwrap();
+        }
+        assert!(factorizer.resume_state().curves_by_b1[&2_000] <= 3);
+
+        // Never more curves than allowed
+        let options = EcmOptions::new().b1(100).b2(1_000).seed(1);
+        let options = options.max_curves(2).max_escalations(0).threads(8);
+        let options = options.small_cofactor_bits(0);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        while factorizer.step(Duration::ZERO).unwrap() == StepOutcome::Pending {}
+        assert_eq!(factorizer.resume_state().curves_by_b1[&100], 2);
+    }
+
+    #[cfg(any(feature = "parallel", feature = "rayon"))]
+    #[test]
+    fn deterministic() {
+        let p = Integer::from(100327907731u64);
+        let n = Integer::from(398883434337287u64) * &p * 1_000_000_007u64;
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .small_cofactor_bits(0)
+            .deterministic();
+        for seed in 1..4 {
+            let run = |threads| {
+                let options = options.clone().seed(seed).threads(threads);
+                let (factors, stats) = options.run_with_stats(&n).unwrap();
+                (factors, stats.curves_by_b1, stats.stages.curves)
+            };
+            let expected = run(1);
+            assert!(expected.0.is_complete());
+            for threads in [2, 3, 8] {
+                assert_eq!(run(threads), expected);
+            }
+        }
+
+        // Batches stop at the hand-off to the sieve
+        let n = Integer::from(398883434337287u64) * 1_000_000_007;
+        let options = EcmOptions::new().small_cofactor_bits(0).deterministic();
+        for seed in 1..4 {
+            let run = |threads| {
+                let options = options.clone().seed(seed).threads(threads);
+                let mut factorizer = options.factorizer(&n).unwrap();
+                while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+                let stats = factorizer.stats();
+                (stats.factors.clone(), stats.curves_by_b1.clone())
+            };
+            assert_eq!(run(4), run(1));
+        }
+    }
+
+    #[cfg(all(feature = "audit", any(feature = "parallel", feature = "rayon")))]
+    #[test]
+    fn deterministic_audit() {
+        let p = Integer::from(100327907731u64);
+        let n = Integer::from(398883434337287u64) * &p * 1_000_000_007u64;
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .small_cofactor_bits(0)
+            .deterministic()
+            .seed(2);
+        let run = |threads| {
+            let log = Arc::new(AuditLog::new(*b"key"));
+            let options = options.clone().threads(threads).audit_log(log.clone());
+            let (_, stats) = options.run_with_stats(&n).unwrap();
+            let entries = log.entries();
+            assert!(AuditLog::verify(&entries, b"key"));
+            // Only the curves counted are recorded, the ones drawn again are not duplicated
+            assert_eq!(
+                entries.len() as u64,
+                stats.curves_by_b1.values().sum::<u64>()
+            );
+            let curves: Vec<(Integer, u64)> =
+                entries.into_iter().map(|e| (e.sigma, e.b1)).collect();
+            curves
+        };
+        let expected = run(1);
+        for threads in [2, 8] {
+            assert_eq!(run(threads), expected);
+        }
+    }
+
+    #[test]
+    fn prime_table() {
+        let n = Integer::from_str("631211032315670776841").unwrap();
+        let options = EcmOptions::new().b1(2_000).b2(160_000);
+        let expected = options.run(&n).unwrap();
+
+        let table = Arc::new(PrimeTable::for_bounds(2_000, 160_000));
         assert_eq!(
-            ecm(&Integer::from_str("64211816600515193").unwrap()).unwrap(),
-            HashMap::from([
-                (Integer::from_str("281719").unwrap(), 1),
-                (Integer::from_str("359641").unwrap(), 1),
-                (Integer::from_str("633767").unwrap(), 1),
-            ])
+            options.clone().prime_table(table).run(&n).unwrap(),
+            expected
         );
+
+        // Not covering the bounds, primes are generated on the fly
+        let table = Arc::new(PrimeTable::generate(0, 1_000));
+        assert_eq!(options.prime_table(table).run(&n).unwrap(), expected);
     }
 
     #[test]
-    fn sympy_4() {
+    fn trial_division_bound() {
+        let n = Integer::from(2 * 3 * 1_000_003 * 1_299_709u64) * 1_299_721u64 * 1_299_743u64;
+        let options = EcmOptions::new().b1(2_000).b2(160_000).max_escalations(0);
+        let options = options.small_cofactor_bits(0);
+        let factorizer = |bound| {
+            let mut factorizer = options
+                .clone()
+                .trial_division_bound(bound)
+                .factorizer(&n)
+                .unwrap();
+            factorizer.trial_division(n.clone(), || false).unwrap();
+            factorizer
+        };
+
+        // Skipped: n is left as is
+        let skipped = factorizer(0);
+        assert!(skipped.factors().is_empty());
+        assert_eq!(skipped.composites, [(n.clone(), 1)]);
+
+        let small = factorizer(1_000_003);
+        assert_eq!(small.factors().len(), 2);
+
+        // Default: the first 100,000 primes, up to 1299709
+        let default = factorizer(DEFAULT_TRIAL_DIVISION_BOUND);
+        assert_eq!(default.factors().len(), 4);
+        assert_eq!(default.composites.len(), 1);
+
+        // Beyond the cached primes, the cofactor 1299743 is prime
+        let large = factorizer(1_299_722);
+        assert_eq!(large.factors().len(), 6);
+        assert!(large.composites.is_empty());
+
+        let expected = Factorization::from([
+            (2.into(), 1),
+            (3.into(), 1),
+            (1_000_003.into(), 1),
+            (1_299_709.into(), 1),
+            (1_299_721.into(), 1),
+            (1_299_743.into(), 1),
+        ]);
+        for bound in [0, 1_000, DEFAULT_TRIAL_DIVISION_BOUND] {
+            assert_eq!(
+                options.clone().trial_division_bound(bound).run(&n).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn huge_inputs() {
+        // 10^10000 + 1, stringifying or testing it would already take a while
+        let n = Integer::from(Integer::u_pow_u(10, 10_000)) + 1;
+        let start = Instant::now();
+        assert!(matches!(
+            EcmOptions::new().run(&n),
+            Err(Error::InputTooLarge(33_220))
+        ));
+        assert!(matches!(
+            ecm_one_factor(&n, 2_000, 160_000, 1, &mut RandState::new(), None,),
+            Err(Error::InputTooLarge(33_220))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // The limit can be lowered, or raised
+        let n = Integer::from_str("398883434337287").unwrap();
+        assert!(matches!(
+            EcmOptions::new().max_input_bits(48).run(&n),
+            Err(Error::InputTooLarge(49))
+        ));
         assert_eq!(
-            ecm(&Integer::from_str("168541512131094651323").unwrap()).unwrap(),
-            HashMap::from([
-                (Integer::from_str("79").unwrap(), 1),
-                (Integer::from_str("113").unwrap(), 1),
-                (Integer::from_str("11011069").unwrap(), 1),
-                (Integer::from_str("1714635721").unwrap(), 1),
-            ])
+            EcmOptions::new().max_input_bits(49).run(&n).unwrap().len(),
+            2
         );
     }
 
     #[test]
-    fn sympy_5() {
+    fn known_factors() {
+        let p = Integer::from(100327907731u64);
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q) * &p * 3;
+        let options = EcmOptions::new().b1(100).b2(2_000).max_curves(1).seed(1234);
+        let options = options.small_cofactor_bits(0);
+
+        // ECM alone fails with such small bounds
+        let partial = options.clone().max_escalations(0).run(&n).unwrap();
+        assert_eq!(partial.len(), 1);
+        assert_eq!(partial.cofactors().count(), 1);
+        assert!(!partial.is_complete());
+
+        let expected = Factorization::from([(3.into(), 1), (p.clone(), 2), (q.clone(), 1)]);
         assert_eq!(
-            ecm(&Integer::from_str("631211032315670776841").unwrap()).unwrap(),
-            HashMap::from([
-                (Integer::from_str("9312934919").unwrap(), 1),
-                (Integer::from_str("67777885039").unwrap(), 1),
-            ])
+            options
+                .clone()
+                .max_escalations(0)
+                .known_factors([p.clone(), Integer::from(7)])
+                .run(&n)
+                .unwrap(),
+            expected
+        );
+        // Composite known factor
+        assert_eq!(
+            options
+                .max_escalations(0)
+                .known_factors([Integer::from(&p * &q) * 5])
+                .run(&n)
+                .unwrap(),
+            expected
         );
     }
 
+    #[cfg(feature = "coppersmith")]
     #[test]
-    fn sympy_6() {
+    fn high_bits_hint() {
+        let p = Integer::from(100327907731u64);
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q) * &p * 3;
+        let options = EcmOptions::new().b1(100).b2(2_000).max_curves(1).seed(1234);
+        let options = options.small_cofactor_bits(0).max_escalations(0);
+
+        let expected = Factorization::from([(3.into(), 1), (p.clone(), 2), (q.clone(), 1)]);
+        let hint = HighBitsHint::new(Integer::from(&p >> 16), 16);
+        let mut factorizer = options.clone().hint(hint).factorizer(&n).unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        assert_eq!(factorizer.stats().factors[&p], FactorMethod::KnownFactor);
+        assert_eq!(factorizer.into_factors(), expected);
+
+        // Too many bits for the default solver, or a wrong hint
+        let partial = options.clone().run(&n).unwrap();
+        for hint in [
+            HighBitsHint::new(Integer::from(&p >> 26), 26),
+            HighBitsHint::new(Integer::from(&q >> 16), 64),
+        ] {
+            assert_eq!(options.clone().hint(hint).run(&n).unwrap(), partial);
+        }
+        let solver = Arc::new(BruteForceSolver {
+            max_unknown_bits: 26,
+        });
+        let hint = HighBitsHint::new(Integer::from(&p >> 26), 26);
+        let options = options.hint(hint).partial_key_solver(solver);
+        assert_eq!(options.run(&n).unwrap(), expected);
+    }
+
+    #[test]
+    fn progress_observer() {
+        #[derive(Default)]
+        struct Record {
+            started: Mutex<Vec<(u64, Option<u64>)>>,
+            finished: Mutex<Vec<u64>>,
+            found: Mutex<Vec<(Integer, Integer)>>,
+            estimate: Mutex<Option<Estimate>>,
+        }
+        impl ProgressObserver for Record {
+            fn curve_started(&self, curve: u64, max_curves: Option<u64>) {
+                self.started.lock().unwrap().push((curve, max_curves));
+            }
+            fn curve_finished(&self, curve: u64) {
+                self.finished.lock().unwrap().push(curve);
+            }
+            fn estimate_updated(&self, estimate: &Estimate) {
+                *self.estimate.lock().unwrap() = Some(*estimate);
+            }
+            fn factor_found(&self, factor: &Integer, composite: &Integer) {
+                self.found
+                    .lock()
+                    .unwrap()
+                    .push((factor.clone(), composite.clone()));
+            }
+        }
+
+        let n = Integer::from_str("398883434337287").unwrap();
+        let record = Record::default();
+        let factor = ecm_one_factor_with_sigmas(
+            &n,
+            2_000,
+            160_000,
+            (6..1_000).map(Integer::from),
+            Some(&record),
+        )
+        .unwrap();
+        let started = record.started.lock().unwrap().clone();
+        let finished = record.finished.lock().unwrap().clone();
+        assert_eq!(started[0], (1, Some(994)));
+        assert_eq!(started.iter().map(|s| s.0).collect::<Vec<_>>(), finished);
+        assert_eq!(*record.found.lock().unwrap(), [(factor, n.clone())]);
+
+        // Every split is reported, the ones by the small factorizer too
+        let observer = Arc::new(Record::default());
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .seed(1)
+            .small_cofactor_bits(64)
+            .progress_observer(observer.clone());
+        let n = Integer::from_str("631211032315670776841").unwrap() * &n;
+        let factors = options.run(&n).unwrap();
+        assert_eq!(factors.len(), 4);
+        assert_eq!(observer.found.lock().unwrap().len(), 3);
+        let started = observer.started.lock().unwrap();
+        assert!(started.iter().all(|&(curve, max)| curve <= max.unwrap()));
+        assert_eq!(started.len(), observer.finished.lock().unwrap().len());
+        let estimate = observer.estimate.lock().unwrap().unwrap();
+        assert_eq!(estimate.curves_run, started.len() as u64);
+    }
+
+    #[test]
+    fn estimate() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from_str("300000000000000000000000000007").unwrap();
+        let n = Integer::from(&p * &q) * 3;
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .max_curves(10)
+            .max_escalations(0)
+            .seed(1);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        let estimate = factorizer.estimate();
         assert_eq!(
-            ecm(&Integer::from_str("4132846513818654136451").unwrap()).unwrap(),
-            HashMap::from([
-                (Integer::from_str("47").unwrap(), 1),
-                (Integer::from_str("160343").unwrap(), 1),
-                (Integer::from_str("2802377").unwrap(), 1),
-                (Integer::from_str("195692803").unwrap(), 1),
-            ])
+            (estimate.curves_run, estimate.curves_left, estimate.eta),
+            (0, 10, None)
         );
+
+        while factorizer.progress().curve < 4 {
+            factorizer.step(Duration::ZERO).unwrap();
+        }
+        let estimate = factorizer.estimate();
+        assert_eq!((estimate.curves_run, estimate.curves_left), (4, 6));
+        assert_eq!(estimate.percent(), 40.0);
+        assert!(estimate.eta.is_some());
+
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        let estimate = factorizer.estimate();
+        assert_eq!((estimate.curves_left, estimate.fraction()), (0, 1.0));
+        assert_eq!(estimate.eta, Some(Duration::ZERO));
     }
 
     #[test]
-    fn sympy_7() {
+    fn success_probability_decisions() {
+        let small = Integer::from(398883434337287u64);
+        let large =
+            Integer::from_str("30000000000000000000000000009600000000000000000000000002233")
+                .unwrap();
+        // About half of the curves of B1 = 2000 find a factor of 25 bits, 100 of them cannot all miss
+        assert_eq!(expected_curves(&small, 2_000, 160_000, 100), 2);
+        assert!(bounds_exhausted(&small, 2_000, 160_000, 100));
+        assert_eq!(expected_curves(&large, 2_000, 160_000, 10), 10);
+        assert!(!bounds_exhausted(&large, 2_000, 160_000, 10));
+        assert!(!bounds_exhausted(&small, 2_000, 160_000, 0));
+    }
+
+    #[test]
+    fn dual_progress() {
+        #[derive(Default)]
+        struct Record(Mutex<Vec<Progress>>);
+        impl ProgressSink for Record {
+            fn progress(&self, progress: &Progress) {
+                self.0.lock().unwrap().push(*progress);
+            }
+        }
+
+        let n = Integer::from_str("168541512131094651323").unwrap();
+        let sink = Arc::new(Record::default());
+        let mut factorizer = Factorizer::new(&n, 2_000, 160_000, 100, Some(1234)).unwrap();
+        factorizer.set_progress_sink(sink.clone());
+        factorizer.set_small_cofactor_bits(0);
+        assert_eq!(factorizer.progress().remaining_digits, 21);
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+
+        let records = sink.0.lock().unwrap();
+        assert!(!records.is_empty());
+        assert!(records.iter().all(|p| p.total_digits == 21));
+        assert!(records
+            .windows(2)
+            .all(|w| w[0].job_fraction() <= w[1].job_fraction()));
+        assert_eq!(records.last().unwrap().job_fraction(), 1.0);
+    }
+
+    #[test]
+    fn factorizer_proven_primes() {
+        let n = Integer::from_str("4269021180054189416198169786894227").unwrap();
+        let mut factorizer = Factorizer::new(&n, 2_000, 160_000, 100, Some(1234)).unwrap();
+        factorizer.set_primality_standard(PrimalityStandard::Proven);
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        assert_eq!(factorizer.factors().len(), 6);
+
+        // p-1 = 66 * q1 * q2 with q1, q2 primes above the trial division bound
+        let p = Integer::from_str("79789104097613371762829419").unwrap();
+        let mut factorizer = Factorizer::new(&p, 2_000, 160_000, 100, Some(1234)).unwrap();
+        factorizer.set_primality_standard(PrimalityStandard::Proven);
+        assert!(matches!(
+            factorizer.step(Duration::MAX),
+            Err(Error::PrimalityNotProven(q)) if q == p
+        ));
+    }
+
+    #[test]
+    fn options() {
+        let n = Integer::from_str("4132846513818654136451").unwrap();
+        let expected = Factorization::from([
+            (Integer::from_str("47").unwrap(), 1),
+            (Integer::from_str("160343").unwrap(), 1),
+            (Integer::from_str("2802377").unwrap(), 1),
+            (Integer::from_str("195692803").unwrap(), 1),
+        ]);
+
+        assert_eq!(EcmOptions::new().run(&n).unwrap(), expected);
         assert_eq!(
-            ecm(&Integer::from_str("4516511326451341281684513").unwrap()).unwrap(),
-            HashMap::from([
-                (Integer::from_str("3").unwrap(), 2),
-                (Integer::from_str("39869").unwrap(), 1),
-                (Integer::from_str("131743543").unwrap(), 1),
-                (Integer::from_str("95542348571").unwrap(), 1),
-            ])
+            EcmOptions::new()
+                .b1(2_000)
+                .b2(160_000)
+                .max_curves(100)
+                .seed(1)
+                .run(&n)
+                .unwrap(),
+            expected
+        );
+        assert!(matches!(
+            EcmOptions::new().b1(2_001).run(&n),
+            Err(Error::BoundsNotEven)
+        ));
+        // Too small for the baby steps of stage 2, or B2 below B1
+        for (b1, b2) in [(2, 160_000), (4, 4), (100, 50)] {
+            assert!(matches!(
+                EcmOptions::new().b1(b1).b2(b2).run(&n),
+                Err(Error::BoundsTooSmall)
+            ));
+            assert!(matches!(
+                ecm_one_factor(&n, b1, b2, 3, &mut RandState::new(), None),
+                Err(Error::BoundsTooSmall)
+            ));
+        }
+        assert!(EcmOptions::new().b1(6).b2(6).run(&n).is_ok());
+        assert_eq!(
+            EcmOptions::new()
+                .priority(Priority::Background)
+                .cpu_limit(50)
+                .run(&n)
+                .unwrap(),
+            expected
         );
     }
 
     #[test]
-    fn sympy_8() {
+    fn b2_from_b1() {
+        assert_eq!(optimal_b2(0), 0);
+        for (b1, b2, _) in (1..=70).step_by(5).map(optimal_params) {
+            let ratio = optimal_b2(b1) as f64 / b2 as f64;
+            assert!((0.5..2.0).contains(&ratio), "B1 = {b1}");
+            assert!(optimal_b2(b1).is_multiple_of(2));
+        }
+
+        let n = Integer::from_str("398883434337287").unwrap();
         assert_eq!(
-            ecm(&Integer::from_str("3146531246531241245132451321").unwrap(),).unwrap(),
-            HashMap::from([
-                (Integer::from_str("3").unwrap(), 1),
-                (Integer::from_str("100327907731").unwrap(), 1),
-                (Integer::from_str("10454157497791297").unwrap(), 1),
-            ])
+            ecm_with_params(&n, 2_000, None, 100, None).unwrap().len(),
+            2
         );
     }
 
     #[test]
-    fn sympy_9() {
+    fn entropy() {
+        assert_ne!(entropy_seed(), entropy_seed());
+        assert_eq!(EcmOptions::new().seed, None);
+        assert_eq!(EcmOptions::new().seed(5).seed, Some(5));
+
+        // The seed drawn runs the same curves again
+        let n = Integer::from_str("1048843748843747081710817107").unwrap() * 398883434337287u64;
+        let options = EcmOptions::new().b1(2_000).b2(160_000).max_curves(1_000);
+        let options = options.rho_steps(0).pm1_bounds(0, 0);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        let mut seeded = options.seed(factorizer.seed()).factorizer(&n).unwrap();
+        while seeded.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        let curves = factorizer.resume_state().curves_by_b1;
+        assert!(!curves.is_empty());
+        assert_eq!(seeded.resume_state().curves_by_b1, curves);
+        assert_eq!(seeded.stats().factors, factorizer.stats().factors);
+    }
+
+    #[test]
+    fn caller_rand_state() {
+        // Beyond 128 bits and factors out of reach of rho, so curves are run
+        let n = Integer::from_str("1048843748843747081710817107").unwrap()
+            * Integer::from_str("100000000000000000000000000319").unwrap();
+        let seeded = |seed: u64| {
+            let mut rgen = RandState::new();
+            rgen.seed(&seed.into());
+            rgen
+        };
+
+        let mut rgen = seeded(1234);
+        let factors = ecm_with_rand_state(&n, 2_000, None, 100, &mut rgen).unwrap();
         assert_eq!(
-            ecm(&Integer::from_str("4269021180054189416198169786894227").unwrap()).unwrap(),
-            HashMap::from([
-                (Integer::from_str("184039").unwrap(), 1),
-                (Integer::from_str("241603").unwrap(), 1),
-                (Integer::from_str("333331").unwrap(), 1),
-                (Integer::from_str("477973").unwrap(), 1),
-                (Integer::from_str("618619").unwrap(), 1),
-                (Integer::from_str("974123").unwrap(), 1),
-            ])
+            factors,
+            EcmOptions::new().b1(2_000).max_curves(100).run(&n).unwrap()
         );
+
+        // The generator was advanced by the curves
+        assert_ne!(rgen.bits(32), seeded(1234).bits(32));
+
+        // A bad input leaves the generator untouched
+        let mut rgen = seeded(7);
+        let options = EcmOptions::new().b1(2_000).b2(3_001);
+        assert!(options.run_with_rand_state(&n, &mut rgen).is_err());
+        assert_eq!(rgen.bits(32), seeded(7).bits(32));
     }
 
     #[test]
-    fn same_factors() {
+    fn residue_recycling() {
+        let entry = &crate::corpus::almost_primes(1, 3, 30, 1)[0];
+        let mut factorizer = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .seed(1)
+            .trial_division_bound(0)
+            .small_cofactor_bits(0)
+            .factorizer(&entry.n)
+            .unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+
+        // Stage 1 split n, the same curve then split the composite part
+        let stats = factorizer.recycle_stats();
         assert_eq!(
-            ecm(&Integer::from_str("7853316850129").unwrap()).unwrap(),
-            HashMap::from([(Integer::from_str("2802377").unwrap(), 2)])
+            stats,
+            RecycleStats {
+                attempts: 1,
+                hits: 1
+            }
+        );
+        assert_eq!(stats.hit_rate(), 1.0);
+        assert_eq!(factorizer.into_factors(), *entry.factors.as_ref().unwrap());
+    }
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(Error::BoundsNotEven.code(), 1);
+        assert_eq!(Error::ECMFailed.kind(), ErrorKind::ECMFailed);
+        assert_eq!(Error::NumberIsPrime.code(), 4);
+        assert_eq!(
+            Error::Internal("boom".to_string()).kind(),
+            ErrorKind::Internal
+        );
+        assert_eq!(Error::Internal("boom".to_string()).code(), 7);
+        assert_eq!(ErrorKind::InvalidNumber.code(), 9);
+        assert_eq!(Error::InputTooLarge(40_000).code(), 10);
+        assert_eq!(Error::ZeroInput.code(), 11);
+        assert_eq!(Error::Cancelled(Box::default()).code(), 12);
+        assert_eq!(Error::ResumeFile(io::ErrorKind::NotFound.into()).code(), 13);
+    }
+
+    #[test]
+    fn max_duration() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from_str("300000000000000000000000000007").unwrap();
+        let n = Integer::from(&p * &q) * 1_000_003 * 2;
+        let start = Instant::now();
+        let options = EcmOptions::new()
+            .seed(1)
+            .max_duration(Duration::from_millis(200));
+        let partial = options.run(&n).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(partial.product(), n);
+        assert_eq!(partial.cofactors().collect::<Vec<_>>(), [(&(p * q), 1)]);
+
+        // Done before the deadline
+        let n = Integer::from_str("398883434337287").unwrap();
+        let options = options.max_duration(Duration::from_secs(3600));
+        assert!(options.run(&n).unwrap().is_complete());
+    }
+
+    #[test]
+    fn resume_file() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from_str("300000000000000000000000000007").unwrap();
+        let n = Integer::from(&p * &q);
+        let path = std::env::temp_dir().join(format!("ecm-{}-resume.save", std::process::id()));
+
+        // Cancelled after a few curves, none of them finding a factor
+        let token = CancelToken::new();
+        let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1);
+        let mut factorizer = (options.clone())
+            .cancel_token(token.clone())
+            .resume_file(&path, ResumeFormat::GmpEcm)
+            .factorizer(&n)
+            .unwrap();
+        while factorizer.progress().total_curves < 3 {
+            factorizer.step(Duration::ZERO).unwrap();
+        }
+        token.cancel();
+        assert!(matches!(
+            factorizer.step(Duration::MAX),
+            Err(Error::Cancelled(_))
+        ));
+        let file = std::io::BufReader::new(File::open(&path).unwrap());
+        let residues = crate::read_save_file(file).unwrap();
+        assert_eq!(residues.len() as u64, factorizer.progress().total_curves);
+        assert!(residues.iter().all(|residue| residue.n == n));
+        std::fs::remove_file(&path).unwrap();
+
+        // Not written when the factorization completes, nor without path
+        let options = options.max_duration(Duration::from_secs(3600));
+        let done = (options.clone())
+            .resume_file(&path, ResumeFormat::GmpEcm)
+            .run(&Integer::from(398883434337287u64))
+            .unwrap();
+        assert!(done.is_complete());
+        assert!(!path.exists());
+        assert!(options.factorizer(&n).unwrap().write_resume_file().is_ok());
+
+        // Directory which does not exist
+        let missing = std::env::temp_dir()
+            .join("ecm-missing-dir")
+            .join("resume.save");
+        let mut factorizer = (EcmOptions::new())
+            .resume_file(missing, ResumeFormat::GmpEcm)
+            .factorizer(&n)
+            .unwrap();
+        assert!(matches!(
+            factorizer.write_resume_file(),
+            Err(Error::ResumeFile(_))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn resume_file_checkpoint() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from_str("300000000000000000000000000007").unwrap();
+        let n = Integer::from(&p * &q) * 6;
+        let path = std::env::temp_dir().join(format!("ecm-{}-resume.json", std::process::id()));
+
+        // Out of time, the checkpoint continues the curves run
+        let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1);
+        let partial = (options.clone())
+            .max_duration(Duration::from_millis(300))
+            .resume_file(&path, ResumeFormat::Checkpoint)
+            .run(&n)
+            .unwrap();
+        let checkpoint: crate::Checkpoint =
+            serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(checkpoint.progress().curve > 0);
+
+        let mut resumed = options.factorizer(checkpoint.n()).unwrap();
+        let curves = checkpoint.progress().curves_by_b1.clone();
+        resumed.restore_checkpoint(checkpoint).unwrap();
+        assert_eq!(resumed.partial_factors(), partial);
+        assert_eq!(resumed.resume_state().curves_by_b1, curves);
+    }
+
+    #[test]
+    fn stop_after_factors() {
+        let p: Integer = "100000000000000000000000000319".parse().unwrap();
+        let q: Integer = "300000000000000000000000000007".parse().unwrap();
+        let n = Integer::from(&p * &q) * 12;
+
+        // The factors of trial division are enough, no curve is run
+        let mut factorizer = EcmOptions::new()
+            .stop_after_factors(2)
+            .factorizer(&n)
+            .unwrap();
+        assert_eq!(factorizer.step(Duration::MAX).unwrap(), StepOutcome::Done);
+        assert_eq!(factorizer.progress().total_curves, 0);
+        let factors = factorizer.into_factors();
+        assert_eq!(factors.multiplicity(&Integer::from(2)), 2);
+        assert_eq!(factors.len(), 2);
+        assert_eq!(
+            factors.cofactors().next(),
+            Some((&Integer::from(&p * &q), 1))
+        );
+        assert_eq!(factors.product(), n);
+
+        // Stops after the split of the first composite
+        let n = Integer::from(398883434337287u64) * &p;
+        let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1);
+        let factors = options
+            .small_cofactor_bits(0)
+            .stop_after_factors(1)
+            .run(&n)
+            .unwrap();
+        assert_eq!(factors.len(), 1);
+        assert_eq!(factors.cofactors().count(), 1);
+        assert_eq!(factors.product(), n);
+    }
+
+    #[test]
+    fn stats() {
+        let (p, q, r) = (1_000_003u64, 999_983u64, 4_009_823u64);
+        let n = Integer::from(12) * p * q * r;
+        let options = EcmOptions::new().trial_division_bound(1_000);
+        let (factors, stats) = options
+            .known_factors([r.into()])
+            .run_with_stats(&n)
+            .unwrap();
+        assert!(factors.is_complete());
+        let method = |factor: u64| stats.factors[&Integer::from(factor)];
+        assert_eq!(method(2), FactorMethod::TrialDivision);
+        assert_eq!(method(3), FactorMethod::TrialDivision);
+        assert_eq!(method(r), FactorMethod::KnownFactor);
+        assert_eq!(
+            (method(p), method(q)),
+            (FactorMethod::Squfof, FactorMethod::Squfof)
+        );
+        assert_eq!(stats.curves(), 0);
+        assert_eq!(stats.gcds, 1);
+
+        let n = Integer::from(398883434337287u64) * &Integer::from(r);
+        let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1);
+        let (_, stats) = options.small_cofactor_bits(0).run_with_stats(&n).unwrap();
+        assert!(stats
+            .factors
+            .values()
+            .any(|m| matches!(m, FactorMethod::Ecm(_))));
+        assert_eq!(stats.curves(), stats.stages.curves);
+        assert!(stats.stages.gcds >= stats.curves());
+        assert!(stats.stages.stage1_time > Duration::ZERO);
+    }
+
+    #[test]
+    fn small_cofactors() {
+        // 31 digits smooth cofactor, the schedule would run curves with B1 = 11000
+        let primes = [20000003u64, 40000003, 60000011, 80000023];
+        let n = primes.iter().fold(Integer::from(6), |n, &p| n * p);
+        let run = |options: EcmOptions| {
+            let mut factorizer = options.seed(1).factorizer(&n).unwrap();
+            while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+            let curves: u64 = factorizer.resume_state().curves_by_b1.values().sum();
+            (factorizer.into_factors(), curves)
+        };
+        let expected: Factorization = [2, 3]
+            .into_iter()
+            .chain(primes)
+            .map(|p| (p.into(), 1))
+            .collect();
+
+        // The cofactor left by trial division has 102 bits, it is split without any curve
+        assert_eq!(run(EcmOptions::new()), (expected.clone(), 0));
+        assert_eq!(run(EcmOptions::new().small_cofactor_bits(104)).1, 0);
+
+        // Disabled, or cofactor too large: curves are run
+        for bits in [0, 64] {
+            let options = EcmOptions::new().small_cofactor_bits(bits).siqs_digits(0);
+            let (factors, curves) = run(options);
+            assert_eq!(factors, expected);
+            assert!(curves > 0);
+        }
+    }
+
+    #[test]
+    fn squfof_cofactors() {
+        let n = Integer::from(398883434337287u64) * 6;
+        let mut factorizer = EcmOptions::new().seed(1).factorizer(&n).unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        assert_eq!(
+            factorizer.resume_state().curves_by_b1.values().sum::<u64>(),
+            0
+        );
+        let methods = factorizer.stats().factors;
+        assert_eq!(methods[&4009823.into()], FactorMethod::Squfof);
+        assert_eq!(methods[&99476569.into()], FactorMethod::Squfof);
+        assert!(factorizer.into_factors().is_complete());
+
+        // Beyond 62 bits, rho splits it first
+        let n = Integer::from(2_000_003u64 * 3_000_017) * 10_000_019u64;
+        let options = EcmOptions::new().seed(1).one_line_bits(0..=0);
+        let mut factorizer = options.factorizer(&n).unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        let methods = factorizer.stats().factors;
+        assert!(methods.values().any(|&method| method == FactorMethod::Rho));
+    }
+
+    #[test]
+    fn one_line_cofactors() {
+        // 70 bits, too large for SQUFOF
+        let (p, q) = (30_000_000_001u64, 31_000_000_027u64);
+        let n = Integer::from(p) * q * 6;
+        let run = |options: EcmOptions| {
+            let mut factorizer = options.seed(1).factorizer(&n).unwrap();
+            while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+            assert_eq!(
+                factorizer.resume_state().curves_by_b1.values().sum::<u64>(),
+                0
+            );
+            let method = factorizer.stats().factors[&p.into()];
+            assert_eq!(factorizer.into_factors().product(), n);
+            method
+        };
+
+        assert_eq!(run(EcmOptions::new()), FactorMethod::OneLine);
+        assert_eq!(
+            run(EcmOptions::new().one_line_bits(50..=60)),
+            FactorMethod::Rho
+        );
+    }
+
+    #[test]
+    fn rho_cofactors() {
+        // 30 digits prime times factors just above the bound of trial division
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let n = Integer::from(&p * 2_000_003u64) * 1_000_000_007u64;
+        let run = |options: EcmOptions| {
+            let options = options.b1(2_000).b2(160_000).max_curves(20).seed(1);
+            let mut factorizer = options.factorizer(&n).unwrap();
+            while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+            let curves: u64 = factorizer.resume_state().curves_by_b1.values().sum();
+            let method = factorizer
+                .stats()
+                .factors
+                .get(&1_000_000_007.into())
+                .copied();
+            (factorizer.into_factors(), curves, method)
+        };
+
+        let (factors, curves, method) = run(EcmOptions::new());
+        assert!(factors.is_complete());
+        assert_eq!(factors.product(), n);
+        assert_eq!((curves, method), (0, Some(FactorMethod::Rho)));
+
+        // Disabled, curves are run
+        let (factors, curves, _) = run(EcmOptions::new().rho_steps(0).pm1_bounds(0, 0));
+        assert_eq!(factors.product(), n);
+        assert!(curves > 0);
+    }
+
+    #[test]
+    fn fermat_cofactors() {
+        // RSA modulus of two consecutive primes
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from_str("100000000000000000000000000379").unwrap();
+        let n = Integer::from(&p * &q) * 3;
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .max_curves(5)
+            .seed(1);
+
+        let mut factorizer = options.clone().factorizer(&n).unwrap();
+        while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+        assert_eq!(
+            factorizer.resume_state().curves_by_b1.values().sum::<u64>(),
+            0
+        );
+        assert_eq!(factorizer.stats().factors[&p], FactorMethod::Fermat);
+        assert_eq!(
+            factorizer.into_factors(),
+            Factorization::from([(3.into(), 1), (p, 1), (q, 1)])
+        );
+
+        let factors = options.fermat_steps(0).max_escalations(0).run(&n).unwrap();
+        assert_eq!(factors.cofactors().count(), 1);
+    }
+
+    #[test]
+    fn pm1_cofactors() {
+        // p - 1 = 2^6 * 7 * 13 * 19 * 31 * 61 * 67 * 3709 * 19231, out of reach of rho
+        let p = Integer::from_str("1000000000000001729").unwrap();
+        let n = Integer::from_str("100000000000000000000000000319").unwrap() * &p;
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .max_curves(5)
+            .seed(1);
+        let run = |options: EcmOptions| {
+            let mut factorizer = options.factorizer(&n).unwrap();
+            while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+            let curves: u64 = factorizer.resume_state().curves_by_b1.values().sum();
+            let method = factorizer.stats().factors.get(&p).copied();
+            (factorizer.into_factors().is_complete(), curves, method)
+        };
+
+        let found = (true, 0, Some(FactorMethod::PMinusOne));
+        assert_eq!(run(options.clone()), found);
+        // Stage 2 is needed, the curves run instead
+        for options in [
+            options.clone().pm1_bounds(10_000, 10_000),
+            options.pm1_bounds(0, 0),
+        ] {
+            let (_, curves, method) = run(options);
+            assert!(curves > 0);
+            assert_ne!(method, Some(FactorMethod::PMinusOne));
+        }
+    }
+
+    #[test]
+    fn siqs_cofactors() {
+        // Two factors of 22 digits, out of reach of the methods run before the curves
+        let p = Integer::from_str("1000000000000000000117").unwrap();
+        let q = Integer::from_str("3000000000000000000053").unwrap();
+        let n = Integer::from(&p * &q) * 6;
+        let run = |options: EcmOptions, budget: Duration| {
+            let mut factorizer = options.seed(1).factorizer(&n).unwrap();
+            while factorizer.step(budget).unwrap() == StepOutcome::Pending {}
+            let curves: u64 = factorizer.resume_state().curves_by_b1.values().sum();
+            let method = factorizer.stats().factors.get(&p).copied();
+            (factorizer.into_factors().is_complete(), curves, method)
+        };
+
+        // A curve for factors of 45 digits costs more than t15, the pretest of 44 digits
+        let found = (true, 0, Some(FactorMethod::Siqs));
+        assert_eq!(run(EcmOptions::new(), Duration::MAX), found);
+        // Sieved over many steps
+        assert_eq!(run(EcmOptions::new(), Duration::ZERO), found);
+
+        // With bounds given, only if asked for, once the curves failed
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .max_curves(5)
+            .max_escalations(0);
+        assert_eq!(
+            run(options.clone().siqs_digits(50), Duration::MAX),
+            (true, 5, Some(FactorMethod::Siqs))
+        );
+        assert_eq!(run(options, Duration::MAX), (false, 5, None));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans() {
+        use tracing::{span, Event, Metadata, Subscriber};
+
+        /// Names of the spans, and levels of the events, in order.
+        struct Record(Arc<Mutex<Vec<String>>>);
+
+        impl Subscriber for Record {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                let mut records = self.0.lock().unwrap();
+                records.push(span.metadata().name().to_string());
+                span::Id::from_u64(records.len() as u64)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let level = event.metadata().level().to_string();
+                self.0.lock().unwrap().push(level);
+            }
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let n = Integer::from_str("398883434337287").unwrap();
+        let sigmas = (6..1_000).map(Integer::from);
+        tracing::subscriber::with_default(Record(records.clone()), || {
+            ecm_one_factor_with_sigmas(&n, 2_000, 160_000, sigmas, None).unwrap();
+        });
+        let one_factor = std::mem::take(&mut *records.lock().unwrap());
+        for name in ["curve", "stage1", "stage2"] {
+            assert!(one_factor.iter().any(|record| record == name));
+        }
+        assert_eq!(one_factor.last().unwrap(), "INFO");
+
+        let options = EcmOptions::new().seed(1).small_cofactor_bits(0);
+        tracing::subscriber::with_default(Record(records.clone()), || {
+            options.run(&n).unwrap();
+        });
+        let driver = records.lock().unwrap();
+        assert_eq!(driver[0], "DEBUG");
+        assert!(driver.iter().any(|record| record == "curve"));
+        assert_eq!(driver.iter().filter(|record| *record == "INFO").count(), 1);
+    }
+
+    #[test]
+    fn cancel() {
+        let p = Integer::from_str("1000000000000000003").unwrap();
+        let q = Integer::from_str("1000000000000000009").unwrap();
+        let pq = Integer::from(&p * &q);
+        let n = Integer::from(&pq * 6);
+
+        // Cancelled during the first curves, stage 2 up to about 10^9 takes seconds
+        let token = CancelToken::new();
+        let options = EcmOptions::new()
+            .b1(1_000_000)
+            .seed(1)
+            .cancel_token(token.clone());
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            token.cancel();
+        });
+        let start = Instant::now();
+        let Err(Error::Cancelled(partial)) = options.run(&n) else {
+            panic!("not cancelled");
+        };
+        canceller.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(partial.multiplicity(&2.into()), 1);
+        assert_eq!(partial.multiplicity(&3.into()), 1);
+        assert_eq!(partial.cofactors().collect::<Vec<_>>(), [(&pq, 1)]);
+
+        // Cancelled before the methods cheaper than the curves, none of them is run
+        let token = CancelToken::new();
+        let options = EcmOptions::new()
+            .trial_division_bound(1_000)
+            .cancel_token(token.clone());
+        let mut factorizer = options.factorizer(&n).unwrap();
+        factorizer.step(Duration::ZERO).unwrap();
+        assert_eq!(factorizer.pretests.get(&pq), Some(&Pretest::Small));
+        token.cancel();
+        assert!(matches!(
+            factorizer.step(Duration::MAX),
+            Err(Error::Cancelled(_))
+        ));
+        assert_eq!(factorizer.pretests.get(&pq), Some(&Pretest::Small));
+
+        // Cancelled before the first step
+        let token = CancelToken::new();
+        token.cancel();
+        let mut factorizer = EcmOptions::new()
+            .cancel_token(token)
+            .factorizer(&n)
+            .unwrap();
+        let Err(Error::Cancelled(partial)) = factorizer.step(Duration::MAX) else {
+            panic!("not cancelled");
+        };
+        assert_eq!(*partial, {
+            let mut partial = Factorization::new();
+            partial.add_cofactor(n, 1);
+            partial
+        });
+    }
+
+    #[test]
+    fn zero_one_and_negative() {
+        assert!(matches!(ecm(&Integer::new()), Err(Error::ZeroInput)));
+        assert!(ecm(&Integer::from(1)).unwrap().is_empty());
+        assert!(ecm(&Integer::from(-1)).unwrap().is_empty());
+        assert_eq!(
+            ecm(&Integer::from(-2)).unwrap(),
+            Factorization::from([(Integer::from(2), 1)])
         );
+
+        let n = Integer::from_str("398883434337287").unwrap();
+        assert_eq!(ecm(&Integer::from(-&n)).unwrap(), ecm(&n).unwrap());
+
+        let mut rgen = RandState::new();
+        let one_factor = |n: i64, rgen: &mut RandState<'_>| {
+            ecm_one_factor(&Integer::from(n), 2_000, 160_000, 10, rgen, None)
+        };
+        assert!(matches!(one_factor(0, &mut rgen), Err(Error::ZeroInput)));
+        assert!(matches!(one_factor(-1, &mut rgen), Err(Error::ECMFailed)));
+        let factor = one_factor(-398883434337287, &mut rgen).unwrap();
+        assert!(factor > 1 && n.is_divisible(&factor) && factor != n);
     }
 
     #[test]
     fn small_prime() {
         assert_eq!(
             ecm(&Integer::from(17)).unwrap(),
-            HashMap::from([(Integer::from(17), 1)])
+            Factorization::from([(Integer::from(17), 1)])
+        );
+    }
+
+    #[cfg(feature = "catch-unwind")]
+    #[test]
+    fn panic_is_caught() {
+        let err = catch_panic(&Integer::from(15), || panic!("boom")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Internal error: panic while factoring 15: boom"
         );
+        assert_eq!(catch_panic(&Integer::from(15), || 3).unwrap(), 3);
     }
 
     #[test]
     fn big_prime() {
         assert_eq!(
             ecm(&Integer::from_str("21472883178031195225853317139").unwrap()).unwrap(),
-            HashMap::from([(
+            Factorization::from([(
                 Integer::from_str("21472883178031195225853317139").unwrap(),
                 1
             )])
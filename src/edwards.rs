@@ -0,0 +1,366 @@
+use crate::{
+    curve::{try_invert_or_factor, Curve, FactorFound},
+    point::Point,
+};
+use rug::Integer;
+
+/// Modular multiplications of [`EdwardsPoint::add`], multiplications by `a` and `d` included.
+pub(crate) const EDWARDS_ADD_MULMODS: u64 = 11;
+
+/// Modular multiplications of [`EdwardsPoint::double`], squarings included.
+pub(crate) const EDWARDS_DOUBLE_MULMODS: u64 = 9;
+
+/// Largest window of [`EdwardsPoint::mul`], its table holds `2^(w - 1)` points.
+const MAX_WINDOW: u32 = 8;
+
+/// Twisted Edwards curve `a*x^2 + y^2 = 1 + d*x^2*y^2 (mod n)`.
+///
+/// The constants are shared by reference by the arithmetic of [`EdwardsPoint`],
+/// so adding and doubling points only creates their coordinates.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdwardsCurve {
+    /// Parameter `a` of the curve
+    pub a: Integer,
+    /// Parameter `d` of the curve
+    pub d: Integer,
+    /// modulus
+    pub modulus: Integer,
+}
+
+impl EdwardsCurve {
+    /// Maps a Montgomery curve and its starting point to a twisted Edwards curve.
+    ///
+    /// With `u = X0/Z0` and `B = u^3 + A*u^2 + u`, the starting point `(u, 1)` lies on
+    /// `B*v^2 = u^3 + A*u^2 + u`, which is mapped to the Edwards curve
+    /// `a = (A + 2)/B`, `d = (A - 2)/B` by `x = u`, `y = (u - 1)/(u + 1)`.
+    /// Stage 1 therefore runs in the same group as the Montgomery ladder,
+    /// and [`EdwardsPoint::into_montgomery`] maps its result back.
+    ///
+    /// Returns [`FactorFound`] if `Z0*B` is not invertible modulo n.
+    ///
+    /// # Parameters
+    ///
+    /// - `curve`: Montgomery curve and starting point.
+    pub fn from_montgomery(curve: &Curve) -> Result<(EdwardsCurve, EdwardsPoint), FactorFound> {
+        let n = curve.modulus();
+        let (x0, z0) = (curve.x_cord(), curve.z_cord());
+        let a_24_4 = Integer::from(curve.a_24() * 4u32);
+
+        // Z0^4*B = X0*(X0^2 + A*X0*Z0 + Z0^2), with A = 4*a_24 - 2
+        let xz = Integer::from(x0 * z0);
+        let quadratic = Integer::from(x0.square_ref())
+            + (Integer::from(&a_24_4 - 2u32) * xz)
+            + Integer::from(z0.square_ref());
+        let b_z0_4 = x0 * quadratic % n;
+        let inverse = try_invert_or_factor(&Integer::from(&b_z0_4 * z0), n)?;
+        // 1/B = Z0^3/(Z0^4*B)
+        let z0_2 = Integer::from(z0.square_ref());
+        let b_inv = Integer::from(&z0_2 * &z0_2) * inverse % n;
+
+        let a = Integer::from(&a_24_4 * &b_inv) % n;
+        let d = (&a - 4u32 * b_inv) % n;
+
+        let sum = Integer::from(x0 + z0);
+        let diff = Integer::from(x0 - z0);
+        let point = EdwardsPoint {
+            x_cord: Integer::from(x0 * &sum) % n,
+            y_cord: Integer::from(z0 * &diff) % n,
+            z_cord: Integer::from(z0 * &sum) % n,
+            t_cord: Integer::from(x0 * &diff) % n,
+        };
+        let curve = EdwardsCurve {
+            a,
+            d,
+            modulus: n.clone(),
+        };
+        Ok((curve, point))
+    }
+
+    /// Returns true if `p` and `q` are the same point of the curve.
+    ///
+    /// X/Z and Y/Z are compared by cross-multiplying, so Z does not need to be invertible.
+    ///
+    /// # Parameters
+    ///
+    /// - `p`: Point on the curve.
+    /// - `q`: Point on the curve.
+    pub fn same_point(&self, p: &EdwardsPoint, q: &EdwardsPoint) -> bool {
+        let same = |lhs: &Integer, rhs: &Integer| {
+            let lhs = Integer::from(lhs * &q.z_cord);
+            let rhs = Integer::from(rhs * &p.z_cord);
+            (lhs - rhs).is_divisible(&self.modulus)
+        };
+        same(&p.x_cord, &q.x_cord) && same(&p.y_cord, &q.y_cord)
+    }
+}
+
+/// Extended coordinates of Points in a twisted Edwards curve, see [`EdwardsCurve`].
+///
+/// The point `(X : Y : Z : T)` stands for `x = X/Z`, `y = Y/Z`, with `T = X*Y/Z`.
+/// The neutral element is `(0 : 1 : 1 : 0)`.
+///
+/// A doubling costs 4 multiplications, 4 squarings and a multiplication by `a`,
+/// one multiplication less when the result is doubled again, and an addition
+/// 11 multiplications. With a sliding window of precomputed odd multiples,
+/// stage 1 costs about 9.5 modular multiplications per bit of the multiplier,
+/// against 11 for the Montgomery ladder of [`Point`].
+///
+/// References
+/// ----------
+/// - Hisil, Wong, Carter, Dawson, "Twisted Edwards Curves Revisited", 2008.
+/// - Bernstein, Birkner, Lange, Peters, "ECM using Edwards curves", 2013.
+#[derive(Debug, Clone, Default)]
+pub struct EdwardsPoint {
+    /// X coordinate of the Point
+    pub x_cord: Integer,
+    /// Y coordinate of the Point
+    pub y_cord: Integer,
+    /// Z coordinate of the Point
+    pub z_cord: Integer,
+    /// T coordinate of the Point, equal to `X*Y/Z`
+    pub t_cord: Integer,
+}
+
+impl EdwardsPoint {
+    /// Point of the Montgomery curve mapped by [`EdwardsCurve::from_montgomery`],
+    /// `(Z + Y : Z - Y)`.
+    ///
+    /// # Parameters
+    ///
+    /// - `curve`: Curve of the point.
+    /// - `a_24`: Parameter of the Montgomery curve, see [`Curve::a_24`].
+    pub fn into_montgomery(self, curve: &EdwardsCurve, a_24: Integer) -> Point {
+        let n = &curve.modulus;
+        let x_cord = Integer::from(&self.z_cord + &self.y_cord) % n;
+        let z_cord = (self.z_cord - self.y_cord) % n;
+        Point::new(x_cord, z_cord, a_24, n.clone())
+    }
+
+    /// Neutral element `(0 : 1 : 1 : 0)`, the same on every curve.
+    fn neutral() -> EdwardsPoint {
+        EdwardsPoint {
+            x_cord: Integer::new(),
+            y_cord: Integer::from(1),
+            z_cord: Integer::from(1),
+            t_cord: Integer::new(),
+        }
+    }
+
+    /// Adds two points, with the unified formulas which also double a point.
+    ///
+    /// # Parameters
+    ///
+    /// - `q`: Point on the same curve.
+    /// - `curve`: Curve of both points.
+    pub fn add(&self, q: &EdwardsPoint, curve: &EdwardsCurve) -> EdwardsPoint {
+        let n = &curve.modulus;
+        let xx = Integer::from(&self.x_cord * &q.x_cord) % n;
+        let yy = Integer::from(&self.y_cord * &q.y_cord) % n;
+        let tt = Integer::from(&self.t_cord * &q.t_cord) % n * &curve.d % n;
+        let zz = Integer::from(&self.z_cord * &q.z_cord) % n;
+        let e = Integer::from(&self.x_cord + &self.y_cord) * Integer::from(&q.x_cord + &q.y_cord)
+            - &xx
+            - &yy;
+        let f = Integer::from(&zz - &tt);
+        let g = zz + tt;
+        let h = yy - Integer::from(&curve.a * &xx);
+
+        EdwardsPoint {
+            x_cord: Integer::from(&e * &f) % n,
+            y_cord: Integer::from(&g * &h) % n,
+            z_cord: f * g % n,
+            t_cord: e * h % n,
+        }
+    }
+
+    /// Doubles a point in a twisted Edwards curve.
+    ///
+    /// # Parameters
+    ///
+    /// - `curve`: Curve of the point.
+    pub fn double(&self, curve: &EdwardsCurve) -> EdwardsPoint {
+        self.double_with(curve, true)
+    }
+
+    /// Doubles a point, computing T only if `extended`, as doublings do not use it.
+    fn double_with(&self, curve: &EdwardsCurve, extended: bool) -> EdwardsPoint {
+        let n = &curve.modulus;
+        let xx = Integer::from(self.x_cord.square_ref()) % n;
+        let yy = Integer::from(self.y_cord.square_ref()) % n;
+        let zz_2 = Integer::from(self.z_cord.square_ref()) % n * 2u32;
+        let a_xx = Integer::from(&curve.a * &xx) % n;
+        let e = Integer::from(&self.x_cord + &self.y_cord).square() - xx - &yy;
+        let g = Integer::from(&a_xx + &yy);
+        let f = Integer::from(&g - &zz_2);
+        let h = a_xx - yy;
+
+        EdwardsPoint {
+            x_cord: Integer::from(&e * &f) % n,
+            y_cord: Integer::from(&g * &h) % n,
+            z_cord: f * g % n,
+            t_cord: match extended {
+                true => e * h % n,
+                false => Integer::new(),
+            },
+        }
+    }
+
+    /// Scalar multiplication `k*self`, with a sliding window of odd multiples.
+    ///
+    /// ```
+    /// use ecm::{EdwardsCurve, SuyamaCurve};
+    /// use rug::Integer;
+    ///
+    /// let n = Integer::from(398883434337287u64);
+    /// let curve = SuyamaCurve::derive(&Integer::from(7), &n).unwrap();
+    /// let (edwards, point) = EdwardsCurve::from_montgomery(&curve).unwrap();
+    /// // Same multiple as the Montgomery ladder
+    /// let q = point.mul(&Integer::from(1000), &edwards);
+    /// let q = q.into_montgomery(&edwards, curve.a_24().clone());
+    /// assert_eq!(q, curve.point().ladder_with_exponent(&Integer::from(1000)));
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `k`: The non-negative integer multiplier
+    /// - `curve`: Curve of the point.
+    pub fn mul(&self, k: &Integer, curve: &EdwardsCurve) -> EdwardsPoint {
+        self.mul_counted(k, curve).0
+    }
+
+    /// Same as [`EdwardsPoint::mul`], also returning the number of modular multiplications.
+    pub(crate) fn mul_counted(&self, k: &Integer, curve: &EdwardsCurve) -> (EdwardsPoint, u64) {
+        let bits = k.significant_bits();
+        if bits == 0 {
+            return (EdwardsPoint::neutral(), 0);
+        }
+
+        // Table of the odd multiples, costing a doubling and 2^(w - 1) - 1 additions,
+        // and about one addition every w + 1 bits
+        let w = (1..=MAX_WINDOW.min(bits))
+            .min_by_key(|&w| u64::from(bits / (w + 1)) + (1 << (w - 1)))
+            .unwrap_or(1);
+        let mut table = vec![self.clone()];
+        let mut mulmods = 0;
+        if w > 1 {
+            let double = self.double(curve);
+            mulmods += EDWARDS_DOUBLE_MULMODS;
+            for i in 1..1 << (w - 1) {
+                table.push(table[i - 1].add(&double, curve));
+                mulmods += EDWARDS_ADD_MULMODS;
+            }
+        }
+
+        let mut q: Option<EdwardsPoint> = None;
+        // Doublings not done yet, the last one before an addition computes T
+        let mut doublings = 0;
+        // Bits after `top`, most significant first
+        let mut top = bits;
+        while top > 0 {
+            let i = top - 1;
+            if !k.get_bit(i) {
+                doublings += 1;
+                top = i;
+                continue;
+            }
+            // Window of at most w bits ending with a one
+            let mut j = i.saturating_sub(w - 1);
+            while !k.get_bit(j) {
+                j += 1;
+            }
+            let odd = (j..=i)
+                .rev()
+                .fold(0, |odd, b| 2 * odd + usize::from(k.get_bit(b)));
+            q = Some(match q {
+                None => table[odd / 2].clone(),
+                Some(mut q) => {
+                    doublings += i - j + 1;
+                    for d in (0..doublings).rev() {
+                        q = q.double_with(curve, d == 0);
+                        mulmods += EDWARDS_DOUBLE_MULMODS - u64::from(d != 0);
+                    }
+                    mulmods += EDWARDS_ADD_MULMODS;
+                    q.add(&table[odd / 2], curve)
+                }
+            });
+            doublings = 0;
+            top = j;
+        }
+
+        let mut q = q.unwrap_or_else(EdwardsPoint::neutral);
+        for d in (0..doublings).rev() {
+            q = q.double_with(curve, d == 0);
+            mulmods += EDWARDS_DOUBLE_MULMODS - u64::from(d != 0);
+        }
+        (q, mulmods)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SuyamaCurve;
+
+    #[test]
+    fn on_curve() {
+        // 2^61 - 1
+        let n = Integer::from(2305843009213693951u64);
+        let curve = SuyamaCurve::derive(&Integer::from(11), &n).unwrap();
+        let (c, p) = EdwardsCurve::from_montgomery(&curve).unwrap();
+        // (a*X^2 + Y^2)*Z^2 = Z^4 + d*X^2*Y^2, and T*Z = X*Y
+        for q in [
+            p.clone(),
+            p.double(&c),
+            p.add(&p.double(&c), &c),
+            p.mul(&1234.into(), &c),
+        ] {
+            let (xx, yy, zz) = (
+                q.x_cord.square_ref(),
+                q.y_cord.square_ref(),
+                q.z_cord.square_ref(),
+            );
+            let (xx, yy, zz) = (Integer::from(xx), Integer::from(yy), Integer::from(zz));
+            let lhs = (Integer::from(&c.a * &xx) + &yy) * &zz;
+            let rhs = Integer::from(zz.square_ref()) + Integer::from(&c.d * &xx) * &yy;
+            assert!((lhs - rhs).is_divisible(&n));
+            let t = Integer::from(&q.t_cord * &q.z_cord) - Integer::from(&q.x_cord * &q.y_cord);
+            assert!(t.is_divisible(&n));
+        }
+        assert!(c.same_point(&p.add(&p, &c), &p.double(&c)));
+        assert!(!c.same_point(&p, &p.double(&c)));
+        let neutral = EdwardsPoint::neutral();
+        assert!(c.same_point(&p.mul(&0.into(), &c), &neutral));
+        assert!(c.same_point(&neutral.add(&p, &c), &p));
+    }
+
+    #[test]
+    fn same_as_montgomery() {
+        let n = Integer::from(2305843009213693951u64);
+        for sigma in [6, 7, 11, 100] {
+            let curve = SuyamaCurve::derive(&Integer::from(sigma), &n).unwrap();
+            let (c, p) = EdwardsCurve::from_montgomery(&curve).unwrap();
+            assert_eq!(
+                p.clone().into_montgomery(&c, curve.a_24().clone()),
+                curve.point()
+            );
+            for k in [1u64, 2, 3, 12, 255, 256, 1_000_003, 720720720720] {
+                let k = Integer::from(k);
+                let q = p.mul(&k, &c).into_montgomery(&c, curve.a_24().clone());
+                assert_eq!(q, curve.point().ladder_with_exponent(&k));
+            }
+        }
+    }
+
+    #[test]
+    fn mulmods() {
+        let n = Integer::from(2305843009213693951u64);
+        let curve = SuyamaCurve::derive(&Integer::from(6), &n).unwrap();
+        let (c, p) = EdwardsCurve::from_montgomery(&curve).unwrap();
+        // One doubling
+        assert_eq!(p.mul_counted(&2.into(), &c).1, EDWARDS_DOUBLE_MULMODS);
+        // Fewer than the 11 per bit of the Montgomery ladder
+        let k = crate::stage1_exponent(10_000, []);
+        let mulmods = p.mul_counted(&k, &c).1;
+        assert!(mulmods < 10 * u64::from(k.significant_bits()));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{EcmStage, Parametrization, Progress};
+use rug::Integer;
+
+/// Event of a running factorization, see [`Factorizer::events`](crate::Factorizer::events).
+///
+/// Events are sent on a channel, so dashboards can be built on top of a
+/// factorizer running on another thread:
+///
+/// ```
+/// use ecm::{EcmEvent, EcmOptions, StepOutcome};
+/// use rug::Integer;
+/// use std::time::Duration;
+///
+/// let mut factorizer = EcmOptions::new()
+///     .factorizer(&Integer::from(398883434337287u64))
+///     .unwrap();
+/// let events = factorizer.events();
+///
+/// let job = std::thread::spawn(move || {
+///     while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+/// });
+///
+/// // The channel is closed once the factorizer is dropped
+/// let found = events
+///     .iter()
+///     .filter(|event| matches!(event, EcmEvent::FactorFound { .. }))
+///     .count();
+/// job.join().unwrap();
+/// assert_eq!(found, 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EcmEvent {
+    /// Trial division is done, before the cofactor is tested and queued.
+    TrialDivisionDone {
+        /// Part of n left by trial division, 1 if n was fully factored.
+        cofactor: Integer,
+    },
+    /// A curve is about to run.
+    CurveStarted {
+        /// Curve number on the current composite, starting at 1.
+        curve: u64,
+        /// Parameter of the curve, see [`SuyamaCurve`](crate::SuyamaCurve).
+        sigma: Integer,
+        /// Family of the curve of the sigma.
+        parametrization: Parametrization,
+        /// Stage 1 bound.
+        b1: u64,
+    },
+    /// A curve split a composite.
+    FactorFound {
+        /// Factor found, not necessarily prime.
+        factor: Integer,
+        /// Composite it was found in.
+        composite: Integer,
+        /// Curve number on the composite, starting at 1, or 0 when the factor was
+        /// found without a curve, by residue recycling, see [`RecycleStats`](crate::RecycleStats),
+        /// or on a small composite, see [`SMALL_COFACTOR_BITS`](crate::SMALL_COFACTOR_BITS).
+        curve: u64,
+        /// Stage of the curve which found the factor, `None` if found without a curve.
+        stage: Option<EcmStage>,
+    },
+    /// Stage 1 of a curve did not split the composite, stage 2 is about to run.
+    Stage2Started {
+        /// Curve number on the current composite, starting at 1.
+        curve: u64,
+    },
+    /// Progress of the factorization, sent after each curve.
+    Stats(Progress),
+    /// The factorization is complete, sent once.
+    Finished {
+        /// Curves run on all the composites, including the ones of a restored run.
+        curves: u64,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EcmOptions;
+    use std::sync::mpsc;
+
+    #[test]
+    fn sequence() {
+        let n = Integer::from(398883434337287u64);
+        let (sender, receiver) = mpsc::channel();
+        let factors = EcmOptions::new()
+            .seed(1234)
+            .small_cofactor_bits(0)
+            .event_sender(sender)
+            .run(&n)
+            .unwrap();
+        let events: Vec<EcmEvent> = receiver.iter().collect();
+
+        // Each curve starts, then reports its stats
+        let started = events
+            .iter()
+            .filter(|event| matches!(event, EcmEvent::CurveStarted { .. }))
+            .count();
+        let stats = events
+            .iter()
+            .filter(|event| matches!(event, EcmEvent::Stats(_)))
+            .count();
+        assert!(started > 0);
+        assert_eq!(started, stats);
+        assert_eq!(
+            events[0],
+            EcmEvent::TrialDivisionDone {
+                cofactor: n.clone()
+            }
+        );
+        assert!(matches!(events[1], EcmEvent::CurveStarted { curve: 1, .. }));
+
+        // Stage 2 is only run by the curves whose stage 1 failed
+        let stage2 = events
+            .iter()
+            .filter(|event| matches!(event, EcmEvent::Stage2Started { .. }))
+            .count();
+        assert!(stage2 <= started);
+
+        let Some(EcmEvent::FactorFound {
+            factor,
+            composite,
+            stage,
+            ..
+        }) = events
+            .iter()
+            .find(|event| matches!(event, EcmEvent::FactorFound { .. }))
+        else {
+            panic!("no factor found");
+        };
+        assert_eq!(*composite, n);
+        assert_eq!(factors.multiplicity(factor), 1);
+        assert!(stage.is_some());
+        let [.., EcmEvent::Stats(progress), EcmEvent::Finished { curves }] = &events[..] else {
+            panic!("not finished");
+        };
+        assert_eq!(progress.remaining_digits, 0);
+        assert_eq!(*curves, started as u64);
+    }
+}
@@ -0,0 +1,491 @@
+//! Client of FactorDB, with the `factordb` feature.
+//!
+//! Requests are sent over plain HTTP and the answers are not authenticated,
+//! see [`FactorDb`]. Responses larger than [`MAX_RESPONSE_SIZE`] are rejected.
+
+use crate::{EcmOptions, Error, Factorization};
+use rug::{integer::IsPrime, Integer};
+use serde::Deserialize;
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Host of the public FactorDB instance.
+pub const FACTORDB_HOST: &str = "factordb.com";
+
+/// Default size limit of a response, headers included, see [`FactorDb::max_response_size`].
+pub const MAX_RESPONSE_SIZE: usize = 1 << 20;
+
+/// Error occured while querying FactorDB.
+#[derive(thiserror::Error, Debug)]
+pub enum FactorDbError {
+    /// The connection to the server failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// The server answered with an HTTP error.
+    #[error("HTTP error {0}")]
+    Http(u16),
+    /// The answer of the server is not a valid HTTP response.
+    #[error("Invalid HTTP response: {0}")]
+    InvalidResponse(&'static str),
+    /// The response is larger than the limit, see [`FactorDb::max_response_size`].
+    #[error("Response larger than {0} bytes")]
+    TooLarge(usize),
+    /// The answer of the server is not the JSON of the API.
+    #[error("Invalid answer: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A factor of the answer is not a number.
+    #[error("Invalid factor {0:?}")]
+    InvalidFactor(String),
+    /// The factorization failed.
+    #[error(transparent)]
+    Ecm(#[from] Error),
+}
+
+/// Status of a number in FactorDB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FactorDbStatus {
+    /// Composite, no factor known (`C`).
+    Composite,
+    /// Composite, some factors known (`CF`).
+    CompositeFactorsKnown,
+    /// Fully factored (`FF`).
+    FullyFactored,
+    /// Proven prime (`P`).
+    Prime,
+    /// Probable prime (`PRP`).
+    ProbablePrime,
+    /// Not known to be prime or composite yet (`U`).
+    Unknown,
+    /// Any other status, e.g. `Unit` for 1.
+    Other(String),
+}
+
+impl From<&str> for FactorDbStatus {
+    fn from(status: &str) -> FactorDbStatus {
+        match status {
+            "C" => FactorDbStatus::Composite,
+            "CF" => FactorDbStatus::CompositeFactorsKnown,
+            "FF" => FactorDbStatus::FullyFactored,
+            "P" => FactorDbStatus::Prime,
+            "PRP" => FactorDbStatus::ProbablePrime,
+            "U" => FactorDbStatus::Unknown,
+            status => FactorDbStatus::Other(status.to_string()),
+        }
+    }
+}
+
+/// Entry of a number in FactorDB, see [`FactorDb::query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactorDbEntry {
+    /// Identifier of the number in the database.
+    pub id: String,
+    /// Status of the number.
+    pub status: FactorDbStatus,
+    /// Factors known, with their multiplicity, not necessarily prime unless fully factored.
+    pub factors: Vec<(Integer, usize)>,
+}
+
+impl FactorDbEntry {
+    /// Known factors other than 1 and n, to be divided out before ECM.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number of the entry.
+    pub fn proper_factors(&self, n: &Integer) -> impl Iterator<Item = &Integer> {
+        let n = Integer::from(n.abs_ref());
+        (self.factors.iter())
+            .map(|(factor, _)| factor)
+            .filter(move |factor| **factor != 1 && **factor != n)
+    }
+}
+
+/// Answer of the `api` endpoint.
+#[derive(Deserialize)]
+struct ApiAnswer {
+    id: serde_json::Value,
+    status: String,
+    factors: Vec<(String, usize)>,
+}
+
+/// Client of [FactorDB](http://factordb.com), with the `factordb` feature.
+///
+/// FactorDB stores the known factors of many numbers: querying it before
+/// running ECM saves hours on well-known composites, e.g. Fermat or
+/// Cunningham numbers. The client speaks plain HTTP on the standard library
+/// sockets, without TLS.
+///
+/// The numbers queried and reported can thus be read by anyone on the path to
+/// the server, and the answers are not authenticated: a forged answer can
+/// claim wrong factors or statuses. [`FactorDb::factor`] only divides out the
+/// known factors which divide n, see [`EcmOptions::known_factors`], so they
+/// cannot corrupt the factorization, but a [`FactorDbEntry`] should not be
+/// trusted as is. Responses are limited in size, see [`FactorDb::max_response_size`].
+///
+/// ```no_run
+/// use ecm::{EcmOptions, FactorDb};
+/// use rug::Integer;
+///
+/// let n = (Integer::from(1) << 128) + 1;
+/// let factors = FactorDb::new().factor(&n, &EcmOptions::new()).unwrap();
+/// assert_eq!(factors.len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactorDb {
+    host: String,
+    port: u16,
+    timeout: Duration,
+    report: bool,
+    max_response_size: usize,
+}
+
+impl Default for FactorDb {
+    fn default() -> FactorDb {
+        FactorDb::new()
+    }
+}
+
+impl FactorDb {
+    /// Client of the public instance, [`FACTORDB_HOST`].
+    pub fn new() -> FactorDb {
+        FactorDb::with_host(FACTORDB_HOST, 80)
+    }
+
+    /// Client of the instance at `host`, e.g. a mirror.
+    ///
+    /// # Parameters
+    ///
+    /// - `host`: Host name or address of the server.
+    /// - `port`: HTTP port of the server.
+    pub fn with_host(host: impl Into<String>, port: u16) -> FactorDb {
+        FactorDb {
+            host: host.into(),
+            port,
+            timeout: Duration::from_secs(30),
+            report: false,
+            max_response_size: MAX_RESPONSE_SIZE,
+        }
+    }
+
+    /// Sets the timeout of each connection, read and write, defaults to 30 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the size limit of a response, headers included, defaults to [`MAX_RESPONSE_SIZE`].
+    ///
+    /// Larger responses fail with [`FactorDbError::TooLarge`].
+    pub fn max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = bytes;
+        self
+    }
+
+    /// Reports the factors found by [`FactorDb::factor`] which were not known, defaults to false.
+    pub fn report(mut self, report: bool) -> Self {
+        self.report = report;
+        self
+    }
+
+    /// Returns the entry of n, added to the database if it was not known.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to look up.
+    pub fn query(&self, n: &Integer) -> Result<FactorDbEntry, FactorDbError> {
+        let body = self.request("GET", &format!("/api?query={n}"), None)?;
+        let answer: ApiAnswer = serde_json::from_slice(&body)?;
+        let factors = (answer.factors.into_iter())
+            .map(|(factor, multiplicity)| match factor.parse::<Integer>() {
+                Ok(factor) => Ok((factor, multiplicity)),
+                Err(_) => Err(FactorDbError::InvalidFactor(factor)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(FactorDbEntry {
+            id: match answer.id {
+                serde_json::Value::String(id) => id,
+                id => id.to_string(),
+            },
+            status: FactorDbStatus::from(answer.status.as_str()),
+            factors,
+        })
+    }
+
+    /// Reports factors of n to the database.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number factored.
+    /// - `factors`: Factors of n, not necessarily prime.
+    pub fn submit<'a>(
+        &self,
+        n: &Integer,
+        factors: impl IntoIterator<Item = &'a Integer>,
+    ) -> Result<(), FactorDbError> {
+        // One `n=factor` line per factor, form encoded
+        let report: Vec<String> = factors.into_iter().map(|f| format!("{n}%3D{f}")).collect();
+        let body = format!("report={}&format=7", report.join("%0A"));
+        self.request("POST", "/report.php", Some(&body))?;
+        Ok(())
+    }
+
+    /// Factors n with `options`, using the factors known by the database.
+    ///
+    /// The known factors are divided out before ECM, see [`EcmOptions::known_factors`],
+    /// and with [`FactorDb::report`], the prime factors found which were not known
+    /// are reported. A number fully factored in the database runs no curve.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `options`: Settings of the factorization.
+    pub fn factor(
+        &self,
+        n: &Integer,
+        options: &EcmOptions,
+    ) -> Result<Factorization, FactorDbError> {
+        let entry = self.query(n)?;
+        let known: Vec<Integer> = entry.proper_factors(n).cloned().collect();
+        let factors = options.clone().known_factors(known).run(n)?;
+
+        let prime = |factor: &Integer| factor.is_probably_prime(30) != IsPrime::No;
+        let new: Vec<&Integer> = (factors.iter())
+            .map(|(factor, _)| factor)
+            .filter(|factor| {
+                !entry
+                    .factors
+                    .iter()
+                    .any(|(known, _)| known == *factor && prime(known))
+            })
+            .collect();
+        if self.report && entry.status != FactorDbStatus::FullyFactored && !new.is_empty() {
+            self.submit(n, new)?;
+        }
+        Ok(factors)
+    }
+
+    /// Sends a request, returning the body of the response.
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<Vec<u8>, FactorDbError> {
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or(FactorDbError::InvalidResponse("unknown host"))?;
+        let mut stream = TcpStream::connect_timeout(&address, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nUser-Agent: ecm-rs/{}\r\nConnection: close\r\n",
+            self.host,
+            env!("CARGO_PKG_VERSION"),
+        );
+        if let Some(body) = body {
+            request += "Content-Type: application/x-www-form-urlencoded\r\n";
+            request += &format!("Content-Length: {}\r\n", body.len());
+        }
+        request += "\r\n";
+        request += body.unwrap_or_default();
+        stream.write_all(request.as_bytes())?;
+
+        let mut response = Vec::new();
+        let limit = self.max_response_size as u64;
+        stream
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut response)?;
+        if response.len() > self.max_response_size {
+            return Err(FactorDbError::TooLarge(self.max_response_size));
+        }
+        parse_response(&response)
+    }
+}
+
+/// Returns the body of an HTTP/1.1 response, failing on statuses other than 2xx.
+fn parse_response(response: &[u8]) -> Result<Vec<u8>, FactorDbError> {
+    let end = (response.windows(4))
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or(FactorDbError::InvalidResponse("no end of headers"))?;
+    let head = std::str::from_utf8(&response[..end])
+        .map_err(|_| FactorDbError::InvalidResponse("headers are not UTF-8"))?;
+    let body = &response[end + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = (lines.next())
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or(FactorDbError::InvalidResponse("no status"))?;
+    if !(200..300).contains(&status) {
+        return Err(FactorDbError::Http(status));
+    }
+
+    let chunked = lines
+        .filter_map(|line| line.split_once(':'))
+        .any(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding")
+                && value.trim().eq_ignore_ascii_case("chunked")
+        });
+    match chunked {
+        true => decode_chunked(body),
+        false => Ok(body.to_vec()),
+    }
+}
+
+/// Decodes a body sent with the chunked transfer encoding.
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>, FactorDbError> {
+    let invalid = || FactorDbError::InvalidResponse("invalid chunk");
+    let mut decoded = Vec::new();
+    loop {
+        let line = (body.windows(2))
+            .position(|w| w == b"\r\n")
+            .ok_or_else(invalid)?;
+        let size = std::str::from_utf8(&body[..line]).map_err(|_| invalid())?;
+        // Chunk extensions after a semicolon are ignored
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| invalid())?;
+        body = &body[line + 2..];
+        if size == 0 {
+            return Ok(decoded);
+        }
+        let chunk = body.get(..size).ok_or_else(invalid)?;
+        decoded.extend_from_slice(chunk);
+        body = body.get(size + 2..).ok_or_else(invalid)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    /// Serves `responses` in order, returning the requests received.
+    fn serve(responses: Vec<String>) -> (FactorDb, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                // The request ends with its headers, or with its body once announced
+                loop {
+                    let read = stream.read(&mut buffer).unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = (head.lines())
+                            .find_map(|line| line.strip_prefix("Content-Length: "))
+                            .map_or(0, |length| length.parse().unwrap());
+                        if body.len() >= length {
+                            break;
+                        }
+                    }
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+            }
+            requests
+        });
+        (FactorDb::with_host("127.0.0.1", port), server)
+    }
+
+    fn ok(body: &str) -> String {
+        format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{body}")
+    }
+
+    #[test]
+    fn query() {
+        let body = r#"{"id":"1100000000000000042","status":"CF","factors":[["3",2],["1000000000000000000000000000033",1]]}"#;
+        let (db, server) = serve(vec![
+            ok(body),
+            ok(r#"{"id":7,"status":"Unit","factors":[]}"#),
+        ]);
+
+        let n = Integer::from(9)
+            * Integer::from_str_radix("1000000000000000000000000000033", 10).unwrap();
+        let entry = db.query(&n).unwrap();
+        assert_eq!(entry.id, "1100000000000000042");
+        assert_eq!(entry.status, FactorDbStatus::CompositeFactorsKnown);
+        assert_eq!(entry.factors[0], (Integer::from(3), 2));
+        assert_eq!(entry.proper_factors(&n).count(), 2);
+
+        let entry = db.query(&Integer::from(1)).unwrap();
+        assert_eq!(entry.id, "7");
+        assert_eq!(entry.status, FactorDbStatus::Other("Unit".to_string()));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with(&format!("GET /api?query={n} HTTP/1.1\r\n")));
+        assert!(requests[1].starts_with("GET /api?query=1 HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn factor_and_report() {
+        // The database knows the 30-digit factor, out of reach of ECM in a test
+        let p = Integer::from_str_radix("100000000000000000000000000319", 10).unwrap();
+        let n = Integer::from(&p * 4009823) * 99476569;
+        let body =
+            format!(r#"{{"id":"1","status":"CF","factors":[["{p}",1],["398883434337287",1]]}}"#);
+        let (db, server) = serve(vec![ok(&body), ok("Found 2 factors")]);
+
+        let options = EcmOptions::new().seed(1);
+        let factors = db.report(true).factor(&n, &options).unwrap();
+        assert!(factors.is_complete());
+        assert_eq!(factors.len(), 3);
+
+        // Only the new prime factors are reported
+        let requests = server.join().unwrap();
+        assert!(requests[1].starts_with("POST /report.php HTTP/1.1\r\n"));
+        assert!(requests[1].ends_with(&format!(
+            "\r\n\r\nreport={n}%3D4009823%0A{n}%3D99476569&format=7"
+        )));
+    }
+
+    #[test]
+    fn errors() {
+        let (db, server) = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\n\r\n".to_string(),
+            ok("<html>"),
+            ok(r#"{"id":"1","status":"CF","factors":[["abc",1]]}"#),
+        ]);
+        let n = Integer::from(15);
+        assert!(matches!(db.query(&n), Err(FactorDbError::Http(503))));
+        assert!(matches!(db.query(&n), Err(FactorDbError::Json(_))));
+        assert!(matches!(db.query(&n), Err(FactorDbError::InvalidFactor(f)) if f == "abc"));
+        server.join().unwrap();
+
+        let (db, server) = serve(vec![ok(&"1".repeat(1_000))]);
+        let db = db.max_response_size(1_000);
+        assert!(matches!(db.query(&n), Err(FactorDbError::TooLarge(1_000))));
+        server.join().unwrap();
+
+        // Nothing listening anymore
+        assert!(matches!(db.query(&n), Err(FactorDbError::Io(_))));
+    }
+
+    #[test]
+    fn responses() {
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"a\"\r\n5;ext=1\r\n:1}\r\n\r\n0\r\n\r\n";
+        assert_eq!(parse_response(chunked).unwrap(), b"{\"a\":1}\r\n");
+        assert_eq!(
+            parse_response(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap(),
+            b""
+        );
+        for invalid in [
+            &b"HTTP/1.1 200 OK\r\n"[..],
+            b"garbage\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nshort\r\n",
+        ] {
+            assert!(matches!(
+                parse_response(invalid),
+                Err(FactorDbError::InvalidResponse(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,596 @@
+use rug::{integer::IsPrime, Integer};
+use std::{
+    collections::{btree_map, BTreeMap, HashMap},
+    fmt,
+    iter::FusedIterator,
+};
+
+/// Factors of a number with their multiplicity.
+///
+/// Factors are always iterated as `(factor, multiplicity)` pairs in ascending order
+/// of the factors, each factor once, whatever the order they were found or added in,
+/// so the traversal of equal factorizations is deterministic.
+/// When ECM fails to split a composite, the factorization is partial:
+/// the composite is kept apart as a cofactor, see [`Factorization::cofactors`].
+///
+/// ```
+/// use ecm::Factorization;
+/// use rug::Integer;
+///
+/// let factorization = Factorization::from([(7.into(), 1), (2.into(), 3), (7.into(), 1)]);
+/// let factors: Vec<(Integer, usize)> = factorization.into_iter().collect();
+/// assert_eq!(factors, [(2.into(), 3), (7.into(), 2)]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Factorization {
+    factors: BTreeMap<Integer, usize>,
+    /// Composites which could not be split.
+    cofactors: BTreeMap<Integer, usize>,
+}
+
+impl Factorization {
+    /// Creates an empty factorization, whose product is 1.
+    pub fn new() -> Factorization {
+        Factorization::default()
+    }
+
+    /// Adds `multiplicity` times the factor `factor`.
+    ///
+    /// # Parameters
+    ///
+    /// - `factor`: Factor to add.
+    /// - `multiplicity`: Number of times the factor divides the number.
+    pub fn add(&mut self, factor: Integer, multiplicity: usize) {
+        if multiplicity > 0 {
+            *self.factors.entry(factor).or_insert(0) += multiplicity;
+        }
+    }
+
+    /// Adds `multiplicity` times the composite `cofactor`, which could not be split.
+    ///
+    /// # Parameters
+    ///
+    /// - `cofactor`: Composite cofactor to add.
+    /// - `multiplicity`: Number of times the cofactor divides the number.
+    pub fn add_cofactor(&mut self, cofactor: Integer, multiplicity: usize) {
+        if multiplicity > 0 {
+            *self.cofactors.entry(cofactor).or_insert(0) += multiplicity;
+        }
+    }
+
+    /// Splits the cofactors sharing a factor with `factor`, e.g. found by another machine.
+    ///
+    /// Parts of the cofactors are added as factors when they are probable primes
+    /// (30 Miller-Rabin rounds), as cofactors otherwise. Returns true if a cofactor was split.
+    ///
+    /// ```
+    /// use ecm::Factorization;
+    /// use rug::Integer;
+    ///
+    /// let mut factorization = Factorization::from([(2.into(), 1)]);
+    /// factorization.add_cofactor(Integer::from(398883434337287u64), 1);
+    /// assert!(factorization.merge_factor(&Integer::from(4009823)));
+    /// assert!(factorization.is_complete());
+    /// assert_eq!(factorization.to_string(), "2 * 4009823 * 99476569");
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `factor`: Factor of the number, not necessarily prime.
+    pub fn merge_factor(&mut self, factor: &Integer) -> bool {
+        let split: Vec<_> = (self.cofactors.iter())
+            .filter(|(cofactor, _)| {
+                let g = Integer::from(cofactor.gcd_ref(factor));
+                g != 1 && &g != *cofactor
+            })
+            .map(|(cofactor, multiplicity)| (cofactor.clone(), *multiplicity))
+            .collect();
+
+        for (cofactor, multiplicity) in &split {
+            self.cofactors.remove(cofactor);
+            let mut parts = vec![cofactor.clone()];
+            while let Some(part) = parts.pop() {
+                let g = Integer::from(part.gcd_ref(factor));
+                if g != 1 && g != part {
+                    parts.push(Integer::from(&part / &g));
+                    parts.push(g);
+                } else if part.is_probably_prime(30) != IsPrime::No {
+                    self.add(part, *multiplicity);
+                } else {
+                    self.add_cofactor(part, *multiplicity);
+                }
+            }
+        }
+        !split.is_empty()
+    }
+
+    /// Iterates over `(cofactor, multiplicity)` for the composites which could not be split,
+    /// sorted by cofactor.
+    pub fn cofactors(&self) -> impl Iterator<Item = (&Integer, usize)> {
+        self.cofactors
+            .iter()
+            .map(|(cofactor, multiplicity)| (cofactor, *multiplicity))
+    }
+
+    /// Multiplicity of `factor`, 0 if it is not a factor.
+    pub fn multiplicity(&self, factor: &Integer) -> usize {
+        self.factors.get(factor).copied().unwrap_or(0)
+    }
+
+    /// Iterates over `(factor, multiplicity)`, sorted by factor, cofactors excluded.
+    pub fn iter(&self) -> Factors<'_> {
+        Factors(self.factors.iter())
+    }
+
+    /// Number of distinct factors, cofactors excluded.
+    pub fn len(&self) -> usize {
+        self.factors.len()
+    }
+
+    /// Returns true if there is no factor and no cofactor, i.e. if the product is 1.
+    pub fn is_empty(&self) -> bool {
+        self.factors.is_empty() && self.cofactors.is_empty()
+    }
+
+    /// Product of the factors and cofactors raised to their multiplicity.
+    pub fn product(&self) -> Integer {
+        Integer::product(
+            self.factors
+                .iter()
+                .chain(&self.cofactors)
+                .flat_map(|(factor, multiplicity)| std::iter::repeat_n(factor, *multiplicity)),
+        )
+        .into()
+    }
+
+    /// Returns true if there is no cofactor and all factors are prime.
+    ///
+    /// Factors are tested with 30 Miller-Rabin rounds.
+    pub fn is_complete(&self) -> bool {
+        self.cofactors.is_empty()
+            && self
+                .factors
+                .keys()
+                .all(|factor| factor.is_probably_prime(30) != IsPrime::No)
+    }
+
+    /// Exponents of the factorization over a factor base.
+    ///
+    /// Returns None if a factor is not in the base, i.e. if the number is not smooth over it,
+    /// or if there is a cofactor.
+    ///
+    /// ```
+    /// use ecm::Factorization;
+    /// use rug::Integer;
+    ///
+    /// let factorization = Factorization::from([(2.into(), 3), (7.into(), 1)]);
+    /// let base: Vec<Integer> = [2, 3, 5, 7].map(Integer::from).to_vec();
+    /// assert_eq!(factorization.exponent_vector(&base), Some(vec![3, 0, 0, 1]));
+    /// assert_eq!(factorization.exponent_vector(&base[..2]), None);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `base`: Factor base.
+    pub fn exponent_vector(&self, base: &[Integer]) -> Option<Vec<usize>> {
+        if !self.cofactors.is_empty() || self.factors.keys().any(|factor| !base.contains(factor)) {
+            return None;
+        }
+        Some(base.iter().map(|p| self.multiplicity(p)).collect())
+    }
+
+    /// Formats the factorization like [`Display`](fmt::Display), with factors in base `radix`.
+    ///
+    /// Exponents are written in decimal, cofactors are written after the factors.
+    ///
+    /// ```
+    /// use ecm::Factorization;
+    ///
+    /// let factorization = Factorization::from([(2.into(), 3), (255.into(), 1)]);
+    /// assert_eq!(factorization.to_string_radix(16), "2^3 * ff");
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `radix`: Base of the factors, in `2..=36`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `radix` is not in `2..=36`.
+    pub fn to_string_radix(&self, radix: i32) -> String {
+        if self.is_empty() {
+            return "1".to_string();
+        }
+        self.iter()
+            .chain(self.cofactors())
+            .map(|(factor, multiplicity)| match multiplicity {
+                1 => factor.to_string_radix(radix),
+                _ => format!("{}^{multiplicity}", factor.to_string_radix(radix)),
+            })
+            .collect::<Vec<_>>()
+            .join(" * ")
+    }
+
+    /// SageMath code checking the product and the primality of the factors.
+    ///
+    /// Cofactors are listed in `C` and only counted in the product.
+    ///
+    /// ```
+    /// use ecm::Factorization;
+    ///
+    /// let factorization = Factorization::from([(3.into(), 2), (7.into(), 1)]);
+    /// assert_eq!(
+    ///     factorization.to_sage(),
+    ///     "n = 63\n\
+    ///      F = [(3, 2), (7, 1)]\n\
+    ///      assert prod(p^e for p, e in F) == n\n\
+    ///      assert all(is_prime(p) for p, _ in F)\n"
+    /// );
+    /// ```
+    pub fn to_sage(&self) -> String {
+        let factors = self
+            .iter()
+            .map(|(factor, multiplicity)| format!("({factor}, {multiplicity})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (cofactors, product) = if self.cofactors.is_empty() {
+            (String::new(), "F")
+        } else {
+            let cofactors = self
+                .cofactors()
+                .map(|(cofactor, multiplicity)| format!("({cofactor}, {multiplicity})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            (format!("C = [{cofactors}]\n"), "F + C")
+        };
+        format!(
+            "n = {}\n\
+             F = [{factors}]\n\
+             {cofactors}\
+             assert prod(p^e for p, e in {product}) == n\n\
+             assert all(is_prime(p) for p, _ in F)\n",
+            self.product()
+        )
+    }
+
+    /// Pari/GP code checking the product and the primality of the factors.
+    ///
+    /// Cofactors are listed in `C` and only counted in the product.
+    ///
+    /// ```
+    /// use ecm::Factorization;
+    ///
+    /// let factorization = Factorization::from([(3.into(), 2), (7.into(), 1)]);
+    /// assert_eq!(
+    ///     factorization.to_pari(),
+    ///     "n = 63;\n\
+    ///      F = [3, 2; 7, 1];\n\
+    ///      if(factorback(F) != n, error(\"wrong product\"));\n\
+    ///      for(i = 1, #F~, if(!isprime(F[i, 1]), error(\"composite factor \", F[i, 1])));\n"
+    /// );
+    /// ```
+    pub fn to_pari(&self) -> String {
+        let matrix = |rows: Vec<String>| match rows.is_empty() {
+            true => "matrix(0, 2)".to_string(),
+            false => format!("[{}]", rows.join("; ")),
+        };
+        let factors = matrix(
+            self.iter()
+                .map(|(factor, multiplicity)| format!("{factor}, {multiplicity}"))
+                .collect(),
+        );
+        let (cofactors, product) = if self.cofactors.is_empty() {
+            (String::new(), "factorback(F)")
+        } else {
+            let rows = self
+                .cofactors()
+                .map(|(cofactor, multiplicity)| format!("{cofactor}, {multiplicity}"))
+                .collect();
+            (
+                format!("C = {};\n", matrix(rows)),
+                "factorback(F) * factorback(C)",
+            )
+        };
+        format!(
+            "n = {};\n\
+             F = {factors};\n\
+             {cofactors}\
+             if({product} != n, error(\"wrong product\"));\n\
+             for(i = 1, #F~, if(!isprime(F[i, 1]), error(\"composite factor \", F[i, 1])));\n",
+            self.product()
+        )
+    }
+}
+
+/// Formats the factorization as `2^3 * 7`, or `1` if there is no factor.
+impl fmt::Display for Factorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_radix(10))
+    }
+}
+
+impl From<HashMap<Integer, usize>> for Factorization {
+    fn from(factors: HashMap<Integer, usize>) -> Self {
+        factors.into_iter().collect()
+    }
+}
+
+impl<const N: usize> From<[(Integer, usize); N]> for Factorization {
+    fn from(factors: [(Integer, usize); N]) -> Self {
+        factors.into_iter().collect()
+    }
+}
+
+impl FromIterator<(Integer, usize)> for Factorization {
+    fn from_iter<I: IntoIterator<Item = (Integer, usize)>>(iter: I) -> Self {
+        let mut factorization = Factorization::new();
+        for (factor, multiplicity) in iter {
+            factorization.add(factor, multiplicity);
+        }
+        factorization
+    }
+}
+
+/// Iterator over the `(factor, multiplicity)` pairs of a [`Factorization`], by ascending factor.
+///
+/// Created by [`Factorization::iter`].
+#[derive(Debug, Clone)]
+pub struct Factors<'a>(btree_map::Iter<'a, Integer, usize>);
+
+impl<'a> Iterator for Factors<'a> {
+    type Item = (&'a Integer, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next()
+            .map(|(factor, multiplicity)| (factor, *multiplicity))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Factors<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0
+            .next_back()
+            .map(|(factor, multiplicity)| (factor, *multiplicity))
+    }
+}
+
+impl ExactSizeIterator for Factors<'_> {}
+
+impl FusedIterator for Factors<'_> {}
+
+/// Owning iterator over the `(factor, multiplicity)` pairs of a [`Factorization`],
+/// by ascending factor. Cofactors are dropped.
+///
+/// Created by [`Factorization::into_iter`].
+#[derive(Debug)]
+pub struct IntoFactors(btree_map::IntoIter<Integer, usize>);
+
+impl Iterator for IntoFactors {
+    type Item = (Integer, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for IntoFactors {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+impl ExactSizeIterator for IntoFactors {}
+
+impl FusedIterator for IntoFactors {}
+
+impl IntoIterator for Factorization {
+    type Item = (Integer, usize);
+    type IntoIter = IntoFactors;
+
+    fn into_iter(self) -> IntoFactors {
+        IntoFactors(self.factors.into_iter())
+    }
+}
+
+impl<'a> IntoIterator for &'a Factorization {
+    type Item = (&'a Integer, usize);
+    type IntoIter = Factors<'a>;
+
+    fn into_iter(self) -> Factors<'a> {
+        self.iter()
+    }
+}
+
+/// Cofactors are merged with the factors.
+impl From<Factorization> for Vec<(Integer, u32)> {
+    fn from(factorization: Factorization) -> Self {
+        let mut factors: Vec<_> = factorization
+            .factors
+            .into_iter()
+            .chain(factorization.cofactors)
+            .map(|(factor, multiplicity)| (factor, multiplicity as u32))
+            .collect();
+        factors.sort();
+        factors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn merge_factor() {
+        let p = Integer::from(4009823);
+        let q = Integer::from(99476569);
+        let r = Integer::from(1_000_003);
+        let mut factorization = Factorization::new();
+        factorization.add_cofactor(Integer::from(&p * &p) * &q * &r, 2);
+        factorization.add_cofactor(Integer::from(&q * &r), 1);
+
+        // Not a factor, or the whole cofactor
+        assert!(!factorization.merge_factor(&Integer::from(7)));
+        assert!(!factorization.merge_factor(&(Integer::from(&p * &p) * &q * &r)));
+        assert_eq!(factorization.cofactors().count(), 2);
+
+        // Composite factor sharing p with the first cofactor, the parts left stay cofactors
+        assert!(factorization.merge_factor(&(Integer::from(&p * 7))));
+        assert_eq!(factorization.multiplicity(&p), 4);
+        let cofactors: Vec<_> = factorization.cofactors().collect();
+        assert_eq!(cofactors, [(&Integer::from(&q * &r), 3)]);
+
+        assert!(factorization.merge_factor(&r));
+        assert!(factorization.is_complete());
+        assert_eq!(factorization.multiplicity(&q), 3);
+        assert_eq!(factorization.multiplicity(&r), 3);
+    }
+
+    #[test]
+    fn sorted_factors() {
+        let factorization = Factorization::from([
+            (Integer::from(11011069), 1),
+            (Integer::from(3), 2),
+            (Integer::from(113), 1),
+            (Integer::from(3), 1),
+        ]);
+
+        assert_eq!(factorization.len(), 3);
+        assert_eq!(factorization.multiplicity(&Integer::from(3)), 3);
+        assert_eq!(factorization.multiplicity(&Integer::from(5)), 0);
+        assert_eq!(
+            Vec::from(factorization),
+            vec![
+                (Integer::from(3), 3),
+                (Integer::from(113), 1),
+                (Integer::from(11011069), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn ordered_iteration() {
+        let factors = [(113, 1), (3, 2), (11011069, 1), (2, 5)].map(|(f, m)| (Integer::from(f), m));
+        let mut reversed = factors.clone();
+        reversed.reverse();
+        let factorization = Factorization::from(factors);
+        assert_eq!(factorization, Factorization::from(reversed));
+
+        let mut iter = factorization.iter();
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.next(), Some((&Integer::from(2), 5)));
+        assert_eq!(iter.next_back(), Some((&Integer::from(11011069), 1)));
+        assert_eq!(iter.len(), 2);
+
+        let borrowed: Vec<_> = (&factorization)
+            .into_iter()
+            .map(|(f, m)| (f.clone(), m))
+            .collect();
+        let owned: Vec<_> = factorization.into_iter().collect();
+        assert_eq!(borrowed, owned);
+        assert_eq!(
+            owned,
+            [(2, 5), (3, 2), (113, 1), (11011069, 1)].map(|(f, m)| (Integer::from(f), m))
+        );
+    }
+
+    #[test]
+    fn product() {
+        assert_eq!(Factorization::new().product(), 1);
+        let factorization = Factorization::from([
+            (Integer::from(3), 2),
+            (Integer::from_str("100327907731").unwrap(), 1),
+        ]);
+        assert_eq!(factorization.product(), Integer::from(9 * 100327907731u64));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Factorization::new().to_string(), "1");
+        let factorization = Factorization::from([
+            (Integer::from(3), 2),
+            (Integer::from_str("100327907731").unwrap(), 1),
+        ]);
+        assert_eq!(factorization.to_string(), "3^2 * 100327907731");
+        assert_eq!(
+            factorization.to_string_radix(2),
+            "11^2 * 1011101011100000000100110000110010011"
+        );
+        assert_eq!(factorization.to_string_radix(36), "3^2 * 1a38n91v");
+    }
+
+    #[test]
+    fn exponent_vectors() {
+        let factorization = Factorization::from([(Integer::from(3), 2), (Integer::from(11), 1)]);
+        let base = [2, 3, 5, 7, 11].map(Integer::from);
+        assert_eq!(
+            factorization.exponent_vector(&base),
+            Some(vec![0, 2, 0, 0, 1])
+        );
+        assert_eq!(factorization.exponent_vector(&base[..4]), None);
+        assert_eq!(
+            Factorization::new().exponent_vector(&base[..1]),
+            Some(vec![0])
+        );
+    }
+
+    #[test]
+    fn snippets() {
+        let factorization = Factorization::new();
+        assert!(factorization.to_sage().contains("F = []\n"));
+        assert!(factorization.to_pari().contains("F = matrix(0, 2);\n"));
+
+        let factorization = Factorization::from([(Integer::from(3233), 1)]);
+        assert!(factorization
+            .to_sage()
+            .starts_with("n = 3233\nF = [(3233, 1)]\n"));
+        assert!(factorization
+            .to_pari()
+            .starts_with("n = 3233;\nF = [3233, 1];\n"));
+    }
+
+    #[test]
+    fn complete() {
+        assert!(Factorization::new().is_complete());
+        assert!(Factorization::from([(Integer::from(2802377), 2)]).is_complete());
+        assert!(!Factorization::from([(Integer::from(3233), 1)]).is_complete());
+    }
+
+    #[test]
+    fn partial() {
+        let mut factorization = Factorization::from([(Integer::from(3), 2)]);
+        factorization.add_cofactor(Integer::from(3233), 1);
+        factorization.add_cofactor(Integer::from(0), 0);
+
+        assert!(!factorization.is_complete());
+        assert!(!factorization.is_empty());
+        assert_eq!(factorization.len(), 1);
+        assert_eq!(factorization.multiplicity(&Integer::from(3233)), 0);
+        assert_eq!(
+            factorization.cofactors().collect::<Vec<_>>(),
+            [(&Integer::from(3233), 1)]
+        );
+        assert_eq!(factorization.product(), 9 * 3233);
+        assert_eq!(factorization.to_string(), "3^2 * 3233");
+        assert_eq!(factorization.exponent_vector(&[Integer::from(3)]), None);
+        assert!(factorization
+            .to_sage()
+            .contains("C = [(3233, 1)]\nassert prod(p^e for p, e in F + C) == n\n"));
+        assert!(factorization
+            .to_pari()
+            .contains("C = [3233, 1];\nif(factorback(F) * factorback(C) != n"));
+        assert_eq!(
+            Vec::from(factorization),
+            [(Integer::from(3), 2), (Integer::from(3233), 1)]
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    parse_integer, progress::decimal_digits, BoundPreset, EcmOptions, Error, Factorization,
+    DEFAULT_SIQS_DIGITS,
+};
+use rug::Integer;
+
+/// Composites of up to this many digits skip the curves in [`factorize`], the sieve splits them faster.
+const SIQS_ONLY_DIGITS: usize = 45;
+
+/// Settings of [`factorize`] up to a number of decimal digits of n:
+/// stage 1 bound of p-1 and p+1, and first level of the curves.
+const AUTO_PARAMS: [(usize, u64, BoundPreset); 3] = [
+    (SIQS_ONLY_DIGITS, 10_000, BoundPreset::Digits15),
+    (DEFAULT_SIQS_DIGITS, 100_000, BoundPreset::Digits15),
+    (usize::MAX, 1_000_000, BoundPreset::Digits20),
+];
+
+/// Factors n, choosing the methods and their parameters from its size.
+///
+/// Numbers fitting in 32 bits are factored by trial division. Larger ones go
+/// through trial division and perfect powers, then each composite left goes
+/// through rho, p-1 and p+1, with bounds growing with n, and the curves,
+/// whose bounds are raised from t15 or t20 through all the levels as long as
+/// they fail. Composites of up to [`DEFAULT_SIQS_DIGITS`] digits are given
+/// to [`siqs`](crate::siqs) once the curves would take longer, right away up
+/// to 45 digits.
+///
+/// The factorization is complete, unless n has two factors too large for
+/// the curves and more than [`DEFAULT_SIQS_DIGITS`] digits, which could take
+/// years. See [`factorize_options`] to add threads or a time limit. The sign
+/// of n is ignored, 0 fails with [`Error::ZeroInput`].
+///
+/// ```
+/// use ecm::factorize;
+/// use rug::Integer;
+///
+/// let p = Integer::from_str_radix("1000000000000000000117", 10).unwrap();
+/// let q = Integer::from_str_radix("3000000000000000000053", 10).unwrap();
+/// let factors = factorize(&Integer::from(&p * &q)).unwrap();
+/// assert_eq!(factors.multiplicity(&p), 1);
+/// assert_eq!(factors.multiplicity(&q), 1);
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+pub fn factorize(n: &Integer) -> Result<Factorization, Error> {
+    match n.to_u32() {
+        Some(0) => Err(Error::ZeroInput),
+        Some(n) => Ok(trial_division(n)),
+        None => factorize_options(n).run(n),
+    }
+}
+
+/// Settings run by [`factorize`] on n, to be completed with other options.
+///
+/// ```
+/// use ecm::factorize_options;
+/// use rug::Integer;
+/// use std::time::Duration;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let options = factorize_options(&n).max_duration(Duration::from_secs(60));
+/// assert!(options.run(&n).unwrap().is_complete());
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+pub fn factorize_options(n: &Integer) -> EcmOptions {
+    let digits = decimal_digits(n);
+    let (_, b1, first) = AUTO_PARAMS[AUTO_PARAMS.partition_point(|&(max, ..)| max < digits)];
+    let options = EcmOptions::new()
+        .pm1_bounds(b1, 100 * b1)
+        .pp1_bounds(b1, 100 * b1)
+        .preset(first)
+        .siqs_digits(DEFAULT_SIQS_DIGITS);
+    match digits <= SIQS_ONLY_DIGITS {
+        true => options.max_curves(0).max_escalations(0),
+        false => options.max_escalations(BoundPreset::ALL.len() as u32),
+    }
+}
+
+/// Factorization of integers without handling rug types at the call site.
+///
+/// Numbers fitting in 32 bits are factored by trial division,
+/// bigger numbers with [`factorize`]. 0 fails with [`Error::ZeroInput`].
+///
+/// ```
+/// use ecm::Factorize;
+///
+/// let factors = 398883434337287u64.factorize().unwrap();
+/// assert_eq!(factors.len(), 2);
+/// assert_eq!(factors.product(), 398883434337287u64);
+///
+/// let factors = "2^64 + 1".factorize().unwrap();
+/// assert_eq!(factors.len(), 2);
+/// ```
+pub trait Factorize {
+    /// Factors the number.
+    fn factorize(&self) -> Result<Factorization, Error>;
+}
+
+impl Factorize for u32 {
+    fn factorize(&self) -> Result<Factorization, Error> {
+        match *self {
+            0 => Err(Error::ZeroInput),
+            n => Ok(trial_division(n)),
+        }
+    }
+}
+
+impl Factorize for u64 {
+    fn factorize(&self) -> Result<Factorization, Error> {
+        match u32::try_from(*self) {
+            Ok(n) => n.factorize(),
+            Err(_) => Integer::from(*self).factorize(),
+        }
+    }
+}
+
+impl Factorize for u128 {
+    fn factorize(&self) -> Result<Factorization, Error> {
+        match u32::try_from(*self) {
+            Ok(n) => n.factorize(),
+            Err(_) => Integer::from(*self).factorize(),
+        }
+    }
+}
+
+impl Factorize for Integer {
+    fn factorize(&self) -> Result<Factorization, Error> {
+        match self.to_u32() {
+            Some(n) => n.factorize(),
+            None => factorize(self),
+        }
+    }
+}
+
+impl Factorize for &str {
+    /// Parses the number with [`parse_integer`], then factors it.
+    fn factorize(&self) -> Result<Factorization, Error> {
+        parse_integer(self)?.factorize()
+    }
+}
+
+/// Factors n by trial division.
+fn trial_division(mut n: u32) -> Factorization {
+    let mut factorization = Factorization::new();
+    let mut p = 2;
+    while n > 1 && p <= n / p {
+        let mut multiplicity = 0;
+        while n.is_multiple_of(p) {
+            n /= p;
+            multiplicity += 1;
+        }
+        factorization.add(p.into(), multiplicity);
+        p += if p == 2 { 1 } else { 2 };
+    }
+    if n > 1 {
+        factorization.add(n.into(), 1);
+    }
+    factorization
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorKind, FactorMethod, StepOutcome};
+    use std::{str::FromStr, time::Duration};
+
+    #[test]
+    fn primitives() {
+        assert_eq!(
+            360u32.factorize().unwrap(),
+            Factorization::from([(2.into(), 3), (3.into(), 2), (5.into(), 1)])
+        );
+        assert_eq!(
+            u32::MAX.factorize().unwrap(),
+            Factorization::from([
+                (3.into(), 1),
+                (5.into(), 1),
+                (17.into(), 1),
+                (257.into(), 1),
+                (65537.into(), 1),
+            ])
+        );
+        assert_eq!(
+            4294967291u64.factorize().unwrap(),
+            Factorization::from([(4294967291u32.into(), 1)])
+        );
+        assert_eq!(
+            398883434337287u128.factorize().unwrap(),
+            Factorization::from([(4009823.into(), 1), (99476569.into(), 1)])
+        );
+        assert!(1u32.factorize().unwrap().is_empty());
+        assert!(matches!(0u32.factorize(), Err(Error::ZeroInput)));
+        assert!(matches!(0u128.factorize(), Err(Error::ZeroInput)));
+        assert!(matches!(Integer::ZERO.factorize(), Err(Error::ZeroInput)));
+        assert!(matches!("0".factorize(), Err(Error::ZeroInput)));
+    }
+
+    #[test]
+    fn sizes() {
+        let run = |n: &Integer, p: &Integer| {
+            let mut factorizer = factorize_options(n).seed(1).factorizer(n).unwrap();
+            while factorizer.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+            let curves: u64 = factorizer.resume_state().curves_by_b1.values().sum();
+            let method = factorizer.stats().factors.get(p).copied();
+            (factorizer.into_factors().is_complete(), curves, method)
+        };
+
+        // Two factors of 22 digits, sieved without curves
+        let p = Integer::from_str("1000000000000000000117").unwrap();
+        let q = Integer::from_str("3000000000000000000053").unwrap();
+        assert_eq!(
+            run(&Integer::from(&p * &q), &p),
+            (true, 0, Some(FactorMethod::Siqs))
+        );
+
+        // p + 1 = 2^4 * 41 * 53 * 151 * 167 * 2339 * 487637, found before the curves
+        let p = Integer::from_str("1000000000000002607").unwrap();
+        let q = Integer::from_str("100000000000000000000000000319").unwrap();
+        assert_eq!(
+            run(&Integer::from(&p * &q), &p),
+            (true, 0, Some(FactorMethod::PPlusOne { seed: 3 }))
+        );
+
+        assert_eq!(
+            factorize(&Integer::from(360)).unwrap(),
+            Factorization::from([(2.into(), 3), (3.into(), 2), (5.into(), 1)])
+        );
+        assert_eq!(
+            factorize(&Integer::from(-360)).unwrap(),
+            factorize(&Integer::from(360)).unwrap()
+        );
+        assert!(matches!(factorize(&Integer::ZERO), Err(Error::ZeroInput)));
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            "0x10001 * 641".factorize().unwrap(),
+            Factorization::from([(641.into(), 1), (65537.into(), 1)])
+        );
+        assert_eq!(
+            "12a".factorize().unwrap_err().kind(),
+            ErrorKind::InvalidNumber
+        );
+    }
+}
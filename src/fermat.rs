@@ -0,0 +1,88 @@
+use rug::Integer;
+
+/// Steps of Fermat's method run on large composites before the curves by default.
+pub const DEFAULT_FERMAT_STEPS: u64 = 1 << 16;
+
+/// Returns a proper factor of n with Fermat's method, if n has two close factors.
+///
+/// Looks for `n = a^2 - b^2 = (a - b)(a + b)` with `a` starting at `ceil(sqrt(n))`:
+/// it takes one step, an addition and a square test, per value of `a`, and
+/// finds `p <= q` with `q - p` up to about `sqrt(8 * max_steps) * n^(1/4)`,
+/// e.g. the factors of RSA keys generated from a single random starting point,
+/// whatever their size.
+///
+/// Returns `None` if n is less than 4, or if no factor was found,
+/// e.g. if n is prime or if its factors are too far apart.
+///
+/// ```
+/// use ecm::fermat;
+/// use rug::Integer;
+///
+/// let p: Integer = "100000000000000000000000000319".parse().unwrap();
+/// let q: Integer = "100000000000000000000000000379".parse().unwrap();
+/// assert_eq!(fermat(&Integer::from(&p * &q), 1), Some(p));
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `max_steps`: Maximum number of values of `a` tried.
+pub fn fermat(n: &Integer, max_steps: u64) -> Option<Integer> {
+    if *n < 4 {
+        return None;
+    }
+    if n.is_even() {
+        return Some(Integer::from(2));
+    }
+
+    let (mut a, rem) = n.sqrt_rem_ref().into();
+    if rem.is_zero() {
+        return Some(a);
+    }
+    a += 1;
+    // b2 = a^2 - n, increased by 2a + 1 when a is incremented
+    let mut b2 = Integer::from(a.square_ref()) - n;
+    for _ in 0..max_steps {
+        if b2.is_perfect_square() {
+            let g = a - b2.sqrt();
+            return (g != 1).then_some(g);
+        }
+        b2 += &a;
+        b2 += &a;
+        b2 += 1;
+        a += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn close_factors() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from(p.next_prime_ref()).next_prime();
+        assert_eq!(fermat(&Integer::from(&p * &q), 1), Some(p.clone()));
+
+        // q - p = 2 * 10^16, a = (p + q) / 2 is about 5 * 10^2 steps above sqrt(n)
+        let q = Integer::from(&p + 20_000_000_000_000_000u64).next_prime();
+        let n = Integer::from(&p * &q);
+        assert_eq!(fermat(&n, 1 << 10), Some(p.clone()));
+        assert_eq!(fermat(&n, 10), None);
+
+        assert_eq!(fermat(&Integer::from(&p * &p), 0), Some(p));
+        assert_eq!(fermat(&Integer::from(1 << 20), 0), Some(2.into()));
+    }
+
+    #[test]
+    fn no_factor() {
+        for n in [0, 1, 3, 1_000_003] {
+            assert_eq!(fermat(&Integer::from(n), 1 << 10), None);
+        }
+        // 3 * 1000003, a - b = 3 but after about 250000 steps
+        assert_eq!(fermat(&Integer::from(3_000_009), 1 << 10), None);
+        assert_eq!(fermat(&Integer::from(15), 1 << 10), Some(3.into()));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{CancelToken, EcmOptions, Error, Factorization};
+use rug::Integer;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+/// Factors n with `options` on a background thread, without blocking the executor.
+///
+/// The future works with any executor, it only relies on the standard library.
+/// Dropping it before completion cancels the factorization, see [`EcmFuture`].
+///
+/// ```
+/// # fn block_on<F: std::future::Future>(future: F) -> F::Output {
+/// #     use std::{sync::Arc, task::{Context, Poll, Wake}, thread::{self, Thread}};
+/// #     struct Unpark(Thread);
+/// #     impl Wake for Unpark {
+/// #         fn wake(self: Arc<Self>) { self.0.unpark() }
+/// #     }
+/// #     let waker = Arc::new(Unpark(thread::current())).into();
+/// #     let mut future = std::pin::pin!(future);
+/// #     loop {
+/// #         match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+/// #             Poll::Ready(output) => return output,
+/// #             Poll::Pending => thread::park(),
+/// #         }
+/// #     }
+/// # }
+/// use ecm::{ecm_async, EcmOptions};
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let factors = block_on(ecm_async(&n, &EcmOptions::new()));
+/// assert_eq!(factors.unwrap().product(), n);
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `options`: Settings of the factorization, its cancel token is replaced by the one of the future.
+pub fn ecm_async(n: &Integer, options: &EcmOptions) -> EcmFuture {
+    let cancel = CancelToken::new();
+    let options = options.clone().cancel_token(cancel.clone());
+    let shared = Arc::new(Mutex::new(Shared::default()));
+
+    let n = n.clone();
+    let state = Arc::clone(&shared);
+    thread::spawn(move || {
+        // Panics are raised again by the future, instead of never resolving
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| options.run(&n)));
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+
+    EcmFuture { shared, cancel }
+}
+
+#[derive(Default)]
+struct Shared {
+    result: Option<thread::Result<Result<Factorization, Error>>>,
+    /// Waker of the last poll.
+    waker: Option<Waker>,
+}
+
+/// Factorization running on a background thread, see [`ecm_async`].
+///
+/// Resolves to the result of [`EcmOptions::run`]. Dropping the future
+/// cancels the factorization, the thread then stops after the current curve.
+pub struct EcmFuture {
+    shared: Arc<Mutex<Shared>>,
+    cancel: CancelToken,
+}
+
+impl EcmFuture {
+    /// Token of the factorization, to cancel it without dropping the future.
+    ///
+    /// The future then resolves to [`Error::Cancelled`].
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+}
+
+impl Future for EcmFuture {
+    type Output = Result<Factorization, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(payload)) => std::panic::resume_unwind(payload),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for EcmFuture {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        task::Wake,
+        thread::Thread,
+        time::{Duration, Instant},
+    };
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn resolves() {
+        let n = Integer::from(1_000_003) * Integer::from(999_983) * 6;
+        let factors = block_on(ecm_async(&n, &EcmOptions::new().seed(1))).unwrap();
+        assert!(factors.is_complete());
+        assert_eq!(factors.product(), n);
+        assert!(matches!(
+            block_on(ecm_async(&Integer::new(), &EcmOptions::new())),
+            Err(Error::ZeroInput)
+        ));
+    }
+
+    #[test]
+    fn cancel() {
+        // Two primes of 30 digits, far beyond a few curves
+        let p: Integer = "100000000000000000000000000319".parse().unwrap();
+        let q: Integer = "300000000000000000000000000007".parse().unwrap();
+        let n = Integer::from(&p * &q);
+
+        let future = ecm_async(&n, &EcmOptions::new().b1(2_000));
+        let token = future.cancel_token().clone();
+        drop(future);
+        assert!(token.is_cancelled());
+
+        let future = ecm_async(&n, &EcmOptions::new().b1(2_000));
+        future.cancel_token().cancel();
+        let start = Instant::now();
+        assert!(matches!(block_on(future), Err(Error::Cancelled(_))));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{
+    CancelToken, EcmOptions, Error, Factorization, Factorizer, Progress, ResumeState, StepOutcome,
+};
+use rug::Integer;
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+/// Time budget of each step of an [`EcmJob`], bounding the delay before a pause.
+const STEP_BUDGET: Duration = Duration::from_millis(100);
+
+/// State of an [`EcmJob`] at the end of its last step.
+#[derive(Debug, Clone)]
+pub struct JobSnapshot {
+    /// Factors found so far, the parts of n not factored yet are cofactors.
+    pub factors: Factorization,
+    /// Progress of the factorization.
+    pub progress: Progress,
+    /// Curves run so far, to continue in another job with [`EcmOptions::resume_from`].
+    pub resume: ResumeState,
+    /// True if the job is paused and waiting for [`EcmJob::resume`].
+    pub paused: bool,
+    /// True once the factorization is over.
+    pub done: bool,
+}
+
+impl JobSnapshot {
+    fn new(factorizer: &Factorizer) -> JobSnapshot {
+        JobSnapshot {
+            factors: factorizer.partial_factors(),
+            progress: factorizer.progress(),
+            resume: factorizer.resume_state(),
+            paused: false,
+            done: false,
+        }
+    }
+}
+
+struct State {
+    /// Pause requested by [`EcmJob::pause`].
+    paused: bool,
+    snapshot: JobSnapshot,
+    result: Option<thread::Result<Result<Factorization, Error>>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Notified on resume, cancel and once the result is set.
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Factorization running on a background thread, which can be paused and resumed.
+///
+/// The factorizer runs in short steps, a pause takes effect at the end of the
+/// current step, once the curves running are completed: the curves already run
+/// are kept, and resuming continues with the next one. A snapshot taken
+/// while paused can also be used to continue in another job, or after a restart,
+/// with [`EcmOptions::resume_from`]. Dropping the job cancels the factorization.
+///
+/// ```
+/// use ecm::{EcmJob, EcmOptions};
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let job = EcmJob::start(&n, &EcmOptions::new().seed(1)).unwrap();
+/// job.pause();
+/// let snapshot = job.snapshot();
+/// println!("{} curves run", snapshot.progress.total_curves);
+///
+/// job.resume();
+/// let factors = job.wait().unwrap();
+/// assert_eq!(factors.product(), n);
+/// ```
+pub struct EcmJob {
+    shared: Arc<Shared>,
+    cancel: CancelToken,
+}
+
+impl EcmJob {
+    /// Starts factoring n with `options` on a background thread.
+    ///
+    /// Fails if the factorizer cannot be created, see [`EcmOptions::factorizer`].
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `options`: Settings of the factorization, its cancel token is replaced by the one of the job.
+    pub fn start(n: &Integer, options: &EcmOptions) -> Result<EcmJob, Error> {
+        let cancel = CancelToken::new();
+        let mut factorizer = options.clone().cancel_token(cancel.clone()).factorizer(n)?;
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                paused: false,
+                snapshot: JobSnapshot::new(&factorizer),
+                result: None,
+            }),
+            changed: Condvar::new(),
+        });
+
+        let worker = Arc::clone(&shared);
+        let token = cancel.clone();
+        thread::spawn(move || {
+            // Panics are raised again by wait, instead of never finishing
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                run(&mut factorizer, &worker, &token)
+            }));
+            let mut state = worker.lock();
+            state.snapshot = JobSnapshot::new(&factorizer);
+            state.snapshot.done = true;
+            state.result = Some(result);
+            worker.changed.notify_all();
+        });
+
+        Ok(EcmJob { shared, cancel })
+    }
+
+    /// Pauses the factorization at the end of the current step.
+    ///
+    /// Returns immediately, [`JobSnapshot::paused`] tells once the job is actually paused.
+    pub fn pause(&self) {
+        self.shared.lock().paused = true;
+    }
+
+    /// Continues a paused factorization.
+    pub fn resume(&self) {
+        self.shared.lock().paused = false;
+        self.shared.changed.notify_all();
+    }
+
+    /// Returns true if a pause was requested and the job was not resumed since.
+    pub fn is_paused(&self) -> bool {
+        self.shared.lock().paused
+    }
+
+    /// Returns true once the factorization is over.
+    pub fn is_finished(&self) -> bool {
+        self.shared.lock().result.is_some()
+    }
+
+    /// State of the factorization at the end of the last step.
+    pub fn snapshot(&self) -> JobSnapshot {
+        self.shared.lock().snapshot.clone()
+    }
+
+    /// Token of the factorization, to cancel it without dropping the job.
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// Resumes the job if paused, then waits for the end of the factorization.
+    ///
+    /// Returns the same result as [`EcmOptions::run`].
+    pub fn wait(self) -> Result<Factorization, Error> {
+        self.resume();
+        let mut state = self.shared.lock();
+        loop {
+            match state.result.take() {
+                Some(Ok(result)) => return result,
+                Some(Err(payload)) => std::panic::resume_unwind(payload),
+                None => {
+                    state = self
+                        .shared
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner())
+                }
+            }
+        }
+    }
+}
+
+impl Drop for EcmJob {
+    fn drop(&mut self) {
+        // Under the lock, so the thread cannot miss the wake up between its check and its wait
+        let _state = self.shared.lock();
+        self.cancel.cancel();
+        // Wakes the thread up if paused, so it sees the cancellation
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Steps the factorizer until it is done, waiting while the job is paused.
+fn run(
+    factorizer: &mut Factorizer,
+    shared: &Shared,
+    cancel: &CancelToken,
+) -> Result<Factorization, Error> {
+    loop {
+        {
+            let mut state = shared.lock();
+            while state.paused && !cancel.is_cancelled() {
+                state.snapshot.paused = true;
+                state = shared
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(|e| e.into_inner());
+            }
+            state.snapshot.paused = false;
+        }
+
+        let outcome = factorizer.step(STEP_BUDGET)?;
+        shared.lock().snapshot = JobSnapshot::new(factorizer);
+        if outcome == StepOutcome::Done {
+            return Ok(factorizer.partial_factors());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Waits until the job is actually paused.
+    fn paused(job: &EcmJob) -> JobSnapshot {
+        let start = Instant::now();
+        loop {
+            let snapshot = job.snapshot();
+            if snapshot.paused {
+                return snapshot;
+            }
+            assert!(start.elapsed() < Duration::from_secs(30));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn pause_resume() {
+        // Two primes of 30 digits, far beyond the curves of a few steps
+        let p: Integer = "100000000000000000000000000319".parse().unwrap();
+        let q: Integer = "300000000000000000000000000007".parse().unwrap();
+        let n = Integer::from(&p * &q);
+        let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1);
+
+        let job = EcmJob::start(&n, &options).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        job.pause();
+        assert!(job.is_paused());
+        let before = paused(&job);
+        assert!(!before.done);
+
+        // No curve is run while paused
+        thread::sleep(Duration::from_millis(200));
+        let after = job.snapshot();
+        assert_eq!(after.resume, before.resume);
+        assert!(after.paused);
+
+        // Curves continue after the ones already run
+        job.resume();
+        assert!(!job.is_paused());
+        thread::sleep(Duration::from_millis(200));
+        job.pause();
+        let resumed = paused(&job);
+        assert!(resumed.progress.total_curves > before.progress.total_curves);
+        assert_eq!(resumed.factors.product(), n);
+
+        let token = job.cancel_token().clone();
+        drop(job);
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn wait() {
+        let n = Integer::from(398883434337287u64) * 6;
+        let job = EcmJob::start(&n, &EcmOptions::new().seed(1)).unwrap();
+        job.pause();
+        let factors = job.wait().unwrap();
+        assert!(factors.is_complete());
+        assert_eq!(factors.product(), n);
+
+        assert!(matches!(
+            EcmJob::start(&Integer::new(), &EcmOptions::new()),
+            Err(Error::ZeroInput)
+        ));
+
+        let p: Integer = "100000000000000000000000000319".parse().unwrap();
+        let q: Integer = "300000000000000000000000000007".parse().unwrap();
+        let n = Integer::from(&p * &q);
+        let job = EcmJob::start(&n, &EcmOptions::new().b1(2_000)).unwrap();
+        job.cancel_token().cancel();
+        assert!(matches!(job.wait(), Err(Error::Cancelled(_))));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{parse_integer, DriverMode, EcmOptions, Error, Factorization, ParseError};
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Version of the job file format, increased on incompatible changes.
+pub const JOB_VERSION: u32 = 1;
+
+/// Error occured while loading or running a [`Job`].
+#[derive(thiserror::Error, Debug)]
+pub enum JobError {
+    /// The job file or an output could not be read or written.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// The job file is not valid JSON or does not match the schema.
+    #[error("Invalid job file: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The job file was written for an incompatible version.
+    #[error("Unsupported job version {0}")]
+    UnsupportedVersion(u32),
+    /// The input or a known factor is not a valid number.
+    #[error("Invalid number: {0}")]
+    InvalidNumber(#[from] ParseError),
+    /// The factorization failed.
+    #[error(transparent)]
+    Ecm(#[from] Error),
+}
+
+/// Factorization method of a [`Job`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Trial division, then elliptic curves, see [`EcmOptions`].
+    #[default]
+    Ecm,
+    /// Trial division only, see [`DriverMode::TrialDivision`].
+    #[serde(rename = "trial-division")]
+    TrialDivision,
+    /// Trial division, then Pollard's rho method, see [`DriverMode::Rho`].
+    Rho,
+    /// Trial division, then Williams' p+1 method, see [`DriverMode::PPlusOne`].
+    #[serde(rename = "pp1")]
+    PPlusOne,
+}
+
+/// Bounds and curve counts of a [`Job`], defaults of [`EcmOptions`] when missing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schedule {
+    /// Stage 1 bound, see [`EcmOptions::b1`].
+    pub b1: Option<u64>,
+    /// Stage 2 bound, see [`EcmOptions::b2`].
+    pub b2: Option<u64>,
+    /// Curves per composite, see [`EcmOptions::max_curves`].
+    pub max_curves: Option<u64>,
+    /// See [`EcmOptions::max_escalations`].
+    pub max_escalations: Option<u32>,
+    /// Size of the factors looked for, see [`EcmOptions::target_digits`].
+    pub target_digits: Option<usize>,
+    /// See [`EcmOptions::trial_division_bound`].
+    pub trial_division_bound: Option<u32>,
+}
+
+/// Files written once a [`Job`] is done.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Outputs {
+    /// Factorization, formatted like `2^3 * 7`.
+    pub factors: Option<PathBuf>,
+    /// SageMath script checking the factorization, see [`Factorization::to_sage`].
+    pub sage: Option<PathBuf>,
+    /// Pari/GP script checking the factorization, see [`Factorization::to_pari`].
+    pub pari: Option<PathBuf>,
+}
+
+/// Complete description of a factorization run, stored as a JSON job file.
+///
+/// A job file describes a long factorization campaign in a single shareable file:
+/// the same file and the same version always give the same run.
+/// Factors found by previous runs are listed in `known_factors`, so a campaign
+/// can be resumed by adding them to the file.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "input": "2^128 + 1",
+///   "backend": "ecm",
+///   "schedule": { "b1": 11000, "max_curves": 100 },
+///   "seed": 1234,
+///   "known_factors": ["59649589127497217"],
+///   "outputs": { "factors": "factors.txt", "sage": "check.sage" }
+/// }
+/// ```
+///
+/// Only `version` and `input` are required.
+///
+/// ```
+/// use ecm::Job;
+///
+/// let job = Job::from_json(r#"{ "version": 1, "input": "2^64 + 1" }"#).unwrap();
+/// assert_eq!(job.run().unwrap().len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// Version of the format, [`JOB_VERSION`].
+    pub version: u32,
+    /// Number to factor, in the syntax of [`parse_integer`].
+    pub input: String,
+    /// Factorization method.
+    #[serde(default)]
+    pub backend: Backend,
+    /// Bounds and curve counts.
+    #[serde(default)]
+    pub schedule: Schedule,
+    /// Seed of the pseudorandom generator, see [`EcmOptions::seed`].
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+    /// Factors found by previous runs, in the syntax of [`parse_integer`].
+    #[serde(default)]
+    pub known_factors: Vec<String>,
+    /// Files written once the job is done.
+    #[serde(default)]
+    pub outputs: Outputs,
+}
+
+fn default_seed() -> u64 {
+    1234
+}
+
+impl Job {
+    /// Creates a job factoring `input` with the default settings.
+    ///
+    /// # Parameters
+    ///
+    /// - `input`: Number to factor, in the syntax of [`parse_integer`].
+    pub fn new(input: impl Into<String>) -> Job {
+        Job {
+            version: JOB_VERSION,
+            input: input.into(),
+            backend: Backend::default(),
+            schedule: Schedule::default(),
+            seed: default_seed(),
+            known_factors: Vec::new(),
+            outputs: Outputs::default(),
+        }
+    }
+
+    /// Parses a job from JSON.
+    ///
+    /// # Parameters
+    ///
+    /// - `json`: Content of a job file.
+    pub fn from_json(json: &str) -> Result<Job, JobError> {
+        let job: Job = serde_json::from_str(json)?;
+        if job.version != JOB_VERSION {
+            return Err(JobError::UnsupportedVersion(job.version));
+        }
+        Ok(job)
+    }
+
+    /// Loads a job file.
+    ///
+    /// Relative output paths are resolved against the directory of the file.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: Path of the job file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Job, JobError> {
+        let path = path.as_ref();
+        let mut job = Job::from_json(&std::fs::read_to_string(path)?)?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let outputs = &mut job.outputs;
+        for output in [&mut outputs.factors, &mut outputs.sage, &mut outputs.pari]
+            .into_iter()
+            .flatten()
+        {
+            *output = dir.join(&*output);
+        }
+        Ok(job)
+    }
+
+    /// Serializes the job to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, JobError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Number to factor.
+    pub fn number(&self) -> Result<Integer, JobError> {
+        Ok(parse_integer(&self.input)?)
+    }
+
+    /// Settings of the run.
+    pub fn options(&self) -> Result<EcmOptions, JobError> {
+        let schedule = &self.schedule;
+        let mut options = EcmOptions::new().seed(self.seed).mode(match self.backend {
+            Backend::Ecm => DriverMode::Ecm,
+            Backend::TrialDivision => DriverMode::TrialDivision,
+            Backend::Rho => DriverMode::Rho,
+            Backend::PPlusOne => DriverMode::PPlusOne,
+        });
+        if let Some(b1) = schedule.b1 {
+            options = options.b1(b1);
+        }
+        if let Some(b2) = schedule.b2 {
+            options = options.b2(b2);
+        }
+        if let Some(max_curves) = schedule.max_curves {
+            options = options.max_curves(max_curves);
+        }
+        if let Some(max_escalations) = schedule.max_escalations {
+            options = options.max_escalations(max_escalations);
+        }
+        if let Some(digits) = schedule.target_digits {
+            options = options.target_digits(digits);
+        }
+        if let Some(bound) = schedule.trial_division_bound {
+            options = options.trial_division_bound(bound);
+        }
+
+        let known_factors = self
+            .known_factors
+            .iter()
+            .map(|factor| parse_integer(factor))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(options.known_factors(known_factors))
+    }
+
+    /// Runs the job, then writes its outputs.
+    pub fn run(&self) -> Result<Factorization, JobError> {
+        let factorization = self.options()?.run(&self.number()?)?;
+
+        let outputs = &self.outputs;
+        if let Some(path) = &outputs.factors {
+            std::fs::write(path, format!("{factorization}\n"))?;
+        }
+        if let Some(path) = &outputs.sage {
+            std::fs::write(path, factorization.to_sage())?;
+        }
+        if let Some(path) = &outputs.pari {
+            std::fs::write(path, factorization.to_pari())?;
+        }
+        Ok(factorization)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        let job = Job::from_json(r#"{ "version": 1, "input": "398883434337287" }"#).unwrap();
+        assert_eq!(job, Job::new("398883434337287"));
+        assert_eq!(Job::from_json(&job.to_json().unwrap()).unwrap(), job);
+    }
+
+    #[test]
+    fn schema() {
+        let job = Job::from_json(
+            r#"{
+                "version": 1,
+                "input": "2^64 + 1",
+                "backend": "ecm",
+                "schedule": { "b1": 2000, "b2": 160000, "max_curves": 10, "trial_division_bound": 0 },
+                "seed": 7,
+                "known_factors": ["274177"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(job.schedule.b1, Some(2_000));
+        assert_eq!(job.schedule.max_escalations, None);
+        assert_eq!(job.seed, 7);
+        assert_eq!(
+            job.run().unwrap(),
+            Factorization::from([(274177.into(), 1), (67280421310721u64.into(), 1)])
+        );
+        assert_eq!(Job::from_json(&job.to_json().unwrap()).unwrap(), job);
+
+        assert!(matches!(
+            Job::from_json(r#"{ "version": 2, "input": "15" }"#),
+            Err(JobError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            Job::from_json(r#"{ "version": 1, "input": "15", "sigma": 6 }"#),
+            Err(JobError::Json(_))
+        ));
+        assert!(matches!(
+            Job::from_json(r#"{ "version": 1, "input": "15", "backend": "qs" }"#),
+            Err(JobError::Json(_))
+        ));
+
+        let job =
+            r#"{ "version": 1, "input": "1000000007 * 1000000009", "backend": "trial-division" }"#;
+        let partial = Job::from_json(job).unwrap().run().unwrap();
+        assert_eq!(partial.cofactors().count(), 1);
+        let job = r#"{ "version": 1, "input": "1000000007 * 1000000009", "backend": "rho" }"#;
+        assert_eq!(Job::from_json(job).unwrap().run().unwrap().len(), 2);
+        let job = r#"{ "version": 1, "input": "15", "backend": "pp1" }"#;
+        assert_eq!(Job::from_json(job).unwrap().backend, Backend::PPlusOne);
+        assert!(matches!(
+            Job::new("12a").run(),
+            Err(JobError::InvalidNumber(_))
+        ));
+    }
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join(format!("ecm-job-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut job = Job::new("0x10001 * 641");
+        job.outputs.factors = Some("factors.txt".into());
+        job.outputs.pari = Some("check.gp".into());
+        std::fs::write(dir.join("job.json"), job.to_json().unwrap()).unwrap();
+
+        let loaded = Job::from_file(dir.join("job.json")).unwrap();
+        assert_eq!(loaded.outputs.factors, Some(dir.join("factors.txt")));
+        loaded.run().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("factors.txt")).unwrap(),
+            "641 * 65537\n"
+        );
+        assert!(std::fs::read_to_string(dir.join("check.gp"))
+            .unwrap()
+            .starts_with("n = 42009217;"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(
+            Job::from_file(dir.join("job.json")),
+            Err(JobError::Io(_))
+        ));
+    }
+}
@@ -1,8 +1,103 @@
 #![doc = include_str!("../README.md")]
 #![deny(rust_2018_idioms)]
 #![warn(missing_docs)]
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used))]
 
+#[cfg(feature = "audit")]
+mod audit;
+mod batch;
+mod cancel;
+#[cfg(feature = "serde")]
+mod checkpoint;
+#[cfg(feature = "coppersmith")]
+mod coppersmith;
+pub mod corpus;
+mod curve;
+pub mod demo;
 mod ecm;
+mod edwards;
+#[cfg(feature = "events")]
+mod events;
+#[cfg(feature = "factordb")]
+mod factordb;
+mod factorization;
+mod factorize;
+mod fermat;
+#[cfg(feature = "async")]
+mod future;
+mod handle;
+#[cfg(feature = "job")]
+mod job;
+#[cfg(feature = "mpz")]
+mod mpz;
+mod olf;
+mod parse;
+mod pm1;
 mod point;
+pub mod poly;
+#[cfg(any(feature = "parallel", feature = "rayon"))]
+mod pool;
+mod pp1;
+mod preset;
+mod primality;
+mod prime_table;
+mod priority;
+mod progress;
+mod rho;
+mod rsa;
+mod save;
+mod scheduler;
+mod siqs;
+mod small;
+mod squfof;
+mod stats;
+mod stream;
+pub mod tables;
+mod tree;
+mod work;
 
+#[cfg(feature = "audit")]
+pub use crate::audit::*;
+pub use crate::batch::*;
+pub use crate::cancel::*;
+#[cfg(feature = "serde")]
+pub use crate::checkpoint::*;
+#[cfg(feature = "coppersmith")]
+pub use crate::coppersmith::*;
+pub use crate::curve::*;
 pub use crate::ecm::*;
+pub use crate::edwards::*;
+#[cfg(feature = "events")]
+pub use crate::events::*;
+#[cfg(feature = "factordb")]
+pub use crate::factordb::*;
+pub use crate::factorization::*;
+pub use crate::factorize::*;
+pub use crate::fermat::*;
+#[cfg(feature = "async")]
+pub use crate::future::*;
+pub use crate::handle::*;
+#[cfg(feature = "job")]
+pub use crate::job::*;
+#[cfg(feature = "mpz")]
+pub use crate::mpz::*;
+pub use crate::olf::*;
+pub use crate::parse::*;
+pub use crate::pm1::*;
+pub use crate::point::*;
+pub use crate::pp1::*;
+pub use crate::preset::*;
+pub use crate::primality::*;
+pub use crate::prime_table::*;
+pub use crate::priority::*;
+pub use crate::progress::*;
+pub use crate::rho::*;
+pub use crate::rsa::*;
+pub use crate::save::*;
+pub use crate::scheduler::*;
+pub use crate::siqs::*;
+pub use crate::small::*;
+pub use crate::squfof::*;
+pub use crate::stats::*;
+pub use crate::stream::*;
+pub use crate::work::*;
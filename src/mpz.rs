@@ -0,0 +1,62 @@
+use crate::EcmOptions;
+use gmp_mpfr_sys::gmp::mpz_t;
+use rug::{integer::BorrowInteger, Integer};
+
+/// Returns a proper factor of the GMP integer `raw_n`, see [`EcmOptions::first_factor`].
+///
+/// For callers already holding `mpz_t` values from other FFI: n is read in
+/// place, without being copied into an [`Integer`].
+///
+/// Safe code holding an [`Integer`] calls [`EcmOptions::first_factor`]
+/// directly, [`Integer`] being a transparent wrapper of `mpz_t`
+/// (see [`Integer::as_raw`] and [`Integer::from_raw`]).
+///
+/// ```
+/// use ecm::{ecm_factor_mpz, EcmOptions};
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// // Safety: n is initialized and not modified during the call
+/// let factor = unsafe { ecm_factor_mpz(n.as_raw(), &EcmOptions::new()) }.unwrap();
+/// assert!(n.is_divisible(&factor));
+/// ```
+///
+/// # Safety
+///
+/// - `raw_n` must point to an initialized `mpz_t`, e.g. by `mpz_init`.
+/// - The value must not be modified or cleared during the call.
+///
+/// # Parameters
+///
+/// - `raw_n`: Number to be factored.
+/// - `options`: Settings of the factorization.
+pub unsafe fn ecm_factor_mpz(raw_n: *const mpz_t, options: &EcmOptions) -> Option<Integer> {
+    // The caller guarantees that the value is initialized and left unchanged,
+    // the borrow does not outlive the call
+    let n = unsafe { BorrowInteger::from_raw(*raw_n) };
+    options.first_factor(&n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gmp_mpfr_sys::gmp;
+    use std::mem::MaybeUninit;
+
+    #[test]
+    fn raw_mpz() {
+        unsafe {
+            // Initialized and freed by GMP, as foreign code would
+            let mut raw = MaybeUninit::<mpz_t>::uninit();
+            gmp::mpz_init_set_ui(raw.as_mut_ptr(), 1_000_003 * 999_983);
+            let mut raw = raw.assume_init();
+
+            let factor = ecm_factor_mpz(&raw, &EcmOptions::new().seed(1)).unwrap();
+            assert!(factor == 1_000_003 || factor == 999_983);
+
+            gmp::mpz_set_ui(&mut raw, 1_000_003);
+            assert_eq!(ecm_factor_mpz(&raw, &EcmOptions::new()), None);
+            gmp::mpz_clear(&mut raw);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::ops::RangeInclusive;
+
+/// Composites with a number of bits in this range are given to [`one_line_factor`] by default.
+pub const ONE_LINE_BITS: RangeInclusive<u32> = 50..=70;
+
+/// Maximum number of steps of [`one_line_factor`] in the driver, about `n^(1/3)` at 2^60.
+pub(crate) const ONE_LINE_STEPS: u64 = 1 << 20;
+
+/// Multiplier of n, which makes `s^2 mod n` a square more often.
+const MULTIPLIER: u128 = 480;
+
+/// Returns a proper factor of n with Hart's one line factorization.
+///
+/// For `i = 1, 2, ...`, computes `s = ceil(sqrt(480 * n * i))` and tests whether
+/// `s^2 mod n` is a square `t^2`, in which case `gcd(s - t, n)` is usually a
+/// proper factor of n. It takes about `n^(1/3)` steps on native integers,
+/// with no setup, which suits composites of 50 to 70 bits.
+///
+/// Returns `None` if n is less than 4, has more than 80 bits, or if no factor
+/// was found within `max_steps` steps, e.g. if n is prime.
+///
+/// ```
+/// use ecm::one_line_factor;
+///
+/// let n = 30_000_000_001 * 31_000_000_027;
+/// assert!(matches!(one_line_factor(n, 1 << 20), Some(30_000_000_001 | 31_000_000_027)));
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `max_steps`: Maximum number of steps.
+pub fn one_line_factor(n: u128, max_steps: u64) -> Option<u128> {
+    if n < 4 || n >> 80 != 0 {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+
+    let kn = MULTIPLIER * n;
+    for i in 1..=u128::from(max_steps) {
+        let product = kn.checked_mul(i)?;
+        let mut s = product.isqrt();
+        if s * s != product {
+            s += 1;
+        }
+        // s^2 = s^2 - 480 * n * i (mod n), which is below 2s + 1
+        let m = (s * s - product) % n;
+        let t = m.isqrt();
+        if t * t == m {
+            let g = gcd(s.abs_diff(t) % n, n);
+            if g != 1 && g != n {
+                return Some(g);
+            }
+        }
+    }
+    None
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primal::Primes;
+
+    #[test]
+    fn semiprimes() {
+        for (p, q) in [
+            (30_000_000_001u128, 31_000_000_027u128),
+            (4_009_823, 99_476_569),
+            (1_000_003, 1_099_511_627_791),
+            (3, 1_000_003),
+        ] {
+            let g = one_line_factor(p * q, ONE_LINE_STEPS).unwrap();
+            assert!(g == p || g == q, "{p} * {q}: {g}");
+        }
+
+        let primes: Vec<u128> = Primes::all()
+            .skip(2_000_000)
+            .step_by(7_919)
+            .take(40)
+            .map(|p| p as u128)
+            .collect();
+        for pair in primes.windows(2) {
+            let n = pair[0] * pair[1] * 12_289;
+            assert!(one_line_factor(n, ONE_LINE_STEPS).is_some_and(|g| n % g == 0));
+        }
+    }
+
+    #[test]
+    fn no_factor() {
+        for n in [0, 1, 3, 1_000_003, 1 << 81] {
+            assert_eq!(one_line_factor(n, 1 << 10), None);
+        }
+        assert_eq!(one_line_factor(1 << 40, 0), Some(2));
+        assert_eq!(one_line_factor(15, 0), None);
+    }
+}
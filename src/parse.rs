@@ -0,0 +1,485 @@
+use crate::MAX_INPUT_BITS;
+use rug::{ops::Pow, Integer};
+use std::ops::Range;
+
+/// Largest accepted exponent in `a^b` and `aeb` expressions.
+const MAX_EXPONENT: u32 = 1_000_000;
+
+/// Largest accepted number of bits of the values of an expression, about 10^10000.
+const MAX_VALUE_BITS: u64 = MAX_INPUT_BITS as u64;
+
+/// Kind of error occured while parsing a number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The input is empty.
+    Empty,
+    /// Unexpected character.
+    UnexpectedChar(char),
+    /// The input ended in the middle of an expression.
+    UnexpectedEnd,
+    /// Digit separators must be between two digits.
+    MisplacedSeparator,
+    /// A number prefix is not followed by any digit.
+    MissingDigits,
+    /// Parenthesis is never closed.
+    UnclosedParenthesis,
+    /// Exponent is negative or too large.
+    InvalidExponent,
+    /// Value of an expression with more bits than [`MAX_INPUT_BITS`].
+    TooLarge,
+}
+
+/// Error occured while parsing a number.
+///
+/// The span is the byte range of the input which caused the error.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("{} at {}..{}", .kind.message(), .span.start, .span.end)]
+pub struct ParseError {
+    /// Kind of error.
+    pub kind: ParseErrorKind,
+    /// Byte range of the input which caused the error.
+    pub span: Range<usize>,
+}
+
+impl ParseErrorKind {
+    fn message(&self) -> String {
+        match self {
+            ParseErrorKind::Empty => "Empty input".to_string(),
+            ParseErrorKind::UnexpectedChar(c) => format!("Unexpected character {c:?}"),
+            ParseErrorKind::UnexpectedEnd => "Unexpected end of input".to_string(),
+            ParseErrorKind::MisplacedSeparator => "Misplaced digit separator".to_string(),
+            ParseErrorKind::MissingDigits => "Missing digits after prefix".to_string(),
+            ParseErrorKind::UnclosedParenthesis => "Unclosed parenthesis".to_string(),
+            ParseErrorKind::InvalidExponent => "Invalid exponent".to_string(),
+            ParseErrorKind::TooLarge => "Number too large".to_string(),
+        }
+    }
+}
+
+/// Parses an integer written as a number or a simple expression.
+///
+/// Accepted syntax:
+///
+/// - Digit groups separated by underscores: `1_000_003`.
+/// - Base prefixes: `0x1f`, `0o17`, `0b1011` (case insensitive).
+/// - Exponent notation for decimal numbers: `1e100`, `3E5`.
+/// - Operators `+`, `-`, `*`, `^` (right associative) and parentheses: `1e100+33`, `2^127-1`.
+///
+/// Whitespaces between tokens are ignored. Expressions whose values, or the
+/// values of their parts, have more than [`MAX_INPUT_BITS`] bits are rejected
+/// before being computed.
+///
+/// # Parameters
+///
+/// - `s`: String to parse.
+pub fn parse_integer(s: &str) -> Result<Integer, ParseError> {
+    let mut parser = Parser { input: s, pos: 0 };
+
+    parser.skip_whitespaces();
+    if parser.pos == s.len() {
+        return Err(ParseError {
+            kind: ParseErrorKind::Empty,
+            span: 0..s.len(),
+        });
+    }
+
+    let value = parser.expr()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(parser.unexpected(c)),
+    }
+}
+
+/// Recursive descent parser over the input string.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespaces(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Returns the next non whitespace char, without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespaces();
+        self.input[self.pos..].chars().next()
+    }
+
+    fn unexpected(&self, c: char) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::UnexpectedChar(c),
+            span: self.pos..self.pos + c.len_utf8(),
+        }
+    }
+
+    fn end(&self) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::UnexpectedEnd,
+            span: self.pos..self.pos,
+        }
+    }
+
+    /// Returns a [`ParseErrorKind::TooLarge`] error over `start..self.pos` if a value
+    /// has at least `bits` bits, and more than [`MAX_VALUE_BITS`].
+    fn bounded(&self, bits: u64, start: usize) -> Result<(), ParseError> {
+        match bits > MAX_VALUE_BITS {
+            true => Err(ParseError {
+                kind: ParseErrorKind::TooLarge,
+                span: start..self.pos,
+            }),
+            false => Ok(()),
+        }
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Integer, ParseError> {
+        self.skip_whitespaces();
+        let start = self.pos;
+        let mut value = self.term()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.pos += 1;
+                    value += self.term()?;
+                }
+                Some('-') => {
+                    self.pos += 1;
+                    value -= self.term()?;
+                }
+                _ => return Ok(value),
+            }
+            self.bounded(value.significant_bits().into(), start)?;
+        }
+    }
+
+    /// term := power ('*' power)*
+    fn term(&mut self) -> Result<Integer, ParseError> {
+        self.skip_whitespaces();
+        let start = self.pos;
+        let mut value = self.power()?;
+        while self.peek() == Some('*') {
+            self.pos += 1;
+            let factor = self.power()?;
+            // A product has at least one bit less than the bits of its factors
+            let bits = u64::from(value.significant_bits() + factor.significant_bits());
+            self.bounded(bits.saturating_sub(1), start)?;
+            value *= factor;
+            self.bounded(value.significant_bits().into(), start)?;
+        }
+        Ok(value)
+    }
+
+    /// power := unary ('^' power)?
+    fn power(&mut self) -> Result<Integer, ParseError> {
+        self.skip_whitespaces();
+        let base_start = self.pos;
+        let base = self.unary()?;
+        if self.peek() != Some('^') {
+            return Ok(base);
+        }
+        self.pos += 1;
+
+        self.skip_whitespaces();
+        let start = self.pos;
+        let exp = self.power()?;
+        let exp = exp
+            .to_u32()
+            .filter(|&exp| exp <= MAX_EXPONENT)
+            .ok_or(ParseError {
+                kind: ParseErrorKind::InvalidExponent,
+                span: start..self.pos,
+            })?;
+        // |base| >= 2^(bits - 1), so base^exp has more than (bits - 1) * exp bits
+        let bits = u64::from(base.significant_bits().saturating_sub(1)) * u64::from(exp);
+        self.bounded(bits, base_start)?;
+        let value = base.pow(exp);
+        self.bounded(value.significant_bits().into(), base_start)?;
+        Ok(value)
+    }
+
+    /// unary := '-' unary | primary
+    fn unary(&mut self) -> Result<Integer, ParseError> {
+        if self.peek() == Some('-') {
+            self.pos += 1;
+            Ok(-self.unary()?)
+        } else {
+            self.primary()
+        }
+    }
+
+    /// primary := number | '(' expr ')'
+    fn primary(&mut self) -> Result<Integer, ParseError> {
+        match self.peek() {
+            Some('(') => {
+                let start = self.pos;
+                self.pos += 1;
+                let value = self.expr()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.pos += 1;
+                        Ok(value)
+                    }
+                    Some(c) => Err(self.unexpected(c)),
+                    None => Err(ParseError {
+                        kind: ParseErrorKind::UnclosedParenthesis,
+                        span: start..start + 1,
+                    }),
+                }
+            }
+            Some(c) if c.is_ascii_digit() => self.number(),
+            Some(c) => Err(self.unexpected(c)),
+            None => Err(self.end()),
+        }
+    }
+
+    /// number := prefix? digits (('e' | 'E') digits)?
+    fn number(&mut self) -> Result<Integer, ParseError> {
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        let radix = match bytes.get(self.pos..self.pos + 2) {
+            Some([b'0', b'x' | b'X']) => 16,
+            Some([b'0', b'o' | b'O']) => 8,
+            Some([b'0', b'b' | b'B']) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.pos += 2;
+        }
+
+        let mantissa = self.digits(radix, start)?;
+        if radix != 10 || !matches!(bytes.get(self.pos), Some(b'e' | b'E')) {
+            return Ok(mantissa);
+        }
+
+        self.pos += 1;
+        let exp_start = self.pos;
+        let exp = self
+            .digits(10, start)?
+            .to_u32()
+            .filter(|&exp| exp <= MAX_EXPONENT)
+            .ok_or(ParseError {
+                kind: ParseErrorKind::InvalidExponent,
+                span: exp_start..self.pos,
+            })?;
+        // 10^exp > 2^(3 * exp)
+        if mantissa != 0 {
+            let bits = u64::from(mantissa.significant_bits() - 1) + 3 * u64::from(exp);
+            self.bounded(bits, start)?;
+        }
+        let value = mantissa * Integer::from(10).pow(exp);
+        self.bounded(value.significant_bits().into(), start)?;
+        Ok(value)
+    }
+
+    /// Parses digits in the given radix, allowing `_` between two digits.
+    fn digits(&mut self, radix: u32, start: usize) -> Result<Integer, ParseError> {
+        let digits_start = self.pos;
+        let mut digits = String::new();
+        let mut last_separator = None;
+
+        for c in self.input[self.pos..].chars() {
+            if c == '_' {
+                if last_separator == Some(self.pos) || digits.is_empty() {
+                    return Err(ParseError {
+                        kind: ParseErrorKind::MisplacedSeparator,
+                        span: self.pos..self.pos + 1,
+                    });
+                }
+                last_separator = Some(self.pos + 1);
+            } else if c.is_digit(radix) {
+                digits.push(c);
+            } else if c.is_ascii_alphanumeric() && !(radix == 10 && matches!(c, 'e' | 'E')) {
+                return Err(ParseError {
+                    kind: ParseErrorKind::UnexpectedChar(c),
+                    span: self.pos..self.pos + 1,
+                });
+            } else {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+
+        if last_separator == Some(self.pos) {
+            return Err(ParseError {
+                kind: ParseErrorKind::MisplacedSeparator,
+                span: self.pos - 1..self.pos,
+            });
+        }
+        if digits.is_empty() {
+            return Err(ParseError {
+                kind: if digits_start == self.input.len() && radix == 10 {
+                    ParseErrorKind::UnexpectedEnd
+                } else {
+                    ParseErrorKind::MissingDigits
+                },
+                span: start..self.pos,
+            });
+        }
+
+        Integer::from_str_radix(&digits, radix as i32).map_err(|_| ParseError {
+            kind: ParseErrorKind::MissingDigits,
+            span: start..self.pos,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn plain_numbers() {
+        assert_eq!(parse_integer("17").unwrap(), 17);
+        assert_eq!(parse_integer("  42 ").unwrap(), 42);
+        assert_eq!(parse_integer("-5").unwrap(), -5);
+        assert_eq!(
+            parse_integer("398883434337287").unwrap(),
+            Integer::from_str("398883434337287").unwrap()
+        );
+    }
+
+    #[test]
+    fn digit_groups() {
+        assert_eq!(parse_integer("1_000_003").unwrap(), 1_000_003);
+        assert_eq!(parse_integer("0xff_ff").unwrap(), 0xffff);
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(parse_integer("0x1F").unwrap(), 31);
+        assert_eq!(parse_integer("0X1e5").unwrap(), 0x1e5);
+        assert_eq!(parse_integer("0o17").unwrap(), 15);
+        assert_eq!(parse_integer("0b1011").unwrap(), 11);
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(
+            parse_integer("1e100+33").unwrap(),
+            Integer::from(10).pow(100) + 33
+        );
+        assert_eq!(parse_integer("3E5").unwrap(), 300_000);
+        assert_eq!(
+            parse_integer("2^127-1").unwrap(),
+            (Integer::from(1) << 127) - 1
+        );
+        assert_eq!(parse_integer("2^3^2").unwrap(), 512);
+        assert_eq!(parse_integer("(2 + 3) * 4").unwrap(), 20);
+        assert_eq!(parse_integer("2 * -3").unwrap(), -6);
+    }
+
+    #[test]
+    fn error_spans() {
+        assert_eq!(
+            parse_integer("").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::Empty,
+                span: 0..0
+            }
+        );
+        assert_eq!(
+            parse_integer("12a4").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::UnexpectedChar('a'),
+                span: 2..3
+            }
+        );
+        assert_eq!(
+            parse_integer("1__000").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::MisplacedSeparator,
+                span: 2..3
+            }
+        );
+        assert_eq!(
+            parse_integer("1000_").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::MisplacedSeparator,
+                span: 4..5
+            }
+        );
+        assert_eq!(
+            parse_integer("0x").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::MissingDigits,
+                span: 0..2
+            }
+        );
+        assert_eq!(
+            parse_integer("(1 + 2").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::UnclosedParenthesis,
+                span: 0..1
+            }
+        );
+        assert_eq!(
+            parse_integer("1 +").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::UnexpectedEnd,
+                span: 3..3
+            }
+        );
+        assert_eq!(
+            parse_integer("2^-1").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::InvalidExponent,
+                span: 2..4
+            }
+        );
+        assert_eq!(
+            parse_integer("0b102").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::UnexpectedChar('2'),
+                span: 4..5
+            }
+        );
+    }
+
+    #[test]
+    fn too_large() {
+        // Rejected before being computed
+        for (s, span) in [
+            ("(2^1000000)^1000000", 1..10),
+            ("(2^30000)^2", 0..11),
+            ("1e1000000", 0..9),
+            ("2^30000 * 2^30000", 0..17),
+            ("1 + 2^40000", 4..11),
+        ] {
+            let kind = ParseErrorKind::TooLarge;
+            assert_eq!(parse_integer(s).unwrap_err(), ParseError { kind, span });
+        }
+        // Up to about 10^10000
+        assert_eq!(parse_integer("2^33218").unwrap(), Integer::from(1) << 33218);
+        assert_eq!(parse_integer("1^1000000").unwrap(), 1);
+        assert_eq!(parse_integer("0e1000000 + 3").unwrap(), 3);
+        assert_eq!(
+            parse_integer("2^33219").unwrap_err().kind,
+            ParseErrorKind::TooLarge
+        );
+        assert_eq!(
+            parse_integer("2^33218 * 2").unwrap_err().kind,
+            ParseErrorKind::TooLarge
+        );
+        assert_eq!(
+            parse_integer("2^33218 + 2^33218").unwrap_err().kind,
+            ParseErrorKind::TooLarge
+        );
+        assert_eq!(
+            parse_integer("1e10000").unwrap_err().kind,
+            ParseErrorKind::TooLarge
+        );
+        assert!(parse_integer("1e9999").is_ok());
+    }
+
+    #[test]
+    fn error_display() {
+        assert_eq!(
+            parse_integer("12a4").unwrap_err().to_string(),
+            "Unexpected character 'a' at 2..3"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::ecm::{pow_mod, proper_factor, Stages};
+use rug::Integer;
+
+/// Stage 1 bound of Pollard's p-1 method run before the curves by default.
+pub const DEFAULT_PM1_B1: u64 = 10_000;
+
+/// Stage 2 bound of Pollard's p-1 method run before the curves by default.
+pub const DEFAULT_PM1_B2: u64 = 1_000_000;
+
+/// Base of the powers of p-1.
+const BASE: u32 = 3;
+
+/// Even gaps between consecutive primes of stage 2 with a precomputed power, larger ones are computed.
+const GAP_STEPS: usize = 64;
+
+/// Returns a proper factor of n with Pollard's p-1 method.
+///
+/// Stage 1 computes `x = 3^k (mod n)`, with the same exponent k as the stage 1
+/// of the curves, the product of the prime powers up to B1: it finds the
+/// factors p of n such that p-1 is B1-smooth. Stage 2 then walks the primes q
+/// of `(B1, B2]` from one to the next with precomputed powers of x, to find
+/// the factors such that p-1 is B1-smooth except for one prime q.
+///
+/// Unlike the curves, which can be retried with another group, the group of
+/// p-1 is fixed by p: it costs about as much as one curve, and catches the
+/// factors with a smooth p-1 whatever their size.
+///
+/// Returns `None` if n is prime, or less than 4, or if no factor was found,
+/// including when all the factors of n are found at once.
+///
+/// ```
+/// use ecm::pm1;
+/// use rug::Integer;
+///
+/// // p - 1 = 2^6 * 7 * 13 * 19 * 31 * 61 * 67 * 3709 * 19231
+/// let p = Integer::from_str_radix("1000000000000001729", 10).unwrap();
+/// let n = Integer::from_str_radix("100000000000000000000000000319", 10).unwrap() * &p;
+/// assert_eq!(pm1(&n, 10_000, 1_000_000), Some(p));
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound, no stage 2 is run if it is at most B1.
+pub fn pm1(n: &Integer, b1: u64, b2: u64) -> Option<Integer> {
+    if *n < 4 {
+        return None;
+    }
+    if n.is_even() {
+        return Some(Integer::from(2));
+    }
+    pm1_stages(n, &Stages::new(b1, b2.max(b1)))
+}
+
+/// Runs stage 1 and stage 2 of p-1 with precomputed stages, see [`pm1`].
+///
+/// # Parameters
+///
+/// - `n`: Odd number to be factored.
+/// - `stages`: Stage 1 multiplier and primes of stage 2.
+pub(crate) fn pm1_stages(n: &Integer, stages: &Stages) -> Option<Integer> {
+    // Stage 1
+    let x = pow_mod(&Integer::from(BASE), &stages.k, n);
+    let g = Integer::from(&x - 1).gcd(n);
+    if g != 1 {
+        return proper_factor(g, n);
+    }
+    if stages.b2 <= stages.b1 {
+        return None;
+    }
+
+    // Stage 2: x^q for each prime q of (B1, B2], from the power of the previous prime
+    let mut gaps: Vec<Integer> = Vec::with_capacity(GAP_STEPS);
+    gaps.push(Integer::from(x.square_ref()) % n);
+    for i in 1..GAP_STEPS {
+        let next = Integer::from(&gaps[i - 1] * &gaps[0]) % n;
+        gaps.push(next);
+    }
+
+    let mut primes = stages
+        .primes_above(stages.b1, stages.b2)
+        .take_while(|&q| q <= stages.b2);
+    let mut previous = primes.next()?;
+    let mut y = pow_mod(&x, &Integer::from(previous), n);
+    let mut product = Integer::from(&y - 1);
+    for q in primes {
+        let gap = ((q - previous) / 2) as usize;
+        let step = match gaps.get(gap - 1) {
+            Some(step) => step.clone(),
+            None => pow_mod(&x, &Integer::from(q - previous), n),
+        };
+        y = y * step % n;
+        product = product * Integer::from(&y - 1) % n;
+        previous = q;
+    }
+    proper_factor(product.gcd(n), n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn stage1() {
+        // 1000003 - 1 = 2 * 3 * 166667, 2000003 - 1 = 2 * 101 * 9901
+        let n = Integer::from(1_000_003u64 * 2_000_003);
+        assert_eq!(pm1(&n, 10_000, 10_000), Some(2_000_003.into()));
+        assert_eq!(pm1(&n, 100, 100), None);
+    }
+
+    #[test]
+    fn stage2() {
+        // 2000003 - 1 = 2 * 101 * 9901, 9901 is found by stage 2 only
+        let n = Integer::from(1_000_003u64 * 2_000_003);
+        assert_eq!(pm1(&n, 200, 100), None);
+        assert_eq!(pm1(&n, 200, 10_000), Some(2_000_003.into()));
+        // 1000003 - 1 = 2 * 3 * 166667, both are found
+        assert_eq!(pm1(&n, 200, 200_000), None);
+    }
+
+    #[test]
+    fn large() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from_str("300000000000000000000000000007").unwrap();
+        assert_eq!(pm1(&Integer::from(&p * &q), 10_000, 1_000_000), None);
+        for n in [0, 1, 3, 1_000_003] {
+            assert_eq!(pm1(&Integer::from(n), 1_000, 10_000), None);
+        }
+        assert_eq!(pm1(&Integer::from(6), 1_000, 10_000), Some(2.into()));
+    }
+}
@@ -88,11 +88,41 @@ impl Point {
     ///
     /// - `k`: The positive integer multiplier
     pub fn mont_ladder(&self, k: &Integer) -> Point {
+        self.ladder_with_exponent(k)
+    }
+
+    /// Stage 1 of ECM with a custom exponent: computes `k*self`.
+    ///
+    /// The exponent is usually the product of the prime powers below a bound,
+    /// see [`stage1_exponent`](crate::stage1_exponent), possibly multiplied by
+    /// guesses of the group order. A factor of the modulus may then be found
+    /// with `gcd(k*P.z, n)`. `k = 0` gives the point at infinity `(1 : 0)`.
+    ///
+    /// ```
+    /// use ecm::{stage1_exponent, SuyamaCurve};
+    /// use rug::Integer;
+    ///
+    /// let n = Integer::from(398883434337287u64);
+    /// let point = SuyamaCurve::derive(&Integer::from(7), &n).unwrap().point();
+    /// let k = stage1_exponent(2_000, []);
+    /// let q = point.ladder_with_exponent(&k);
+    /// // Prime powers of k are multiplied in any order
+    /// assert_eq!(q, point.ladder_with_exponent(&Integer::from(&k / 2)).double());
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `k`: The non-negative integer multiplier
+    pub fn ladder_with_exponent(&self, k: &Integer) -> Point {
+        if *k == 0 {
+            return Point::new(1.into(), 0.into(), self.a_24.clone(), self.modulus.clone());
+        }
+
         let mut q = self.clone();
         let mut r = self.double();
-
-        for i in format!("{:b}", k)[1..].chars() {
-            if i == '1' {
+        // Bits after the leading one, most significant first
+        for i in (0..k.significant_bits() - 1).rev() {
+            if k.get_bit(i) {
                 q = r.add(&q, self);
                 r = r.double();
             } else {
@@ -106,13 +136,15 @@ impl Point {
 
 impl PartialEq for Point {
     /// Two points are equal if X/Z of both points are equal.
+    ///
+    /// The fractions are compared by cross-multiplying, so Z does not need to be invertible.
     fn eq(&self, other: &Self) -> bool {
         if self.a_24 != other.a_24 || self.modulus != other.modulus {
             false
         } else {
-            self.z_cord.clone().invert(&self.modulus).unwrap() * &self.x_cord % &self.modulus
-                == other.z_cord.clone().invert(&self.modulus).unwrap() * &other.x_cord
-                    % &self.modulus
+            let lhs = Integer::from(&self.x_cord * &other.z_cord);
+            let rhs = Integer::from(&other.x_cord * &self.z_cord);
+            (lhs - rhs).is_divisible(&self.modulus)
         }
     }
 }
@@ -150,6 +182,19 @@ mod tests {
         assert_eq!(p3.z_cord, Integer::from(17));
     }
 
+    #[test]
+    fn test_point_ladder_with_exponent() {
+        let p1 = Point::new(10.into(), 17.into(), 3.into(), 101.into());
+        let p4 = p1.double().double();
+        assert_eq!(p1.ladder_with_exponent(&1.into()), p1);
+        assert_eq!(p1.ladder_with_exponent(&4.into()), p4);
+        assert_eq!(
+            p1.ladder_with_exponent(&12.into()),
+            p4.mont_ladder(&3.into())
+        );
+        assert_eq!(p1.ladder_with_exponent(&0.into()).z_cord, Integer::ZERO);
+    }
+
     #[test]
     fn test_point() {
         let modulus = 101.into();
@@ -0,0 +1,382 @@
+//! Polynomial arithmetic modulo n, groundwork for an FFT stage 2.
+//!
+//! rug has no fast polynomial multiplication, so products are computed with
+//! number theoretic transforms (NTT) modulo word-size primes, then lifted
+//! to the integers with the chinese remainder theorem and reduced modulo n.
+//! Enough primes are used for their product to exceed the coefficients of
+//! the product over the integers, so the result is exact.
+//!
+//! ```
+//! use ecm::poly::mul_mod;
+//! use rug::Integer;
+//!
+//! // (x + 2) * (3x + 4) = 3x^2 + 10x + 8 = 3x^2 + 3x + 1 (mod 7)
+//! let a = [2, 1].map(Integer::from);
+//! let b = [4, 3].map(Integer::from);
+//! assert_eq!(mul_mod(&a, &b, &Integer::from(7)), [1, 3, 3]);
+//! ```
+
+use rug::Integer;
+use std::sync::Mutex;
+
+/// Every NTT prime is `k * 2^TWO_ADICITY + 1`, transforms have at most `2^TWO_ADICITY` points.
+const TWO_ADICITY: u32 = 32;
+
+/// NTT primes are below `2^PRIME_BITS`, so the sum of two residues fits in a word.
+const PRIME_BITS: u32 = 62;
+
+/// Prime `p = k * 2^32 + 1` supporting transforms of up to `2^32` points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NttPrime {
+    p: u64,
+    /// Primitive `2^32`-th root of unity modulo p.
+    root: u64,
+}
+
+impl NttPrime {
+    /// The prime p.
+    pub fn modulus(&self) -> u64 {
+        self.p
+    }
+
+    /// Product of two polynomials modulo p.
+    ///
+    /// Coefficients are in ascending degree and must be below p.
+    ///
+    /// # Parameters
+    ///
+    /// - `a`: First polynomial.
+    /// - `b`: Second polynomial.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the product has more than `2^32` coefficients.
+    pub fn mul(&self, a: &[u64], b: &[u64]) -> Vec<u64> {
+        if a.is_empty() || b.is_empty() {
+            return Vec::new();
+        }
+
+        let len = a.len() + b.len() - 1;
+        let size = len.next_power_of_two();
+        assert!(
+            size.trailing_zeros() <= TWO_ADICITY,
+            "polynomial product too large"
+        );
+
+        let mut fa = a.to_vec();
+        let mut fb = b.to_vec();
+        fa.resize(size, 0);
+        fb.resize(size, 0);
+        self.transform(&mut fa, false);
+        self.transform(&mut fb, false);
+        for (x, y) in fa.iter_mut().zip(&fb) {
+            *x = mul(*x, *y, self.p);
+        }
+        self.transform(&mut fa, true);
+
+        let scale = pow(size as u64, self.p - 2, self.p);
+        fa.truncate(len);
+        for x in &mut fa {
+            *x = mul(*x, scale, self.p);
+        }
+        fa
+    }
+
+    /// In place iterative radix-2 transform, without the `1/size` scaling of the inverse.
+    fn transform(&self, a: &mut [u64], inverse: bool) {
+        let p = self.p;
+        let size = a.len();
+
+        // Bit reversal permutation
+        let mut j = 0;
+        for i in 1..size {
+            let mut bit = size >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                a.swap(i, j);
+            }
+        }
+
+        let mut half = 1;
+        while half < size {
+            let mut w = pow(self.root, (1 << TWO_ADICITY) / (2 * half as u64), p);
+            if inverse {
+                w = pow(w, p - 2, p);
+            }
+            for block in a.chunks_mut(2 * half) {
+                let (lo, hi) = block.split_at_mut(half);
+                let mut wk = 1;
+                for (x, y) in lo.iter_mut().zip(hi) {
+                    let t = mul(*y, wk, p);
+                    *y = sub(*x, t, p);
+                    *x = add(*x, t, p);
+                    wk = mul(wk, w, p);
+                }
+            }
+            half *= 2;
+        }
+    }
+}
+
+/// The first `count` NTT primes, in decreasing order.
+///
+/// Primes are searched once and cached.
+///
+/// # Parameters
+///
+/// - `count`: Number of primes.
+pub fn ntt_primes(count: usize) -> Vec<NttPrime> {
+    static PRIMES: Mutex<Vec<NttPrime>> = Mutex::new(Vec::new());
+
+    let mut primes = PRIMES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut k = match primes.last() {
+        Some(prime) => prime.p >> TWO_ADICITY,
+        None => 1 << (PRIME_BITS - TWO_ADICITY),
+    };
+    while primes.len() < count {
+        k -= 1;
+        let p = (k << TWO_ADICITY) | 1;
+        if Integer::from(p).is_probably_prime(30) == rug::integer::IsPrime::No {
+            continue;
+        }
+
+        // x^k has order 2^32 iff its 2^31-th power is -1
+        let mut x = 2;
+        let root = loop {
+            let w = pow(x, k, p);
+            if pow(w, 1 << (TWO_ADICITY - 1), p) == p - 1 {
+                break w;
+            }
+            x += 1;
+        };
+        primes.push(NttPrime { p, root });
+    }
+    primes[..count].to_vec()
+}
+
+/// Product of two polynomials modulo n.
+///
+/// Coefficients are in ascending degree, the result has `a.len() + b.len() - 1`
+/// coefficients in `[0, n)`, or none if a polynomial is empty.
+///
+/// # Parameters
+///
+/// - `a`: First polynomial.
+/// - `b`: Second polynomial.
+/// - `n`: Modulus, positive.
+///
+/// # Panics
+///
+/// Panics if the product has more than `2^32` coefficients.
+pub fn mul_mod(a: &[Integer], b: &[Integer], n: &Integer) -> Vec<Integer> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+
+    let reduce = |c: &Integer| {
+        let c = Integer::from(c % n);
+        if c < 0 {
+            c + n
+        } else {
+            c
+        }
+    };
+    let a: Vec<Integer> = a.iter().map(reduce).collect();
+    let b: Vec<Integer> = b.iter().map(reduce).collect();
+
+    // Coefficients of the product over the integers are below min(|a|, |b|) * n^2
+    let bound = Integer::from(n.square_ref()) * a.len().min(b.len()) as u64;
+    let mut primes = Vec::new();
+    let mut modulus = Integer::from(1);
+    while modulus <= bound {
+        primes = ntt_primes(primes.len() + 1);
+        modulus *= primes[primes.len() - 1].p;
+    }
+    let count = primes.len();
+
+    let residues: Vec<Vec<u64>> = primes
+        .iter()
+        .map(|prime| {
+            let p = Integer::from(prime.p);
+            let residues = |poly: &[Integer]| -> Vec<u64> {
+                poly.iter()
+                    .map(|c| Integer::from(c % &p).to_u64_wrapping())
+                    .collect()
+            };
+            prime.mul(&residues(&a), &residues(&b))
+        })
+        .collect();
+
+    // Garner's algorithm: x = v_0 + v_1 p_0 + v_2 p_0 p_1 + ...
+    let inverses: Vec<u64> = (0..count)
+        .map(|i| {
+            let p = primes[i].p;
+            let prefix = primes[..i].iter().fold(1, |acc, q| mul(acc, q.p % p, p));
+            pow(prefix, p - 2, p)
+        })
+        .collect();
+    let mut digits = vec![0; count];
+    (0..a.len() + b.len() - 1)
+        .map(|j| {
+            for i in 0..count {
+                let p = primes[i].p;
+                let mut x = 0;
+                let mut radix = 1;
+                for (digit, q) in digits.iter().zip(&primes).take(i) {
+                    x = add(x, mul(digit % p, radix, p), p);
+                    radix = mul(radix, q.p % p, p);
+                }
+                digits[i] = mul(sub(residues[i][j], x, p), inverses[i], p);
+            }
+
+            let mut x = Integer::from(digits[count - 1]);
+            for i in (0..count - 1).rev() {
+                x = x * primes[i].p + digits[i];
+            }
+            x % n
+        })
+        .collect()
+}
+
+fn add(a: u64, b: u64, p: u64) -> u64 {
+    let s = a + b;
+    if s >= p {
+        s - p
+    } else {
+        s
+    }
+}
+
+fn sub(a: u64, b: u64, p: u64) -> u64 {
+    if a >= b {
+        a - b
+    } else {
+        a + p - b
+    }
+}
+
+fn mul(a: u64, b: u64, p: u64) -> u64 {
+    ((a as u128 * b as u128) % p as u128) as u64
+}
+
+fn pow(mut base: u64, mut exp: u64, p: u64) -> u64 {
+    let mut result = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul(result, base, p);
+        }
+        base = mul(base, base, p);
+        exp >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Schoolbook product modulo n.
+    fn mul_naive(a: &[Integer], b: &[Integer], n: &Integer) -> Vec<Integer> {
+        let mut c = vec![Integer::new(); a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                c[i + j] += Integer::from(x * y);
+            }
+        }
+        c.into_iter()
+            .map(|x| {
+                let x = x % n;
+                if x < 0 {
+                    x + n
+                } else {
+                    x
+                }
+            })
+            .collect()
+    }
+
+    /// Deterministic pseudorandom polynomial with coefficients below n.
+    fn poly(len: usize, n: &Integer, seed: u64) -> Vec<Integer> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                let mut x = Integer::new();
+                for _ in 0..n.significant_bits() / 32 + 1 {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    x = (x << 32) + (state >> 32);
+                }
+                x % n
+            })
+            .collect()
+    }
+
+    #[test]
+    fn primes() {
+        let primes = ntt_primes(4);
+        assert_eq!(ntt_primes(2), primes[..2]);
+        for prime in primes {
+            let p = prime.modulus();
+            assert!(p < 1 << PRIME_BITS);
+            assert_eq!((p - 1) % (1 << TWO_ADICITY), 0);
+            assert_ne!(
+                Integer::from(p).is_probably_prime(30),
+                rug::integer::IsPrime::No
+            );
+            assert_eq!(pow(prime.root, 1 << TWO_ADICITY, p), 1);
+            assert_eq!(pow(prime.root, 1 << (TWO_ADICITY - 1), p), p - 1);
+        }
+    }
+
+    #[test]
+    fn ntt_product() {
+        let prime = ntt_primes(1)[0];
+        let p = prime.modulus();
+        let a: Vec<u64> = (0..37).map(|i| (i * i * 1_000_003) % p).collect();
+        let b: Vec<u64> = (0..20).map(|i| p - 1 - i).collect();
+
+        let mut expected = vec![0; a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                expected[i + j] = add(expected[i + j], mul(*x, *y, p), p);
+            }
+        }
+        assert_eq!(prime.mul(&a, &b), expected);
+        assert_eq!(prime.mul(&[5], &[7]), [35]);
+        assert!(prime.mul(&[], &b).is_empty());
+    }
+
+    #[test]
+    fn product_modulo_n() {
+        let moduli = [
+            Integer::from(1),
+            Integer::from(7),
+            Integer::from(u64::MAX),
+            Integer::from_str("398883434337287398883434337287398883434337287398883434337287")
+                .unwrap(),
+            (Integer::from(1) << 1000) - 1,
+        ];
+        for (k, n) in moduli.iter().enumerate() {
+            for (la, lb) in [(1, 1), (3, 5), (64, 64), (100, 27)] {
+                let a = poly(la, n, k as u64);
+                let b = poly(lb, n, k as u64 + 100);
+                assert_eq!(mul_mod(&a, &b, n), mul_naive(&a, &b, n), "n = {n}");
+            }
+        }
+    }
+
+    #[test]
+    fn unreduced_coefficients() {
+        let n = Integer::from(1_000_003);
+        let a = [Integer::from(-1), Integer::from(3_000_010)];
+        let b = [Integer::from(2), Integer::from(-5_000_000)];
+        assert_eq!(mul_mod(&a, &b, &n), mul_naive(&a, &b, &n));
+        assert!(mul_mod(&[], &b, &n).is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::ecm::available_threads;
+#[cfg(feature = "rayon")]
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+#[cfg(not(feature = "rayon"))]
+use std::sync::Mutex;
+
+/// Threads running the curves of a [`Factorizer`](crate::Factorizer) concurrently.
+///
+/// Uses a dedicated rayon pool with the `rayon` feature, otherwise scoped
+/// threads of the standard library spawned for each batch of curves,
+/// so the `parallel` feature gets multicore speedups without any dependency.
+pub(crate) struct CurvePool {
+    #[cfg(feature = "rayon")]
+    pool: ThreadPool,
+    #[cfg(not(feature = "rayon"))]
+    threads: usize,
+}
+
+impl CurvePool {
+    /// Pool of `threads` threads, 0 for all available cores.
+    ///
+    /// Returns `None` if the threads cannot be spawned.
+    pub(crate) fn new(threads: usize) -> Option<CurvePool> {
+        let threads = match threads {
+            0 => available_threads(),
+            threads => threads,
+        };
+        #[cfg(feature = "rayon")]
+        return ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .ok()
+            .map(|pool| CurvePool { pool });
+        #[cfg(not(feature = "rayon"))]
+        Some(CurvePool { threads })
+    }
+
+    /// Number of threads of the pool.
+    pub(crate) fn threads(&self) -> usize {
+        #[cfg(feature = "rayon")]
+        return self.pool.current_num_threads();
+        #[cfg(not(feature = "rayon"))]
+        self.threads
+    }
+
+    /// Calls `f` on the items concurrently, until one of the calls returns a result.
+    ///
+    /// Items not yet started once a result is found are skipped, calls already
+    /// running are completed. Returns the number of calls and their results.
+    pub(crate) fn find_any<I: Sync, T: Send>(
+        &self,
+        items: &[I],
+        f: impl Fn(&I) -> Option<T> + Sync,
+    ) -> (u64, Vec<T>) {
+        let done = AtomicBool::new(false);
+        let calls = AtomicU64::new(0);
+        let call = |item: &I| {
+            if done.load(Ordering::Relaxed) {
+                return None;
+            }
+            calls.fetch_add(1, Ordering::Relaxed);
+            let found = f(item);
+            if found.is_some() {
+                done.store(true, Ordering::Relaxed);
+            }
+            found
+        };
+
+        #[cfg(feature = "rayon")]
+        let found = self
+            .pool
+            .install(|| items.par_iter().filter_map(call).collect());
+
+        #[cfg(not(feature = "rayon"))]
+        let found = {
+            let found = Mutex::new(Vec::new());
+            self.for_each(items, |_, item| {
+                if let Some(result) = call(item) {
+                    found.lock().unwrap_or_else(|e| e.into_inner()).push(result);
+                }
+            });
+            found.into_inner().unwrap_or_else(|e| e.into_inner())
+        };
+
+        (calls.into_inner(), found)
+    }
+
+    /// Calls `f` on the items concurrently, returning the first result in the order of the items.
+    ///
+    /// Items after one with a result are skipped, the ones before it are all
+    /// completed, so the result does not depend on the scheduling. Returns the
+    /// index of the item with the result.
+    pub(crate) fn find_first<I: Sync, T: Send>(
+        &self,
+        items: &[I],
+        f: impl Fn(&I) -> Option<T> + Sync,
+    ) -> Option<(usize, T)> {
+        let first = AtomicUsize::new(usize::MAX);
+        let call = |i: usize, item: &I| {
+            if i > first.load(Ordering::Relaxed) {
+                return None;
+            }
+            let found = f(item)?;
+            first.fetch_min(i, Ordering::Relaxed);
+            Some((i, found))
+        };
+
+        #[cfg(feature = "rayon")]
+        return self.pool.install(|| {
+            items
+                .par_iter()
+                .enumerate()
+                .filter_map(|(i, item)| call(i, item))
+                .min_by_key(|(i, _)| *i)
+        });
+
+        #[cfg(not(feature = "rayon"))]
+        {
+            let found = Mutex::new(None);
+            self.for_each(items, |i, item| {
+                if let Some((i, result)) = call(i, item) {
+                    let mut found = found.lock().unwrap_or_else(|e| e.into_inner());
+                    if found.as_ref().is_none_or(|(j, _)| i < *j) {
+                        *found = Some((i, result));
+                    }
+                }
+            });
+            found.into_inner().unwrap_or_else(|e| e.into_inner())
+        }
+    }
+
+    /// Calls `f` with the index of each item on scoped threads, items are taken in order.
+    #[cfg(not(feature = "rayon"))]
+    fn for_each<I: Sync>(&self, items: &[I], f: impl Fn(usize, &I) + Sync) {
+        let next = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads.min(items.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    f(i, item);
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_any() {
+        let pool = CurvePool::new(4).unwrap();
+        assert_eq!(pool.threads(), 4);
+        assert!(CurvePool::new(0).unwrap().threads() >= 1);
+
+        let items: Vec<u64> = (1..=100).collect();
+        let (calls, found) = pool.find_any(&items, |&i| (i % 7 == 0).then_some(i));
+        assert!(!found.is_empty() && calls <= 100);
+        assert!(found.iter().all(|i| i % 7 == 0));
+
+        let (calls, found) = pool.find_any(&items, |_| None::<u64>);
+        assert_eq!((calls, found), (100, Vec::new()));
+    }
+
+    #[test]
+    fn find_first() {
+        let pool = CurvePool::new(4).unwrap();
+        let items: Vec<u64> = (1..=100).collect();
+        for _ in 0..10 {
+            let found = pool.find_first(&items, |&i| (i % 7 == 0).then_some(i * 2));
+            assert_eq!(found, Some((6, 14)));
+        }
+        assert_eq!(pool.find_first(&items, |_| None::<u64>), None);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::ecm::{proper_factor, Stages};
+use rug::Integer;
+
+/// Starting values x0 of the Lucas sequences of [`pp1`], tried in order.
+///
+/// p+1 finds p only if `x0^2 - 4` is not a square modulo p, otherwise it
+/// behaves as p-1: the discriminants 5, 3, 2, 7, 11, 13, 17 and 23 of these
+/// seeds have independent quadratic characters, so about 1/2, 3/4 and 7/8 of
+/// the primes have a seed giving a non-square after one, two and three tries.
+pub const PP1_SEEDS: [u32; 8] = [3, 4, 6, 16, 20, 11, 66, 48];
+
+/// Seeds of [`PP1_SEEDS`] tried by [`pp1`], see [`Factorizer::set_pp1_seeds`](crate::Factorizer::set_pp1_seeds).
+pub const DEFAULT_PP1_SEEDS: usize = 3;
+
+/// Returns a proper factor of n with Williams' p+1 method.
+///
+/// Tries the first [`DEFAULT_PP1_SEEDS`] seeds of [`PP1_SEEDS`] with [`pp1_with_seeds`].
+///
+/// ```
+/// use ecm::pp1;
+/// use rug::Integer;
+///
+/// // p + 1 = 2^4 * 41 * 53 * 151 * 167 * 2339 * 487637
+/// let p = Integer::from_str_radix("1000000000000002607", 10).unwrap();
+/// let n = Integer::from_str_radix("100000000000000000000000000319", 10).unwrap() * &p;
+/// assert_eq!(pp1(&n, 10_000, 1_000_000), Some(p));
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound, no stage 2 is run if it is at most B1.
+pub fn pp1(n: &Integer, b1: u64, b2: u64) -> Option<Integer> {
+    pp1_with_seeds(n, b1, b2, DEFAULT_PP1_SEEDS).map(|(factor, _)| factor)
+}
+
+/// Returns a proper factor of n with Williams' p+1 method, and the seed which found it.
+///
+/// Tries the first `seeds` seeds of [`PP1_SEEDS`] with [`pp1_with_seed`] until one finds a factor.
+///
+/// ```
+/// use ecm::pp1_with_seeds;
+/// use rug::Integer;
+///
+/// // x0^2 - 4 is a square modulo p for x0 = 3, not for x0 = 4
+/// let p = Integer::from_str_radix("1000000000000014499", 10).unwrap();
+/// let n = Integer::from_str_radix("100000000000000000000000000319", 10).unwrap() * &p;
+/// assert_eq!(pp1_with_seeds(&n, 10_000, 1_000_000, 3), Some((p, 4)));
+/// assert_eq!(pp1_with_seeds(&n, 10_000, 1_000_000, 1), None);
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound, no stage 2 is run if it is at most B1.
+/// - `seeds`: Number of seeds tried, at most the length of [`PP1_SEEDS`].
+pub fn pp1_with_seeds(n: &Integer, b1: u64, b2: u64, seeds: usize) -> Option<(Integer, u32)> {
+    if *n < 4 {
+        return None;
+    }
+    if n.is_even() {
+        return Some((Integer::from(2), PP1_SEEDS[0]));
+    }
+    pp1_seeds(n, &Stages::new(b1, b2.max(b1)), seeds)
+}
+
+/// Returns a proper factor of n with Williams' p+1 method, starting from `x0`.
+///
+/// Stage 1 computes the Lucas sequence `v = V_k(x0) (mod n)`, with the same
+/// exponent k as the stage 1 of the curves: if `x0^2 - 4` is not a square
+/// modulo a factor p of n, it finds p when p+1 is B1-smooth. Stage 2 is the
+/// standard continuation: with baby steps `V_j(v)` for the odd j up to d and
+/// giant steps `V_(2md)(v)`, each prime `q = 2md ± j` of `(B1, B2]` adds
+/// `V_(2md)(v) - V_j(v)` to the product, which vanishes modulo p if p+1 is
+/// B1-smooth except for q.
+///
+/// If `x0^2 - 4` is a square modulo p, it finds p when p-1 is smooth instead.
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `B1`: Stage 1 Bound.
+/// - `B2`: Stage 2 Bound, no stage 2 is run if it is at most B1.
+/// - `x0`: Starting value of the sequence, at least 3.
+pub fn pp1_with_seed(n: &Integer, b1: u64, b2: u64, x0: u32) -> Option<Integer> {
+    if *n < 4 || x0 < 3 {
+        return None;
+    }
+    if n.is_even() {
+        return Some(Integer::from(2));
+    }
+    pp1_stages(n, &Stages::new(b1, b2.max(b1)), x0)
+}
+
+/// Runs p+1 from the first `seeds` seeds of [`PP1_SEEDS`] with precomputed stages, see [`pp1_with_seeds`].
+pub(crate) fn pp1_seeds(n: &Integer, stages: &Stages, seeds: usize) -> Option<(Integer, u32)> {
+    (PP1_SEEDS.into_iter().take(seeds))
+        .find_map(|x0| pp1_stages(n, stages, x0).map(|factor| (factor, x0)))
+}
+
+/// Runs stage 1 and stage 2 of p+1 from `x0` with precomputed stages, see [`pp1_with_seed`].
+///
+/// # Parameters
+///
+/// - `n`: Odd number to be factored.
+/// - `stages`: Stage 1 multiplier, width and primes of stage 2.
+/// - `x0`: Starting value of the sequence.
+pub(crate) fn pp1_stages(n: &Integer, stages: &Stages, x0: u32) -> Option<Integer> {
+    // Stage 1
+    let v = lucas(&Integer::from(x0), &stages.k, n);
+    let g = Integer::from(&v - 2).gcd(n);
+    if g != 1 {
+        return proper_factor(g, n);
+    }
+    if stages.b2 <= stages.b1 {
+        return None;
+    }
+
+    // Baby steps V_j(v) for the odd j up to d, from V_(j+2) = V_j * V_2 - V_(j-2)
+    let d = stages.d as u64;
+    let v2 = reduce(Integer::from(v.square_ref()) - 2, n);
+    let mut baby = vec![v.clone(), reduce(Integer::from(&v * &v2) - &v, n)];
+    while (2 * baby.len() as u64) < d {
+        let j = baby.len();
+        let next = reduce(Integer::from(&baby[j - 1] * &v2) - &baby[j - 2], n);
+        baby.push(next);
+    }
+
+    // Giant steps V_(mD)(v), from V_((m+1)D) = V_(mD) * V_D - V_((m-1)D)
+    let two_d = 2 * d;
+    let mut m = stages.b1 / two_d;
+    let giant = lucas(&v, &Integer::from(two_d), n);
+    let mut previous = lucas(&v, &Integer::from(m.saturating_sub(1) * two_d), n);
+    let mut current = lucas(&v, &Integer::from(m * two_d), n);
+
+    let mut product = Integer::from(1);
+    let primes = stages.primes_above(stages.b1, stages.b2);
+    for q in primes.take_while(|&q| q <= stages.b2) {
+        while q > m * two_d + d {
+            let next = reduce(Integer::from(&current * &giant) - &previous, n);
+            previous = std::mem::replace(&mut current, next);
+            m += 1;
+        }
+        let j = q.abs_diff(m * two_d);
+        product = product * Integer::from(&current - &baby[(j / 2) as usize]) % n;
+    }
+    proper_factor(product.gcd(n), n)
+}
+
+/// Returns the Lucas sequence `V_k(x) (mod n)`, with `V_0 = 2`, `V_1 = x`
+/// and `V_(i+1) = x * V_i - V_(i-1)`.
+///
+/// Computed with a ladder on the bits of k, keeping `(V_i, V_(i+1))`.
+fn lucas(x: &Integer, k: &Integer, n: &Integer) -> Integer {
+    let mut low = Integer::from(2);
+    let mut high = x.clone();
+    for i in (0..k.significant_bits()).rev() {
+        let cross = reduce(Integer::from(&low * &high) - x, n);
+        if k.get_bit(i) {
+            high = reduce(Integer::from(high.square_ref()) - 2, n);
+            low = cross;
+        } else {
+            low = reduce(Integer::from(low.square_ref()) - 2, n);
+            high = cross;
+        }
+    }
+    low
+}
+
+/// Returns x modulo n, in `[0, n)`.
+fn reduce(x: Integer, n: &Integer) -> Integer {
+    x.modulo(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pm1;
+    use std::str::FromStr;
+
+    #[test]
+    fn lucas() {
+        // V_i(3): 2, 3, 7, 18, 47, 123, 322
+        let n = Integer::from(1_000_003);
+        for (k, expected) in [2, 3, 7, 18, 47, 123, 322].into_iter().enumerate() {
+            assert_eq!(super::lucas(&3.into(), &k.into(), &n), expected);
+        }
+        assert_eq!(super::lucas(&3.into(), &6.into(), &100.into()), 22);
+    }
+
+    #[test]
+    fn stages() {
+        // p + 1 = 2 * 3^2 * 7 * 17 * 467, p - 1 = 2^3 * 19 * 6581
+        let n = Integer::from(1_000_313) * 100_000_000_000_000_003u64;
+        assert_eq!(pp1(&n, 500, 500), Some(1_000_313.into()));
+        assert_eq!(pp1(&n, 100, 100), None);
+        // 467 is found by stage 2
+        assert_eq!(pp1(&n, 100, 3_000), Some(1_000_313.into()));
+        assert_eq!(pm1(&n, 100, 3_000), None);
+    }
+
+    #[test]
+    fn seeds() {
+        let q = Integer::from_str("100000000000000000000000000319").unwrap();
+        // x0^2 - 4 is a square modulo p for x0 = 3, not for x0 = 4
+        let p = Integer::from_str("1000000000000014499").unwrap();
+        let n = Integer::from(&p * &q);
+        assert_eq!(pp1_with_seed(&n, 10_000, 1_000_000, 3), None);
+        assert_eq!(pp1_with_seed(&n, 10_000, 1_000_000, 4), Some(p.clone()));
+        assert_eq!(pp1(&n, 10_000, 1_000_000), Some(p.clone()));
+        assert_eq!(pm1(&n, 10_000, 1_000_000), None);
+        assert_eq!(pp1_with_seeds(&n, 10_000, 1_000_000, 1), None);
+        assert_eq!(pp1_with_seeds(&n, 10_000, 1_000_000, 2), Some((p, 4)));
+
+        // Squares for the default seeds, not for the fourth one
+        let p = Integer::from_str("1000000000000001879").unwrap();
+        let n = Integer::from(&p * &q);
+        assert_eq!(pp1(&n, 10_000, 1_000_000), None);
+        assert_eq!(pp1_with_seeds(&n, 10_000, 1_000_000, 4), Some((p, 16)));
+        for n in [0, 1, 3, 1_000_003] {
+            assert_eq!(pp1(&Integer::from(n), 1_000, 10_000), None);
+        }
+        assert_eq!(pp1_with_seed(&Integer::from(15), 1_000, 10_000, 2), None);
+    }
+}
@@ -0,0 +1,146 @@
+/// Standard bounds to find factors of up to a number of decimal digits (t-levels).
+///
+/// Each level carries the stage 1 bound, the stage 2 bound and the expected
+/// number of curves recommended by GMP-ECM, see <https://gitlab.inria.fr/zimmerma/ecm>.
+/// Running all the curves of a level, "running t35", finds a factor of that
+/// size with probability about `1 - 1/e`.
+///
+/// ```
+/// use ecm::{ecm_with_params, BoundPreset, EcmOptions};
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let t20 = BoundPreset::Digits20;
+/// let factors = ecm_with_params(&n, t20.b1(), Some(t20.b2()), t20.curves(), Some(1));
+/// assert_eq!(factors.unwrap().product(), n);
+///
+/// let factors = EcmOptions::new().preset(t20).seed(1).run(&n).unwrap();
+/// assert_eq!(factors.product(), n);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BoundPreset {
+    /// t10: B1 = 2000, B2 = 160000, 35 curves.
+    Digits10,
+    /// t15: B1 = 5000, B2 = 500000, 500 curves.
+    Digits15,
+    /// t20: B1 = 11000, B2 = 1.9e6, 74 curves.
+    Digits20,
+    /// t25: B1 = 5e4, B2 = 1.3e7, 214 curves.
+    Digits25,
+    /// t30: B1 = 2.5e5, B2 = 1.3e8, 430 curves.
+    Digits30,
+    /// t35: B1 = 1e6, B2 = 1e9, 904 curves.
+    Digits35,
+    /// t40: B1 = 3e6, B2 = 5.7e9, 2350 curves.
+    Digits40,
+    /// t45: B1 = 1.1e7, B2 = 3.5e10, 4480 curves.
+    Digits45,
+    /// t50: B1 = 4.4e7, B2 = 2.4e11, 7553 curves.
+    Digits50,
+    /// t55: B1 = 1.1e8, B2 = 7.8e11, 17769 curves.
+    Digits55,
+    /// t60: B1 = 2.6e8, B2 = 3.2e12, 42017 curves.
+    Digits60,
+    /// t65: B1 = 8.5e8, B2 = 1.6e13, 69408 curves.
+    Digits65,
+}
+
+impl BoundPreset {
+    /// All the levels, by increasing size of factors.
+    pub const ALL: [BoundPreset; 12] = [
+        BoundPreset::Digits10,
+        BoundPreset::Digits15,
+        BoundPreset::Digits20,
+        BoundPreset::Digits25,
+        BoundPreset::Digits30,
+        BoundPreset::Digits35,
+        BoundPreset::Digits40,
+        BoundPreset::Digits45,
+        BoundPreset::Digits50,
+        BoundPreset::Digits55,
+        BoundPreset::Digits60,
+        BoundPreset::Digits65,
+    ];
+
+    /// Smallest level covering factors of `digits` decimal digits, the last one beyond 65 digits.
+    ///
+    /// # Parameters
+    ///
+    /// - `digits`: Number of decimal digits of the factors.
+    pub fn for_digits(digits: usize) -> BoundPreset {
+        BoundPreset::ALL
+            .into_iter()
+            .find(|preset| digits <= preset.digits())
+            .unwrap_or(BoundPreset::Digits65)
+    }
+
+    /// Number of decimal digits of the factors targeted by the level.
+    pub fn digits(self) -> usize {
+        10 + 5 * self as usize
+    }
+
+    /// Stage 1 bound, stage 2 bound and expected number of curves.
+    pub fn params(self) -> (u64, u64, u64) {
+        match self {
+            BoundPreset::Digits10 => (2_000, 160_000, 35),
+            BoundPreset::Digits15 => (5_000, 500_000, 500),
+            BoundPreset::Digits20 => (11_000, 1_900_000, 74),
+            BoundPreset::Digits25 => (50_000, 13_000_000, 214),
+            BoundPreset::Digits30 => (250_000, 130_000_000, 430),
+            BoundPreset::Digits35 => (1_000_000, 1_000_000_000, 904),
+            BoundPreset::Digits40 => (3_000_000, 5_700_000_000, 2350),
+            BoundPreset::Digits45 => (11_000_000, 35_000_000_000, 4480),
+            BoundPreset::Digits50 => (44_000_000, 240_000_000_000, 7553),
+            BoundPreset::Digits55 => (110_000_000, 780_000_000_000, 17769),
+            BoundPreset::Digits60 => (260_000_000, 3_200_000_000_000, 42017),
+            BoundPreset::Digits65 => (850_000_000, 16_000_000_000_000, 69408),
+        }
+    }
+
+    /// Stage 1 bound.
+    pub fn b1(self) -> u64 {
+        self.params().0
+    }
+
+    /// Stage 2 bound.
+    pub fn b2(self) -> u64 {
+        self.params().1
+    }
+
+    /// Expected number of curves to find a factor of [`BoundPreset::digits`] digits.
+    pub fn curves(self) -> u64 {
+        self.params().2
+    }
+
+    /// Next level, `None` after [`BoundPreset::Digits65`].
+    pub fn next(self) -> Option<BoundPreset> {
+        BoundPreset::ALL.get(self as usize + 1).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        assert_eq!(
+            BoundPreset::Digits35.params(),
+            (1_000_000, 1_000_000_000, 904)
+        );
+        assert_eq!(BoundPreset::Digits35.digits(), 35);
+        assert_eq!(BoundPreset::Digits65.digits(), 65);
+        assert_eq!(BoundPreset::for_digits(1), BoundPreset::Digits10);
+        assert_eq!(BoundPreset::for_digits(31), BoundPreset::Digits35);
+        assert_eq!(BoundPreset::for_digits(35), BoundPreset::Digits35);
+        assert_eq!(BoundPreset::for_digits(200), BoundPreset::Digits65);
+        assert_eq!(BoundPreset::Digits60.next(), Some(BoundPreset::Digits65));
+        assert_eq!(BoundPreset::Digits65.next(), None);
+
+        for pair in BoundPreset::ALL.windows(2) {
+            assert!(pair[0] < pair[1] && pair[0].b1() < pair[1].b1());
+            assert!(pair[0].b2() < pair[1].b2());
+            assert_eq!(BoundPreset::for_digits(pair[1].digits()), pair[1]);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::ecm::pow_mod;
+use primal::Primes;
+use rug::{integer::IsPrime, Integer};
+
+/// Trial division bound used to factor n-1 in Pocklington's test.
+const POCKLINGTON_TRIAL_BOUND: usize = 1_000_000;
+
+/// Number of bases tried per prime factor in Pocklington's test.
+const POCKLINGTON_MAX_BASES: u32 = 100;
+
+/// Standard a cofactor must meet to be considered prime by the driver.
+///
+/// The driver stops factoring a cofactor as soon as it passes the test,
+/// stronger standards trade speed for confidence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimalityStandard {
+    /// Single strong probable prime test to base 2.
+    Heuristic,
+    /// Baillie-PSW probable prime test, without known counterexample.
+    Bpsw,
+    /// Baillie-PSW test followed by Miller-Rabin rounds, as done by GMP's `mpz_probab_prime_p`
+    /// (rounds above 24 add `rounds - 24` Miller-Rabin tests).
+    MillerRabin(u32),
+    /// Proven primality, using Pocklington's theorem for numbers above 2^64.
+    ///
+    /// Probable primes that cannot be proven make the driver fail with
+    /// [`Error::PrimalityNotProven`](crate::Error::PrimalityNotProven).
+    Proven,
+}
+
+impl Default for PrimalityStandard {
+    fn default() -> Self {
+        PrimalityStandard::MillerRabin(1000)
+    }
+}
+
+impl PrimalityStandard {
+    /// Tests the primality of n.
+    ///
+    /// Returns [`IsPrime::Yes`] if n is proven prime, [`IsPrime::Probably`]
+    /// if n passed the test, and [`IsPrime::No`] otherwise.
+    /// With [`PrimalityStandard::Proven`], [`IsPrime::Probably`] means that
+    /// n is very likely prime but no proof was found.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be tested.
+    pub fn test(&self, n: &Integer) -> IsPrime {
+        match self {
+            PrimalityStandard::Heuristic => strong_probable_prime_base_2(n),
+            PrimalityStandard::Bpsw => n.is_probably_prime(24),
+            PrimalityStandard::MillerRabin(rounds) => n.is_probably_prime(*rounds),
+            PrimalityStandard::Proven => match n.is_probably_prime(24) {
+                IsPrime::No => IsPrime::No,
+                _ if prove_prime(n) => IsPrime::Yes,
+                _ => IsPrime::Probably,
+            },
+        }
+    }
+
+    /// Returns true if n meets the standard.
+    pub fn accepts(&self, n: &Integer) -> bool {
+        match self.test(n) {
+            IsPrime::Yes => true,
+            IsPrime::Probably => *self != PrimalityStandard::Proven,
+            IsPrime::No => false,
+        }
+    }
+}
+
+/// Strong probable prime test to base 2.
+fn strong_probable_prime_base_2(n: &Integer) -> IsPrime {
+    if *n < 2 {
+        return IsPrime::No;
+    }
+    if *n < 4 {
+        return IsPrime::Yes;
+    }
+    if n.is_even() {
+        return IsPrime::No;
+    }
+
+    let n_minus_one = Integer::from(n - 1);
+    // n - 1 is positive, so it has a set bit
+    let s = n_minus_one.find_one(0).unwrap_or(0);
+    let d = Integer::from(&n_minus_one >> s);
+
+    let mut x = pow_mod(&Integer::from(2), &d, n);
+    if x == 1 || x == n_minus_one {
+        return IsPrime::Probably;
+    }
+    for _ in 1..s {
+        x = x.square() % n;
+        if x == n_minus_one {
+            return IsPrime::Probably;
+        }
+    }
+    IsPrime::No
+}
+
+/// Proves the primality of n.
+///
+/// Below 2^64 the Baillie-PSW test is deterministic. Above, Pocklington's
+/// theorem is used: if n-1 = F*R with F > sqrt(n) fully factored and, for each
+/// prime q dividing F, there is a base a such that a^(n-1) = 1 (mod n) and
+/// gcd(a^((n-1)/q) - 1, n) = 1, then n is prime.
+/// n-1 is factored using trial division and, recursively, a large prime cofactor.
+///
+/// Returns false if n is composite or if no proof was found.
+fn prove_prime(n: &Integer) -> bool {
+    if n.significant_bits() <= 64 {
+        return n.is_probably_prime(24) != IsPrime::No;
+    }
+    if n.is_probably_prime(24) == IsPrime::No {
+        return false;
+    }
+
+    let n_minus_one = Integer::from(n - 1);
+    let mut rest = n_minus_one.clone();
+    let mut primes = Vec::new();
+    for p in Primes::all().take_while(|&p| p < POCKLINGTON_TRIAL_BOUND) {
+        if rest.is_divisible_u(p as u32) {
+            let p = Integer::from(p);
+            while rest.is_divisible(&p) {
+                rest /= &p;
+            }
+            primes.push(p);
+        }
+    }
+    if rest > 1 && prove_prime(&rest) {
+        primes.push(rest);
+        rest = Integer::from(1);
+    }
+
+    // F = (n-1) / rest must be greater than sqrt(n)
+    let f = Integer::from(&n_minus_one / &rest);
+    if f.square() <= *n {
+        return false;
+    }
+
+    primes.iter().all(|q| {
+        let e = Integer::from(&n_minus_one / q);
+        (2..POCKLINGTON_MAX_BASES + 2).any(|a| {
+            let a = Integer::from(a);
+            pow_mod(&a, &n_minus_one, n) == 1 && (pow_mod(&a, &e, n) - 1u32).gcd(n) == 1
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn heuristic() {
+        let standard = PrimalityStandard::Heuristic;
+        assert!(standard.accepts(&Integer::from(2)));
+        assert!(standard.accepts(&Integer::from(2802377)));
+        assert!(!standard.accepts(&Integer::from(1)));
+        assert!(!standard.accepts(&Integer::from(3233)));
+        // Strong pseudoprime to base 2
+        assert!(standard.accepts(&Integer::from(2047)));
+        assert!(!PrimalityStandard::Bpsw.accepts(&Integer::from(2047)));
+    }
+
+    #[test]
+    fn miller_rabin() {
+        let standard = PrimalityStandard::MillerRabin(30);
+        let p = Integer::from_str("21472883178031195225853317139").unwrap();
+        assert_eq!(standard.test(&p), IsPrime::Probably);
+        assert_eq!(standard.test(&Integer::from(&p * 3)), IsPrime::No);
+    }
+
+    #[test]
+    fn proven() {
+        let standard = PrimalityStandard::Proven;
+        assert_eq!(standard.test(&Integer::from(2802377)), IsPrime::Yes);
+
+        // 2^127 - 1, n-1 has a large prime factor 77158673929
+        let m127 = (Integer::from(1) << 127) - 1u32;
+        assert_eq!(standard.test(&m127), IsPrime::Yes);
+        assert!(standard.accepts(&m127));
+
+        let composite = Integer::from(&m127 * 3);
+        assert_eq!(standard.test(&composite), IsPrime::No);
+    }
+
+    #[test]
+    fn unprovable() {
+        // p-1 = 66 * q1 * q2 with q1, q2 primes above the trial division bound
+        let p = Integer::from_str("79789104097613371762829419").unwrap();
+        assert_eq!(PrimalityStandard::Proven.test(&p), IsPrime::Probably);
+        assert!(!PrimalityStandard::Proven.accepts(&p));
+        assert!(PrimalityStandard::Bpsw.accepts(&p));
+    }
+}
@@ -0,0 +1,446 @@
+use primal::Primes;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// First bytes of a prime table file.
+const MAGIC: &[u8; 8] = b"ECMPRIME";
+
+/// Version of the file format, increased on incompatible changes.
+pub const PRIME_TABLE_VERSION: u32 = 1;
+
+/// Size of the header: magic, version, reserved word, low, high and count.
+const HEADER_LEN: usize = 40;
+
+/// Error occured while loading a prime table.
+#[derive(thiserror::Error, Debug)]
+pub enum PrimeTableError {
+    /// The file could not be read.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// The file is not a prime table.
+    #[error("Not a prime table")]
+    InvalidMagic,
+    /// The file was written by an incompatible version.
+    #[error("Unsupported prime table version {0}")]
+    UnsupportedVersion(u32),
+    /// The size of the file does not match its header.
+    #[error("Truncated prime table")]
+    Truncated,
+    /// The primes are not in increasing order, or not in the range of the header.
+    #[error("Primes out of order or out of range")]
+    InvalidPrimes,
+}
+
+/// Sorted table of the primes in a range, shareable between processes.
+///
+/// The table is generated once, written with [`PrimeTable::write`], then
+/// opened by each worker with [`PrimeTable::open`]. On Linux, Android and macOS
+/// the file is memory-mapped read-only, so all workers share the same pages.
+///
+/// # File format
+///
+/// All integers are little-endian.
+///
+/// | Offset | Size | Content |
+/// |--------|------|---------|
+/// | 0 | 8 | Magic `ECMPRIME` |
+/// | 8 | 4 | Version, [`PRIME_TABLE_VERSION`] |
+/// | 12 | 4 | Reserved, 0 |
+/// | 16 | 8 | Lower bound of the range |
+/// | 24 | 8 | Upper bound of the range |
+/// | 32 | 8 | Number of primes |
+/// | 40 | 8 per prime | Primes, in increasing order |
+///
+/// ```
+/// use ecm::{EcmOptions, PrimeTable};
+/// use rug::Integer;
+/// use std::sync::Arc;
+///
+/// let path = std::env::temp_dir().join(format!("ecm-doc-{}.primes", std::process::id()));
+/// PrimeTable::for_bounds(2_000, 160_000).write(&path).unwrap();
+///
+/// let table = Arc::new(PrimeTable::open(&path).unwrap());
+/// let factors = EcmOptions::new()
+///     .b1(2_000)
+///     .b2(160_000)
+///     .prime_table(table)
+///     .run(&Integer::from(398883434337287u64))
+///     .unwrap();
+/// assert_eq!(factors.len(), 2);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PrimeTable {
+    low: u64,
+    high: u64,
+    storage: Storage,
+}
+
+#[derive(Debug)]
+enum Storage {
+    Owned(Vec<u64>),
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android", target_os = "macos"),
+        target_endian = "little"
+    ))]
+    Mapped(Mapping),
+}
+
+impl PrimeTable {
+    /// Generates the table of the primes in `[low, high]`.
+    ///
+    /// # Parameters
+    ///
+    /// - `low`: Lower bound of the range.
+    /// - `high`: Upper bound of the range.
+    pub fn generate(low: u64, high: u64) -> PrimeTable {
+        let primes = Primes::all()
+            .map(|p| p as u64)
+            .skip_while(|&p| p < low)
+            .take_while(|&p| p <= high)
+            .collect();
+        PrimeTable {
+            low,
+            high,
+            storage: Storage::Owned(primes),
+        }
+    }
+
+    /// Generates the table of the primes used by stage 2 with the given bounds.
+    ///
+    /// # Parameters
+    ///
+    /// - `B1`: Stage 1 Bound.
+    /// - `B2`: Stage 2 Bound.
+    pub fn for_bounds(b1: u64, b2: u64) -> PrimeTable {
+        // Stage 2 steps by 2*sqrt(B2) from B1 - 1 and may overshoot B2 by one step
+        PrimeTable::generate(b1.saturating_sub(1), b2 + 2 * b2.isqrt())
+    }
+
+    /// Opens a table written by [`PrimeTable::write`].
+    ///
+    /// The primes are checked to be in increasing order and in the range of the
+    /// header, which reads the whole file once. Their primality is not checked.
+    ///
+    /// The file must not be modified while the table is in use: on Linux, Android
+    /// and macOS, the primes are read from a memory mapping of the file, which
+    /// may reflect later writes, breaking the order checked above, and a
+    /// truncation makes reading the primes past the new end crash the process.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: Path of the table.
+    pub fn open(path: impl AsRef<Path>) -> Result<PrimeTable, PrimeTableError> {
+        let file = File::open(path)?;
+
+        #[cfg(all(
+            any(target_os = "linux", target_os = "android", target_os = "macos"),
+            target_endian = "little"
+        ))]
+        {
+            let mapping = Mapping::new(&file)?;
+            let (low, high) = parse_header(mapping.bytes())?;
+            PrimeTable {
+                low,
+                high,
+                storage: Storage::Mapped(mapping),
+            }
+            .validated()
+        }
+
+        #[cfg(not(all(
+            any(target_os = "linux", target_os = "android", target_os = "macos"),
+            target_endian = "little"
+        )))]
+        {
+            use std::io::Read;
+
+            let mut bytes = Vec::new();
+            { file }.read_to_end(&mut bytes)?;
+            let (low, high) = parse_header(&bytes)?;
+            let primes = bytes[HEADER_LEN..]
+                .chunks_exact(8)
+                .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+                .collect();
+            PrimeTable {
+                low,
+                high,
+                storage: Storage::Owned(primes),
+            }
+            .validated()
+        }
+    }
+
+    /// Returns the table if its primes are increasing and in `[low, high]`, as stage 2 expects.
+    fn validated(self) -> Result<PrimeTable, PrimeTableError> {
+        let primes = self.primes();
+        let increasing = primes.windows(2).all(|pair| pair[0] < pair[1]);
+        let in_range = self.low <= self.high
+            && primes.first().is_none_or(|&p| p >= self.low)
+            && primes.last().is_none_or(|&p| p <= self.high);
+        match increasing && in_range {
+            true => Ok(self),
+            false => Err(PrimeTableError::InvalidPrimes),
+        }
+    }
+
+    /// Writes the table to `path`.
+    ///
+    /// The table is written to a temporary file which is then renamed,
+    /// so workers never open a partially written table.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: Path of the table.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&PRIME_TABLE_VERSION.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&self.low.to_le_bytes())?;
+        writer.write_all(&self.high.to_le_bytes())?;
+        writer.write_all(&(self.primes().len() as u64).to_le_bytes())?;
+        for p in self.primes() {
+            writer.write_all(&p.to_le_bytes())?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+
+        std::fs::rename(&tmp, path)
+    }
+
+    /// Lower bound of the range.
+    pub fn low(&self) -> u64 {
+        self.low
+    }
+
+    /// Upper bound of the range.
+    pub fn high(&self) -> u64 {
+        self.high
+    }
+
+    /// Primes of the range, in increasing order.
+    pub fn primes(&self) -> &[u64] {
+        match &self.storage {
+            Storage::Owned(primes) => primes,
+            #[cfg(all(
+                any(target_os = "linux", target_os = "android", target_os = "macos"),
+                target_endian = "little"
+            ))]
+            Storage::Mapped(mapping) => mapping.primes(),
+        }
+    }
+
+    /// Returns true if the table contains all primes in `[low, high]`.
+    pub fn covers(&self, low: u64, high: u64) -> bool {
+        self.low <= low && high <= self.high
+    }
+}
+
+/// Validates the header and returns the bounds of the range.
+fn parse_header(bytes: &[u8]) -> Result<(u64, u64), PrimeTableError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(PrimeTableError::Truncated);
+    }
+    if &bytes[..8] != MAGIC {
+        return Err(PrimeTableError::InvalidMagic);
+    }
+
+    let word = |offset: usize| {
+        let mut word = [0; 8];
+        word.copy_from_slice(&bytes[offset..offset + 8]);
+        u64::from_le_bytes(word)
+    };
+    let version = word(8) as u32;
+    if version != PRIME_TABLE_VERSION {
+        return Err(PrimeTableError::UnsupportedVersion(version));
+    }
+
+    let count = word(32);
+    let expected = count
+        .checked_mul(8)
+        .and_then(|len| len.checked_add(HEADER_LEN as u64));
+    if expected != Some(bytes.len() as u64) {
+        return Err(PrimeTableError::Truncated);
+    }
+    Ok((word(16), word(24)))
+}
+
+/// Read-only memory mapping of a whole file.
+#[cfg(all(
+    any(target_os = "linux", target_os = "android", target_os = "macos"),
+    target_endian = "little"
+))]
+#[derive(Debug)]
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is read-only and owned by the table
+#[cfg(all(
+    any(target_os = "linux", target_os = "android", target_os = "macos"),
+    target_endian = "little"
+))]
+unsafe impl Send for Mapping {}
+#[cfg(all(
+    any(target_os = "linux", target_os = "android", target_os = "macos"),
+    target_endian = "little"
+))]
+unsafe impl Sync for Mapping {}
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "android", target_os = "macos"),
+    target_endian = "little"
+))]
+impl Mapping {
+    fn new(file: &File) -> Result<Mapping, PrimeTableError> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = file.metadata()?.len() as usize;
+        // Empty mappings are not allowed
+        if len < HEADER_LEN {
+            return Err(PrimeTableError::Truncated);
+        }
+
+        // Private and read-only, the table never writes to the file
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(Mapping { ptr, len })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    fn primes(&self) -> &[u64] {
+        // Mappings are page aligned and the header is a multiple of 8 bytes,
+        // the length was validated by `parse_header`
+        unsafe {
+            std::slice::from_raw_parts(
+                (self.ptr as *const u8).add(HEADER_LEN) as *const u64,
+                (self.len - HEADER_LEN) / 8,
+            )
+        }
+    }
+}
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "android", target_os = "macos"),
+    target_endian = "little"
+))]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, path::PathBuf};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ecm-{}-{name}.primes", std::process::id()))
+    }
+
+    #[test]
+    fn generate() {
+        let table = PrimeTable::generate(10, 30);
+        assert_eq!(table.primes(), [11, 13, 17, 19, 23, 29]);
+        assert!(table.covers(10, 30));
+        assert!(!table.covers(9, 30));
+
+        let table = PrimeTable::for_bounds(100, 2_000);
+        assert_eq!(table.low(), 99);
+        assert_eq!(table.high(), 2_088);
+        assert_eq!(table.primes()[0], 101);
+    }
+
+    #[test]
+    fn write_and_open() {
+        let path = temp_path("roundtrip");
+        let table = PrimeTable::generate(1_000, 100_000);
+        table.write(&path).unwrap();
+
+        let opened = PrimeTable::open(&path).unwrap();
+        assert_eq!(opened.low(), 1_000);
+        assert_eq!(opened.high(), 100_000);
+        assert_eq!(opened.primes(), table.primes());
+
+        // Shared between threads
+        let opened = std::sync::Arc::new(opened);
+        let clone = opened.clone();
+        std::thread::spawn(move || assert_eq!(clone.primes().len(), 9_424))
+            .join()
+            .unwrap();
+
+        drop(opened);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_files() {
+        let path = temp_path("invalid");
+        let mut bytes = Vec::new();
+        PrimeTable::generate(0, 100).write(&path).unwrap();
+        File::open(&path).unwrap().read_to_end(&mut bytes).unwrap();
+
+        let open = |content: &[u8]| {
+            std::fs::write(&path, content).unwrap();
+            PrimeTable::open(&path)
+        };
+        assert!(matches!(open(&[]), Err(PrimeTableError::Truncated)));
+        assert!(matches!(
+            open(&bytes[..bytes.len() - 1]),
+            Err(PrimeTableError::Truncated)
+        ));
+
+        let mut other = bytes.clone();
+        other[0] = b'X';
+        assert!(matches!(open(&other), Err(PrimeTableError::InvalidMagic)));
+
+        // Out of order, then out of range
+        let mut other = bytes.clone();
+        other[HEADER_LEN..HEADER_LEN + 8].copy_from_slice(&5u64.to_le_bytes());
+        assert!(matches!(open(&other), Err(PrimeTableError::InvalidPrimes)));
+        let mut other = bytes.clone();
+        other[24..32].copy_from_slice(&50u64.to_le_bytes());
+        assert!(matches!(open(&other), Err(PrimeTableError::InvalidPrimes)));
+
+        let mut other = bytes.clone();
+        other[8] = 2;
+        assert!(matches!(
+            open(&other),
+            Err(PrimeTableError::UnsupportedVersion(2))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            PrimeTable::open(&path),
+            Err(PrimeTableError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+/// Nice value of background threads.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const BACKGROUND_NICE: libc::c_int = 19;
+
+/// Operating system priority of the thread running the factorization.
+///
+/// Lowering the priority lets long factorizations run on a workstation
+/// without degrading interactive use. The priority is applied on a best
+/// effort basis, platforms without support run at normal priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// Priority inherited from the caller.
+    #[default]
+    Normal,
+    /// Lowest nice value on Linux and Android, background QoS on macOS.
+    Background,
+    /// Idle scheduling class on Linux and Android: only runs when the CPU
+    /// would otherwise be idle. Same as [`Priority::Background`] elsewhere.
+    Idle,
+}
+
+impl Priority {
+    /// Applies the priority to the calling thread.
+    ///
+    /// Returns true if the priority was changed.
+    pub(crate) fn apply(self) -> bool {
+        match self {
+            Priority::Normal => false,
+            Priority::Background => lower_thread_priority(),
+            Priority::Idle => idle_thread_priority() || lower_thread_priority(),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn lower_thread_priority() -> bool {
+    // On Linux the nice value of `who = 0` is the one of the calling thread
+    unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, BACKGROUND_NICE) == 0 }
+}
+
+#[cfg(target_os = "macos")]
+fn lower_thread_priority() -> bool {
+    unsafe { libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, libc::PRIO_DARWIN_BG) == 0 }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn lower_thread_priority() -> bool {
+    false
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn idle_thread_priority() -> bool {
+    let param = libc::sched_param { sched_priority: 0 };
+    unsafe { libc::sched_setscheduler(0, libc::SCHED_IDLE, &param) == 0 }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn idle_thread_priority() -> bool {
+    false
+}
+
+/// Time to sleep after working for `busy` to use at most `cpu_percent` of a CPU.
+///
+/// # Parameters
+///
+/// - `busy`: Time spent working.
+/// - `cpu_percent`: Target CPU usage, clamped to `1..=100`.
+pub(crate) fn throttle_delay(busy: Duration, cpu_percent: u8) -> Duration {
+    let cpu_percent = cpu_percent.clamp(1, 100) as u32;
+    busy * (100 - cpu_percent) / cpu_percent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttle() {
+        let busy = Duration::from_millis(30);
+        assert_eq!(throttle_delay(busy, 100), Duration::ZERO);
+        assert_eq!(throttle_delay(busy, 25), Duration::from_millis(90));
+        assert_eq!(throttle_delay(busy, 0), Duration::from_millis(2970));
+    }
+
+    #[test]
+    fn background_thread() {
+        assert!(!Priority::Normal.apply());
+        std::thread::spawn(|| Priority::Idle.apply())
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+use rug::Integer;
+use std::{collections::BTreeMap, f64::consts::LOG10_2, time::Duration};
+
+/// Progress of a factorization, at two levels.
+///
+/// The composite level counts the curves run on the composite currently factored,
+/// the job level measures the digits of n which are still not factored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Curves run on the current composite.
+    pub curve: u64,
+    /// Maximum number of curves run on the current composite.
+    pub max_curves: u64,
+    /// Curves run on all the composites, including the ones of a restored run.
+    pub total_curves: u64,
+    /// Digits of the current composite.
+    pub composite_digits: usize,
+    /// Digits of the composites left to factor, with their multiplicity.
+    pub remaining_digits: usize,
+    /// Digits of n.
+    pub total_digits: usize,
+}
+
+impl Progress {
+    /// Fraction of the curves run on the current composite, in `[0, 1]`.
+    pub fn composite_fraction(&self) -> f64 {
+        if self.max_curves == 0 {
+            1.0
+        } else {
+            (self.curve as f64 / self.max_curves as f64).min(1.0)
+        }
+    }
+
+    /// Fraction of the digits of n already factored, in `[0, 1]`.
+    pub fn job_fraction(&self) -> f64 {
+        if self.total_digits == 0 {
+            1.0
+        } else {
+            1.0 - (self.remaining_digits as f64 / self.total_digits as f64).min(1.0)
+        }
+    }
+}
+
+/// Estimated completion of a factorization, see [`Factorizer::estimate`](crate::Factorizer::estimate).
+///
+/// The curves left are the ones of the schedule of the current composite and,
+/// for each other composite, the curves expected to find a factor of half its
+/// digits with the default bounds, as its smallest factor has at most that many.
+/// The estimate is pessimistic: the factorization usually ends sooner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    /// Curves run so far, including the ones of a restored run.
+    pub curves_run: u64,
+    /// Curves expected to be left.
+    pub curves_left: u64,
+    /// Time left, from the average time of the curves run so far, `None` before the first one.
+    pub eta: Option<Duration>,
+}
+
+impl Estimate {
+    /// Estimated fraction of the factorization done, in `[0, 1]`.
+    pub fn fraction(&self) -> f64 {
+        match self.curves_run + self.curves_left {
+            0 => 1.0,
+            total => self.curves_run as f64 / total as f64,
+        }
+    }
+
+    /// Estimated percentage of the factorization done, in `[0, 100]`.
+    pub fn percent(&self) -> f64 {
+        100.0 * self.fraction()
+    }
+}
+
+/// Progress of a factorization, saved by [`Factorizer::resume_state`](crate::Factorizer::resume_state).
+///
+/// Restored with [`EcmOptions::resume_from`](crate::EcmOptions::resume_from),
+/// the progress reports of the resumed run continue where the saved run left off.
+///
+/// ```
+/// use ecm::{EcmOptions, StepOutcome};
+/// use rug::Integer;
+/// use std::time::Duration;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let options = EcmOptions::new().b1(2_000).b2(160_000).max_curves(100);
+/// let mut factorizer = options.factorizer(&n).unwrap();
+/// // Trial division, then the stages and one curve
+/// factorizer.step(Duration::ZERO).unwrap();
+/// factorizer.step(Duration::ZERO).unwrap();
+/// let state = factorizer.resume_state();
+///
+/// let mut resumed = options.resume_from(state.clone()).factorizer(&n).unwrap();
+/// assert_eq!(resumed.progress().total_curves, state.curves_by_b1.values().sum::<u64>());
+/// while resumed.step(Duration::MAX).unwrap() == StepOutcome::Pending {}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResumeState {
+    /// Stage 1 bound of the current composite.
+    pub b1: u64,
+    /// Stage 2 bound of the current composite.
+    pub b2: u64,
+    /// Curves run on the current composite.
+    pub curve: u64,
+    /// Maximum number of curves run on the current composite.
+    pub max_curves: u64,
+    /// Number of times the bounds were raised.
+    pub escalations: u32,
+    /// Curves run on all the composites, by stage 1 bound.
+    pub curves_by_b1: BTreeMap<u64, u64>,
+}
+
+/// Receiver of the progress of a [`Factorizer`](crate::Factorizer), e.g. to update a UI.
+///
+/// ```
+/// use ecm::{EcmOptions, Progress, ProgressSink};
+/// use rug::Integer;
+/// use std::sync::{Arc, Mutex};
+///
+/// #[derive(Default)]
+/// struct Last(Mutex<Option<Progress>>);
+///
+/// impl ProgressSink for Last {
+///     fn progress(&self, progress: &Progress) {
+///         *self.0.lock().unwrap() = Some(*progress);
+///     }
+/// }
+///
+/// let sink = Arc::new(Last::default());
+/// EcmOptions::new()
+///     .progress_sink(sink.clone())
+///     .small_cofactor_bits(0)
+///     .run(&Integer::from(398883434337287u64))
+///     .unwrap();
+/// assert_eq!(sink.0.lock().unwrap().unwrap().job_fraction(), 1.0);
+/// ```
+pub trait ProgressSink: Send + Sync {
+    /// Called after each curve.
+    ///
+    /// # Parameters
+    ///
+    /// - `progress`: Progress of the factorization.
+    fn progress(&self, progress: &Progress);
+}
+
+/// Receiver of the curves run by a factorization, e.g. to drive a GUI, a TUI or a server.
+///
+/// Every method does nothing by default. With the `progress-bar` feature,
+/// it is implemented by `indicatif::ProgressBar`, which shows the curves run
+/// on the current composite.
+///
+/// ```
+/// use ecm::{EcmOptions, ProgressObserver};
+/// use rug::Integer;
+/// use std::sync::{
+///     atomic::{AtomicU64, Ordering},
+///     Arc,
+/// };
+///
+/// #[derive(Default)]
+/// struct Curves(AtomicU64);
+///
+/// impl ProgressObserver for Curves {
+///     fn curve_finished(&self, _curve: u64) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let curves = Arc::new(Curves::default());
+/// EcmOptions::new()
+///     .progress_observer(curves.clone())
+///     .small_cofactor_bits(0)
+///     .run(&Integer::from(398883434337287u64))
+///     .unwrap();
+/// assert!(curves.0.load(Ordering::Relaxed) > 0);
+/// ```
+pub trait ProgressObserver: Send + Sync {
+    /// Called before each curve.
+    ///
+    /// # Parameters
+    ///
+    /// - `curve`: Number of the curve on the current composite, starting at 1.
+    /// - `max_curves`: Maximum number of curves run on the current composite, if known.
+    fn curve_started(&self, curve: u64, max_curves: Option<u64>) {
+        let _ = (curve, max_curves);
+    }
+
+    /// Called after each curve, whether it found a factor or not.
+    ///
+    /// # Parameters
+    ///
+    /// - `curve`: Number of the curve on the current composite, starting at 1.
+    fn curve_finished(&self, curve: u64) {
+        let _ = curve;
+    }
+
+    /// Called by factorizers after each curve with the estimated completion of the factorization.
+    ///
+    /// # Parameters
+    ///
+    /// - `estimate`: Curves left and time left.
+    fn estimate_updated(&self, estimate: &Estimate) {
+        let _ = estimate;
+    }
+
+    /// Called when a composite is split, by a curve or not.
+    ///
+    /// # Parameters
+    ///
+    /// - `factor`: Factor found, not necessarily prime.
+    /// - `composite`: Composite it was found in.
+    fn factor_found(&self, factor: &Integer, composite: &Integer) {
+        let _ = (factor, composite);
+    }
+}
+
+#[cfg(feature = "progress-bar")]
+impl ProgressObserver for indicatif::ProgressBar {
+    fn curve_started(&self, curve: u64, max_curves: Option<u64>) {
+        if let Some(max_curves) = max_curves {
+            self.set_length(max_curves);
+        }
+        self.set_position(curve);
+    }
+}
+
+/// Number of decimal digits of n, without converting it to a string.
+pub(crate) fn decimal_digits(n: &Integer) -> usize {
+    if *n == 0 {
+        return 1;
+    }
+
+    // 2^(bits-1) <= |n| < 2^bits, so the estimate is exact or one below
+    let digits = ((n.significant_bits() - 1) as f64 * LOG10_2) as u32 + 1;
+    if *n.as_abs() >= Integer::from(Integer::u_pow_u(10, digits)) {
+        digits as usize + 1
+    } else {
+        digits as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions() {
+        let progress = Progress {
+            curve: 25,
+            max_curves: 100,
+            total_curves: 25,
+            composite_digits: 20,
+            remaining_digits: 30,
+            total_digits: 40,
+        };
+        assert_eq!(progress.composite_fraction(), 0.25);
+        assert_eq!(progress.job_fraction(), 0.25);
+    }
+
+    #[test]
+    fn estimate() {
+        let estimate = Estimate {
+            curves_run: 30,
+            curves_left: 90,
+            eta: None,
+        };
+        assert_eq!(estimate.fraction(), 0.25);
+        assert_eq!(estimate.percent(), 25.0);
+
+        let done = Estimate {
+            curves_run: 0,
+            curves_left: 0,
+            eta: None,
+        };
+        assert_eq!(done.fraction(), 1.0);
+    }
+
+    #[test]
+    fn digits() {
+        assert_eq!(decimal_digits(&Integer::from(0)), 1);
+        assert_eq!(decimal_digits(&Integer::from(9)), 1);
+        assert_eq!(decimal_digits(&Integer::from(12345)), 5);
+        assert_eq!(decimal_digits(&Integer::from(u64::MAX)), 20);
+        assert_eq!(decimal_digits(&Integer::from(10)), 2);
+        assert_eq!(decimal_digits(&Integer::from(1000)), 4);
+        assert_eq!(decimal_digits(&Integer::from(-999)), 3);
+        for k in 1..100 {
+            let n = Integer::from(Integer::u_pow_u(10, k));
+            assert_eq!(decimal_digits(&n), k as usize + 1);
+            assert_eq!(decimal_digits(&(n - 1u32)), k as usize);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use rug::Integer;
+
+/// Number of steps of the sequence between two gcds.
+const GCD_BATCH: u64 = 128;
+
+/// Steps of Pollard's rho method run on large composites before the curves by default,
+/// enough for factors up to about 10^8.
+pub const DEFAULT_RHO_STEPS: u64 = 1 << 16;
+
+/// Returns a proper factor of n with Pollard's rho method, see [`pollard_brent`].
+///
+/// Finding a factor p takes about `sqrt(p)` steps, whatever the size of n, so it
+/// is much faster than curves for factors just above the bound of trial division.
+/// The sequences of the constants 1, 2 and 3 are tried in turn, sharing `max_steps`,
+/// in case one cycles modulo n itself.
+///
+/// Returns `None` if n is prime, or less than 4, or if no factor was found.
+///
+/// ```
+/// use ecm::pollard_rho;
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// assert_eq!(pollard_rho(&n, 1 << 16), Some(Integer::from(4009823)));
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `max_steps`: Maximum number of steps of the sequences.
+pub fn pollard_rho(n: &Integer, max_steps: u64) -> Option<Integer> {
+    if *n < 4 {
+        return None;
+    }
+    if n.is_even() {
+        return Some(Integer::from(2));
+    }
+    (1..=3).find_map(|c| pollard_brent(n, c, max_steps / 3))
+}
+
+/// Pollard's rho method, with Brent's cycle detection and batched gcds.
+///
+/// Iterates `x -> x^2 + c (mod n)` until the sequence cycles modulo a factor of n,
+/// which takes about `sqrt(p)` steps for the smallest prime factor p.
+/// Returns a proper factor of n, or `None` if none was found within `max_steps`
+/// steps or if the sequence cycled modulo n itself.
+///
+/// # Parameters
+///
+/// - `n`: Odd composite number to be factored.
+/// - `c`: Constant of the sequence, neither 0 nor -2.
+/// - `max_steps`: Maximum number of steps of the sequence.
+pub(crate) fn pollard_brent(n: &Integer, c: u64, max_steps: u64) -> Option<Integer> {
+    let f = |x: &Integer| (Integer::from(x.square_ref()) + c) % n;
+
+    let mut y = Integer::from(2);
+    let mut x = y.clone();
+    let mut ys = y.clone();
+    let mut q = Integer::from(1);
+    let mut g = Integer::from(1);
+    let mut r = 1;
+    let mut steps = 0;
+    while g == 1 {
+        if steps > max_steps {
+            return None;
+        }
+        x.clone_from(&y);
+        for _ in 0..r {
+            y = f(&y);
+        }
+        let mut k = 0;
+        while k < r && g == 1 {
+            // Start of the batch, to backtrack if the batch overshoots
+            ys.clone_from(&y);
+            for _ in 0..GCD_BATCH.min(r - k) {
+                y = f(&y);
+                q = q * Integer::from(&x - &y).abs() % n;
+            }
+            g = Integer::from(q.gcd_ref(n));
+            k += GCD_BATCH;
+        }
+        steps += 2 * r;
+        r *= 2;
+    }
+
+    if &g == n {
+        // All the factors were caught in the same batch, retry one step at a time
+        loop {
+            ys = f(&ys);
+            g = Integer::from(&x - &ys).abs().gcd(n);
+            if g != 1 {
+                break;
+            }
+        }
+    }
+    (&g != n).then_some(g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn factors() {
+        let n = Integer::from(1_000_003u64 * 999_983);
+        let g = pollard_brent(&n, 1, 1 << 20).unwrap();
+        assert!(g == 1_000_003 || g == 999_983);
+
+        let p = Integer::from_str("100327907731").unwrap();
+        let n = Integer::from(&p * 2802377);
+        let g = pollard_brent(&n, 1, 1 << 20).unwrap();
+        assert!(g == p || g == 2802377);
+    }
+
+    #[test]
+    fn rho() {
+        let n = Integer::from_str("100000000000000000000000000319").unwrap() * 100_000_007u64;
+        assert_eq!(pollard_rho(&n, DEFAULT_RHO_STEPS), Some(100_000_007.into()));
+        assert_eq!(pollard_rho(&Integer::from(1 << 20), 10), Some(2.into()));
+        for n in [0, 1, 3, 1_000_003] {
+            assert_eq!(pollard_rho(&Integer::from(n), 1 << 10), None);
+        }
+    }
+
+    #[test]
+    fn budget() {
+        // The smallest factor, about 10^11, needs about 3 * 10^5 steps
+        let n = Integer::from_str("1048843748843747081710817107").unwrap();
+        assert_eq!(pollard_brent(&n, 1, 1 << 10), None);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{ecm::pow_mod, tree, EcmOptions, Error, Factorization};
+use primal::Primes;
+use rug::Integer;
+
+/// Number of small prime bases tried before giving up.
+const MAX_BASES: usize = 100;
+
+/// Recovers the factors `p` and `q` of an RSA modulus from a known keypair.
+///
+/// Since `e*d = 1 (mod lambda(n))`, writing `e*d - 1 = 2^t * r` with r odd,
+/// for most bases g the sequence `g^r, g^(2r), ..., g^(2^t*r) = 1 (mod n)`
+/// contains a non-trivial square root of unity x, and `gcd(x - 1, n)` is a factor of n.
+///
+/// Small primes are used as bases, so the result is deterministic and each base
+/// succeeds with probability at least 1/2.
+///
+/// Returns `(p, q)` with `p <= q`.
+///
+/// # Parameters
+///
+/// - `n`: RSA modulus.
+/// - `e`: Public exponent.
+/// - `d`: Private exponent.
+pub fn factor_from_rsa_exponents(
+    n: &Integer,
+    e: &Integer,
+    d: &Integer,
+) -> Result<(Integer, Integer), Error> {
+    let k: Integer = Integer::from(e * d) - 1;
+    if *n <= 3 || k <= 0 || k.is_odd() {
+        return Err(Error::InvalidRsaKey);
+    }
+
+    // k is positive, so it has a set bit
+    let t = k.find_one(0).unwrap_or(0);
+    let r = k >> t;
+    let n_minus_one = Integer::from(n - 1);
+
+    for g in Primes::all().take(MAX_BASES) {
+        let g = Integer::from(g);
+        if g >= *n {
+            break;
+        }
+
+        let p = g.clone().gcd(n);
+        if p != 1 {
+            return Ok(sorted_pair(n, p));
+        }
+
+        let mut x = pow_mod(&g, &r, n);
+        if x == 1 || x == n_minus_one {
+            continue;
+        }
+
+        for _ in 0..t {
+            let y = x.clone().square() % n;
+            if y == 1 {
+                // x is a non-trivial square root of 1
+                return Ok(sorted_pair(n, Integer::from(&x - 1).gcd(n)));
+            }
+            if y == n_minus_one {
+                break;
+            }
+            x = y;
+        }
+    }
+
+    Err(Error::InvalidRsaKey)
+}
+
+/// Returns `gcd(n_i, product of the other moduli)` for each modulus, e.g. to audit a set of RSA keys.
+///
+/// Moduli sharing a prime factor, generated with a weak random generator, have
+/// a gcd other than 1. The product of all the moduli is computed with a product
+/// tree, then reduced modulo the square of each modulus with a remainder tree,
+/// which is quasi-linear in the total size of the moduli instead of quadratic
+/// for the gcds of all the pairs.
+///
+/// A gcd equal to its modulus means that all its factors are shared, e.g. by
+/// duplicated moduli. Signs are ignored, and zero moduli are skipped with a gcd of 1.
+///
+/// ```
+/// use ecm::batch_gcd;
+/// use rug::Integer;
+///
+/// let [p, q, r, s] = [1_000_003, 1_000_033, 1_000_037, 1_000_039].map(Integer::from);
+/// let moduli = [Integer::from(&p * &q), Integer::from(&p * &r), Integer::from(&r * &s)];
+/// assert_eq!(batch_gcd(&moduli), [p, moduli[1].clone(), r]);
+/// ```
+///
+/// # Parameters
+///
+/// - `moduli`: Numbers to compare.
+pub fn batch_gcd(moduli: &[Integer]) -> Vec<Integer> {
+    let leaves: Vec<Integer> = (moduli.iter())
+        .map(|n| match n.is_zero() {
+            true => Integer::from(1),
+            false => Integer::from(n.abs_ref()),
+        })
+        .collect();
+    let levels = tree::product_tree(leaves);
+
+    // Product modulo the square of each node, from the root down to the leaves
+    let mut remainders = levels.last().cloned().unwrap_or_default();
+    for level in levels.iter().rev().skip(1) {
+        remainders = (level.iter().enumerate())
+            .map(|(i, node)| &remainders[i / 2] % Integer::from(node.square_ref()))
+            .collect();
+    }
+    (levels[0].iter().zip(remainders))
+        .map(|(n, remainder)| (remainder / n).gcd(n))
+        .collect()
+}
+
+/// Factors each modulus, using the factors shared with the others first.
+///
+/// Shared factors are found by [`batch_gcd`], then each modulus is factored
+/// with `options` and its shared factor as a known factor, see
+/// [`EcmOptions::known_factors`]: moduli with a shared factor do not run any curve
+/// once it is divided out, the others fall back to ECM.
+///
+/// ```
+/// use ecm::{factor_moduli, EcmOptions};
+/// use rug::Integer;
+///
+/// // A 30-digit prime shared by two moduli, out of reach of ECM in a test
+/// let p: Integer = "100000000000000000000000000319".parse().unwrap();
+/// let q: Integer = "300000000000000000000000000007".parse().unwrap();
+/// let moduli = [Integer::from(&p * 3_000_017), Integer::from(&p * &q), Integer::from(15)];
+/// let factors = factor_moduli(&moduli, &EcmOptions::new());
+/// assert_eq!(factors[1].as_ref().unwrap().to_string(), format!("{p} * {q}"));
+/// assert_eq!(factors[2].as_ref().unwrap().to_string(), "3 * 5");
+/// ```
+///
+/// # Parameters
+///
+/// - `moduli`: Numbers to be factored.
+/// - `options`: Settings of the factorization of each modulus.
+pub fn factor_moduli(
+    moduli: &[Integer],
+    options: &EcmOptions,
+) -> Vec<Result<Factorization, Error>> {
+    (moduli.iter().zip(batch_gcd(moduli)))
+        .map(
+            |(n, shared)| match shared != 1 && shared != Integer::from(n.abs_ref()) {
+                true => options.clone().known_factors([shared]).run(n),
+                false => options.run(n),
+            },
+        )
+        .collect()
+}
+
+/// Returns `(p, n/p)` ordered by size.
+fn sorted_pair(n: &Integer, p: Integer) -> (Integer, Integer) {
+    let q = Integer::from(n / &p);
+    if p <= q {
+        (p, q)
+    } else {
+        (q, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn textbook_key() {
+        assert_eq!(
+            factor_from_rsa_exponents(&3233.into(), &17.into(), &2753.into()).unwrap(),
+            (53.into(), 61.into())
+        );
+    }
+
+    #[test]
+    fn large_key() {
+        let p = Integer::from_str("100327907731").unwrap();
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q);
+        let e = Integer::from(65537);
+        let phi = Integer::from(&p - 1) * Integer::from(&q - 1);
+        let d = e.clone().invert(&phi).unwrap();
+
+        assert_eq!(factor_from_rsa_exponents(&n, &e, &d).unwrap(), (p, q));
+    }
+
+    #[test]
+    fn batch_gcd() {
+        // Products of pairs of primes, some of them shared
+        let primes: Vec<Integer> = Primes::all()
+            .skip(1_000)
+            .step_by(7)
+            .take(40)
+            .map(Integer::from)
+            .collect();
+        let mut moduli: Vec<Integer> = (0..30)
+            .map(|i| Integer::from(&primes[i] * &primes[(i * 7 + 3) % 40]))
+            .collect();
+        moduli.extend([
+            Integer::from(-15),
+            Integer::new(),
+            Integer::from(1),
+            moduli[4].clone(),
+        ]);
+
+        let naive: Vec<Integer> = (0..moduli.len())
+            .map(|i| {
+                let others = (moduli.iter().enumerate())
+                    .filter(|&(j, n)| j != i && !n.is_zero())
+                    .map(|(_, n)| n);
+                let product = Integer::from(Integer::product(others));
+                match moduli[i].is_zero() {
+                    true => Integer::from(1),
+                    false => product.gcd(&moduli[i]),
+                }
+            })
+            .collect();
+        assert_eq!(super::batch_gcd(&moduli), naive);
+        assert!(naive.iter().any(|g| *g != 1));
+        assert!(super::batch_gcd(&[]).is_empty());
+        assert_eq!(super::batch_gcd(&[Integer::from(35)]), [1]);
+    }
+
+    #[test]
+    fn factor_moduli() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        let q = Integer::from_str("300000000000000000000000000007").unwrap();
+        let pq = Integer::from(&p * &q);
+        let moduli = [
+            Integer::from(&p * 7),
+            pq.clone(),
+            pq.clone(),
+            Integer::from(1_000_003 * 1_000_033u64),
+            Integer::new(),
+        ];
+        let options = EcmOptions::new()
+            .b1(2_000)
+            .b2(160_000)
+            .max_curves(10)
+            .max_escalations(0)
+            .seed(1);
+        let factors = super::factor_moduli(&moduli, &options);
+
+        assert_eq!(factors[0].as_ref().unwrap().to_string(), format!("7 * {p}"));
+        // Duplicated moduli share all their factors, left to ECM
+        for factorization in &factors[1..3] {
+            let factorization = factorization.as_ref().unwrap();
+            assert_eq!(factorization.cofactors().next(), Some((&pq, 1)));
+        }
+        assert!(factors[3].as_ref().unwrap().is_complete());
+        assert!(matches!(factors[4], Err(Error::ZeroInput)));
+    }
+
+    #[test]
+    fn invalid_key() {
+        assert!(matches!(
+            factor_from_rsa_exponents(&3233.into(), &17.into(), &2754.into()),
+            Err(Error::InvalidRsaKey)
+        ));
+        assert!(matches!(
+            factor_from_rsa_exponents(&3233.into(), &0.into(), &2753.into()),
+            Err(Error::InvalidRsaKey)
+        ));
+    }
+}
@@ -0,0 +1,473 @@
+use crate::{
+    ecm::{proper_factor, Stages},
+    parse_integer, try_invert_or_factor, FactorFound, ParseError, Point, SuyamaCurve,
+};
+use rug::Integer;
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+    str::FromStr,
+    sync::Mutex,
+};
+
+/// Modulus of the checksums of GMP-ECM save files, the largest prime below 2^32.
+const CHECKSUM_MODULUS: u32 = 4294967291;
+
+/// Error occured while reading a GMP-ECM save file.
+#[derive(thiserror::Error, Debug)]
+pub enum ResidueError {
+    /// The save file could not be read.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// A field needed to run stage 2 is missing.
+    #[error("Missing field {0}")]
+    MissingField(&'static str),
+    /// A field is not of the form `KEY=VALUE`.
+    #[error("Invalid field {0:?}")]
+    InvalidField(String),
+    /// A number is not valid.
+    #[error("Invalid number in {field}: {source}")]
+    InvalidNumber {
+        /// Key of the field.
+        field: &'static str,
+        /// Parse error of the value.
+        source: ParseError,
+    },
+    /// The residue is not of an elliptic curve, e.g. of P-1 or P+1.
+    #[error("Unsupported method {0}")]
+    UnsupportedMethod(String),
+    /// The curve is not given by Suyama's parametrization of sigma, `PARAM=0`.
+    #[error("Unsupported curve parametrization {0}")]
+    UnsupportedParam(String),
+    /// The checksum of the line does not match its fields.
+    #[error("Checksum mismatch: {found} in the file, {expected} computed")]
+    ChecksumMismatch {
+        /// Checksum computed from the fields.
+        expected: u32,
+        /// Checksum of the line.
+        found: u32,
+    },
+}
+
+/// Result of stage 1 of a curve, which stage 2 can continue.
+///
+/// Formatted with [`Display`](fmt::Display), it is a line of a GMP-ECM save
+/// file: `gmp-ecm -resume` runs stage 2 on it without running stage 1 again.
+/// The curve is given by Suyama's parametrization of sigma (`PARAM=0`), and the
+/// point by its affine x-coordinate.
+///
+/// Conversely, lines of GMP-ECM save files are parsed with [`FromStr`], see
+/// also [`read_save_file`], and [`Residue::stage2`] continues them.
+///
+/// ```
+/// use ecm::Residue;
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let residue = Residue::stage1(&n, &Integer::from(12), 2_000).unwrap();
+/// let line = residue.to_string();
+/// assert!(line.starts_with("METHOD=ECM; PARAM=0; SIGMA=12; B1=2000; N=398883434337287; X=0x"));
+///
+/// let parsed: Residue = line.parse().unwrap();
+/// assert_eq!(parsed, residue);
+/// assert_eq!(parsed.stage2(160_000), Some(Integer::from(99476569)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Residue {
+    /// Number the curve is run on.
+    pub n: Integer,
+    /// Parameter of the curve, see [`SuyamaCurve`].
+    pub sigma: Integer,
+    /// Stage 1 bound, stage 2 starts above it.
+    pub b1: u64,
+    /// Affine x-coordinate of the result of stage 1 modulo n.
+    pub x: Integer,
+}
+
+impl Residue {
+    /// Runs stage 1 of the curve of parameter `sigma` on n.
+    ///
+    /// Fails with the factor found if the curve cannot be built, or if stage 1 finds
+    /// a factor of n, which may be n itself.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number the curve is run on.
+    /// - `sigma`: Parameter of the curve, see [`SuyamaCurve`].
+    /// - `B1`: Stage 1 Bound.
+    pub fn stage1(n: &Integer, sigma: &Integer, b1: u64) -> Result<Residue, FactorFound> {
+        let stages = Stages::new(b1, b1);
+        let q = stages.stage1(&SuyamaCurve::derive(sigma, n)?.into_point());
+        Residue::from_point(n, sigma, b1, &q)
+    }
+
+    /// Residue of the result `q` of stage 1, failing if its z-coordinate is not invertible.
+    pub(crate) fn from_point(
+        n: &Integer,
+        sigma: &Integer,
+        b1: u64,
+        q: &Point,
+    ) -> Result<Residue, FactorFound> {
+        let inverse = try_invert_or_factor(&q.z_cord, n)?;
+        Ok(Residue {
+            n: n.clone(),
+            sigma: sigma.clone(),
+            b1,
+            x: inverse * &q.x_cord % n,
+        })
+    }
+
+    /// Runs stage 2 up to B2, returning the proper factor of n found, if any.
+    ///
+    /// Stage 2 covers the primes in `(B1, B2]`, B1 being rounded up to an even number.
+    ///
+    /// # Parameters
+    ///
+    /// - `B2`: Stage 2 Bound.
+    pub fn stage2(&self, b2: u64) -> Option<Integer> {
+        let curve = match SuyamaCurve::derive(&self.sigma, &self.n) {
+            Ok(curve) => curve,
+            Err(FactorFound(g)) => return proper_factor(g, &self.n),
+        };
+        // Giant steps start below B1, which must leave room for the baby steps
+        let b1 = (self.b1 + self.b1 % 2).max(6);
+        if b2 <= b1 {
+            return None;
+        }
+
+        let q = Point::new(
+            self.x.clone(),
+            Integer::from(1),
+            curve.a_24().clone(),
+            self.n.clone(),
+        );
+        let stages = Stages::stage2_only(b1, b2 + b2 % 2);
+        proper_factor(stages.stage2(&self.n, &q), &self.n)
+    }
+
+    /// Checksum of the line, the product of B1, sigma, n and x modulo `4294967291`.
+    pub fn checksum(&self) -> u32 {
+        let mut checksum = Integer::from(self.b1 % u64::from(CHECKSUM_MODULUS));
+        for value in [&self.sigma, &self.n, &self.x] {
+            checksum *= value.mod_u(CHECKSUM_MODULUS);
+            checksum %= CHECKSUM_MODULUS;
+        }
+        checksum.to_u32().unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Residue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "METHOD=ECM; PARAM=0; SIGMA={}; B1={}; N={}; X={:#x}; CHECKSUM={}; PROGRAM=ecm-rs {};",
+            self.sigma,
+            self.b1,
+            self.n,
+            self.x,
+            self.checksum(),
+            env!("CARGO_PKG_VERSION"),
+        )
+    }
+}
+
+impl FromStr for Residue {
+    type Err = ResidueError;
+
+    /// Parses a line of a GMP-ECM save file, `KEY=VALUE` fields separated by semicolons.
+    ///
+    /// Only curves of Suyama's parametrization are supported, the checksum is
+    /// verified if present, and the fields not needed by stage 2 are ignored.
+    fn from_str(line: &str) -> Result<Residue, ResidueError> {
+        let (mut n, mut sigma, mut b1, mut x, mut checksum) = (None, None, None, None, None);
+        let number = |field: &'static str, value: &str| {
+            parse_integer(value).map_err(|source| ResidueError::InvalidNumber { field, source })
+        };
+
+        for field in line.split(';').map(str::trim).filter(|f| !f.is_empty()) {
+            let Some((key, value)) = field.split_once('=') else {
+                return Err(ResidueError::InvalidField(field.to_string()));
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_uppercase().as_str() {
+                "METHOD" if value.eq_ignore_ascii_case("ECM") => {}
+                "METHOD" => return Err(ResidueError::UnsupportedMethod(value.to_string())),
+                "PARAM" if value == "0" => {}
+                "PARAM" => return Err(ResidueError::UnsupportedParam(format!("PARAM={value}"))),
+                // Curves given by their coefficient instead of sigma
+                "A" => return Err(ResidueError::UnsupportedParam("A".to_string())),
+                "N" => n = Some(number("N", value)?),
+                "SIGMA" => sigma = Some(number("SIGMA", value)?),
+                "B1" => b1 = Some(number("B1", value)?),
+                "X" => x = Some(number("X", value)?),
+                "CHECKSUM" => checksum = Some(number("CHECKSUM", value)?),
+                _ => {}
+            }
+        }
+
+        let b1 = b1.ok_or(ResidueError::MissingField("B1"))?;
+        let residue = Residue {
+            n: n.ok_or(ResidueError::MissingField("N"))?,
+            sigma: sigma.ok_or(ResidueError::MissingField("SIGMA"))?,
+            b1: b1
+                .to_u64()
+                .ok_or(ResidueError::InvalidField(format!("B1={b1}")))?,
+            x: x.ok_or(ResidueError::MissingField("X"))?,
+        };
+        match checksum {
+            Some(found) if found != residue.checksum() => Err(ResidueError::ChecksumMismatch {
+                expected: residue.checksum(),
+                found: found.to_u32().unwrap_or(u32::MAX),
+            }),
+            _ => Ok(residue),
+        }
+    }
+}
+
+/// Reads the residues of a GMP-ECM save file, one per line.
+///
+/// Blank lines and comments, starting with `#`, are skipped.
+///
+/// ```
+/// use ecm::{read_save_file, Residue};
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let residue = Residue::stage1(&n, &Integer::from(12), 2_000).unwrap();
+/// let file = format!("# From ecm-rs\n{residue}\n\n");
+/// assert_eq!(read_save_file(file.as_bytes()).unwrap(), [residue]);
+/// ```
+///
+/// # Parameters
+///
+/// - `reader`: Save file.
+pub fn read_save_file(reader: impl BufRead) -> Result<Vec<Residue>, ResidueError> {
+    let mut residues = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            residues.push(line.parse()?);
+        }
+    }
+    Ok(residues)
+}
+
+/// Content of the resume file of an interrupted factorization, see [`EcmOptions::resume_file`](crate::EcmOptions::resume_file).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumeFormat {
+    /// GMP-ECM save file of the stage 1 residues of the curves run, see [`ResidueLog`].
+    ///
+    /// The curves can be continued with larger stage 2 bounds, by ecm-rs or GMP-ECM.
+    GmpEcm,
+    /// JSON of the [`Checkpoint`](crate::Checkpoint) of the factorizer, with the `serde` feature.
+    ///
+    /// The factorization continues exactly where it was interrupted.
+    #[cfg(feature = "serde")]
+    Checkpoint,
+}
+
+/// Stage 1 residues of the curves of a factorization which reached stage 2.
+///
+/// Written with [`ResidueLog::write_to`], the residues form a GMP-ECM save file,
+/// so stage 2 can be run again with larger bounds, or by other programs.
+///
+/// ```
+/// use ecm::{EcmOptions, ResidueLog};
+/// use rug::Integer;
+/// use std::sync::Arc;
+///
+/// let log = Arc::new(ResidueLog::new());
+/// let n = Integer::from(398883434337287u64);
+/// let options = EcmOptions::new().seed(1).small_cofactor_bits(0);
+/// options.residue_log(log.clone()).run(&n).unwrap();
+///
+/// let mut file = Vec::new();
+/// log.write_to(&mut file).unwrap();
+/// let file = String::from_utf8(file).unwrap();
+/// assert_eq!(file.lines().count(), log.residues().len());
+/// ```
+#[derive(Debug, Default)]
+pub struct ResidueLog(Mutex<Vec<Residue>>);
+
+impl ResidueLog {
+    /// Creates an empty log.
+    pub fn new() -> ResidueLog {
+        ResidueLog::default()
+    }
+
+    /// Appends the result `q` of stage 1 of the curve of parameter `sigma` on n.
+    pub(crate) fn record(&self, n: &Integer, sigma: &Integer, b1: u64, q: &Point) {
+        // Stage 2 is only run once the z-coordinate is invertible
+        if let Ok(residue) = Residue::from_point(n, sigma, b1, q) {
+            self.0
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(residue);
+        }
+    }
+
+    /// Residues recorded so far, in order.
+    pub fn residues(&self) -> Vec<Residue> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Writes the residues recorded so far, one line each.
+    ///
+    /// # Parameters
+    ///
+    /// - `writer`: Destination of the save file.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        for residue in self.residues() {
+            writeln!(writer, "{residue}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EcmOptions;
+    use std::sync::Arc;
+
+    #[test]
+    fn residue() {
+        let n = Integer::from(398883434337287u64);
+        let sigma = Integer::from(12);
+        let residue = Residue::stage1(&n, &sigma, 2_000).unwrap();
+
+        // Same point as stage 1, x = X / Z
+        let stages = Stages::new(2_000, 2_000);
+        let q = stages.stage1(&SuyamaCurve::derive(&sigma, &n).unwrap().into_point());
+        assert!(Integer::from(&residue.x * &q.z_cord - &q.x_cord).is_divisible(&n));
+        assert!(residue.x > 0 && residue.x < n);
+
+        let checksum = Integer::from(2_000) * &sigma * &n * &residue.x % CHECKSUM_MODULUS;
+        assert_eq!(residue.checksum(), checksum);
+        assert_eq!(
+            residue.to_string(),
+            format!(
+                "METHOD=ECM; PARAM=0; SIGMA=12; B1=2000; N=398883434337287; X={:#x}; CHECKSUM={}; PROGRAM=ecm-rs {};",
+                residue.x,
+                checksum,
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        // Factors found by stage 1
+        let n = Integer::from(4009823u64 * 2);
+        assert!(Residue::stage1(&n, &sigma, 2_000).is_err());
+    }
+
+    #[test]
+    fn log() {
+        let log = Arc::new(ResidueLog::new());
+        let n = Integer::from_str_radix("1048843748843747081710817107", 10).unwrap();
+        let options = EcmOptions::new().b1(100).b2(2_000).max_curves(3);
+        let options = options.max_escalations(0).small_cofactor_bits(0).seed(1);
+        options.residue_log(log.clone()).run(&n).unwrap();
+
+        // Every curve which failed reached stage 2
+        let residues = log.residues();
+        assert_eq!(residues.len(), 3);
+        for residue in &residues {
+            assert_eq!((&residue.n, residue.b1), (&n, 100));
+            assert_eq!(Residue::stage1(&n, &residue.sigma, 100).unwrap(), *residue);
+        }
+
+        let mut file = Vec::new();
+        log.write_to(&mut file).unwrap();
+        let file = String::from_utf8(file).unwrap();
+        let lines: Vec<_> = file.lines().collect();
+        assert_eq!(
+            lines,
+            residues.iter().map(|r| r.to_string()).collect::<Vec<_>>()
+        );
+        assert_eq!(read_save_file(file.as_bytes()).unwrap(), residues);
+    }
+
+    #[test]
+    fn parse() {
+        let n = Integer::from(398883434337287u64);
+        let residue = Residue::stage1(&n, &Integer::from(12), 2_000).unwrap();
+        let x = format!("{:#x}", residue.x);
+        assert_eq!(residue.to_string().parse::<Residue>().unwrap(), residue);
+
+        // Fields in any order, with the ones not needed by stage 2 and without checksum
+        let line = format!(
+            "METHOD=ECM; B1=2000; SIGMA=12; N=398883434337287; X={x}; PROGRAM=GMP-ECM 7.0.5; WHO=me@host; TIME=Thu Jan  1 00:00:00 1970;"
+        );
+        assert_eq!(line.parse::<Residue>().unwrap(), residue);
+        let decimal = format!(
+            "METHOD=ECM; SIGMA=12; B1=2000; N=398883434337287; X={}",
+            residue.x
+        );
+        assert_eq!(decimal.parse::<Residue>().unwrap(), residue);
+
+        let parse = |line: String| line.parse::<Residue>().unwrap_err();
+        assert!(matches!(
+            parse(format!(
+                "METHOD=ECM; SIGMA=12; B1=2000; N=398883434337287; X={x}; CHECKSUM=1"
+            )),
+            ResidueError::ChecksumMismatch { found: 1, .. }
+        ));
+        assert!(matches!(
+            parse(format!("METHOD=P-1; B1=2000; N=398883434337287; X={x}")),
+            ResidueError::UnsupportedMethod(method) if method == "P-1"
+        ));
+        assert!(matches!(
+            parse(format!(
+                "METHOD=ECM; PARAM=1; SIGMA=12; B1=2000; N=398883434337287; X={x}"
+            )),
+            ResidueError::UnsupportedParam(_)
+        ));
+        assert!(matches!(
+            parse(format!(
+                "METHOD=ECM; A=5; B1=2000; N=398883434337287; X={x}"
+            )),
+            ResidueError::UnsupportedParam(_)
+        ));
+        assert!(matches!(
+            parse(format!("METHOD=ECM; SIGMA=12; N=398883434337287; X={x}")),
+            ResidueError::MissingField("B1")
+        ));
+        assert!(matches!(
+            parse(format!("METHOD=ECM; SIGMA=12; B1=2000; N=12a; X={x}")),
+            ResidueError::InvalidNumber { field: "N", .. }
+        ));
+        assert!(matches!(
+            parse("METHOD=ECM; SIGMA".to_string()),
+            ResidueError::InvalidField(_)
+        ));
+        assert!(matches!(
+            read_save_file("METHOD=ECM\n".as_bytes()),
+            Err(ResidueError::MissingField(_))
+        ));
+    }
+
+    #[test]
+    fn stage2() {
+        // Same factor as a curve run from the start
+        let n = Integer::from(398883434337287u64);
+        let sigma = Integer::from(12);
+        let residue = Residue::stage1(&n, &sigma, 2_000).unwrap();
+        let stages = Stages::new(2_000, 160_000);
+        let q = stages.stage1(&SuyamaCurve::derive(&sigma, &n).unwrap().into_point());
+        let expected = stages.stage2(&n, &q);
+        assert_eq!(residue.stage2(160_000), Some(expected));
+
+        // Stage 2 continued with larger bounds than the ones of the curves run
+        let n = Integer::from_str_radix("1048843748843747081710817107", 10).unwrap();
+        let found = (1..100u32).find_map(|sigma| {
+            let residue = Residue::stage1(&n, &Integer::from(sigma), 2_000).ok()?;
+            residue.stage2(200_000)
+        });
+        assert_eq!(found, Some(Integer::from(100327907731u64)));
+
+        // Odd and small bounds, and no stage 2
+        let residue = Residue { b1: 3, ..residue };
+        let _ = residue.stage2(1_001);
+        assert_eq!(residue.stage2(2), None);
+    }
+}
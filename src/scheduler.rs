@@ -0,0 +1,350 @@
+use crate::{BatchResult, EcmOptions, Error, Factorization, Factorizer, StepOutcome};
+use rug::Integer;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time budget of each step, after which the worker moves on to the smallest input waiting.
+const STEP_BUDGET: Duration = Duration::from_millis(100);
+
+/// Input being factored, waiting for a worker between its steps.
+struct Task {
+    index: usize,
+    n: Integer,
+    factorizer: Factorizer,
+    /// Time spent stepping the factorizer.
+    elapsed: Duration,
+}
+
+impl Task {
+    /// Smallest inputs first, then in the order of submission.
+    fn key(&self) -> (u32, usize) {
+        (self.n.significant_bits(), self.index)
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Task) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Task {}
+
+impl PartialOrd for Task {
+    fn partial_cmp(&self, other: &Task) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Task {
+    fn cmp(&self, other: &Task) -> Ordering {
+        // Reversed, the heap pops the greatest task
+        other.key().cmp(&self.key())
+    }
+}
+
+struct Queue {
+    tasks: BinaryHeap<Task>,
+    /// Set once the scheduler is dropped.
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Notified when a task is queued and when the scheduler is dropped.
+    available: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, task: Task) {
+        self.lock().tasks.push(task);
+        self.available.notify_one();
+    }
+}
+
+type WorkerResult = (usize, Integer, thread::Result<Result<Factorization, Error>>);
+
+/// Factorization of many numbers on a fixed pool of threads, smallest numbers first.
+///
+/// Unlike [`BatchOptions`](crate::BatchOptions), which factors each input from
+/// start to end on one thread, the workers factor the inputs in short steps:
+/// after each step, a worker continues with the smallest input waiting. A large
+/// input submitted first does not hold back the small ones submitted after it,
+/// which complete first. Each input is reported as soon as its factorization is
+/// done, by iterating over the scheduler.
+///
+/// The stage 1 multiplier and the primes of stage 2 are computed once
+/// for all the inputs needing the same bounds. With [`EcmOptions::max_duration`],
+/// the time is the one spent factoring each input, excluding the time waiting
+/// for a worker.
+///
+/// ```
+/// use ecm::{EcmOptions, Scheduler};
+/// use rug::Integer;
+///
+/// let mut scheduler = Scheduler::new(EcmOptions::new().threads(2));
+/// let large = scheduler.submit(Integer::from(1048843748843747081710817107u128));
+/// for n in 1_000_000u64..1_000_010 {
+///     scheduler.submit(Integer::from(n));
+/// }
+/// assert_eq!(scheduler.pending(), 11);
+///
+/// for done in &mut scheduler {
+///     assert_eq!(done.result.unwrap().product(), done.n);
+/// }
+/// assert_eq!(large, 0);
+/// ```
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    options: EcmOptions,
+    sender: Sender<WorkerResult>,
+    /// Behind a mutex so that the scheduler is `Sync`, only locked through `&mut self`.
+    results: Mutex<Receiver<WorkerResult>>,
+    /// Index of the next input submitted.
+    next_index: usize,
+    /// Inputs submitted but not yet yielded.
+    pending: usize,
+}
+
+impl Scheduler {
+    /// Starts the workers, on the number of threads of the options.
+    ///
+    /// Each input is factored on a single thread at a time, see [`EcmOptions::threads`].
+    ///
+    /// # Parameters
+    ///
+    /// - `options`: Settings of each factorization.
+    pub fn new(options: EcmOptions) -> Scheduler {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                tasks: BinaryHeap::new(),
+                closed: false,
+            }),
+            available: Condvar::new(),
+        });
+        let (sender, results) = mpsc::channel();
+
+        for _ in 0..options.thread_count() {
+            let shared = Arc::clone(&shared);
+            let results = sender.clone();
+            let (priority, max_duration) = (options.priority, options.max_duration);
+            thread::spawn(move || {
+                priority.apply();
+                work(&shared, &results, max_duration);
+            });
+        }
+
+        Scheduler {
+            shared,
+            // Inputs of the same size use the same bounds, the workers already use all the threads
+            options: options.share_stages().threads(1),
+            sender,
+            results: Mutex::new(results),
+            next_index: 0,
+            pending: 0,
+        }
+    }
+
+    /// Queues n, returning its index, starting at 0 for the first input submitted.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    pub fn submit(&mut self, n: Integer) -> usize {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.pending += 1;
+        match self.options.factorizer(&n) {
+            Ok(factorizer) => self.shared.push(Task {
+                index,
+                n,
+                factorizer,
+                elapsed: Duration::ZERO,
+            }),
+            // The receiver is owned by the scheduler
+            Err(err) => {
+                let _ = self.sender.send((index, n, Ok(Err(err))));
+            }
+        }
+        index
+    }
+
+    /// Number of inputs submitted but not yet yielded.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Returns the next input whose factorization is done, without waiting.
+    pub fn try_next(&mut self) -> Option<BatchResult> {
+        let results = self.results.get_mut().unwrap_or_else(|e| e.into_inner());
+        let result = results.try_recv().ok()?;
+        Some(self.completed(result))
+    }
+
+    fn completed(&mut self, (index, n, result): WorkerResult) -> BatchResult {
+        self.pending -= 1;
+        match result {
+            Ok(result) => BatchResult { index, n, result },
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl Iterator for Scheduler {
+    type Item = BatchResult;
+
+    /// Waits for the next input whose factorization is done, `None` once no input is pending.
+    fn next(&mut self) -> Option<BatchResult> {
+        if self.pending == 0 {
+            return None;
+        }
+        let results = self.results.get_mut().unwrap_or_else(|e| e.into_inner());
+        let result = results.recv().ok()?;
+        Some(self.completed(result))
+    }
+}
+
+impl Drop for Scheduler {
+    /// Stops the workers, the inputs not yet done are dropped after their current step.
+    fn drop(&mut self) {
+        let mut queue = self.shared.lock();
+        queue.closed = true;
+        queue.tasks.clear();
+        self.shared.available.notify_all();
+    }
+}
+
+/// Steps the smallest task waiting until the scheduler is dropped.
+fn work(shared: &Shared, results: &Sender<WorkerResult>, max_duration: Option<Duration>) {
+    loop {
+        let mut task = {
+            let mut queue = shared.lock();
+            loop {
+                if queue.closed {
+                    return;
+                }
+                if let Some(task) = queue.tasks.pop() {
+                    break task;
+                }
+                queue = shared
+                    .available
+                    .wait(queue)
+                    .unwrap_or_else(|e| e.into_inner());
+            }
+        };
+
+        let budget = match max_duration {
+            Some(max) => STEP_BUDGET.min(max.saturating_sub(task.elapsed)),
+            None => STEP_BUDGET,
+        };
+        let start = Instant::now();
+        // Panics are raised again by the scheduler, instead of losing the input
+        let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            task.factorizer.step(budget)
+        }));
+        task.elapsed += start.elapsed();
+        let out_of_time = max_duration.is_some_and(|max| task.elapsed >= max);
+
+        let result = match outcome {
+            Ok(Ok(StepOutcome::Pending)) if !out_of_time => {
+                shared.push(task);
+                continue;
+            }
+            Ok(Ok(_)) => Ok(Ok(task.factorizer.partial_factors())),
+            Ok(Err(err)) => Ok(Err(err)),
+            Err(payload) => Err(payload),
+        };
+        if results.send((task.index, task.n, result)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> impl Iterator<Item = Integer> {
+        // Products of two numbers, all small enough to be fast
+        (0..40u64).map(|i| Integer::from(1_000_003 + 2 * i) * Integer::from(999_983 - 2 * i))
+    }
+
+    #[test]
+    fn smallest_first() {
+        // Two primes of 30 digits, far beyond the curves run in a second
+        let p: Integer = "100000000000000000000000000319".parse().unwrap();
+        let q: Integer = "300000000000000000000000000007".parse().unwrap();
+        let large = Integer::from(&p * &q);
+        let options = EcmOptions::new().seed(1).threads(1);
+        let mut scheduler = Scheduler::new(options.max_duration(Duration::from_secs(1)));
+
+        assert_eq!(scheduler.submit(large.clone()), 0);
+        for n in inputs().take(5) {
+            scheduler.submit(n);
+        }
+        assert_eq!(scheduler.pending(), 6);
+
+        // The small inputs are done first, the large one once out of time
+        let results: Vec<_> = scheduler.by_ref().collect();
+        let indices: Vec<_> = results.iter().map(|result| result.index).collect();
+        assert_eq!(indices, [1, 2, 3, 4, 5, 0]);
+        for result in &results[..5] {
+            assert!(result.result.as_ref().unwrap().is_complete());
+        }
+        let partial = results[5].result.as_ref().unwrap();
+        assert!(!partial.is_complete());
+        assert_eq!(partial.product(), large);
+        assert_eq!(scheduler.pending(), 0);
+        assert!(scheduler.next().is_none());
+    }
+
+    #[test]
+    fn many() {
+        let mut scheduler = Scheduler::new(EcmOptions::new().seed(1).threads(3));
+        assert!(scheduler.try_next().is_none());
+        for n in inputs() {
+            scheduler.submit(n);
+        }
+        let mut indices: Vec<_> = scheduler
+            .by_ref()
+            .map(|result| {
+                assert_eq!(result.result.unwrap().product(), result.n);
+                result.index
+            })
+            .collect();
+        indices.sort();
+        assert_eq!(indices, (0..40).collect::<Vec<_>>());
+
+        // Inputs can still be submitted once the others are done
+        assert_eq!(scheduler.submit(Integer::from(15)), 40);
+        assert_eq!(scheduler.next().unwrap().result.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn errors() {
+        let mut scheduler = Scheduler::new(EcmOptions::new().threads(1));
+        scheduler.submit(Integer::new());
+        let result = scheduler.next().unwrap();
+        assert!(matches!(result.result, Err(Error::ZeroInput)));
+        assert_eq!(scheduler.pending(), 0);
+
+        // Dropped with inputs not done
+        let p: Integer = "100000000000000000000000000319".parse().unwrap();
+        scheduler.submit(Integer::from(&p * &p) + 2);
+        let start = Instant::now();
+        drop(scheduler);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
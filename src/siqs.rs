@@ -0,0 +1,853 @@
+use crate::progress::decimal_digits;
+use rug::{rand::RandState, Integer};
+use std::{
+    collections::{HashMap, HashSet},
+    task::Poll,
+};
+
+/// Composites of up to this many decimal digits are given to [`siqs`] by default, once ECM failed on them.
+pub const DEFAULT_SIQS_DIGITS: usize = 95;
+
+/// Composites with fewer decimal digits are left to the other methods by [`siqs`].
+pub const SIQS_MIN_DIGITS: usize = 18;
+
+/// Fraction of the digits of a composite that ECM looks for before [`siqs`] takes over,
+/// e.g. t25 on 80 digits: past this size, finding a factor with curves takes longer than the sieve.
+pub(crate) const SIQS_PRETEST_RATIO: (usize, usize) = (4, 13);
+
+/// Multipliers k tried by Knuth-Schroeppel, the one with the most small primes in the factor base of `k*n` is kept.
+const MULTIPLIERS: [u32; 31] = [
+    1, 3, 5, 7, 11, 13, 15, 17, 19, 21, 23, 29, 31, 33, 35, 37, 39, 41, 43, 47, 51, 53, 55, 57, 59,
+    61, 65, 67, 69, 71, 73,
+];
+
+/// Size of the factor base and half width of the sieve interval, by decimal digits of n.
+const PARAMS: [(usize, usize, u32); 9] = [
+    (20, 150, 32_768),
+    (30, 300, 32_768),
+    (40, 700, 32_768),
+    (50, 1_600, 32_768),
+    (60, 5_000, 65_536),
+    (70, 13_000, 65_536),
+    (80, 22_000, 98_304),
+    (90, 32_000, 131_072),
+    (100, 45_000, 163_840),
+];
+
+/// Primes of the factor base below this bound are not sieved, only divided out of the candidates.
+const SMALL_PRIME: u32 = 100;
+
+/// Large primes of the partial relations are below this multiple of the largest prime of the factor base.
+const LARGE_PRIME_MULTIPLIER: u64 = 256;
+
+/// Bits of `Q(x)` left to the primes not sieved and to the rounding of the logarithms.
+const THRESHOLD_SLACK: f64 = 16.0;
+
+/// Bytes of the sieve interval processed at once, about the size of the L1 cache.
+const BLOCK: usize = 32_768;
+
+/// Relations collected beyond the size of the factor base, each one a chance to split n.
+const EXTRA_RELATIONS: usize = 64;
+
+/// Returns a proper factor of n with the self-initializing quadratic sieve.
+///
+/// Collects relations `y^2 = Q(y) (mod n)` where `Q(y)` factors over a base of
+/// small primes, sieving the values of polynomials `(A x + B)^2 - k n` over
+/// `[-M, M]`, and switching from one B to the next in a few additions. Once
+/// there are more relations than primes, a product of them is a square on both
+/// sides, `X^2 = Y^2 (mod n)`, and `gcd(X - Y, n)` is a factor of n with
+/// probability 1/2. Relations with one large prime left are kept and paired.
+///
+/// Unlike the curves, its running time depends on the size of n only, not on
+/// the size of its factors: it beats ECM on composites of 60 to 95 digits
+/// whose smallest factor has more than about a third of their digits.
+///
+/// Returns `None` if n has fewer than [`SIQS_MIN_DIGITS`] digits, or if n is a
+/// prime or a perfect power, where all the relations only give trivial factors.
+///
+/// ```
+/// use ecm::siqs;
+/// use rug::Integer;
+///
+/// let p = Integer::from_str_radix("1000000000000000000117", 10).unwrap();
+/// let q = Integer::from_str_radix("3000000000000000000053", 10).unwrap();
+/// let g = siqs(&Integer::from(&p * &q)).unwrap();
+/// assert!(g == p || g == q);
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+pub fn siqs(n: &Integer) -> Option<Integer> {
+    let mut siqs = match Siqs::new(n) {
+        Ok(siqs) => siqs,
+        Err(factor) => return factor,
+    };
+    loop {
+        if let Poll::Ready(factor) = siqs.run(&|| false) {
+            return factor;
+        }
+    }
+}
+
+/// Quadratic sieve of a composite, which can be stopped between two polynomials and resumed.
+pub(crate) struct Siqs {
+    sieve: Sieve,
+    /// Polynomial being sieved, with the index of its next B.
+    poly: Option<(Poly, usize)>,
+    /// Relations to collect before looking for dependencies.
+    wanted: usize,
+    buffer: Vec<u8>,
+}
+
+impl Siqs {
+    /// Sets up the sieve of n, or returns its outcome right away: a factor met
+    /// while building the factor base, or `None` where [`siqs`] gives up.
+    pub(crate) fn new(n: &Integer) -> Result<Siqs, Option<Integer>> {
+        let digits = decimal_digits(n);
+        if digits < SIQS_MIN_DIGITS {
+            return Err(None);
+        }
+        if n.is_even() {
+            return Err(Some(Integer::from(2)));
+        }
+        if n.is_perfect_power() || n.is_probably_prime(25) != rug::integer::IsPrime::No {
+            return Err(None);
+        }
+
+        let (fb_size, m) = params(digits);
+        let kn = Integer::from(n * multiplier(n));
+        let fb = FactorBase::new(n, &kn, fb_size).map_err(Some)?;
+        Ok(Siqs {
+            wanted: fb.primes.len() + 1 + EXTRA_RELATIONS,
+            sieve: Sieve::new(n.clone(), kn, fb, m),
+            poly: None,
+            buffer: vec![0; 2 * m as usize],
+        })
+    }
+
+    /// Composite sieved.
+    pub(crate) fn n(&self) -> &Integer {
+        &self.sieve.n
+    }
+
+    /// Collects relations until the matrix gives a factor, `None` if the polynomials run out.
+    ///
+    /// Returns [`Poll::Pending`] once `stop` returns true, checked after each polynomial.
+    pub(crate) fn run(&mut self, stop: &dyn Fn() -> bool) -> Poll<Option<Integer>> {
+        loop {
+            while self.sieve.relations.len() < self.wanted {
+                let (mut poly, i) = match self.poly.take() {
+                    Some(next) => next,
+                    None => match self.sieve.next_a() {
+                        Some(poly) => (poly, 0),
+                        None => return Poll::Ready(None),
+                    },
+                };
+                if i > 0 {
+                    self.sieve.next_b(&mut poly, i.trailing_zeros() as usize);
+                }
+                self.sieve.sieve_poly(&poly, &mut self.buffer);
+                if i + 1 < 1 << (poly.terms.len() - 1) {
+                    self.poly = Some((poly, i + 1));
+                }
+                if stop() {
+                    return Poll::Pending;
+                }
+            }
+            if let Some(factor) = self.sieve.combine() {
+                return Poll::Ready(Some(factor));
+            }
+            self.wanted = self.sieve.relations.len() + EXTRA_RELATIONS;
+        }
+    }
+}
+
+/// Size of the factor base and half width of the interval, interpolated in [`PARAMS`].
+fn params(digits: usize) -> (usize, u32) {
+    let i = PARAMS.partition_point(|&(d, _, _)| d < digits);
+    let Some(&(d1, fb1, m1)) = PARAMS.get(i) else {
+        let (_, fb, m) = PARAMS[PARAMS.len() - 1];
+        return (fb, m);
+    };
+    if i == 0 {
+        return (fb1, m1);
+    }
+    let (d0, fb0, m0) = PARAMS[i - 1];
+    let t = (digits - d0) as f64 / (d1 - d0) as f64;
+    let fb = fb0 as f64 + t * (fb1 - fb0) as f64;
+    // Whole blocks
+    let m = (f64::from(m0) + t * f64::from(m1 - m0)) as u32;
+    (fb as usize, m.div_ceil(BLOCK as u32) * BLOCK as u32)
+}
+
+/// Knuth-Schroeppel multiplier of n: weighs the expected contribution of the small primes to `k*n`.
+fn multiplier(n: &Integer) -> u32 {
+    let primes: Vec<u32> = primal::Primes::all()
+        .skip(1)
+        .take(300)
+        .map(|p| p as u32)
+        .collect();
+    let score = |k: u32| {
+        let kn = Integer::from(n * k);
+        let mut score = -0.5 * f64::from(k).ln();
+        score += match kn.mod_u(8) {
+            1 => 2.0 * 2f64.ln(),
+            5 => 2f64.ln(),
+            _ => 0.5 * 2f64.ln(),
+        };
+        for &p in &primes {
+            let r = u64::from(kn.mod_u(p));
+            let ln = f64::from(p).ln();
+            if r == 0 {
+                score += ln / f64::from(p);
+            } else if legendre(r, u64::from(p)) {
+                score += 2.0 * ln / f64::from(p - 1);
+            }
+        }
+        score
+    };
+    MULTIPLIERS
+        .into_iter()
+        .map(|k| (k, score(k)))
+        .fold((1, f64::MIN), |best, (k, s)| match s > best.1 {
+            true => (k, s),
+            false => best,
+        })
+        .0
+}
+
+/// Prime of the factor base, with a square root of `k*n` modulo it.
+struct Prime {
+    p: u32,
+    sqrt: u32,
+    log: u8,
+    /// `ceil(2^64 / p)`, to reduce offsets modulo p without a division.
+    magic: u64,
+}
+
+impl Prime {
+    fn new(p: u32, sqrt: u32) -> Prime {
+        Prime {
+            p,
+            sqrt,
+            log: f64::from(p).log2().round() as u8,
+            magic: u64::MAX / u64::from(p) + 1,
+        }
+    }
+
+    /// Returns `j mod p`, with Lemire's fastmod.
+    fn reduce(&self, j: u32) -> u32 {
+        let low = self.magic.wrapping_mul(u64::from(j));
+        ((u128::from(low) * u128::from(self.p)) >> 64) as u32
+    }
+}
+
+/// Primes p such that `k*n` is a square modulo p, starting with 2.
+struct FactorBase {
+    primes: Vec<Prime>,
+}
+
+impl FactorBase {
+    /// Builds a factor base of `size` primes, or returns a prime factor of n met along the way.
+    fn new(n: &Integer, kn: &Integer, size: usize) -> Result<FactorBase, Integer> {
+        let mut primes = vec![Prime::new(2, kn.mod_u(2))];
+        for p in primal::Primes::all().skip(1) {
+            if primes.len() >= size {
+                break;
+            }
+            let p = p as u32;
+            if n.is_divisible_u(p) {
+                return Err(Integer::from(p));
+            }
+            let r = u64::from(kn.mod_u(p));
+            if r != 0 && !legendre(r, u64::from(p)) {
+                continue;
+            }
+            primes.push(Prime::new(p, sqrt_mod(r, u64::from(p)) as u32));
+        }
+        Ok(FactorBase { primes })
+    }
+
+    /// Largest prime of the factor base.
+    fn max_prime(&self) -> u32 {
+        self.primes.last().map_or(2, |prime| prime.p)
+    }
+}
+
+/// Relation `y^2 = (-1)^e0 * prod(p_i) * large^2 (mod n)`.
+struct Relation {
+    y: Integer,
+    /// Indices of the factors in the matrix, 0 for -1 and `i + 1` for the i-th prime, with repetitions.
+    factors: Vec<u32>,
+    /// Product of the large primes of the paired partial relations, whose squares divide `Q(y)`.
+    large: Integer,
+}
+
+/// State of the sieve: factor base, relations collected and polynomials already used.
+struct Sieve {
+    n: Integer,
+    kn: Integer,
+    fb: FactorBase,
+    m: u32,
+    large_bound: u64,
+    relations: Vec<Relation>,
+    partials: HashMap<u64, Relation>,
+    used: HashSet<Vec<usize>>,
+    rand: RandState<'static>,
+}
+
+/// Polynomial `(A x + B)^2 - k n = A (A x^2 + 2 B x + C)`, with the roots of the sieve for each prime.
+struct Poly {
+    a: Integer,
+    b: Integer,
+    c: Integer,
+    /// Indices of the primes dividing A.
+    a_factors: Vec<usize>,
+    /// Terms `B_l` of B, such that B is a sum of `±B_l`.
+    terms: Vec<Integer>,
+    signs: Vec<bool>,
+    /// `2 B_l / A (mod p)` for each term and each prime.
+    deltas: Vec<Vec<u32>>,
+    /// Offsets `x + M (mod p)` in the interval of the roots x of `(A x + B)^2 = k n (mod p)`,
+    /// `u32::MAX` for the primes dividing A.
+    roots: Vec<(u32, u32)>,
+}
+
+impl Sieve {
+    fn new(n: Integer, kn: Integer, fb: FactorBase, m: u32) -> Sieve {
+        let max = u64::from(fb.max_prime());
+        let large_bound = (max * LARGE_PRIME_MULTIPLIER).min(max * max);
+        let mut rand = RandState::new();
+        rand.seed(&n);
+        Sieve {
+            n,
+            kn,
+            fb,
+            m,
+            large_bound,
+            relations: vec![],
+            partials: HashMap::new(),
+            used: HashSet::new(),
+            rand,
+        }
+    }
+
+    /// First polynomial of a new A, a product of primes of the factor base close to `sqrt(2 k n) / M`.
+    fn next_a(&mut self) -> Option<Poly> {
+        let target = Integer::from(&self.kn * 2).sqrt() / self.m;
+        let target_bits = target.significant_bits() as f64;
+        let primes = &self.fb.primes;
+
+        // Primes of about 11 bits, smaller for small n, the last one is chosen to fit the target
+        let usable = |i: usize| primes[i].p > SMALL_PRIME && primes[i].sqrt != 0;
+        let max_bits = f64::from(self.fb.max_prime()).log2() - 1.0;
+        let mut s = (target_bits / max_bits.min(11.0)).round().max(1.0) as usize;
+        if target_bits / (s as f64) > max_bits {
+            s += 1;
+        }
+        let q_bits = target_bits / s as f64;
+        let lo = primes.partition_point(|prime| f64::from(prime.p).log2() < q_bits - 0.5);
+        let hi = primes.partition_point(|prime| f64::from(prime.p).log2() < q_bits + 0.5);
+        let candidates: Vec<usize> = (lo.saturating_sub(8)..(hi + 8).min(primes.len()))
+            .filter(|&i| usable(i))
+            .collect();
+        if candidates.len() < s + 2 {
+            return None;
+        }
+
+        for _ in 0..1_000 {
+            let mut a_factors: Vec<usize> = vec![];
+            let mut a = Integer::from(1);
+            while a_factors.len() + 1 < s {
+                let pick = Integer::from(candidates.len()).random_below(&mut self.rand);
+                let i = candidates[pick.to_usize().unwrap_or(0)];
+                if !a_factors.contains(&i) {
+                    a_factors.push(i);
+                    a *= primes[i].p;
+                }
+            }
+            let rest = Integer::from(&target / &a).to_u64().unwrap_or(u64::MAX);
+            let last = (1..primes.len())
+                .filter(|&i| usable(i) && !a_factors.contains(&i))
+                .min_by_key(|&i| u64::from(primes[i].p).abs_diff(rest));
+            let Some(last) = last else { continue };
+            a_factors.push(last);
+            a *= primes[last].p;
+            a_factors.sort_unstable();
+            if self.used.insert(a_factors.clone()) {
+                return Some(self.first_b(a, a_factors));
+            }
+        }
+        None
+    }
+
+    /// Terms `B_l` and roots of the first B of A.
+    fn first_b(&self, a: Integer, a_factors: Vec<usize>) -> Poly {
+        let primes = &self.fb.primes;
+        let mut terms = vec![];
+        for &i in &a_factors {
+            let q = primes[i].p;
+            let a_q = Integer::from(&a / q);
+            let inverse = inv_mod(u64::from(a_q.mod_u(q)), u64::from(q));
+            let mut gamma = u64::from(primes[i].sqrt) * inverse % u64::from(q);
+            if gamma > u64::from(q / 2) {
+                gamma = u64::from(q) - gamma;
+            }
+            terms.push(a_q * gamma);
+        }
+        let b: Integer = terms.iter().sum();
+        let c = (Integer::from(b.square_ref()) - &self.kn) / &a;
+
+        let mut roots = Vec::with_capacity(primes.len());
+        let mut deltas = vec![Vec::with_capacity(primes.len()); terms.len()];
+        for prime in primes {
+            let p = u64::from(prime.p);
+            let a_p = u64::from(a.mod_u(prime.p));
+            if a_p == 0 {
+                roots.push((u32::MAX, u32::MAX));
+                for delta in &mut deltas {
+                    delta.push(0);
+                }
+                continue;
+            }
+            let a_inv = inv_mod(a_p, p);
+            let b_p = u64::from(b.mod_u(prime.p));
+            let t = u64::from(prime.sqrt);
+            let m = u64::from(self.m) % p;
+            let r1 = ((t + p - b_p) % p * a_inv + m) % p;
+            let r2 = ((2 * p - t - b_p) % p * a_inv + m) % p;
+            roots.push((r1 as u32, r2 as u32));
+            for (l, term) in terms.iter().enumerate() {
+                let delta = 2 * u64::from(term.mod_u(prime.p)) % p * a_inv % p;
+                deltas[l].push(delta as u32);
+            }
+        }
+        Poly {
+            a,
+            b,
+            c,
+            a_factors,
+            signs: vec![true; terms.len()],
+            terms,
+            deltas,
+            roots,
+        }
+    }
+
+    /// Next B of the same A, flipping the sign of `B_l` in B.
+    fn next_b(&self, poly: &mut Poly, l: usize) {
+        // B' = B - 2 B_l if B_l was added, B + 2 B_l otherwise
+        let added = poly.signs[l];
+        poly.signs[l] = !added;
+        let twice = Integer::from(&poly.terms[l] * 2);
+        match added {
+            true => poly.b -= twice,
+            false => poly.b += twice,
+        }
+        poly.c = (Integer::from(poly.b.square_ref()) - &self.kn) / &poly.a;
+
+        // Roots (±t - B) / A move by -(±2 B_l) / A
+        let deltas = &poly.deltas[l];
+        for ((prime, roots), &delta) in self.fb.primes.iter().zip(&mut poly.roots).zip(deltas) {
+            if roots.0 == u32::MAX {
+                continue;
+            }
+            let p = prime.p;
+            let delta = match added {
+                true => delta,
+                false => p - delta,
+            };
+            let shift = |r: u32| match r + delta {
+                r if r >= p => r - p,
+                r => r,
+            };
+            *roots = (shift(roots.0), shift(roots.1));
+        }
+    }
+
+    /// Sieves `[-M, M)` with the polynomial and keeps the relations found.
+    fn sieve_poly(&mut self, poly: &Poly, sieve: &mut [u8]) {
+        sieve.fill(0);
+        let len = sieve.len();
+        let sieved = self
+            .fb
+            .primes
+            .partition_point(|prime| prime.p < SMALL_PRIME);
+        let medium = self
+            .fb
+            .primes
+            .partition_point(|prime| prime.p < BLOCK as u32);
+
+        // Primes below the size of a block, one block at a time to stay in the cache
+        let mut next: Vec<(usize, usize)> = poly.roots[sieved..medium]
+            .iter()
+            .map(|&(r1, r2)| (r1 as usize, r2 as usize))
+            .collect();
+        for start in (0..len).step_by(BLOCK) {
+            let end = start + BLOCK;
+            let primes = &self.fb.primes[sieved..medium];
+            for (prime, (j1, j2)) in primes.iter().zip(&mut next) {
+                if *j1 == u32::MAX as usize {
+                    continue;
+                }
+                let p = prime.p as usize;
+                while *j1 < end {
+                    sieve[*j1] = sieve[*j1].wrapping_add(prime.log);
+                    *j1 += p;
+                }
+                while *j2 < end {
+                    sieve[*j2] = sieve[*j2].wrapping_add(prime.log);
+                    *j2 += p;
+                }
+            }
+        }
+        // Larger primes hit the interval a few times at most
+        for (prime, &(r1, r2)) in self.fb.primes[medium..].iter().zip(&poly.roots[medium..]) {
+            if r1 == u32::MAX {
+                continue;
+            }
+            let p = prime.p as usize;
+            for mut j in [r1 as usize, r2 as usize] {
+                while j < len {
+                    sieve[j] = sieve[j].wrapping_add(prime.log);
+                    j += p;
+                }
+            }
+        }
+
+        // |A x^2 + 2 B x + C| is below M sqrt(k n / 2), of which the large prime and the small primes are not sieved
+        let max_bits = (self.kn.significant_bits() / 2) as f64 + f64::from(self.m).log2() - 0.5;
+        let threshold = max_bits - (self.large_bound as f64).log2() - THRESHOLD_SLACK;
+        let threshold = threshold.max(0.0) as u8;
+        for (chunk, values) in sieve.chunks_exact(64).enumerate() {
+            if values.iter().all(|&value| value < threshold) {
+                continue;
+            }
+            for (j, &value) in values.iter().enumerate() {
+                if value >= threshold {
+                    self.check(poly, (chunk * 64 + j) as u32);
+                }
+            }
+        }
+    }
+
+    /// Divides `A x^2 + 2 B x + C` by the factor base, keeping it as a full or partial relation.
+    fn check(&mut self, poly: &Poly, j: u32) {
+        let x = i64::from(j) - i64::from(self.m);
+        let mut value = Integer::from(&poly.a * x);
+        value += &poly.b;
+        let y = value.clone();
+        value += &poly.b;
+        value *= x;
+        value += &poly.c;
+
+        let mut factors = vec![];
+        if value < 0 {
+            factors.push(0);
+            value = -value;
+        }
+        if value == 0 {
+            return;
+        }
+        for (i, prime) in self.fb.primes.iter().enumerate() {
+            let p = prime.p;
+            let (r1, r2) = poly.roots[i];
+            let divides = match r1 {
+                // 2 and the primes dividing A
+                u32::MAX => value.is_divisible_u(p),
+                _ if p == 2 => value.is_even(),
+                _ => {
+                    let r = prime.reduce(j);
+                    r == r1 || r == r2
+                }
+            };
+            if !divides {
+                continue;
+            }
+            while value.is_divisible_u(p) {
+                value.div_exact_u_mut(p);
+                factors.push(i as u32 + 1);
+            }
+        }
+        for &i in &poly.a_factors {
+            factors.push(i as u32 + 1);
+        }
+
+        let relation = Relation {
+            y,
+            factors,
+            large: Integer::from(1),
+        };
+        if value == 1 {
+            self.relations.push(relation);
+        } else if let Some(large) = value.to_u64().filter(|&large| large < self.large_bound) {
+            match self.partials.remove(&large) {
+                Some(other) => {
+                    let mut factors = relation.factors;
+                    factors.extend(other.factors);
+                    self.relations.push(Relation {
+                        y: relation.y * other.y % &self.n,
+                        factors,
+                        large: Integer::from(large),
+                    });
+                }
+                None => {
+                    self.partials.insert(large, relation);
+                }
+            }
+        }
+    }
+
+    /// Finds the dependencies between the relations and tries each of them.
+    fn combine(&self) -> Option<Integer> {
+        let columns = self.fb.primes.len() + 1;
+        let vectors: Vec<Vec<u32>> = self
+            .relations
+            .iter()
+            .map(|relation| {
+                let mut odd = vec![];
+                let mut factors = relation.factors.clone();
+                factors.sort_unstable();
+                for chunk in factors.chunk_by(|a, b| a == b) {
+                    if chunk.len() % 2 == 1 {
+                        odd.push(chunk[0]);
+                    }
+                }
+                odd
+            })
+            .collect();
+
+        for dependency in null_space(&vectors, columns) {
+            let mut x = Integer::from(1);
+            let mut large = Integer::from(1);
+            let mut exponents = vec![0u32; columns];
+            for &r in &dependency {
+                let relation = &self.relations[r];
+                x = x * &relation.y % &self.n;
+                large = large * &relation.large % &self.n;
+                for &i in &relation.factors {
+                    exponents[i as usize] += 1;
+                }
+            }
+            let mut y = large;
+            for (i, &e) in exponents.iter().enumerate().skip(1) {
+                let p = Integer::from(self.fb.primes[i - 1].p);
+                if let Ok(power) = p.pow_mod(&Integer::from(e / 2), &self.n) {
+                    y = y * power % &self.n;
+                }
+            }
+            let g = Integer::from(&x - &y).gcd(&self.n);
+            if g != 1 && g != self.n {
+                return Some(g);
+            }
+        }
+        None
+    }
+}
+
+/// Sets of vectors over GF(2) summing to zero, from the odd coordinates of each vector.
+///
+/// Gaussian elimination on the transposed matrix, one row per coordinate and one bit per vector,
+/// after dropping the vectors with a coordinate that no other vector has.
+fn null_space(vectors: &[Vec<u32>], columns: usize) -> Vec<Vec<usize>> {
+    // Singletons cannot be part of a dependency
+    let mut alive = vec![true; vectors.len()];
+    loop {
+        let mut counts = vec![0u32; columns];
+        for (vector, _) in vectors.iter().zip(&alive).filter(|(_, &alive)| alive) {
+            for &i in vector {
+                counts[i as usize] += 1;
+            }
+        }
+        let mut removed = false;
+        for (vector, alive) in vectors.iter().zip(&mut alive) {
+            if *alive && vector.iter().any(|&i| counts[i as usize] == 1) {
+                *alive = false;
+                removed = true;
+            }
+        }
+        if !removed {
+            break;
+        }
+    }
+    let kept: Vec<usize> = (0..vectors.len()).filter(|&r| alive[r]).collect();
+    let mut rows_of = vec![usize::MAX; columns];
+    let mut rows = 0;
+    for &r in &kept {
+        for &i in &vectors[r] {
+            if rows_of[i as usize] == usize::MAX {
+                rows_of[i as usize] = rows;
+                rows += 1;
+            }
+        }
+    }
+
+    let words = kept.len().div_ceil(64);
+    let mut matrix = vec![vec![0u64; words]; rows];
+    for (c, &r) in kept.iter().enumerate() {
+        for &i in &vectors[r] {
+            matrix[rows_of[i as usize]][c / 64] ^= 1 << (c % 64);
+        }
+    }
+
+    // Reduced row echelon form, pivots[r] is the column of the pivot of row r
+    let bit = |row: &[u64], c: usize| row[c / 64] >> (c % 64) & 1 == 1;
+    let mut pivots = vec![];
+    for c in 0..kept.len() {
+        let rank = pivots.len();
+        let Some(r) = (rank..rows).find(|&r| bit(&matrix[r], c)) else {
+            continue;
+        };
+        matrix.swap(r, rank);
+        let pivot = std::mem::take(&mut matrix[rank]);
+        for (r, row) in matrix.iter_mut().enumerate() {
+            if r != rank && bit(row, c) {
+                for (word, &p) in row.iter_mut().zip(&pivot) {
+                    *word ^= p;
+                }
+            }
+        }
+        matrix[rank] = pivot;
+        pivots.push(c);
+    }
+
+    // Each free column f with the pivots of the rows where it is set
+    let mut is_pivot = vec![false; kept.len()];
+    for &c in &pivots {
+        is_pivot[c] = true;
+    }
+    (0..kept.len())
+        .filter(|&f| !is_pivot[f])
+        .map(|f| {
+            let mut dependency = vec![kept[f]];
+            for (r, &c) in pivots.iter().enumerate() {
+                if bit(&matrix[r], f) {
+                    dependency.push(kept[c]);
+                }
+            }
+            dependency
+        })
+        .collect()
+}
+
+/// Returns true if a is a nonzero square modulo the odd prime p.
+fn legendre(a: u64, p: u64) -> bool {
+    pow_mod(a, (p - 1) / 2, p) == 1
+}
+
+/// Returns a square root of the square a modulo the prime p, with Tonelli-Shanks.
+fn sqrt_mod(a: u64, p: u64) -> u64 {
+    let a = a % p;
+    if a == 0 || p == 2 {
+        return a;
+    }
+    // p - 1 = q 2^s
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    let z = (2..p).find(|&z| !legendre(z, p)).unwrap_or(2);
+    let mut m = s;
+    let mut c = pow_mod(z, q, p);
+    let mut t = pow_mod(a, q, p);
+    let mut r = pow_mod(a, q.div_ceil(2), p);
+    while t != 1 {
+        let mut i = 0;
+        let mut t2 = t;
+        while t2 != 1 {
+            t2 = t2 * t2 % p;
+            i += 1;
+        }
+        let b = pow_mod(c, 1 << (m - i - 1), p);
+        m = i;
+        c = b * b % p;
+        t = t * c % p;
+        r = r * b % p;
+    }
+    r
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, p: u64) -> u64 {
+    let mut result = 1;
+    base %= p;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % p;
+        }
+        base = base * base % p;
+        exp >>= 1;
+    }
+    result
+}
+
+/// Inverse of a modulo p, for a coprime to p.
+fn inv_mod(a: u64, p: u64) -> u64 {
+    let (mut r0, mut r1) = (p as i64, a as i64);
+    let (mut t0, mut t1) = (0i64, 1i64);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (t0, t1) = (t1, t0 - q * t1);
+    }
+    t0.rem_euclid(p as i64) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn modular() {
+        for p in [3u64, 5, 13, 17, 97, 65_537, 1_000_003] {
+            for a in 1..50 {
+                if legendre(a % p, p) {
+                    let r = sqrt_mod(a, p);
+                    assert_eq!(r * r % p, a % p, "sqrt({a}) mod {p}");
+                }
+                if a % p != 0 {
+                    assert_eq!(a * inv_mod(a % p, p) % p, 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn null_space() {
+        // {0, 1} + {1, 2} + {0, 2} = 0, {3} is a singleton
+        let vectors = vec![vec![0, 1], vec![1, 2], vec![3], vec![0, 2], vec![]];
+        let mut dependencies = super::null_space(&vectors, 4);
+        for dependency in &mut dependencies {
+            dependency.sort_unstable();
+        }
+        assert!(dependencies.contains(&vec![0, 1, 3]));
+        assert!(dependencies.contains(&vec![4]));
+        assert_eq!(dependencies.len(), 2);
+    }
+
+    #[test]
+    fn semiprimes() {
+        for (p, q) in [
+            ("1000000000000000000117", "3000000000000000000053"),
+            ("4009823", "100000000000000000000000000319"),
+            (
+                "100000000000000000000000000319",
+                "300000000000000000000000000007",
+            ),
+        ] {
+            let p = Integer::from_str(p).unwrap();
+            let q = Integer::from_str(q).unwrap();
+            let g = siqs(&Integer::from(&p * &q)).unwrap();
+            assert!(g == p || g == q, "{p} * {q}: {g}");
+        }
+    }
+
+    #[test]
+    fn no_factor() {
+        let p = Integer::from_str("100000000000000000000000000319").unwrap();
+        assert_eq!(siqs(&p), None);
+        assert_eq!(siqs(&Integer::from(p.square_ref())), None);
+        assert_eq!(siqs(&Integer::from(398883434337287u64)), None);
+        let mut siqs = Siqs::new(&(p * 1_000_003)).ok().unwrap();
+        assert_eq!(siqs.run(&|| true), Poll::Pending);
+    }
+}
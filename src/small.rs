@@ -0,0 +1,185 @@
+/// Number of steps of the sequence between two gcds.
+const GCD_BATCH: u64 = 128;
+
+/// Cofactors of up to this many bits are given to [`small_factor`] by default.
+pub const SMALL_COFACTOR_BITS: u32 = 128;
+
+/// Maximum number of steps of [`small_factor`], enough for factors up to about 10^12.
+pub(crate) const SMALL_RHO_STEPS: u64 = 1 << 20;
+
+/// Returns a proper factor of a composite n fitting in 128 bits.
+///
+/// Runs Pollard's rho method with Brent's cycle detection on native integers,
+/// with Montgomery multiplication, much faster than curves on such small numbers.
+/// Returns `None` if no factor was found within `max_steps` steps of the sequence.
+///
+/// # Parameters
+///
+/// - `n`: Composite number to be factored.
+/// - `max_steps`: Maximum number of steps of the sequence.
+pub(crate) fn small_factor(n: u128, max_steps: u64) -> Option<u128> {
+    if n.is_multiple_of(2) {
+        return (n > 2).then_some(2);
+    }
+
+    let mont = Montgomery::new(n);
+    // Constants giving degenerate sequences modulo the factors are retried
+    (1..=3).find_map(|c| mont.rho(c, max_steps))
+}
+
+/// Montgomery arithmetic modulo an odd n, with `R = 2^128`.
+struct Montgomery {
+    n: u128,
+    /// `-n^-1 mod R`.
+    n_neg_inv: u128,
+}
+
+impl Montgomery {
+    fn new(n: u128) -> Montgomery {
+        // Newton's iteration doubles the correct low bits of the inverse, n is its own inverse mod 8
+        let mut inv = n;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2u128.wrapping_sub(n.wrapping_mul(inv)));
+        }
+        Montgomery {
+            n,
+            n_neg_inv: inv.wrapping_neg(),
+        }
+    }
+
+    /// `a * b / R mod n`.
+    fn mul(&self, a: u128, b: u128) -> u128 {
+        let (hi, lo) = mul_wide(a, b);
+        let m = lo.wrapping_mul(self.n_neg_inv);
+        let (mh, ml) = mul_wide(m, self.n);
+        // lo + ml = 0 mod R, with a carry unless both are 0
+        let carry = u128::from(lo.overflowing_add(ml).1);
+        let (t, o1) = hi.overflowing_add(mh);
+        let (t, o2) = t.overflowing_add(carry);
+        if o1 || o2 || t >= self.n {
+            t.wrapping_sub(self.n)
+        } else {
+            t
+        }
+    }
+
+    /// `a + b mod n`.
+    fn add(&self, a: u128, b: u128) -> u128 {
+        let (s, overflow) = a.overflowing_add(b);
+        if overflow || s >= self.n {
+            s.wrapping_sub(self.n)
+        } else {
+            s
+        }
+    }
+
+    /// Pollard's rho with the sequence `x -> x^2 / R + c (mod n)`, see [`crate::rho`].
+    ///
+    /// The division by R is well defined modulo every factor of n,
+    /// so the sequence cycles modulo the factors as with `x^2 + c`.
+    fn rho(&self, c: u128, max_steps: u64) -> Option<u128> {
+        let n = self.n;
+        let f = |x: u128| self.add(self.mul(x, x), c);
+
+        let mut y = 2;
+        let mut x = y;
+        let mut ys = y;
+        let mut q = 1;
+        let mut g = 1;
+        let mut r = 1;
+        let mut steps = 0;
+        while g == 1 {
+            if steps > max_steps {
+                return None;
+            }
+            x = y;
+            for _ in 0..r {
+                y = f(y);
+            }
+            let mut k = 0;
+            while k < r && g == 1 {
+                // Start of the batch, to backtrack if the batch overshoots
+                ys = y;
+                for _ in 0..GCD_BATCH.min(r - k) {
+                    y = f(y);
+                    q = self.mul(q, x.abs_diff(y));
+                }
+                g = gcd(q, n);
+                k += GCD_BATCH;
+            }
+            steps += 2 * r;
+            r *= 2;
+        }
+
+        if g == n {
+            // All the factors were caught in the same batch, retry one step at a time
+            loop {
+                ys = f(ys);
+                g = gcd(x.abs_diff(ys), n);
+                if g != 1 {
+                    break;
+                }
+            }
+        }
+        (g != n).then_some(g)
+    }
+}
+
+/// 256-bit product of a and b, as `(high, low)` halves.
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a1, a0) = (a >> 64, a & MASK);
+    let (b1, b0) = (b >> 64, b & MASK);
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let mid = (p00 >> 64) + (p01 & MASK) + (p10 & MASK);
+    let lo = (p00 & MASK) | (mid << 64);
+    let hi = a1 * b1 + (p01 >> 64) + (p10 >> 64) + (mid >> 64);
+    (hi, lo)
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rug::Integer;
+
+    #[test]
+    fn montgomery() {
+        let n = u128::MAX - 158; // Odd, close to 2^128
+        let mont = Montgomery::new(n);
+        assert_eq!(n.wrapping_mul(mont.n_neg_inv), u128::MAX);
+
+        let (a, b) = (u128::MAX / 3, u128::MAX / 7 + 12345);
+        // a * b / R mod n, checked with big integers
+        let r_inv = Integer::from(Integer::u_pow_u(2, 128))
+            .invert(&n.into())
+            .unwrap();
+        let expected = Integer::from(a) * b * r_inv % n;
+        assert_eq!(Integer::from(mont.mul(a, b)), expected);
+        assert_eq!(mont.add(n - 1, n - 2), n - 3);
+    }
+
+    #[test]
+    fn factors() {
+        let n: u128 = 20000003 * 40000003 * 60000011 * 80000023;
+        let g = small_factor(n, SMALL_RHO_STEPS).unwrap();
+        assert!(g != 1 && g != n && n.is_multiple_of(g));
+
+        let n = 1_000_003u128 * 999_983;
+        let g = small_factor(n, SMALL_RHO_STEPS).unwrap();
+        assert!(g == 1_000_003 || g == 999_983);
+
+        assert_eq!(small_factor(2 * 1_000_003, 10), Some(2));
+        // Two primes of 64 bits need about 2^32 steps
+        let n = 18446744073709551557u128 * 18446744073709551533;
+        assert_eq!(small_factor(n, 1 << 12), None);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::EcmStage;
+use rug::Integer;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Number of factors found and curves needed for one bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bucket {
+    /// Number of factors found.
+    pub factors: u64,
+    /// Total number of curves run to find these factors.
+    pub curves: u64,
+    /// Fewest curves needed to find one factor.
+    pub min_curves: u64,
+    /// Most curves needed to find one factor.
+    pub max_curves: u64,
+}
+
+impl Bucket {
+    /// Average number of curves needed to find one factor.
+    pub fn mean_curves(&self) -> f64 {
+        if self.factors == 0 {
+            0.0
+        } else {
+            self.curves as f64 / self.factors as f64
+        }
+    }
+
+    fn merge(&mut self, other: &Bucket) {
+        if self.factors == 0 {
+            *self = *other;
+        } else if other.factors != 0 {
+            self.factors += other.factors;
+            self.curves += other.curves;
+            self.min_curves = self.min_curves.min(other.min_curves);
+            self.max_curves = self.max_curves.max(other.max_curves);
+        }
+    }
+}
+
+/// Curves needed to find factors, grouped by factor size and B1.
+///
+/// Prime factors found by elliptic curves are recorded by the [`Factorizer`](crate::Factorizer),
+/// buckets of several runs can be merged and exported as CSV to study
+/// the effectiveness of the bounds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CurveBuckets {
+    /// Buckets indexed by (factor digits, B1).
+    buckets: BTreeMap<(usize, u64), Bucket>,
+}
+
+impl CurveBuckets {
+    /// Creates empty buckets.
+    pub fn new() -> CurveBuckets {
+        CurveBuckets::default()
+    }
+
+    /// Records a prime factor found after running `curves` curves with the stage 1 bound `b1`.
+    ///
+    /// Composite factors, found when a curve splits several primes at once, should
+    /// not be recorded: the buckets measure the curves needed for one prime of a given size.
+    ///
+    /// # Parameters
+    ///
+    /// - `factor`: Prime factor found.
+    /// - `b1`: Stage 1 Bound.
+    /// - `curves`: Number of curves run to find the factor, including the successful one.
+    pub fn record(&mut self, factor: &Integer, b1: u64, curves: u64) {
+        let digits = factor.to_string().trim_start_matches('-').len();
+        self.buckets
+            .entry((digits, b1))
+            .or_default()
+            .merge(&Bucket {
+                factors: 1,
+                curves,
+                min_curves: curves,
+                max_curves: curves,
+            });
+    }
+
+    /// Adds the buckets of another run.
+    pub fn merge(&mut self, other: &CurveBuckets) {
+        for (key, bucket) in &other.buckets {
+            self.buckets.entry(*key).or_default().merge(bucket);
+        }
+    }
+
+    /// Iterates over `((factor digits, B1), bucket)`, sorted by factor digits then B1.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, u64), &Bucket)> {
+        self.buckets.iter().map(|(key, bucket)| (*key, bucket))
+    }
+
+    /// Returns true if no factor was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Exports the buckets as CSV, with a header line.
+    ///
+    /// Columns are `digits,b1,factors,curves,min_curves,max_curves,mean_curves`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("digits,b1,factors,curves,min_curves,max_curves,mean_curves\n");
+        for ((digits, b1), bucket) in self.iter() {
+            // Writing to a String cannot fail
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{:.2}",
+                digits,
+                b1,
+                bucket.factors,
+                bucket.curves,
+                bucket.min_curves,
+                bucket.max_curves,
+                bucket.mean_curves()
+            );
+        }
+        csv
+    }
+}
+
+/// Residue recycling of the [`Factorizer`](crate::Factorizer).
+///
+/// When stage 1 splits a composite, its result is reused on the composite parts
+/// before running fresh curves on them. A hit is a part split this way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecycleStats {
+    /// Composite parts the result of stage 1 was reused on.
+    pub attempts: u64,
+    /// Composite parts split by a reused result.
+    pub hits: u64,
+}
+
+impl RecycleStats {
+    /// Fraction of attempts which split a composite part.
+    pub fn hit_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.attempts as f64
+        }
+    }
+}
+
+/// Statistics of a run of [`ecm_one_factor_ex`](crate::ecm_one_factor_ex).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EcmDiagnostics {
+    /// Curves attempted, including the one which found the factor.
+    pub curves: u64,
+    /// Furthest stage reached by a curve, `None` if no curve was run.
+    pub stage: Option<EcmStage>,
+    /// Modular multiplications of stage 1 and stage 2, squarings included.
+    pub mulmods: u64,
+    /// Time spent deriving the curves.
+    pub setup_time: Duration,
+    /// Time spent in stage 1.
+    pub stage1_time: Duration,
+    /// Time spent in stage 2.
+    pub stage2_time: Duration,
+    /// Gcds computed at the end of stage 1 and stage 2.
+    pub gcds: u64,
+}
+
+impl EcmDiagnostics {
+    /// Total time spent in the stages.
+    pub fn elapsed(&self) -> Duration {
+        self.setup_time + self.stage1_time + self.stage2_time
+    }
+
+    /// Records that a curve reached `stage`.
+    pub(crate) fn reach(&mut self, stage: EcmStage) {
+        self.stage = self.stage.max(Some(stage));
+    }
+
+    /// Adds the statistics of other curves.
+    pub(crate) fn merge(&mut self, other: &EcmDiagnostics) {
+        self.curves += other.curves;
+        self.stage = self.stage.max(other.stage);
+        self.mulmods += other.mulmods;
+        self.setup_time += other.setup_time;
+        self.stage1_time += other.stage1_time;
+        self.stage2_time += other.stage2_time;
+        self.gcds += other.gcds;
+    }
+}
+
+/// Method which found a prime factor, see [`Stats::factors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FactorMethod {
+    /// Divided out by trial division, or prime cofactor left by trial division.
+    TrialDivision,
+    /// Part of a factor given with [`Factorizer::add_known_factor`](crate::Factorizer::add_known_factor).
+    KnownFactor,
+    /// Pollard's rho method, on small composites or with [`DriverMode::Rho`](crate::DriverMode::Rho).
+    Rho,
+    /// Shanks' square forms factorization, on composites of at most [`SQUFOF_BITS`](crate::SQUFOF_BITS) bits.
+    Squfof,
+    /// Hart's one line factorization, on composites of [`ONE_LINE_BITS`](crate::ONE_LINE_BITS) bits by default.
+    OneLine,
+    /// Fermat's method, run before the curves, see [`Factorizer::set_fermat_steps`](crate::Factorizer::set_fermat_steps).
+    Fermat,
+    /// Pollard's p-1 method, run before the curves, see [`Factorizer::set_pm1_bounds`](crate::Factorizer::set_pm1_bounds).
+    PMinusOne,
+    /// Williams' p+1 method, with [`DriverMode::PPlusOne`](crate::DriverMode::PPlusOne)
+    /// or before the curves, see [`Factorizer::set_pp1_bounds`](crate::Factorizer::set_pp1_bounds).
+    PPlusOne {
+        /// Starting value x0 of the Lucas sequence which found the factor, one of [`PP1_SEEDS`](crate::PP1_SEEDS).
+        seed: u32,
+    },
+    /// Self-initializing quadratic sieve, once ECM failed, see [`Factorizer::set_siqs_digits`](crate::Factorizer::set_siqs_digits).
+    Siqs,
+    /// Elliptic curve, at the given stage.
+    Ecm(EcmStage),
+    /// Result of stage 1 reused on a composite part, see [`RecycleStats`].
+    Recycling,
+}
+
+/// Statistics of a run of a [`Factorizer`](crate::Factorizer), see [`Factorizer::stats`](crate::Factorizer::stats).
+///
+/// Times of curves run concurrently are summed, so they may exceed the wall-clock time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Curves run on all the composites, by stage 1 bound.
+    pub curves_by_b1: BTreeMap<u64, u64>,
+    /// Time spent in trial division.
+    pub trial_division_time: Duration,
+    /// Setup, stage 1 and stage 2 of all the curves.
+    pub stages: EcmDiagnostics,
+    /// Gcds computed by trial division and by the curves.
+    pub gcds: u64,
+    /// Prime factors found, with the method which found each of them.
+    pub factors: BTreeMap<Integer, FactorMethod>,
+}
+
+impl Stats {
+    /// Total number of curves run.
+    pub fn curves(&self) -> u64 {
+        self.curves_by_b1.values().sum()
+    }
+}
+
+/// Modular inversions of the whole process, see [`try_invert_or_factor`](crate::try_invert_or_factor).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InversionStats {
+    /// Inversions attempted.
+    pub inversions: u64,
+    /// Inversions which failed, the number was not coprime with the modulus.
+    pub failures: u64,
+    /// Failures which gave a proper factor of the modulus.
+    pub factors: u64,
+}
+
+static INVERSIONS: AtomicU64 = AtomicU64::new(0);
+static INVERSION_FAILURES: AtomicU64 = AtomicU64::new(0);
+static INVERSION_FACTORS: AtomicU64 = AtomicU64::new(0);
+
+/// Modular inversions attempted since the start of the process, by all threads.
+pub fn inversion_stats() -> InversionStats {
+    InversionStats {
+        inversions: INVERSIONS.load(Ordering::Relaxed),
+        failures: INVERSION_FAILURES.load(Ordering::Relaxed),
+        factors: INVERSION_FACTORS.load(Ordering::Relaxed),
+    }
+}
+
+/// Records an inversion modulo n, which failed with the divisor `g` if any.
+pub(crate) fn record_inversion(g: Option<&Integer>, n: &Integer) {
+    INVERSIONS.fetch_add(1, Ordering::Relaxed);
+    if let Some(g) = g {
+        INVERSION_FAILURES.fetch_add(1, Ordering::Relaxed);
+        if *g != 1 && g != n {
+            INVERSION_FACTORS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_merge() {
+        let mut buckets = CurveBuckets::new();
+        buckets.record(&Integer::from(2802377), 2_000, 3);
+        buckets.record(&Integer::from(4009823), 2_000, 7);
+        buckets.record(&Integer::from(99476569), 2_000, 1);
+
+        let mut other = CurveBuckets::new();
+        other.record(&Integer::from(2634823), 2_000, 2);
+        other.record(&Integer::from(2634823), 11_000, 4);
+        buckets.merge(&other);
+
+        assert_eq!(
+            buckets.iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    (7, 2_000),
+                    &Bucket {
+                        factors: 3,
+                        curves: 12,
+                        min_curves: 2,
+                        max_curves: 7
+                    }
+                ),
+                (
+                    (7, 11_000),
+                    &Bucket {
+                        factors: 1,
+                        curves: 4,
+                        min_curves: 4,
+                        max_curves: 4
+                    }
+                ),
+                (
+                    (8, 2_000),
+                    &Bucket {
+                        factors: 1,
+                        curves: 1,
+                        min_curves: 1,
+                        max_curves: 1
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let mut buckets = CurveBuckets::new();
+        assert!(buckets.is_empty());
+        buckets.record(&Integer::from(2802377), 2_000, 3);
+        buckets.record(&Integer::from(4009823), 2_000, 6);
+
+        assert_eq!(
+            buckets.to_csv(),
+            "digits,b1,factors,curves,min_curves,max_curves,mean_curves\n7,2000,2,9,3,6,4.50\n"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{EcmOptions, Error, Factorization, Factorizer, StepOutcome};
+use rug::Integer;
+use std::{collections::HashMap, time::Duration};
+
+/// Factors n, yielding each prime factor as soon as it is isolated.
+///
+/// Prime factors come with the multiplicity found at once, e.g. 2 for a square
+/// taken out by trial division: the same prime may be yielded again if more
+/// copies are found later. Meanwhile the remaining cofactor keeps being processed
+/// by the next calls to [`Iterator::next`]. Composites on which ECM failed are
+/// not yielded, see [`EcmStream::cofactors`].
+///
+/// ```
+/// use ecm::{ecm_stream, EcmOptions};
+/// use rug::Integer;
+///
+/// let n = Integer::from(2u64 * 2 * 1_000_003 * 1_000_000_007);
+/// let mut stream = ecm_stream(&n, &EcmOptions::new()).unwrap();
+/// // Small factors come first
+/// assert_eq!(stream.next().unwrap().unwrap(), (Integer::from(2), 2));
+/// assert_eq!(stream.next().unwrap().unwrap(), (Integer::from(1_000_003), 1));
+/// assert_eq!(stream.next().unwrap().unwrap(), (Integer::from(1_000_000_007), 1));
+/// assert!(stream.next().is_none());
+/// ```
+///
+/// # Parameters
+///
+/// - `n`: Number to be factored.
+/// - `options`: Settings of the factorization.
+pub fn ecm_stream(n: &Integer, options: &EcmOptions) -> Result<EcmStream, Error> {
+    Ok(EcmStream {
+        factorizer: options.factorizer(n)?,
+        yielded: HashMap::new(),
+        ready: Vec::new(),
+        done: false,
+    })
+}
+
+/// Iterator over the prime factors of a number, see [`ecm_stream`].
+pub struct EcmStream {
+    factorizer: Factorizer,
+    /// Multiplicity of the factors already yielded.
+    yielded: HashMap<Integer, usize>,
+    /// Factors found but not yielded yet, largest first.
+    ready: Vec<(Integer, usize)>,
+    done: bool,
+}
+
+impl EcmStream {
+    /// Composites on which ECM failed, once the stream is exhausted.
+    pub fn cofactors(&self) -> &HashMap<Integer, usize> {
+        self.factorizer.cofactors()
+    }
+
+    /// Consumes the stream, returning the factors found so far, yielded or not.
+    pub fn into_factors(self) -> Factorization {
+        self.factorizer.into_factors()
+    }
+
+    /// Queues the factors found since the last call.
+    fn collect_found(&mut self) {
+        for (factor, &multiplicity) in self.factorizer.factors() {
+            let yielded = self.yielded.entry(factor.clone()).or_insert(0);
+            if multiplicity > *yielded {
+                self.ready.push((factor.clone(), multiplicity - *yielded));
+                *yielded = multiplicity;
+            }
+        }
+        self.ready.sort_by(|(a, _), (b, _)| b.cmp(a));
+    }
+}
+
+impl Iterator for EcmStream {
+    type Item = Result<(Integer, usize), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(found) = self.ready.pop() {
+                return Some(Ok(found));
+            }
+            if self.done {
+                return None;
+            }
+
+            // Steps as short as possible: one chunk of primes or one curve
+            match self.factorizer.step(Duration::ZERO) {
+                Ok(outcome) => self.done = outcome == StepOutcome::Done,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+            self.collect_found();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn stream() {
+        let p = Integer::from_str("100327907731").unwrap();
+        let q = Integer::from_str("10454157497791297").unwrap();
+        let n = Integer::from(&p * &q) * &p * 3;
+        let options = EcmOptions::new().b1(2_000).b2(160_000).seed(1234);
+
+        let found: Vec<_> = ecm_stream(&n, &options)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(found[0], (Integer::from(3), 1));
+        let factors: Factorization = found.into_iter().collect();
+        assert_eq!(
+            factors,
+            Factorization::from([(3.into(), 1), (p, 2), (q, 1)])
+        );
+    }
+
+    #[test]
+    fn cofactors_and_errors() {
+        // ECM fails with such small bounds
+        let n = Integer::from_str("1048843748843747081710817107").unwrap();
+        let options = EcmOptions::new()
+            .b1(100)
+            .b2(2_000)
+            .max_curves(1)
+            .max_escalations(0)
+            .small_cofactor_bits(0)
+            .seed(1);
+        let mut stream = ecm_stream(&(n.clone() * 5u32), &options).unwrap();
+        assert_eq!(stream.next().unwrap().unwrap(), (Integer::from(5), 1));
+        assert!(stream.next().is_none());
+        assert_eq!(stream.cofactors(), &HashMap::from([(n.clone(), 1)]));
+        assert_eq!(stream.into_factors().product(), n * 5u32);
+
+        assert!(matches!(
+            ecm_stream(&Integer::new(), &options),
+            Err(Error::ZeroInput)
+        ));
+        let mut stream = ecm_stream(&Integer::from(1_000_003), &options.max_input_bits(8)).unwrap();
+        assert!(matches!(stream.next(), Some(Err(Error::InputTooLarge(20)))));
+        assert!(stream.next().is_none());
+    }
+}
@@ -0,0 +1,230 @@
+//! Probability tables used to plan ECM runs.
+//!
+//! The estimates are based on the Dickman rho function, which gives the
+//! probability that a random integer `x` has no prime factor larger than
+//! `x^(1/u)`. The group order of a random elliptic curve over FF(p) behaves
+//! like a random integer close to p, so the probability that a curve finds
+//! a factor p with bounds B1/B2 can be derived from it.
+
+/// Step between two consecutive entries of [`RHO_TABLE`].
+const RHO_STEP: f64 = 0.1;
+
+/// Dickman rho function sampled on `[0, 30]` with a step of [`RHO_STEP`].
+#[rustfmt::skip]
+const RHO_TABLE: [f64; 301] = [
+    1.0000000000e+00, 1.0000000000e+00, 1.0000000000e+00, 1.0000000000e+00,
+    1.0000000000e+00, 1.0000000000e+00, 1.0000000000e+00, 1.0000000000e+00,
+    1.0000000000e+00, 1.0000000000e+00, 1.0000000000e+00, 9.0468982020e-01,
+    8.1767844321e-01, 7.3763573553e-01, 6.6352776338e-01, 5.9453489189e-01,
+    5.2999637075e-01, 4.6937174894e-01, 4.1221333510e-01, 3.5814611383e-01,
+    3.0685281944e-01, 2.6040578096e-01, 2.2035713868e-01, 1.8579946235e-01,
+    1.5599126461e-01, 1.3031956256e-01, 1.0827244369e-01, 8.9418566431e-02,
+    7.3391581462e-02, 5.9878116679e-02, 4.8608388978e-02, 3.9322970145e-02,
+    3.1703445045e-02, 2.5464724344e-02, 2.0371779475e-02, 1.6229593608e-02,
+    1.2875434506e-02, 1.0172838092e-02, 8.0068724276e-03, 6.2803732659e-03,
+    4.9109258192e-03, 3.8285863163e-03, 2.9754749079e-03, 2.3050506123e-03,
+    1.7799425456e-03, 1.3701178074e-03, 1.0514449096e-03, 8.0455868875e-04,
+    6.1395735744e-04, 4.6727990322e-04, 3.5472472316e-04, 2.6857901688e-04,
+    2.0282154913e-04, 1.5276700613e-04, 1.1477420554e-04, 8.6018618120e-05,
+    6.4314685930e-05, 4.7976519069e-05, 3.5708352338e-05, 2.6518202634e-05,
+    1.9649698319e-05, 1.4528201827e-05, 1.0718305609e-05, 7.8907552587e-06,
+    5.7971066187e-06, 4.2503560288e-06, 3.1101268872e-06, 2.2713421548e-06,
+    1.6555708841e-06, 1.2044299183e-06, 8.7456711941e-07, 6.3386234833e-07,
+    4.5856558201e-07, 3.3115202407e-07, 2.3871865120e-07, 1.7178677751e-07,
+    1.2340904199e-07, 8.8504680187e-08, 6.3365885675e-08, 4.5292226139e-08,
+    3.2320699145e-08, 2.3027003899e-08, 1.6379633697e-08, 1.1632969038e-08,
+    8.2490716949e-09, 5.8405708127e-09, 4.1290332405e-09, 2.9146846418e-09,
+    2.0544355247e-09, 1.4459699028e-09, 1.0162485269e-09, 7.1321816444e-10,
+    4.9984339737e-10, 3.4981736618e-10, 2.4448429029e-10, 1.7063531952e-10,
+    1.1893277029e-10, 8.2785672115e-11, 5.7548811979e-11, 3.9953195254e-11,
+    2.7701726575e-11, 1.9182631967e-11, 1.3266646603e-11, 9.1637054098e-12,
+    6.3218383265e-12, 4.3559540256e-12, 2.9977553469e-12, 2.0605702366e-12,
+    1.4146930982e-12, 9.7012051801e-13, 6.6448114318e-13, 4.5460881895e-13,
+    3.1066754179e-13, 2.1206123825e-13, 1.4459012528e-13, 9.8476459102e-14,
+    6.6995731077e-14, 4.5528896659e-14, 3.0906986529e-14, 2.0958484398e-14,
+    1.4197137590e-14, 9.6069024747e-15, 6.4939893329e-15, 4.3851984488e-15,
+    2.9581505895e-15, 1.9934642324e-15, 1.3420153372e-15, 9.0254945571e-16,
+    6.0639078321e-16, 4.0701059806e-16, 2.7291903533e-16, 1.8282699560e-16,
+    1.2235692907e-16, 8.1809151508e-17, 5.4646651214e-17, 3.6468405445e-17,
+    2.4314464117e-17, 1.6196099272e-17, 1.0778467165e-17, 7.1665053050e-18,
+    4.7606326517e-18, 3.1595968365e-18, 2.0951316606e-18, 1.3880543460e-18,
+    9.1879775639e-19, 6.0765131938e-19, 4.0152514102e-19, 2.6509253492e-19,
+    1.7486840695e-19, 1.1525424293e-19, 7.5899127583e-20, 4.9940568734e-20,
+    3.2832921692e-20, 2.1567876922e-20, 1.4156292138e-20, 9.2840675663e-21,
+    6.0838163496e-21, 3.9835040978e-21, 2.6062023035e-21, 1.7037542386e-21,
+    1.1129201332e-21, 7.2640947320e-22, 4.7376355108e-22, 3.0874974218e-22,
+    2.0105668285e-22, 1.3082763375e-22, 8.5065138224e-23, 5.5268407764e-23,
+    3.5882074893e-23, 2.3278522458e-23, 1.5090809256e-23, 9.7757675762e-24,
+    6.3280858645e-24, 4.0933505028e-24, 2.6458941893e-24, 1.7090503290e-24,
+    1.1031331204e-24, 7.1153049419e-25, 4.5862047612e-25, 2.9539931309e-25,
+    1.9013558452e-25, 1.2229732440e-25, 7.8608925846e-26, 5.0492843617e-26,
+    3.2411008691e-26, 2.0790344444e-26, 1.3327199310e-26, 8.5374034369e-27,
+    5.4654262064e-27, 3.4965132671e-27, 2.2354286920e-27, 1.4282443631e-27,
+    9.1193066561e-28, 5.8188914376e-28, 3.7105589555e-28, 2.3646156659e-28,
+    1.5059321847e-28, 9.5846301752e-29, 6.0963774585e-29, 3.8752195828e-29,
+    2.4617853554e-29, 1.5629131977e-29, 9.9163473891e-30, 6.2878501669e-30,
+    3.9846261278e-30, 2.5235346090e-30, 1.5972341313e-30, 1.0103383837e-30,
+    6.3871245858e-31, 4.0353897325e-31, 2.5480528364e-31, 1.6079609372e-31,
+    1.0141167114e-31, 6.3921508213e-32, 4.0267433714e-32, 2.5351868909e-32,
+    1.5952042951e-32, 1.0031690921e-32, 6.3049938651e-33, 3.9604913134e-33,
+    2.4863857090e-33, 1.5600701697e-33, 9.7831138263e-34, 6.1315257707e-34,
+    3.8407815958e-34, 2.4045365521e-34, 1.5045442898e-34, 9.4089570682e-35,
+    5.8808756801e-35, 3.6737329800e-35, 2.2937142759e-35, 1.4313249652e-35,
+    8.9269976360e-36, 5.5647015303e-36, 3.4669584862e-36, 2.1588712218e-36,
+    1.3436222066e-36, 8.3579727466e-37, 5.1963579918e-37, 3.2290330865e-37,
+    2.0054979497e-37, 1.2449422692e-37, 7.7242162801e-38, 4.7900363881e-38,
+    2.9689529618e-38, 1.8392843442e-38, 1.1388759240e-38, 7.0483400772e-39,
+    4.3599459107e-39, 2.6956286919e-39, 1.6658068905e-39, 1.0289061026e-39,
+    6.3520545618e-40, 3.9195928799e-40, 2.4174462812e-40, 1.4902622416e-40,
+    9.1824677528e-41, 5.6551980861e-41, 3.4811983459e-41, 2.1419192141e-41,
+    1.3172603028e-41, 8.0972051346e-42, 4.9750163792e-42, 3.0552765851e-42,
+    1.8754435410e-42, 1.1506830062e-42, 7.0567776857e-43, 4.3257058893e-43,
+    2.6503799236e-43, 1.6231574282e-43, 9.9360847022e-44, 6.0795693631e-44,
+    3.7182106983e-44, 2.2730006966e-44, 1.3888977649e-44, 8.4829491624e-45,
+    5.1788109406e-45, 3.1602437650e-45, 1.9276099735e-45, 1.1752398725e-45,
+    7.1621490048e-46, 4.3628509294e-46, 2.6564906049e-46, 1.6168054992e-46,
+    9.8360232056e-47, 5.9812818904e-47, 3.6356547594e-47, 2.2089471048e-47,
+    1.3415380114e-47, 8.1439725368e-48, 4.9418087201e-48, 2.9974540346e-48,
+    1.8173423797e-48, 1.1013852238e-48, 6.6720708462e-49, 4.0401882671e-49,
+    2.4454722645e-49, 1.4796012662e-49, 8.9484555779e-50, 5.4097044554e-50,
+    3.2690507992e-50,
+];
+
+/// Extra smoothness of the group order of curves generated using Suyama's
+/// parametrization (the order is always divisible by 12), in natural log units.
+const SUYAMA_EXTRA_SMOOTHNESS: f64 = 3.134;
+
+/// Number of intervals used to integrate the stage 2 contribution.
+const STAGE2_STEPS: usize = 200;
+
+/// Dickman rho function.
+///
+/// Returns the asymptotic probability that a random integer `x`
+/// is `x^(1/u)`-smooth. Values are interpolated from an embedded table,
+/// and `0.0` is returned for `u >= 30`.
+///
+/// # Parameters
+///
+/// - `u`: Ratio between the size of the integer and the size of the smoothness bound.
+pub fn dickman_rho(u: f64) -> f64 {
+    if u <= 1.0 {
+        return 1.0;
+    }
+
+    let pos = u / RHO_STEP;
+    let i = pos as usize;
+    if i + 1 >= RHO_TABLE.len() {
+        return 0.0;
+    }
+
+    // Rho decreases roughly exponentially, interpolate in log space
+    let f = pos - i as f64;
+    (RHO_TABLE[i].ln() * (1.0 - f) + RHO_TABLE[i + 1].ln() * f).exp()
+}
+
+/// Returns the probability that one curve finds a prime factor of `factor_bits` bits.
+///
+/// The curve succeeds when its group order is B1-smooth, except for
+/// at most one prime factor between B1 and B2 (found by stage 2).
+/// Curves are assumed to be generated using Suyama's parametrization.
+///
+/// The expected number of curves needed to find such a factor is
+/// the inverse of the returned probability.
+/// The factorizer uses it for its estimates of the curves left, see
+/// [`Factorizer::estimate`](crate::Factorizer::estimate), and to stop raising the bounds
+/// when the curves run could not have missed a factor, see
+/// [`Factorizer::set_max_escalations`](crate::Factorizer::set_max_escalations).
+///
+/// # Parameters
+///
+/// - `b1`: Stage 1 Bound.
+/// - `b2`: Stage 2 Bound.
+/// - `factor_bits`: Size of the searched factor, in bits.
+pub fn success_probability(b1: u64, b2: u64, factor_bits: u32) -> f64 {
+    if b1 < 2 {
+        return 0.0;
+    }
+
+    let x = factor_bits as f64 * std::f64::consts::LN_2 - SUYAMA_EXTRA_SMOOTHNESS;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let a = (b1 as f64).ln();
+    let b = (b2 as f64).ln();
+
+    // Stage 1: the whole group order is B1-smooth
+    let mut prob = dickman_rho(x / a);
+
+    // Stage 2: one prime q in (B1, B2] and B1-smooth order / q,
+    // integrated over t = ln(q) using Simpson's rule
+    if b > a {
+        let h = (b - a) / STAGE2_STEPS as f64;
+        let mut sum = 0.0;
+        for k in 0..=STAGE2_STEPS {
+            let t = a + k as f64 * h;
+            let weight = if k == 0 || k == STAGE2_STEPS {
+                1.0
+            } else if k % 2 == 1 {
+                4.0
+            } else {
+                2.0
+            };
+            sum += weight * dickman_rho((x - t).max(0.0) / a) / t;
+        }
+        prob += sum * h / 3.0;
+    }
+
+    prob.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rho_known_values() {
+        assert_eq!(dickman_rho(0.5), 1.0);
+        assert!((dickman_rho(2.0) - 0.306_852_819).abs() < 1e-9);
+        assert!((dickman_rho(3.0) - 0.048_608_388).abs() < 1e-9);
+        assert!((dickman_rho(10.0) / 2.770_171_837e-11 - 1.0).abs() < 1e-4);
+        assert_eq!(dickman_rho(30.0), 0.0);
+    }
+
+    #[test]
+    fn rho_is_decreasing() {
+        let mut prev = 1.0;
+        for i in 0..300 {
+            let rho = dickman_rho(i as f64 / 10.0 + 0.05);
+            assert!(rho <= prev);
+            prev = rho;
+        }
+    }
+
+    #[test]
+    fn probability_matches_gmp_ecm_curve_counts() {
+        // (digits, B1, B2, expected curves) from GMP-ECM
+        for (digits, b1, b2, curves) in [
+            (20, 11_000, 1_900_000, 74.0),
+            (25, 50_000, 13_000_000, 214.0),
+            (30, 250_000, 130_000_000, 430.0),
+            (35, 1_000_000, 1_000_000_000, 904.0),
+        ] {
+            let bits = (digits as f64 * 10f64.log2()) as u32;
+            let expected = 1.0 / success_probability(b1, b2, bits);
+            assert!(
+                expected > curves * 0.7 && expected < curves * 1.5,
+                "{digits} digits: {expected} curves"
+            );
+        }
+    }
+
+    #[test]
+    fn probability_bounds() {
+        assert_eq!(success_probability(11_000, 1_900_000, 8), 1.0);
+        assert_eq!(success_probability(0, 0, 64), 0.0);
+        assert!(
+            success_probability(11_000, 11_000, 64) < success_probability(11_000, 1_900_000, 64)
+        );
+        assert!(success_probability(2_000, 160_000, 200) < 1e-10);
+    }
+}
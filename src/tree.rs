@@ -0,0 +1,110 @@
+use rug::Integer;
+
+/// Computes the product of `values` using a balanced product tree.
+///
+/// Multiplying the values one by one into an accumulator is quadratic in the
+/// size of the result, while multiplying numbers of similar sizes lets GMP
+/// use its subquadratic algorithms.
+pub(crate) fn product(values: impl IntoIterator<Item = u64>) -> Integer {
+    // Pack values into words first, then multiply pairwise
+    let mut level: Vec<Integer> = Vec::new();
+    let mut word = 1u64;
+    for value in values {
+        match word.checked_mul(value) {
+            Some(w) => word = w,
+            None => {
+                level.push(Integer::from(word));
+                word = value;
+            }
+        }
+    }
+    level.push(Integer::from(word));
+
+    product_of(level)
+}
+
+/// Computes the product of `level` using a balanced product tree.
+pub(crate) fn product_of(mut level: Vec<Integer>) -> Integer {
+    if level.is_empty() {
+        return Integer::from(1);
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks_mut(2)
+            .map(|pair| match pair {
+                [a, b] => std::mem::take(a) * &*b,
+                [a] => std::mem::take(a),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+
+    level.pop().unwrap_or_else(|| Integer::from(1))
+}
+
+/// Levels of the product tree of `leaves`, from the leaves up to the root.
+///
+/// Each node is the product of its two children, a last odd node is carried up as is.
+pub(crate) fn product_tree(leaves: Vec<Integer>) -> Vec<Vec<Integer>> {
+    let mut levels = vec![leaves];
+    while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+        let next = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => Integer::from(a * b),
+                [a] => a.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn product_matches_naive() {
+        let values: Vec<u64> = (1..2_000).map(|i| i * 7919 + u32::MAX as u64).collect();
+        let mut naive = Integer::from(1);
+        for &v in &values {
+            naive *= v;
+        }
+        assert_eq!(product(values), naive);
+    }
+
+    #[test]
+    fn small_products() {
+        assert_eq!(product([]), 1);
+        assert_eq!(product([u64::MAX]), u64::MAX);
+        assert_eq!(
+            product([u64::MAX, u64::MAX]),
+            Integer::from(u64::MAX).square()
+        );
+        assert_eq!(product([2, 3, 5, 7]), 210);
+        assert_eq!(product_of(vec![]), 1);
+        assert_eq!(product_of(vec![Integer::from(6), Integer::from(35)]), 210);
+    }
+
+    #[test]
+    fn tree_levels() {
+        let levels = product_tree([2, 3, 5, 7, 11].map(Integer::from).to_vec());
+        let levels: Vec<Vec<u32>> = levels
+            .iter()
+            .map(|level| level.iter().map(|node| node.to_u32().unwrap()).collect())
+            .collect();
+        assert_eq!(
+            levels,
+            [
+                vec![2, 3, 5, 7, 11],
+                vec![6, 35, 11],
+                vec![210, 11],
+                vec![2310]
+            ]
+        );
+        assert_eq!(product_tree(vec![]), [Vec::<Integer>::new()]);
+    }
+}
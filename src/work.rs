@@ -0,0 +1,280 @@
+use crate::{
+    ecm::one_factor_with_sigmas, CurveFactor, EcmDiagnostics, Error, Factorization,
+    Parametrization, PrimalityStandard,
+};
+use rug::Integer;
+use std::ops::Range;
+
+/// First sigma of the curves of [`EcmOptions::work_units`](crate::EcmOptions::work_units), as GMP-ECM.
+pub const FIRST_SIGMA: u64 = 6;
+
+/// Curves of a factorization given by a range of sigmas, independent of the other units.
+///
+/// Units of disjoint ranges run different curves, so they can be shipped to
+/// different machines without repeating work. A factor found by any unit is
+/// merged back with [`Factorization::merge_factor`](crate::Factorization::merge_factor).
+///
+/// ```
+/// use ecm::{EcmOptions, Factorization};
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let options = EcmOptions::new().b1(2_000).b2(160_000).max_curves(40);
+/// let units = options.work_units(&n, 4).unwrap();
+/// assert_eq!(units[1].sigmas, 16..26);
+///
+/// let mut factorization = Factorization::new();
+/// factorization.add_cofactor(n, 1);
+/// for unit in &units {
+///     if let Ok(found) = unit.run() {
+///         factorization.merge_factor(&found.factor);
+///     }
+/// }
+/// assert!(factorization.is_complete());
+/// ```
+///
+/// With the `serde` feature, units and their [`WorkResult`] can be sent between
+/// a coordinator and its workers in any serde format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkUnit {
+    /// Number to be factored.
+    pub n: Integer,
+    /// Stage 1 bound.
+    pub b1: u64,
+    /// Stage 2 bound.
+    pub b2: u64,
+    /// Sigmas of the curves, see [`SuyamaCurve`](crate::SuyamaCurve).
+    pub sigmas: Range<u64>,
+    /// Family of the curves of the sigmas, Suyama's one for units from [`WorkUnit::split`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parametrization: Parametrization,
+}
+
+impl WorkUnit {
+    /// Splits the curves of `sigmas` into at most `parts` units of contiguous sigmas.
+    ///
+    /// Units differ by at most one curve, there are fewer units than `parts` only
+    /// if there are fewer curves.
+    ///
+    /// # Parameters
+    ///
+    /// - `n`: Number to be factored.
+    /// - `B1`: Stage 1 Bound.
+    /// - `B2`: Stage 2 Bound.
+    /// - `sigmas`: Sigmas of all the curves.
+    /// - `parts`: Number of units.
+    pub fn split(n: &Integer, b1: u64, b2: u64, sigmas: Range<u64>, parts: usize) -> Vec<WorkUnit> {
+        let curves = sigmas.end.saturating_sub(sigmas.start);
+        let parts = (parts as u64).clamp(1, curves.max(1));
+        let mut start = sigmas.start;
+        (0..parts)
+            .map(|i| curves / parts + u64::from(i < curves % parts))
+            .filter(|&count| count > 0)
+            .map(|count| {
+                start += count;
+                WorkUnit {
+                    n: n.clone(),
+                    b1,
+                    b2,
+                    sigmas: start - count..start,
+                    parametrization: Parametrization::Suyama,
+                }
+            })
+            .collect()
+    }
+
+    /// Number of curves of the unit.
+    pub fn curves(&self) -> u64 {
+        self.sigmas.end.saturating_sub(self.sigmas.start)
+    }
+
+    /// Runs the curves of the unit in order, until one finds a factor of n.
+    ///
+    /// The curve number of the factor found is relative to the unit, starting at 1.
+    /// Fails with [`Error::ECMFailed`] if no curve finds a factor.
+    pub fn run(&self) -> Result<CurveFactor, Error> {
+        one_factor_with_sigmas(
+            &self.n,
+            self.b1,
+            self.b2,
+            self.sigmas.clone().map(Integer::from),
+            self.parametrization,
+            Some(PrimalityStandard::default()),
+            &mut EcmDiagnostics::default(),
+            None,
+        )
+    }
+
+    /// Runs the curves of the unit, returning its outcome to be sent back to the coordinator.
+    ///
+    /// Not finding a factor is an outcome, not an error: the other errors are
+    /// the ones of [`WorkUnit::run`], e.g. [`Error::NumberIsPrime`].
+    pub fn execute(&self) -> Result<WorkResult, Error> {
+        let (curves, factor) = match self.run() {
+            Ok(found) => (found.curve, Some(found)),
+            Err(Error::ECMFailed) => (self.curves(), None),
+            Err(err) => return Err(err),
+        };
+        Ok(WorkResult {
+            unit: self.clone(),
+            curves,
+            factor,
+        })
+    }
+}
+
+/// Outcome of a [`WorkUnit`], sent back by a worker.
+///
+/// ```
+/// use ecm::{Factorization, WorkUnit};
+/// use rug::Integer;
+///
+/// let n = Integer::from(398883434337287u64);
+/// let unit = &WorkUnit::split(&n, 2_000, 160_000, 6..46, 1)[0];
+/// let result = unit.execute().unwrap();
+/// assert!(result.curves <= unit.curves());
+///
+/// let mut factorization = Factorization::new();
+/// factorization.add_cofactor(n, 1);
+/// assert!(result.merge_into(&mut factorization));
+/// assert!(factorization.is_complete());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkResult {
+    /// The unit run.
+    pub unit: WorkUnit,
+    /// Curves run, the curves of the unit up to the one which found a factor.
+    pub curves: u64,
+    /// Factor found, with the curve which found it.
+    pub factor: Option<CurveFactor>,
+}
+
+impl WorkResult {
+    /// Merges the factor found, if any, into the partial factorization of n.
+    ///
+    /// Returns true if a cofactor was split, see [`Factorization::merge_factor`].
+    ///
+    /// # Parameters
+    ///
+    /// - `factorization`: Partial factorization of the number of the unit.
+    pub fn merge_into(&self, factorization: &mut Factorization) -> bool {
+        self.factor
+            .as_ref()
+            .is_some_and(|found| factorization.merge_factor(&found.factor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EcmOptions, Factorization};
+
+    #[test]
+    fn split() {
+        let n = Integer::from(398883434337287u64);
+        let units = WorkUnit::split(&n, 2_000, 160_000, 100..110, 4);
+        let sigmas: Vec<_> = units.iter().map(|unit| unit.sigmas.clone()).collect();
+        assert_eq!(sigmas, [100..103, 103..106, 106..108, 108..110]);
+        assert_eq!(units.iter().map(WorkUnit::curves).sum::<u64>(), 10);
+
+        // More parts than curves, no curve at all
+        assert_eq!(WorkUnit::split(&n, 2_000, 160_000, 100..102, 4).len(), 2);
+        assert!(WorkUnit::split(&n, 2_000, 160_000, 100..100, 4).is_empty());
+        assert_eq!(WorkUnit::split(&n, 2_000, 160_000, 0..5, 0).len(), 1);
+    }
+
+    #[test]
+    fn run_and_merge() {
+        // n = 100327907731 * 10454157497791297
+        let n = Integer::from_str_radix("1048843748843747081710817107", 10).unwrap();
+        let options = EcmOptions::new().b1(2_000).b2(200_000).max_curves(200);
+        let units = options.work_units(&n, 8).unwrap();
+        assert_eq!(units.len(), 8);
+        assert_eq!(units[0].sigmas.start, FIRST_SIGMA);
+        assert_eq!(units[7].sigmas.end, FIRST_SIGMA + 200);
+
+        // Each unit runs the same curves wherever it runs
+        let results: Vec<_> = units.iter().map(WorkUnit::run).collect();
+        for (unit, result) in units.iter().zip(&results) {
+            if let Ok(found) = result {
+                assert!(unit.sigmas.contains(&found.sigma.to_u64().unwrap()));
+                assert_eq!(unit.run().unwrap(), *found);
+            }
+        }
+
+        let mut factorization = Factorization::new();
+        factorization.add_cofactor(n.clone(), 1);
+        for found in results.iter().flatten() {
+            factorization.merge_factor(&found.factor);
+        }
+        assert!(factorization.is_complete());
+        assert_eq!(
+            factorization.multiplicity(&Integer::from(100327907731u64)),
+            1
+        );
+        assert_eq!(factorization.product(), n);
+    }
+
+    #[test]
+    fn parametrization() {
+        let n = Integer::from(398883434337287u64);
+        let options = EcmOptions::new().b1(2_000).b2(160_000).max_curves(40);
+        let options = options.parametrization(Parametrization::Z12);
+        let units = options.work_units(&n, 2).unwrap();
+        assert!(units
+            .iter()
+            .all(|unit| unit.parametrization == Parametrization::Z12));
+
+        // The curves of the family, not Suyama's ones of the same sigmas
+        let found = units.iter().find_map(|unit| unit.run().ok()).unwrap();
+        assert_eq!(found.parametrization, Parametrization::Z12);
+        let suyama = WorkUnit {
+            parametrization: Parametrization::Suyama,
+            ..units[0].clone()
+        };
+        assert_ne!(suyama.run().ok(), units[0].run().ok());
+    }
+
+    #[test]
+    fn execute() {
+        let n = Integer::from(398883434337287u64);
+        let units = WorkUnit::split(&n, 2_000, 160_000, 6..46, 4);
+        for unit in &units {
+            let result = unit.execute().unwrap();
+            assert_eq!(result.unit, *unit);
+            match (&result.factor, unit.run()) {
+                (Some(found), Ok(run)) => {
+                    assert_eq!(*found, run);
+                    assert_eq!(result.curves, found.curve);
+                }
+                (None, Err(Error::ECMFailed)) => assert_eq!(result.curves, unit.curves()),
+                (factor, run) => panic!("{factor:?} and {run:?}"),
+            }
+        }
+
+        // A prime n is an error, an unsplit result merges nothing
+        let prime = &WorkUnit::split(&Integer::from(1_000_003), 2_000, 160_000, 6..16, 1)[0];
+        assert!(matches!(prime.execute(), Err(Error::NumberIsPrime)));
+        let result = WorkResult {
+            unit: prime.clone(),
+            curves: 10,
+            factor: None,
+        };
+        assert!(!result.merge_into(&mut Factorization::new()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let n = Integer::from(398883434337287u64);
+        let unit = WorkUnit::split(&n, 2_000, 160_000, 6..46, 4).remove(1);
+        let json = serde_json::to_string(&unit).unwrap();
+        assert_eq!(serde_json::from_str::<WorkUnit>(&json).unwrap(), unit);
+
+        let result = unit.execute().unwrap();
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(serde_json::from_str::<WorkResult>(&json).unwrap(), result);
+    }
+}
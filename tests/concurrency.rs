@@ -0,0 +1,78 @@
+//! Every public type can be sent to and shared between threads, and the
+//! top-level functions are reentrant: each call owns its random generator.
+
+use ecm::*;
+use rug::Integer;
+use std::{str::FromStr, sync::Arc, thread};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn send_sync() {
+    assert_send_sync::<EcmOptions>();
+    assert_send_sync::<BatchOptions>();
+    assert_send_sync::<Batch<std::vec::IntoIter<Integer>>>();
+    assert_send_sync::<Scheduler>();
+    assert_send_sync::<Factorizer>();
+    assert_send_sync::<EcmStream>();
+    assert_send_sync::<EcmJob>();
+    assert_send_sync::<JobSnapshot>();
+    assert_send_sync::<CancelToken>();
+    assert_send_sync::<Factorization>();
+    assert_send_sync::<Error>();
+    assert_send_sync::<Stats>();
+    assert_send_sync::<Progress>();
+    assert_send_sync::<ResumeState>();
+    assert_send_sync::<PrimeTable>();
+    assert_send_sync::<WorkUnit>();
+    assert_send_sync::<WorkResult>();
+    assert_send_sync::<Residue>();
+    assert_send_sync::<ResidueLog>();
+    assert_send_sync::<ResidueError>();
+    assert_send_sync::<Curve>();
+    assert_send_sync::<Point>();
+    assert_send_sync::<Arc<dyn ProgressSink>>();
+    assert_send_sync::<Arc<dyn ProgressObserver>>();
+    #[cfg(feature = "async")]
+    assert_send_sync::<EcmFuture>();
+    #[cfg(feature = "audit")]
+    assert_send_sync::<AuditLog>();
+    #[cfg(feature = "serde")]
+    assert_send_sync::<Checkpoint>();
+    #[cfg(feature = "factordb")]
+    assert_send_sync::<FactorDb>();
+}
+
+#[test]
+fn shared_options() {
+    let inputs = [
+        "398883434337287",
+        "46167045131415113",
+        "168541512131094651323",
+        "631211032315670776841",
+    ]
+    .map(|n| Integer::from_str(n).unwrap());
+    let options = EcmOptions::new().seed(1).small_cofactor_bits(0);
+    let expected: Vec<_> = inputs.iter().map(|n| options.run(n).unwrap()).collect();
+
+    // The same options, used by many threads at once, give the same factors
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..2)
+            .flat_map(|_| &inputs)
+            .map(|n| scope.spawn(|| options.run(n).unwrap()))
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap(), expected[i % inputs.len()]);
+        }
+    });
+
+    // Each call owns its generator, the seed alone chooses the curves
+    let n = &inputs[0];
+    let run = |seed| ecm_with_params(n, 2_000, None, 100, Some(seed)).unwrap();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..4).map(|seed| scope.spawn(move || run(seed))).collect();
+        for (seed, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap(), run(seed as u64));
+        }
+    });
+}
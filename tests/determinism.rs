@@ -0,0 +1,56 @@
+//! The factors found for a given seed must be identical on every target,
+//! whatever the size of `usize` and of GMP limbs.
+
+use ecm::{ecm_one_factor, ecm_with_params, Factorization};
+use rug::{rand::RandState, Integer};
+use std::str::FromStr;
+
+fn one_factor(n: &str, seed: u64) -> Integer {
+    let mut rgen = RandState::new();
+    rgen.seed(&seed.into());
+    ecm_one_factor(
+        &Integer::from_str(n).unwrap(),
+        2_000,
+        160_000,
+        100,
+        &mut rgen,
+        None,
+    )
+    .unwrap()
+}
+
+#[test]
+fn same_curves_for_same_seed() {
+    // The factor found by the first successful curve identifies the curve sequence
+    let n = "4269021180054189416198169786894227";
+    assert_eq!(
+        one_factor(n, 0),
+        Integer::from_str("12807153190234899892893759617").unwrap()
+    );
+    assert_eq!(
+        one_factor(n, 1),
+        Integer::from_str("28562928467484627982211").unwrap()
+    );
+    assert_eq!(
+        one_factor(n, 1234),
+        Integer::from_str("6900889206529688574386124233").unwrap()
+    );
+    assert_eq!(one_factor(n, 0xdead_beef_cafe), 37949741730281671u64);
+}
+
+#[test]
+fn same_factorization_for_same_seed() {
+    let n = Integer::from_str("4516511326451341281684513").unwrap();
+    let factors =
+        ecm_with_params(&n, 50_000, Some(13_000_000), 214, Some(u32::MAX as u64 + 1)).unwrap();
+
+    assert_eq!(
+        factors,
+        Factorization::from([
+            (Integer::from(3), 2),
+            (Integer::from(39869), 1),
+            (Integer::from(131743543), 1),
+            (Integer::from_str("95542348571").unwrap(), 1),
+        ])
+    );
+}
@@ -0,0 +1,45 @@
+//! Driver shared by the `driver` fuzz target and the regression tests.
+
+use ecm::{EcmOptions, Error};
+use rug::{integer::Order, Integer};
+
+/// Maximum size of the inputs, in bytes.
+pub const MAX_INPUT_BYTES: usize = 32;
+
+/// Factors the number encoded by `data` and checks the result.
+///
+/// The first byte holds the sign in its lowest bit and the seed in the others,
+/// the next bytes (at most [`MAX_INPUT_BYTES`]) are the absolute value, most significant first.
+///
+/// # Panics
+///
+/// Panics if the factorization does not multiply back to |n|, or fails with an unexpected error.
+pub fn drive(data: &[u8]) {
+    let Some((&flags, digits)) = data.split_first() else {
+        return;
+    };
+    let digits = &digits[..digits.len().min(MAX_INPUT_BYTES)];
+    let mut n = Integer::from_digits(digits, Order::Msf);
+    if flags & 1 == 1 {
+        n = -n;
+    }
+
+    let result = EcmOptions::new()
+        .b1(2_000)
+        .b2(160_000)
+        .max_curves(10)
+        .max_escalations(0)
+        .seed(u64::from(flags >> 1))
+        .run(&n);
+    match result {
+        Ok(factorization) => {
+            assert_eq!(
+                factorization.product(),
+                Integer::from(n.abs_ref()),
+                "n = {n}"
+            )
+        }
+        Err(Error::ZeroInput) => assert_eq!(n, 0),
+        Err(err) => panic!("n = {n}: {err}"),
+    }
+}
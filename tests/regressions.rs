@@ -0,0 +1,22 @@
+//! Inputs of the fuzz regression corpus, each one crashed or hung the driver once.
+//!
+//! New crashes found by `cargo fuzz run driver` are added to `tests/corpus/driver`.
+
+mod driver;
+
+#[test]
+fn fuzz_corpus() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus/driver");
+    let mut entries: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+    assert!(!entries.is_empty());
+
+    for path in entries {
+        let data = std::fs::read(&path).unwrap();
+        println!("{}", path.display());
+        driver::drive(&data);
+    }
+}